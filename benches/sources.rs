@@ -0,0 +1,176 @@
+//! 各种 DataSource 的性能测试. `cargo bench --bench sources [过滤字符串]`
+//!
+//! 回归检查使用 criterion 的基线:
+//! - `cargo bench --bench sources -- --save-baseline main` 保存结果
+//! - `cargo bench --bench sources -- --baseline main` 与保存的结果比较
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use data_source::*;
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::path::Path;
+
+/// 在 `root` 下生成 `dirs` 个子目录, 每个目录 `files` 个 `size` 字节的文件, 返回相对路径
+fn gen_tree(root: &Path, dirs: usize, files: usize, size: usize) -> Vec<String> {
+    let mut names = Vec::new();
+    for d in 0..dirs {
+        std::fs::create_dir_all(root.join(format!("d{d}"))).unwrap();
+        for f in 0..files {
+            let n = format!("d{d}/f{f}.dat");
+            std::fs::write(root.join(&n), vec![b'x'; size]).unwrap();
+            names.push(n);
+        }
+    }
+    names
+}
+
+/// 生成包含 `names` 的 tar, 每个文件 `size` 字节
+fn gen_tar(names: &[String], size: usize) -> Vec<u8> {
+    let mut b = tar::Builder::new(Vec::new());
+    for n in names {
+        let mut h = tar::Header::new_gnu();
+        h.set_size(size as u64);
+        h.set_mode(0o644);
+        h.set_cksum();
+        b.append_data(&mut h, n, &vec![b'x'; size][..]).unwrap();
+    }
+    b.into_inner().unwrap()
+}
+
+fn gen_file_map(names: &[String], size: usize) -> HashMap<String, SingleFileSource> {
+    names
+        .iter()
+        .map(|n| (n.clone(), SingleFileSource::Inline(vec![b'x'; size])))
+        .collect()
+}
+
+fn bench_folders(c: &mut Criterion) {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dirs: Vec<String> = (0..3)
+        .map(|i| {
+            let d = temp_dir.path().join(format!("folders{i}"));
+            std::fs::create_dir_all(&d).unwrap();
+            d.to_string_lossy().to_string()
+        })
+        .collect();
+    gen_tree(Path::new(&dirs[2]), 10, 10, 1024);
+    let ds = DataSource::Folders(dirs);
+    let mut g = c.benchmark_group("folders");
+    g.bench_function("hit_last_dir", |b| {
+        b.iter(|| black_box(ds.get_file_content(Path::new("d5/f5.dat")).unwrap()))
+    });
+    g.bench_function("miss", |b| {
+        b.iter(|| black_box(ds.get_file_content(Path::new("nope.dat")).unwrap_err()))
+    });
+    g.finish();
+}
+
+fn bench_tar(c: &mut Criterion) {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let names: Vec<String> = (0..1000).map(|i| format!("t/f{i}.dat")).collect();
+    let t = gen_tar(&names, 512);
+    let p = temp_dir.path().join("bench.tar");
+    std::fs::write(&p, &t).unwrap();
+
+    let ds = DataSource::TarInMemory(t);
+    let mut g = c.benchmark_group("tar_in_memory");
+    g.bench_function("first", |b| {
+        b.iter(|| black_box(ds.get_file_content(Path::new("t/f0.dat")).unwrap()))
+    });
+    g.bench_function("last", |b| {
+        b.iter(|| black_box(ds.get_file_content(Path::new("t/f999.dat")).unwrap()))
+    });
+    g.bench_function("last_ref", |b| {
+        b.iter(|| black_box(ds.get_file_content_ref(Path::new("t/f999.dat")).unwrap()))
+    });
+    g.finish();
+
+    let ds = DataSource::TarFile(TarFile(p.to_string_lossy().to_string()));
+    let mut g = c.benchmark_group("tar_file");
+    g.bench_function("last", |b| {
+        b.iter(|| black_box(ds.get_file_content(Path::new("t/f999.dat")).unwrap()))
+    });
+    g.bench_function("list_files", |b| {
+        b.iter(|| black_box(ds.list_files().unwrap()))
+    });
+    g.finish();
+}
+
+fn bench_file_map(c: &mut Criterion) {
+    let names: Vec<String> = (0..10_000).map(|i| format!("m/f{i}.json")).collect();
+    let ds = DataSource::FileMap(gen_file_map(&names, 256));
+    let mut g = c.benchmark_group("file_map");
+    g.bench_function("hit", |b| {
+        b.iter(|| black_box(ds.get_file_content(Path::new("m/f5000.json")).unwrap()))
+    });
+    g.bench_function("hit_ref", |b| {
+        b.iter(|| black_box(ds.get_file_content_ref(Path::new("m/f5000.json")).unwrap()))
+    });
+    g.finish();
+}
+
+fn bench_cache(c: &mut Criterion) {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let fc = FileCache {
+        update_interval_seconds: Some(3600),
+        cache_file_path: Some(
+            temp_dir
+                .path()
+                .join("cache.bin")
+                .to_string_lossy()
+                .to_string(),
+        ),
+        ..Default::default()
+    };
+    let data = vec![b'x'; 64 * 1024];
+    let mut g = c.benchmark_group("cache");
+    g.bench_function("write_64k", |b| {
+        b.iter(|| black_box(fc.write_cache_file(&data)))
+    });
+    g.bench_function("read_64k", |b| {
+        b.iter(|| black_box(fc.read_cache_file().unwrap()))
+    });
+    let gen = generated::Generator::new(|| Ok(vec![b'x'; 64 * 1024]));
+    g.bench_function("fetch_with_cache_hit", |b| {
+        b.iter(|| black_box(fetch_with_cache(&fc, &gen).unwrap()))
+    });
+    g.finish();
+}
+
+#[cfg(feature = "file_server")]
+fn bench_file_server(c: &mut Criterion) {
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::Service;
+
+    let names: Vec<String> = (0..100).map(|i| format!("s/f{i}.json")).collect();
+    let service =
+        file_server::DataSourceService::new(DataSource::FileMap(gen_file_map(&names, 4096)));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    c.bench_function("file_server/get_4k", |b| {
+        b.iter(|| {
+            let mut s = service.clone();
+            let req = Request::builder()
+                .uri("/files/s/f50.json")
+                .body(())
+                .unwrap();
+            rt.block_on(async {
+                let resp = s.call(req).await.unwrap();
+                black_box(resp.into_body().collect().await.unwrap().to_bytes())
+            })
+        })
+    });
+}
+
+#[cfg(not(feature = "file_server"))]
+fn bench_file_server(_c: &mut Criterion) {}
+
+criterion_group!(
+    benches,
+    bench_folders,
+    bench_tar,
+    bench_file_map,
+    bench_cache,
+    bench_file_server
+);
+criterion_main!(benches);
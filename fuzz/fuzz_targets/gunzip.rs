@@ -0,0 +1,11 @@
+//! gzip 解压不应 panic, 压缩后再解压应得到原数据
+#![no_main]
+
+use data_source::gzip;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = gzip::gunzip(data);
+    let z = gzip::gzip(data);
+    assert_eq!(gzip::gunzip(&z).unwrap(), data);
+});
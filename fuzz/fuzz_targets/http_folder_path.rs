@@ -0,0 +1,19 @@
+//! 请求的文件名不应使生成的 url 跳出 base url 所在的目录
+#![no_main]
+
+use data_source::http_folder::HttpFolderSource;
+use libfuzzer_sys::fuzz_target;
+use std::path::Path;
+
+const BASE: &str = "https://example.com/files/";
+
+fuzz_target!(|name: &str| {
+    let hf = HttpFolderSource::new(BASE);
+    if let Some(s) = hf.source_for(Path::new(name)) {
+        if let Ok(u) = reqwest::Url::parse(&s.url) {
+            assert_eq!(u.host_str(), Some("example.com"), "{name:?}");
+            assert!(u.path().starts_with("/files/"), "{name:?} -> {}", u.path());
+        }
+    }
+    let _ = data_source::normalize_separators(Path::new(name));
+});
@@ -0,0 +1,22 @@
+//! 从不可信的 tar 数据 (开启 gzip 时也可能是 .tar.gz) 中查找和列出文件, 不应 panic
+#![no_main]
+
+use data_source::*;
+use libfuzzer_sys::fuzz_target;
+use std::path::Path;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = get_file_from_tar_in_memory("a/b.txt", data);
+    if let Ok((d, _)) = get_file_ref_from_tar_in_memory("a/b.txt", data) {
+        // 借用的片段与复制读取的结果应当一致
+        if let Ok((v, _)) = get_file_from_tar_in_memory("a/b.txt", data) {
+            assert_eq!(d.as_ref(), v.as_slice());
+        }
+    }
+    let ds = DataSource::TarInMemory(data.to_vec());
+    if let Ok(files) = ds.list_files() {
+        for f in files.iter().take(8) {
+            let _ = ds.get_file_content(Path::new(f));
+        }
+    }
+});
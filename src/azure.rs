@@ -0,0 +1,249 @@
+//! Azure Blob Storage 中的一个 blob, 支持 SAS token 和账户密钥 (Shared Key) 两种认证.
+//! 与 [`s3::S3Source`] 一样可以放在 [`SingleFileSource::AzureBlob`] 中用 FileCache 缓存
+
+use crate::*;
+use ring::hmac;
+
+const API_VERSION: &str = "2021-08-06";
+
+#[derive(Clone)]
+pub enum AzureCredentials {
+    /// SAS token, 即 url 的查询串 (`sv=...&sig=...`), 可以带开头的 `?`
+    Sas(String),
+    /// 存储账户的访问密钥 (base64), 请求使用 Shared Key 签名
+    AccountKey(String),
+}
+
+impl std::fmt::Debug for AzureCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AzureCredentials::Sas(_) => f.write_str("Sas(..)"),
+            AzureCredentials::AccountKey(_) => f.write_str("AccountKey(..)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AzureBlobSource {
+    pub account: String,
+    pub container: String,
+    pub blob: String,
+    /// 如 Azurite 的 `http://127.0.0.1:10000/devstoreaccount1`.
+    /// 为空时使用 `https://{account}.blob.core.windows.net`
+    pub endpoint: Option<String>,
+    /// 为空时发送匿名请求 (公共访问的容器)
+    pub credentials: Option<AzureCredentials>,
+    /// 请求 blob 时使用的代理, 大小限制等, 其 url 和请求头会被覆盖
+    pub http: HttpSource,
+}
+
+impl AzureBlobSource {
+    pub fn new(account: &str, container: &str, blob: &str) -> Self {
+        Self {
+            account: account.to_string(),
+            container: container.to_string(),
+            blob: blob.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn with_sas_token(mut self, sas: &str) -> Self {
+        self.credentials = Some(AzureCredentials::Sas(sas.to_string()));
+        self
+    }
+
+    pub fn with_account_key(mut self, key: &str) -> Self {
+        self.credentials = Some(AzureCredentials::AccountKey(key.to_string()));
+        self
+    }
+
+    /// blob 的 url, 不含 SAS token
+    pub fn url(&self) -> String {
+        let blob = encode_path(self.blob.trim_start_matches('/'));
+        match &self.endpoint {
+            Some(e) => format!("{e}/{}/{blob}", self.container),
+            None => format!(
+                "https://{}.blob.core.windows.net/{}/{blob}",
+                self.account, self.container
+            ),
+        }
+    }
+
+    /// 生成带有认证信息的请求
+    pub fn signed_request(&self) -> Result<HttpSource, FetchError> {
+        let url = self.url();
+        let mut headers = vec![
+            ("x-ms-date".to_string(), rfc1123_date(SystemTime::now())),
+            ("x-ms-version".to_string(), API_VERSION.to_string()),
+        ];
+        let url = match &self.credentials {
+            None => url,
+            Some(AzureCredentials::Sas(sas)) => format!("{url}?{}", sas.trim_start_matches('?')),
+            Some(AzureCredentials::AccountKey(key)) => {
+                let key = base64::decode(key)
+                    .ok_or_else(|| FetchError::Invalid("invalid azure account key".to_string()))?;
+                let path = reqwest::Url::parse(&url)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+                    .path()
+                    .to_string();
+                let auth = authorization(&self.account, &key, &path, &headers);
+                headers.push(("authorization".to_string(), auth));
+                url
+            }
+        };
+        Ok(HttpSource {
+            url,
+            custom_request_headers: Some(headers),
+            ..self.http.clone()
+        })
+    }
+}
+
+impl SyncSource for AzureBlobSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.signed_request()?.fetch()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for AzureBlobSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        self.signed_request()?.fetch_async().await
+    }
+}
+
+/// 百分号编码 blob 名称, 保留 `/`
+fn encode_path(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            r.push(b as char);
+        } else {
+            r.push_str(&format!("%{b:02X}"));
+        }
+    }
+    r
+}
+
+/// GET 请求的签名字符串. 标准头都为空, `headers` 为小写的 `x-ms-*` 头
+fn string_to_sign(account: &str, path: &str, headers: &[(String, String)]) -> String {
+    let mut ms: Vec<&(String, String)> = headers
+        .iter()
+        .filter(|(k, _)| k.starts_with("x-ms-"))
+        .collect();
+    ms.sort();
+    let canonical_headers: String = ms
+        .iter()
+        .map(|(k, v)| format!("{k}:{}\n", v.trim()))
+        .collect();
+    format!("GET{}{canonical_headers}/{account}{path}", "\n".repeat(12))
+}
+
+/// Shared Key 的 Authorization 头
+fn authorization(account: &str, key: &[u8], path: &str, headers: &[(String, String)]) -> String {
+    let s = string_to_sign(account, path, headers);
+    let sig = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), s.as_bytes());
+    format!("SharedKey {account}:{}", base64::encode(sig.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+
+    const AZURITE_KEY: &str =
+        "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+    /// 返回请求行和 Authorization 头
+    fn serve() -> String {
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/devstoreaccount1", l.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let mut lines = BufReader::new(&s).lines().map(|l| l.unwrap());
+                let req = lines.next().unwrap();
+                let auth = lines
+                    .take_while(|l| !l.is_empty())
+                    .find(|l| l.to_ascii_lowercase().starts_with("authorization:"))
+                    .map(|l| l[14..].trim().to_string())
+                    .unwrap_or_default();
+                let body = format!("{req}\n{auth}");
+                write!(
+                    s,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_url_and_signing() {
+        let s = AzureBlobSource::new("acct", "cfg", "a b/c.json");
+        assert_eq!(
+            s.url(),
+            "https://acct.blob.core.windows.net/cfg/a%20b/c.json"
+        );
+        let t = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(784111777);
+        assert_eq!(rfc1123_date(t), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_rfc1123_date(&rfc1123_date(t)), Some(784111777));
+        assert_eq!(parse_rfc1123_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+
+        let headers = vec![
+            ("x-ms-version".to_string(), API_VERSION.to_string()),
+            ("x-ms-date".to_string(), rfc1123_date(t)),
+        ];
+        assert_eq!(
+            string_to_sign("acct", "/cfg/a%20b/c.json", &headers),
+            "GET\n\n\n\n\n\n\n\n\n\n\n\n\
+             x-ms-date:Sun, 06 Nov 1994 08:49:37 GMT\nx-ms-version:2021-08-06\n\
+             /acct/cfg/a%20b/c.json"
+        );
+        let key = base64::decode(AZURITE_KEY).unwrap();
+        let auth = authorization("acct", &key, "/cfg/a%20b/c.json", &headers);
+        assert!(auth.starts_with("SharedKey acct:"));
+        assert_eq!(auth.len(), "SharedKey acct:".len() + 44);
+    }
+
+    #[test]
+    fn test_azure_blob_fetch() {
+        let url = serve();
+        let s = AzureBlobSource::new("devstoreaccount1", "cfg", "a.json").with_endpoint(&url);
+        let d = s.clone().with_sas_token("?sv=1&sig=x").fetch().unwrap();
+        assert_eq!(
+            String::from_utf8(d).unwrap(),
+            "GET /devstoreaccount1/cfg/a.json?sv=1&sig=x HTTP/1.1\n"
+        );
+        let d = s.clone().with_account_key(AZURITE_KEY).fetch().unwrap();
+        let d = String::from_utf8(d).unwrap();
+        assert!(d.contains("SharedKey devstoreaccount1:"), "{d}");
+        assert!(s.with_account_key("not base64!").fetch().is_err());
+    }
+
+    #[test]
+    fn test_azure_blob_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cf = temp_dir.path().join("a.json");
+        let url = serve();
+        let sf = SingleFileSource::AzureBlob(
+            AzureBlobSource::new("devstoreaccount1", "cfg", "a.json").with_endpoint(&url),
+            FileCache {
+                update_interval_seconds: Some(60),
+                cache_file_path: Some(cf.to_string_lossy().to_string()),
+                ..Default::default()
+            },
+        );
+        let d = sf.fetch().unwrap();
+        assert_eq!(std::fs::read(&cf).unwrap(), d);
+        assert_eq!(sf.get_path().unwrap(), format!("{url}/cfg/a.json"));
+    }
+}
@@ -0,0 +1,54 @@
+//! 标准 base64 (带填充) 的编码与解码
+
+const T: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[cfg_attr(not(feature = "reqwest"), allow(dead_code))]
+pub(crate) fn encode(d: &[u8]) -> String {
+    let mut r = String::with_capacity(d.len().div_ceil(3) * 4);
+    for c in d.chunks(3) {
+        let n = (c[0] as u32) << 16
+            | (*c.get(1).unwrap_or(&0) as u32) << 8
+            | *c.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= c.len() {
+                r.push(T[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                r.push('=');
+            }
+        }
+    }
+    r
+}
+
+/// 忽略末尾的 `=`, 有非法字符时返回 None
+pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=').as_bytes();
+    let mut r = Vec::with_capacity(s.len() * 3 / 4);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for &c in s {
+        let v = T.iter().position(|&t| t == c)? as u32;
+        n = n << 6 | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            r.push((n >> bits) as u8);
+        }
+    }
+    Some(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        for s in ["", "a", "an", "any carnal pleas", "\0\u{ff}/routing"] {
+            assert_eq!(decode(&encode(s.as_bytes())).unwrap(), s.as_bytes());
+        }
+        assert_eq!(encode(b"any carnal pleas"), "YW55IGNhcm5hbCBwbGVhcw==");
+        assert_eq!(decode("L3I=").unwrap(), b"/r");
+        assert!(decode("a*b=").is_none());
+    }
+}
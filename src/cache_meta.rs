@@ -0,0 +1,191 @@
+//! 缓存文件旁的元数据 `<cache>.meta.json`: 来源 url, 读取时间, ETag, Content-Type 和 SHA-256.
+//! 每次写入缓存时更新, 使用者可以据此做比修改时间更多的判断, 如缓存内容是否被改动
+
+use crate::*;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheMetadata {
+    /// 写入时不知道 url 的 (如 [`fetch_with_cache`] 的自定义来源) 沿用上一次的记录
+    pub url: Option<String>,
+    /// 最近一次从来源读取 (包括 304 验证) 的时间, unix 秒
+    pub fetched_at: u64,
+    pub etag: Option<String>,
+    pub content_type: Option<String>,
+    /// 缓存文件内容的 sha256, 小写 hex. 加密缓存记录的是密文的
+    pub sha256: String,
+    pub size: u64,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl CacheMetadata {
+    pub fn fetched_at_time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(self.fetched_at)
+    }
+
+    /// `d` 是否与记录的大小和 sha256 一致
+    pub fn matches(&self, d: &[u8]) -> bool {
+        self.size == d.len() as u64 && self.sha256 == cas::sha256_hex(d)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "url": self.url,
+            "fetched_at": self.fetched_at,
+            "etag": self.etag,
+            "content_type": self.content_type,
+            "sha256": self.sha256,
+            "size": self.size,
+        })
+    }
+
+    fn from_json(v: &Value) -> Option<Self> {
+        let s = |k: &str| v.get(k).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            url: s("url"),
+            fetched_at: v.get("fetched_at")?.as_u64()?,
+            etag: s("etag"),
+            content_type: s("content_type"),
+            sha256: s("sha256")?,
+            size: v.get("size")?.as_u64()?,
+        })
+    }
+}
+
+impl FileCache {
+    pub fn meta_path(&self) -> Option<PathBuf> {
+        let cf = self.cache_file_path.as_ref()?;
+        Some(PathBuf::from(format!("{cf}.meta.json")))
+    }
+
+    /// 缓存文件的元数据, 没有记录或记录无法解析时返回 None
+    pub fn metadata(&self) -> Option<CacheMetadata> {
+        let d = std::fs::read(self.meta_path()?).ok()?;
+        CacheMetadata::from_json(&serde_json::from_slice(&d).ok()?)
+    }
+
+    fn write_metadata(&self, m: &CacheMetadata) {
+        let Some(p) = self.meta_path() else {
+            return;
+        };
+        if let Err(e) = std::fs::write(&p, m.to_json().to_string()) {
+            warn!("Failed to write cache metadata: {e}");
+        }
+    }
+
+    pub(crate) fn save_metadata(&self, d: &[u8], info: &SourceInfo) {
+        let url = match info.url {
+            Some(u) => Some(u.to_string()),
+            None => self.metadata().and_then(|m| m.url),
+        };
+        self.write_metadata(&CacheMetadata {
+            url,
+            fetched_at: unix_now(),
+            etag: info.etag.map(str::to_string),
+            content_type: info.content_type.map(str::to_string),
+            sha256: cas::sha256_hex(d),
+            size: d.len() as u64,
+        });
+    }
+
+    /// 开启 verify_checksum 时检查读到的缓存, 损坏时删除缓存及其记录
+    pub(crate) fn verify_cache(&self, d: &[u8]) -> Result<(), FetchError> {
+        if !self.verify_checksum || self.metadata().is_some_and(|m| m.matches(d)) {
+            return Ok(());
+        }
+        Err(self.discard_corrupt())
+    }
+
+    /// 来源确认内容未变 (304) 时只更新读取时间
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn touch_metadata(&self) {
+        if let Some(mut m) = self.metadata() {
+            m.fetched_at = unix_now();
+            self.write_metadata(&m);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_metadata() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), None)
+            .resolve("a")
+            .into_owned();
+        assert!(fc.metadata().is_none());
+        let info = SourceInfo {
+            url: Some("https://a.com/a"),
+            etag: Some("\"v1\""),
+            content_type: Some("text/plain"),
+        };
+        assert!(fc.write_cache_file_from(b"abc", &info));
+        let m = fc.metadata().unwrap();
+        assert_eq!(m.url.as_deref(), Some("https://a.com/a"));
+        assert_eq!(m.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(m.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(
+            m.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(m.matches(b"abc"));
+        assert!(!m.matches(b"abd"));
+        assert!(m.fetched_at_time().elapsed().unwrap().as_secs() < 60);
+
+        // 不知道 url 时沿用旧记录, 其它信息不沿用
+        assert!(fc.write_cache_file(b"abcd"));
+        let m = fc.metadata().unwrap();
+        assert_eq!(m.url.as_deref(), Some("https://a.com/a"));
+        assert_eq!(m.etag, None);
+        assert_eq!(m.size, 4);
+
+        std::fs::write(fc.meta_path().unwrap(), "{").unwrap();
+        assert!(fc.metadata().is_none());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counting(AtomicUsize);
+        impl SyncSource for Counting {
+            fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(b"abc".to_vec())
+            }
+        }
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .with_verify_checksum()
+            .resolve("a")
+            .into_owned();
+        let cf = fc.cache_file_path.clone().unwrap();
+        let s = Counting(AtomicUsize::new(0));
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"abc");
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"abc");
+        assert_eq!(s.0.load(Ordering::SeqCst), 1);
+
+        // 损坏的缓存被丢弃并重新读取
+        std::fs::write(&cf, "abd").unwrap();
+        assert!(fc.read_cache_file().unwrap_err().is_cache_corrupt());
+        assert!(!Path::new(&cf).exists());
+        std::fs::write(&cf, "abd").unwrap();
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"abc");
+        assert_eq!(s.0.load(Ordering::SeqCst), 2);
+        assert!(fc.metadata().unwrap().matches(b"abc"));
+
+        // 没有记录的缓存同样不可信
+        std::fs::remove_file(fc.meta_path().unwrap()).unwrap();
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"abc");
+        assert_eq!(s.0.load(Ordering::SeqCst), 3);
+    }
+}
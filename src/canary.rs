@@ -0,0 +1,150 @@
+use crate::*;
+use std::sync::atomic::AtomicU64;
+
+/// 按比例把一部分请求交给 `canary`, 其余的仍由 `primary` 处理, 用于逐步上线新的数据包.
+///
+/// 是否走 canary 由 key 的哈希决定: 依次取 [`FetchContext::tenant`],
+/// [`FetchContext::trace_id`], 都没有时用文件名, 所以同一个租户总是看到同一份数据
+#[derive(Debug)]
+pub struct CanarySource {
+    pub primary: DataSource,
+    pub canary: DataSource,
+    /// 0 到 100
+    pub percent: u8,
+    primary_served: AtomicU64,
+    canary_served: AtomicU64,
+}
+
+/// [`CanarySource`] 两个分支各自处理的请求数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CanaryStats {
+    pub primary: u64,
+    pub canary: u64,
+}
+
+impl CanarySource {
+    pub fn new(primary: DataSource, canary: DataSource, percent: u8) -> Self {
+        Self {
+            primary,
+            canary,
+            percent: percent.min(100),
+            primary_served: AtomicU64::new(0),
+            canary_served: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> CanaryStats {
+        CanaryStats {
+            primary: self.primary_served.load(Ordering::Relaxed),
+            canary: self.canary_served.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn is_canary(&self, key: &str) -> bool {
+        stable_hash(key) % 100 < self.percent as u64
+    }
+
+    fn select(&self, file_name: &Path, ctx: &FetchContext) -> &DataSource {
+        let name;
+        let key = match ctx.tenant.as_ref().or(ctx.trace_id.as_ref()) {
+            Some(k) => k.as_str(),
+            None => {
+                name = file_name.to_string_lossy();
+                name.as_ref()
+            }
+        };
+        if self.is_canary(key) {
+            self.canary_served.fetch_add(1, Ordering::Relaxed);
+            &self.canary
+        } else {
+            self.primary_served.fetch_add(1, Ordering::Relaxed);
+            &self.primary
+        }
+    }
+}
+
+impl SyncFolderSource for CanarySource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed(file_name)
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.get_file_content_ctx(file_name, &FetchContext::default())
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        self.select(file_name, ctx)
+            .get_file_content_ctx(file_name, ctx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for CanarySource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed_async(file_name)
+            .await
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        self.get_file_content_ctx_async(file_name, &FetchContext::default())
+            .await
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        self.select(file_name, ctx)
+            .get_file_content_ctx_async(file_name, ctx)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inline(d: &[u8]) -> DataSource {
+        DataSource::FileMap(
+            vec![("a".to_string(), SingleFileSource::Inline(d.to_vec()))]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_canary_split() {
+        let cs = CanarySource::new(inline(b"old"), inline(b"new"), 30);
+        let mut new = 0;
+        for i in 0..1000 {
+            let ctx = FetchContext {
+                tenant: Some(format!("tenant-{i}")),
+                ..Default::default()
+            };
+            let fc = cs.get_file_content_ctx(Path::new("a"), &ctx).unwrap();
+            if fc.data == b"new" {
+                new += 1;
+            }
+        }
+        assert!((200..400).contains(&new), "{new}");
+        assert_eq!(cs.stats().canary, new);
+        assert_eq!(cs.stats().primary, 1000 - new);
+
+        let none = CanarySource::new(inline(b"old"), inline(b"new"), 0);
+        assert_eq!(none.get_file_content(Path::new("a")).unwrap().0, b"old");
+    }
+}
@@ -0,0 +1,248 @@
+//! 来源在运行时可查询的能力, 见 [`SyncFolderSource::capabilities`] 和 [`DataSource::capabilities`].
+//!
+//! 包装其它来源的类型 (如 [`overlay::OverlaySource`], [`tags::TaggedSource`]) 的能力不超过内层,
+//! 且不会转发写入, 因此组合后的来源不会意外地变为可写. 文件服务和 [`mirror::sync_to_folder`]
+//! 等辅助函数在操作前检查这些标志. 编译时开启了哪些 feature 见 [`capability_report`]
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub readable: bool,
+    /// 可以通过来源自身的接口写入 (如 [`sqlite::SqliteFolderSource::put`])
+    pub writable: bool,
+    /// [`SyncFolderSource::list_files`] 可用
+    pub listable: bool,
+    /// 不读取内容即可检测变化 (如文件修改时间)
+    pub watchable: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::READ_ONLY
+    }
+}
+
+impl Capabilities {
+    pub const READ_ONLY: Self = Self {
+        readable: true,
+        writable: false,
+        listable: false,
+        watchable: false,
+    };
+    pub const NONE: Self = Self {
+        readable: false,
+        writable: false,
+        listable: false,
+        watchable: false,
+    };
+
+    pub const fn with_listable(mut self) -> Self {
+        self.listable = true;
+        self
+    }
+
+    pub const fn with_watchable(mut self) -> Self {
+        self.watchable = true;
+        self
+    }
+
+    pub const fn with_writable(mut self) -> Self {
+        self.writable = true;
+        self
+    }
+
+    /// 去掉 writable, 用于不转发写入的包装层
+    pub const fn read_only(mut self) -> Self {
+        self.writable = false;
+        self
+    }
+
+    /// 两者都具有的能力, 用于需要所有内层都支持的组合 (如叠加各层的列表)
+    pub const fn intersect(self, o: Self) -> Self {
+        Self {
+            readable: self.readable && o.readable,
+            writable: self.writable && o.writable,
+            listable: self.listable && o.listable,
+            watchable: self.watchable && o.watchable,
+        }
+    }
+
+    /// 没有 listable 时返回与 list_files 相同的 [`FetchError::Unsupported`]
+    pub fn require_listable(self) -> Result<(), FetchError> {
+        if self.listable {
+            Ok(())
+        } else {
+            Err(FetchError::Unsupported("list_files"))
+        }
+    }
+}
+
+impl DataSource {
+    /// 各变体的能力. `Sync` 由内部来源决定, `Async` 不支持列出, 只可读
+    pub fn capabilities(&self) -> Capabilities {
+        let c = Capabilities::READ_ONLY;
+        match self {
+            DataSource::StdReadFile => c,
+            #[cfg(feature = "tokio")]
+            DataSource::Async(_) => c,
+            DataSource::Folders(_) => c.with_listable().with_watchable(),
+            DataSource::TarInMemory(_) if cfg!(feature = "tar") => c.with_listable(),
+            DataSource::TarFile(_) if cfg!(feature = "tar") => c.with_listable().with_watchable(),
+            DataSource::TarInMemory(_) | DataSource::TarFile(_) => Capabilities::NONE,
+            DataSource::FileMap(_) | DataSource::Embedded(_) => c.with_listable(),
+            DataSource::Sync(s) => s.capabilities(),
+        }
+    }
+}
+
+/// 一个 cargo feature 是否编译进来
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureInfo {
+    pub name: &'static str,
+    pub enabled: bool,
+}
+
+/// 编译时开启的 feature, 见 [`capability_report`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapabilityReport {
+    pub version: &'static str,
+    pub features: Vec<FeatureInfo>,
+}
+
+macro_rules! features {
+    ($($f:literal),* $(,)?) => {
+        vec![$(FeatureInfo { name: $f, enabled: cfg!(feature = $f) }),*]
+    };
+}
+
+/// 本 crate 的版本和各 feature 是否开启, 可以在启动时记录到日志, 并在加载配置前用
+/// [`CapabilityReport::require`] 拒绝引用了未编译进来的后端的配置
+pub fn capability_report() -> CapabilityReport {
+    CapabilityReport {
+        version: env!("CARGO_PKG_VERSION"),
+        features: features![
+            "reqwest",
+            "tar",
+            "tokio",
+            "tokio-tar",
+            "file_server",
+            "cas",
+            "cache_meta",
+            "manifest",
+            "json_merge",
+            "extract",
+            "s3",
+            "azure",
+            "gcs",
+            "journal",
+            "keyring",
+            "dns_txt",
+            "hickory",
+            "encrypted",
+            "flate2",
+            "zstd",
+            "sftp",
+            "etcd",
+            "consul",
+            "ipfs",
+            "grpc",
+            "tonic",
+            "sqlite",
+            "unicode",
+            "test-util",
+        ],
+    }
+}
+
+impl CapabilityReport {
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f.name == feature && f.enabled)
+    }
+
+    pub fn enabled(&self) -> Vec<&'static str> {
+        self.features
+            .iter()
+            .filter(|f| f.enabled)
+            .map(|f| f.name)
+            .collect()
+    }
+
+    /// feature 未开启时返回 [`FetchError::FeatureDisabled`], 不存在的 feature 返回 [`FetchError::Invalid`]
+    pub fn require(&self, feature: &str) -> Result<(), FetchError> {
+        match self.features.iter().find(|f| f.name == feature) {
+            Some(f) if f.enabled => Ok(()),
+            Some(f) => Err(FetchError::FeatureDisabled(f.name)),
+            None => Err(FetchError::Invalid(format!("unknown feature `{feature}`"))),
+        }
+    }
+}
+
+/// `data-source 0.1.5 [reqwest, tar, ...]`
+impl std::fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "data-source {} [{}]",
+            self.version,
+            self.enabled().join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overlay::*;
+
+    #[test]
+    fn test_capabilities() {
+        let folders = DataSource::Folders(vec![".".to_string()]);
+        let c = folders.capabilities();
+        assert!(c.readable && c.listable && c.watchable && !c.writable);
+        assert!(!DataSource::StdReadFile.capabilities().listable);
+        assert!(DataSource::StdReadFile
+            .capabilities()
+            .require_listable()
+            .is_err());
+
+        let os = OverlaySource::new(vec![
+            OverlayLayer::new(folders),
+            OverlayLayer::new(DataSource::FileMap(HashMap::new())),
+        ]);
+        let c = DataSource::Sync(Box::new(os)).capabilities();
+        assert!(c.listable && !c.watchable && !c.writable);
+        let os = OverlaySource::new(vec![OverlayLayer::new(DataSource::StdReadFile)]);
+        assert!(!os.capabilities().listable);
+
+        let w = Capabilities::READ_ONLY.with_writable();
+        assert_eq!(w.read_only(), Capabilities::READ_ONLY);
+    }
+
+    #[test]
+    fn test_capability_report() {
+        let r = capability_report();
+        // 与 Cargo.toml 中声明的 feature 保持一致
+        let toml = include_str!("../Cargo.toml");
+        let section = toml.split("[features]").nth(1).unwrap();
+        let section = section.split("\n[").next().unwrap();
+        for line in section.lines() {
+            let Some((name, _)) = line.split_once(" = ") else {
+                continue;
+            };
+            if name != "default" {
+                assert!(r.features.iter().any(|f| f.name == name), "{name}");
+            }
+        }
+        assert_eq!(r.is_enabled("tar"), cfg!(feature = "tar"));
+        assert_eq!(r.require("s3").is_ok(), cfg!(feature = "s3"));
+        if !cfg!(feature = "s3") {
+            assert!(matches!(
+                r.require("s3"),
+                Err(FetchError::FeatureDisabled("s3"))
+            ));
+        }
+        assert!(matches!(r.require("nope"), Err(FetchError::Invalid(_))));
+        assert!(r.to_string().starts_with("data-source "));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::*;
+use std::sync::RwLock;
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    let d = ring::digest::digest(&ring::digest::SHA256, data);
+    hex::encode(d.as_ref())
+}
+
+/// 为可列出的来源 (见 [`SyncFolderSource::list_files`]) 建立 sha256 到文件名的索引,
+/// 以便按内容摘要读取文件, 不受上游改名的影响. 读到的内容会再次校验摘要
+#[derive(Debug)]
+pub struct HashIndexed<S> {
+    pub inner: S,
+    index: RwLock<HashMap<String, String>>,
+}
+
+impl<S: SyncFolderSource> HashIndexed<S> {
+    pub fn new(inner: S) -> Result<Self, FetchError> {
+        let s = Self {
+            inner,
+            index: RwLock::new(HashMap::new()),
+        };
+        s.reindex()?;
+        Ok(s)
+    }
+
+    /// 重新读取所有文件并建立索引
+    pub fn reindex(&self) -> Result<(), FetchError> {
+        let mut m = HashMap::new();
+        for f in self.inner.list_files()? {
+            let (d, _) = self.inner.get_file_content(Path::new(&f))?;
+            m.insert(sha256_hex(&d), f);
+        }
+        *self.index.write().unwrap_or_else(|e| e.into_inner()) = m;
+        Ok(())
+    }
+
+    /// 摘要对应的文件名
+    pub fn name_of(&self, sha256: &str) -> Option<String> {
+        self.index
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&sha256.to_ascii_lowercase())
+            .cloned()
+    }
+
+    pub fn get_by_hash(&self, sha256: &str) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let name = self
+            .name_of(sha256)
+            .ok_or_else(|| FetchError::not_found(sha256))?;
+        let r = self.inner.get_file_content(Path::new(&name))?;
+        if !sha256_hex(&r.0).eq_ignore_ascii_case(sha256) {
+            warn!("content of {name} changed since indexed");
+            return Err(FetchError::not_found(sha256));
+        }
+        Ok(r)
+    }
+}
+
+impl<S: SyncFolderSource> SyncFolderSource for HashIndexed<S> {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.inner.get_file_content(file_name)
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.inner.get_file_content_typed(file_name)
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.inner.capabilities().read_only()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        self.inner.list_files()
+    }
+}
+
+/// 以内容摘要为文件名的本地存储, 文件保存在 `dir/<前两位>/<sha256>`
+#[derive(Clone, Debug)]
+pub struct CasDir(pub String);
+
+impl CasDir {
+    fn path_of(&self, sha256: &str) -> Option<PathBuf> {
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let h = sha256.to_ascii_lowercase();
+        Some(Path::new(&self.0).join(&h[..2]).join(h))
+    }
+
+    /// 保存数据, 返回其 sha256
+    pub fn put(&self, data: &[u8]) -> Result<String, FetchError> {
+        let h = sha256_hex(data);
+        let p = self.path_of(&h).expect("valid digest");
+        if !p.exists() {
+            std::fs::create_dir_all(p.parent().unwrap())?;
+            std::fs::write(&p, data).map_err(|e| local_io_error(e, &p))?;
+        }
+        Ok(h)
+    }
+
+    /// 读取并校验, 文件损坏时返回 [`LookupError::NotFound`]
+    pub fn get_by_hash(&self, sha256: &str) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let p = self
+            .path_of(sha256)
+            .ok_or_else(|| FetchError::not_found(sha256))?;
+        if !p.exists() {
+            return Err(FetchError::not_found(sha256));
+        }
+        let d = read_local_file(&p)?;
+        if !sha256_hex(&d).eq_ignore_ascii_case(sha256) {
+            warn!("corrupted cas file {}", p.to_string_lossy());
+            return Err(FetchError::not_found(sha256));
+        }
+        Ok((d, Some(p.to_string_lossy().to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_get_by_hash() {
+        let ds = DataSource::FileMap(
+            vec![("a.txt".to_string(), SingleFileSource::Inline(b"a".to_vec()))]
+                .into_iter()
+                .collect(),
+        );
+        let h = sha256_hex(b"a");
+        let hi = HashIndexed::new(ds).unwrap();
+        assert_eq!(hi.get_by_hash(&h).unwrap().0, b"a");
+        assert_eq!(hi.name_of(&h.to_ascii_uppercase()).unwrap(), "a.txt");
+        assert!(matches!(
+            hi.get_by_hash(&sha256_hex(b"b")),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+
+        let temp_dir = TempDir::new().unwrap();
+        let cas = CasDir(temp_dir.path().to_string_lossy().to_string());
+        assert_eq!(cas.put(b"a").unwrap(), h);
+        assert_eq!(cas.get_by_hash(&h).unwrap().0, b"a");
+        assert!(matches!(
+            cas.get_by_hash("../x"),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+    }
+}
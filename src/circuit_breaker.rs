@@ -0,0 +1,268 @@
+use crate::*;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// 半开状态下已有一个探测请求在进行
+    probing: bool,
+}
+
+/// 允许请求上游的许可. 探测请求结束 (包括 future 被取消) 时清除探测标记
+struct Admit<'a> {
+    state: &'a Mutex<BreakerState>,
+    probe: bool,
+}
+
+impl Drop for Admit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.state.lock().unwrap_or_else(|e| e.into_inner()).probing = false;
+        }
+    }
+}
+
+/// 断路器: 上游连续失败 `failure_threshold` 次后断开, 在 `cooldown` 时间内不再请求上游,
+/// 直接返回 `fallback_cache` 中 (可能已过期的) 缓存, 没有缓存时直接返回
+/// [`FetchError::CircuitOpen`]. 冷却结束后进入半开状态, 只放行一个探测请求,
+/// 成功则闭合, 失败则重新断开; 探测期间的其它请求仍按断开处理.
+#[derive(Debug)]
+pub struct CircuitBreakerSource<S> {
+    pub inner: S,
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+    pub fallback_cache: Option<FileCache>,
+    state: Mutex<BreakerState>,
+}
+
+impl<S> CircuitBreakerSource<S> {
+    pub fn new(inner: S, failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner,
+            failure_threshold,
+            cooldown,
+            fallback_cache: None,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn with_fallback_cache(mut self, fc: FileCache) -> Self {
+        self.fallback_cache = Some(fc);
+        self
+    }
+
+    /// 是否处于冷却时间内, 不包括半开状态
+    pub fn is_open(&self) -> bool {
+        let st = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        st.opened_at.is_some_and(|t| t.elapsed() < self.cooldown)
+    }
+
+    /// 断开时返回 None; 半开时只有第一个请求得到许可, 作为探测请求
+    fn admit(&self) -> Option<Admit<'_>> {
+        let mut st = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let probe = match st.opened_at {
+            None => false,
+            Some(t) if t.elapsed() < self.cooldown => return None,
+            Some(_) if st.probing => return None,
+            Some(_) => {
+                st.probing = true;
+                true
+            }
+        };
+        Some(Admit {
+            state: &self.state,
+            probe,
+        })
+    }
+
+    fn on_result<T>(&self, r: &Result<T, FetchError>) {
+        let mut st = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match r {
+            Ok(_) => {
+                st.consecutive_failures = 0;
+                st.opened_at = None;
+            }
+            Err(_) => {
+                st.consecutive_failures += 1;
+                if st.consecutive_failures >= self.failure_threshold {
+                    if st.opened_at.is_none_or(|t| t.elapsed() >= self.cooldown) {
+                        warn!(
+                            "circuit breaker opened after {} failures",
+                            st.consecutive_failures
+                        );
+                    }
+                    st.opened_at = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    fn fallback(&self) -> Result<Vec<u8>, FetchError> {
+        match &self.fallback_cache {
+            Some(fc) if fc.is_cache_timeout()?.is_some() => fc.read_cache_file(),
+            _ => Err(FetchError::CircuitOpen),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn fallback_async(&self) -> Result<Vec<u8>, FetchError> {
+        match &self.fallback_cache {
+            Some(fc) if fc.is_cache_timeout()?.is_some() => fc.read_cache_file_async().await,
+            _ => Err(FetchError::CircuitOpen),
+        }
+    }
+}
+
+impl<S: SyncSource> SyncSource for CircuitBreakerSource<S> {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        let Some(_admit) = self.admit() else {
+            return self.fallback();
+        };
+        let r = self.inner.fetch();
+        self.on_result(&r);
+        r
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl<S: AsyncSource> AsyncSource for CircuitBreakerSource<S> {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        let Some(_admit) = self.admit() else {
+            return self.fallback_async().await;
+        };
+        let r = self.inner.fetch_async().await;
+        self.on_result(&r);
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::AtomicBool, atomic::AtomicUsize, mpsc};
+    use tempfile::TempDir;
+
+    #[derive(Default)]
+    struct Flaky {
+        calls: AtomicUsize,
+        ok: AtomicBool,
+        /// 设置后每次请求先等待一个信号, 用来让探测请求停在上游
+        hold: Option<Mutex<mpsc::Receiver<()>>>,
+    }
+
+    impl SyncSource for Flaky {
+        fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if let Some(rx) = &self.hold {
+                rx.lock().unwrap().recv().unwrap();
+            }
+            if self.ok.load(Ordering::SeqCst) {
+                Ok(b"fresh".to_vec())
+            } else {
+                Err(FetchError::not_found("x"))
+            }
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens() {
+        let cb = CircuitBreakerSource::new(Flaky::default(), 2, Duration::from_secs(60));
+        assert!(matches!(
+            cb.fetch(),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+        assert!(matches!(
+            cb.fetch(),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+        assert!(cb.is_open());
+        assert!(matches!(cb.fetch(), Err(FetchError::CircuitOpen)));
+        assert_eq!(cb.inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_circuit_breaker_stale_fallback() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("c");
+        std::fs::write(&path, "stale").unwrap();
+        // 缓存已过期, 断开时仍返回
+        let old = std::time::SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let fc = FileCache {
+            update_interval_seconds: Some(60),
+            cache_file_path: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let cb = CircuitBreakerSource::new(Flaky::default(), 1, Duration::from_secs(60))
+            .with_fallback_cache(fc);
+        assert!(cb.fetch().is_err());
+        assert_eq!(cb.fetch().unwrap(), b"stale");
+        assert_eq!(cb.inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_circuit_breaker_reprobe() {
+        let cooldown = Duration::from_millis(30);
+        let cb = CircuitBreakerSource::new(Flaky::default(), 1, cooldown);
+        assert!(cb.fetch().is_err());
+        assert!(matches!(cb.fetch(), Err(FetchError::CircuitOpen)));
+
+        // 冷却结束后探测失败, 重新断开
+        std::thread::sleep(cooldown + Duration::from_millis(10));
+        assert!(!cb.is_open());
+        assert!(matches!(
+            cb.fetch(),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+        assert!(cb.is_open());
+        assert!(matches!(cb.fetch(), Err(FetchError::CircuitOpen)));
+        assert_eq!(cb.inner.calls.load(Ordering::SeqCst), 2);
+
+        // 探测成功后闭合
+        std::thread::sleep(cooldown + Duration::from_millis(10));
+        cb.inner.ok.store(true, Ordering::SeqCst);
+        assert_eq!(cb.fetch().unwrap(), b"fresh");
+        assert_eq!(cb.fetch().unwrap(), b"fresh");
+        assert_eq!(cb.inner.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_circuit_breaker_single_probe() {
+        let cooldown = Duration::from_millis(30);
+        let (tx, rx) = mpsc::channel();
+        let flaky = Flaky {
+            hold: Some(Mutex::new(rx)),
+            ..Default::default()
+        };
+        let cb = CircuitBreakerSource::new(flaky, 1, cooldown);
+        tx.send(()).unwrap();
+        assert!(cb.fetch().is_err());
+        std::thread::sleep(cooldown + Duration::from_millis(10));
+
+        std::thread::scope(|s| {
+            let probe = s.spawn(|| cb.fetch());
+            while cb.inner.calls.load(Ordering::SeqCst) < 2 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            // 探测请求还没结束, 其它请求不访问上游
+            assert!(matches!(cb.fetch(), Err(FetchError::CircuitOpen)));
+            assert!(matches!(cb.fetch(), Err(FetchError::CircuitOpen)));
+            assert_eq!(cb.inner.calls.load(Ordering::SeqCst), 2);
+            cb.inner.ok.store(true, Ordering::SeqCst);
+            tx.send(()).unwrap();
+            assert_eq!(probe.join().unwrap().unwrap(), b"fresh");
+        });
+        tx.send(()).unwrap();
+        assert_eq!(cb.fetch().unwrap(), b"fresh");
+    }
+}
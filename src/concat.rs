@@ -0,0 +1,69 @@
+use crate::*;
+
+/// 依次读取多个来源并拼接, 如上游把一份规则列表拆成了多个文件.
+/// 设置了 `separator` 时, 在相邻两部分之间插入
+#[derive(Debug, Default)]
+pub struct ConcatSource {
+    pub parts: Vec<SingleFileSource>,
+    pub separator: Option<Vec<u8>>,
+}
+
+impl ConcatSource {
+    pub fn new(parts: Vec<SingleFileSource>) -> Self {
+        Self {
+            parts,
+            separator: None,
+        }
+    }
+
+    pub fn with_separator(mut self, sep: &[u8]) -> Self {
+        self.separator = Some(sep.to_vec());
+        self
+    }
+
+    fn join(&self, parts: Vec<Vec<u8>>) -> Vec<u8> {
+        match &self.separator {
+            Some(sep) => parts.join(sep.as_slice()),
+            None => parts.concat(),
+        }
+    }
+}
+
+impl SyncSource for ConcatSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        let parts = self
+            .parts
+            .iter()
+            .map(|p| p.fetch())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.join(parts))
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for ConcatSource {
+    /// 各部分同时读取
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        let parts =
+            futures::future::try_join_all(self.parts.iter().map(|p| p.fetch_async())).await?;
+        Ok(self.join(parts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat() {
+        let c = ConcatSource::new(vec![
+            SingleFileSource::Inline(b"a.com".to_vec()),
+            SingleFileSource::Inline(b"b.com".to_vec()),
+        ]);
+        assert_eq!(c.fetch().unwrap(), b"a.comb.com");
+        let c = c.with_separator(b"\n");
+        let sf = SingleFileSource::Concat(c);
+        assert_eq!(sf.fetch().unwrap(), b"a.com\nb.com");
+    }
+}
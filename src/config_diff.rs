@@ -0,0 +1,128 @@
+//! 重新加载配置时比较新旧 DataSource, 以便确认改动是否生效
+
+use crate::*;
+
+/// FileMap 中新增、删除和改变的键, 均已排序.
+/// 非 FileMap 的来源整体比较, 有变化时 `changed` 为 `["*"]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// 条目的配置 (而不是读到的内容) 的摘要
+fn fingerprint(sf: &SingleFileSource) -> String {
+    let mut s = explain::describe(sf);
+    match sf {
+        SingleFileSource::Annotated(inner, m) => {
+            s.push_str(&format!(" {m:?} {}", fingerprint(inner)))
+        }
+        SingleFileSource::Inline(d) => s.push_str(&format!(
+            " {:016x}",
+            stable_hash(&String::from_utf8_lossy(d))
+        )),
+        SingleFileSource::DataUri(u) | SingleFileSource::InlineBase64(u) => {
+            s.push_str(&format!(" {:016x}", stable_hash(u)))
+        }
+        _ => {}
+    }
+    s
+}
+
+fn source_fingerprint(ds: &DataSource) -> String {
+    match ds {
+        DataSource::StdReadFile => "std".to_string(),
+        DataSource::Folders(dirs) => format!("folders {dirs:?}"),
+        DataSource::TarInMemory(v) => {
+            format!(
+                "tar in memory {:016x}",
+                stable_hash(&String::from_utf8_lossy(v))
+            )
+        }
+        DataSource::TarFile(tf) => format!("tar {}", tf.0),
+        DataSource::FileMap(_) => "file map".to_string(),
+        DataSource::Embedded(e) => format!("embedded {:?}", e.names()),
+        DataSource::Sync(s) => format!("{s:?}"),
+        #[cfg(feature = "tokio")]
+        DataSource::Async(s) => format!("{s:?}"),
+    }
+}
+
+impl DataSource {
+    /// 从 self 换成 `newer` 时配置的变化, 不读取任何数据
+    pub fn config_diff(&self, newer: &DataSource) -> ConfigDiff {
+        let (DataSource::FileMap(old), DataSource::FileMap(new)) = (self, newer) else {
+            let mut d = ConfigDiff::default();
+            if source_fingerprint(self) != source_fingerprint(newer) {
+                d.changed.push("*".to_string());
+            }
+            return d;
+        };
+        let mut d = ConfigDiff {
+            added: new
+                .keys()
+                .filter(|k| !old.contains_key(*k))
+                .cloned()
+                .collect(),
+            removed: old
+                .keys()
+                .filter(|k| !new.contains_key(*k))
+                .cloned()
+                .collect(),
+            changed: old
+                .iter()
+                .filter(|(k, sf)| {
+                    new.get(*k)
+                        .is_some_and(|n| fingerprint(n) != fingerprint(sf))
+                })
+                .map(|(k, _)| k.clone())
+                .collect(),
+        };
+        d.added.sort();
+        d.removed.sort();
+        d.changed.sort();
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_diff() {
+        let old = DataSource::FileMap(HashMap::from([
+            ("a".to_string(), SingleFileSource::Inline(b"a".to_vec())),
+            (
+                "b".to_string(),
+                SingleFileSource::FilePath("b.txt".to_string()),
+            ),
+            ("c".to_string(), SingleFileSource::Env("C".to_string())),
+        ]));
+        let new = DataSource::FileMap(HashMap::from([
+            ("a".to_string(), SingleFileSource::Inline(b"b".to_vec())),
+            (
+                "b".to_string(),
+                SingleFileSource::FilePath("b.txt".to_string()),
+            ),
+            ("d".to_string(), SingleFileSource::Env("D".to_string())),
+        ]));
+        let d = old.config_diff(&new);
+        assert_eq!(d.added, vec!["d"]);
+        assert_eq!(d.removed, vec!["c"]);
+        assert_eq!(d.changed, vec!["a"]);
+        assert!(new.config_diff(&new).is_empty());
+
+        let f = |d: &str| DataSource::Folders(vec![d.to_string()]);
+        assert!(f("a").config_diff(&f("a")).is_empty());
+        assert_eq!(f("a").config_diff(&f("b")).changed, vec!["*"]);
+        assert_eq!(f("a").config_diff(&new).changed, vec!["*"]);
+    }
+}
@@ -0,0 +1,289 @@
+//! Consul KV 来源, 使用 Consul 的 HTTP API (`/v1/kv/<key>`)
+
+use crate::*;
+
+/// 到 Consul agent 的连接. `http.url` 为地址, 如 `http://127.0.0.1:8500`,
+/// 代理和自定义请求头的设置与 [`HttpSource`] 相同
+#[derive(Debug, Clone, Default)]
+pub struct ConsulClient {
+    pub http: HttpSource,
+    /// 为 None 时使用 agent 所在的数据中心
+    pub datacenter: Option<String>,
+}
+
+/// 键中除 `/` 外的保留字符按百分号编码
+fn encode_key(key: &str) -> String {
+    let mut r = String::with_capacity(key.len());
+    for b in key.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            r.push(b as char);
+        } else {
+            r.push_str(&format!("%{b:02X}"));
+        }
+    }
+    r
+}
+
+impl ConsulClient {
+    pub fn new(address: &str) -> Self {
+        Self {
+            http: HttpSource {
+                url: address.to_string(),
+                ..Default::default()
+            },
+            datacenter: None,
+        }
+    }
+
+    /// ACL token, 通过 `X-Consul-Token` 请求头发送
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.http
+            .custom_request_headers
+            .get_or_insert_with(Vec::new)
+            .push(("X-Consul-Token".to_string(), token.to_string()));
+        self
+    }
+
+    pub fn with_datacenter(mut self, dc: &str) -> Self {
+        self.datacenter = Some(dc.to_string());
+        self
+    }
+
+    /// `query` 为 `raw` 或 `keys`
+    pub fn url(&self, key: &str, query: &str) -> String {
+        let mut u = format!(
+            "{}/v1/kv/{}?{query}",
+            self.http.url.trim_end_matches('/'),
+            encode_key(key.trim_start_matches('/'))
+        );
+        if let Some(dc) = &self.datacenter {
+            u.push_str(&format!("&dc={}", encode_key(dc)));
+        }
+        u
+    }
+
+    /// 404 时返回 None
+    fn check_response(
+        url: &str,
+        status: reqwest::StatusCode,
+        body: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, FetchError> {
+        match status {
+            s if s.is_success() => Ok(Some(body)),
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            reqwest::StatusCode::FORBIDDEN => {
+                Err(FetchError::Lookup(LookupError::Denied(url.to_string())))
+            }
+            s => Err(HttpError::Status {
+                url: url.to_string(),
+                status: s.as_u16(),
+            }
+            .into()),
+        }
+    }
+
+    fn request(&self, url: &str) -> Result<Option<Vec<u8>>, FetchError> {
+        let mut hs = self.http.clone();
+        hs.url = url.to_string();
+        let r = hs.send()?;
+        let status = r.status();
+        Self::check_response(url, status, read_body(r)?)
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn request_async(&self, url: &str) -> Result<Option<Vec<u8>>, FetchError> {
+        let mut hs = self.http.clone();
+        hs.url = url.to_string();
+        let r = hs.send_async().await?;
+        let status = r.status();
+        Self::check_response(url, status, read_body_async(r).await?)
+    }
+
+    /// 读取单个键的值
+    pub fn get(&self, key: &str) -> Result<Vec<u8>, FetchError> {
+        self.request(&self.url(key, "raw"))?
+            .ok_or_else(|| FetchError::not_found(key))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn get_async(&self, key: &str) -> Result<Vec<u8>, FetchError> {
+        self.request_async(&self.url(key, "raw"))
+            .await?
+            .ok_or_else(|| FetchError::not_found(key))
+    }
+
+    /// 以 `prefix` 开头的所有键, 没有时为空
+    pub fn keys(&self, prefix: &str) -> Result<Vec<String>, FetchError> {
+        let Some(body) = self.request(&self.url(prefix, "keys"))? else {
+            return Ok(Vec::new());
+        };
+        serde_json::from_slice(&body).map_err(|e| FetchError::Invalid(format!("consul: {e}")))
+    }
+}
+
+/// 以 `prefix` 下的键作为目录, 文件名即去掉前缀的键.
+/// 如 prefix 为 `routing/` 时, `rules/a.json` 对应键 `routing/rules/a.json`.
+/// 以 `/` 结尾的键 (在 Consul UI 中表示目录) 不会被列出
+#[derive(Debug, Clone, Default)]
+pub struct ConsulFolderSource {
+    pub client: ConsulClient,
+    pub prefix: String,
+}
+
+impl ConsulFolderSource {
+    pub fn new(client: ConsulClient, prefix: &str) -> Self {
+        Self {
+            client,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// 含有 `.` 或 `..` 的路径会被 url 规范化改写, 返回 None
+    pub fn key(&self, file_name: &Path) -> Option<String> {
+        let p = normalize_separators(file_name);
+        let p = p.to_string_lossy();
+        let p = p.trim_start_matches("./");
+        if p.split('/').any(|c| c == "." || c == "..") {
+            return None;
+        }
+        Some(format!("{}{p}", self.prefix))
+    }
+
+    fn key_or_not_found(&self, file_name: &Path) -> Result<String, FetchError> {
+        self.key(file_name)
+            .ok_or_else(|| FetchError::not_found(file_name.to_string_lossy()))
+    }
+}
+
+impl SyncFolderSource for ConsulFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let k = self.key_or_not_found(file_name)?;
+        Ok((self.client.get(&k)?, Some(k)))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY.with_listable()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        let mut v: Vec<String> = self
+            .client
+            .keys(&self.prefix)?
+            .into_iter()
+            .filter_map(|k| {
+                let name = k.strip_prefix(&self.prefix)?;
+                (!name.is_empty() && !name.ends_with('/')).then(|| name.to_string())
+            })
+            .collect();
+        v.sort();
+        Ok(v)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for ConsulFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let k = self.key_or_not_found(file_name)?;
+        Ok((self.client.get_async(&k).await?, Some(k)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// 只实现了 `?raw` 和 `?keys` 的 Consul, 要求 token 为 `t`
+    fn serve(kvs: &'static [(&'static str, &'static str)]) -> String {
+        let l = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", l.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let mut lines = BufReader::new(&s).lines().map(|l| l.unwrap());
+                let req = lines.next().unwrap();
+                let authorized = lines
+                    .take_while(|l| !l.is_empty())
+                    .any(|l| l.eq_ignore_ascii_case("x-consul-token: t"));
+                let target = req.split(' ').nth(1).unwrap();
+                let (path, query) = target.split_once('?').unwrap();
+                let key = path.strip_prefix("/v1/kv/").unwrap().replace("%20", " ");
+                let (status, body) = if !authorized {
+                    ("403 Forbidden", String::new())
+                } else if query.starts_with("keys") {
+                    let keys: Vec<_> = kvs.iter().filter(|kv| kv.0.starts_with(&key)).collect();
+                    if keys.is_empty() {
+                        ("404 Not Found", String::new())
+                    } else {
+                        let keys: Vec<_> = keys.iter().map(|kv| kv.0).collect();
+                        ("200 OK", serde_json::to_string(&keys).unwrap())
+                    }
+                } else {
+                    match kvs.iter().find(|kv| kv.0 == key) {
+                        Some(kv) => ("200 OK", kv.1.to_string()),
+                        None => ("404 Not Found", String::new()),
+                    }
+                };
+                write!(
+                    s,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    const KVS: &[(&str, &str)] = &[
+        ("routing/", ""),
+        ("routing/geo ip.dat", "geo"),
+        ("routing/rules/a.json", "{}"),
+        ("other", "x"),
+    ];
+
+    #[test]
+    fn test_consul_folder_source() {
+        let url = serve(KVS);
+        let c = ConsulClient::new(&url).with_token("t");
+        assert_eq!(
+            c.clone().with_datacenter("dc 1").url("/a b", "raw"),
+            format!("{url}/v1/kv/a%20b?raw&dc=dc%201")
+        );
+        let fs = ConsulFolderSource::new(c.clone(), "routing/");
+        assert_eq!(fs.list_files().unwrap(), vec!["geo ip.dat", "rules/a.json"]);
+        let r = fs.get_file_content(Path::new("geo ip.dat")).unwrap();
+        assert_eq!(r, (b"geo".to_vec(), Some("routing/geo ip.dat".to_string())));
+        assert!(fs
+            .get_file_content(Path::new("nope"))
+            .unwrap_err()
+            .is_not_found());
+        assert!(fs.key(Path::new("rules/../../other")).is_none());
+        assert!(ConsulFolderSource::new(c.clone(), "none/")
+            .list_files()
+            .unwrap()
+            .is_empty());
+
+        let fs = ConsulFolderSource::new(ConsulClient::new(&url), "routing/");
+        let e = fs.get_file_content(Path::new("geo ip.dat")).unwrap_err();
+        assert_eq!(e.kind(), FetchErrorKind::PermissionDenied);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_consul_async() {
+        let url = serve(KVS);
+        let fs = ConsulFolderSource::new(ConsulClient::new(&url).with_token("t"), "routing/");
+        let ds = DataSource::Async(Box::new(fs));
+        let r = ds
+            .get_file_content_async(Path::new("rules/a.json"))
+            .await
+            .unwrap();
+        assert_eq!(r.0, b"{}");
+    }
+}
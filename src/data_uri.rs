@@ -0,0 +1,69 @@
+//! `data:` URI (RFC 2397), 用于把小文件直接写在配置字符串中,
+//! 如 `data:text/plain;base64,aGVsbG8=` 或 `data:,hello%20world`
+
+use crate::*;
+
+/// `data:` URI 中的内容
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataUri {
+    /// 如 `text/plain;charset=utf-8`, 省略时为 None
+    pub media_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+pub fn is_data_uri(s: &str) -> bool {
+    s.get(..5).is_some_and(|p| p.eq_ignore_ascii_case("data:"))
+}
+
+/// 解析 `data:[<media type>][;base64],<data>`
+pub fn parse(uri: &str) -> Result<DataUri, FetchError> {
+    let invalid = |m: &str| FetchError::Invalid(format!("invalid data uri: {m}"));
+    if !is_data_uri(uri) {
+        return Err(invalid("missing `data:` scheme"));
+    }
+    let (header, payload) = uri[5..]
+        .split_once(',')
+        .ok_or_else(|| invalid("missing `,`"))?;
+    let (media_type, is_base64) = match header.rsplit_once(';') {
+        Some((m, p)) if p.eq_ignore_ascii_case("base64") => (m, true),
+        _ => (header, false),
+    };
+    let data = hex::percent_decode(payload);
+    let data = if is_base64 {
+        let s: String = String::from_utf8_lossy(&data)
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        base64::decode(&s).ok_or_else(|| invalid("bad base64"))?
+    } else {
+        data
+    };
+    Ok(DataUri {
+        media_type: (!media_type.is_empty()).then(|| media_type.to_string()),
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_data_uri() {
+        let d = parse("data:text/plain;charset=utf-8;base64,aGVs%0AbG8=").unwrap();
+        assert_eq!(d.media_type.as_deref(), Some("text/plain;charset=utf-8"));
+        assert_eq!(d.data, b"hello");
+        let d = parse("DATA:,a%20b,c").unwrap();
+        assert_eq!(d.media_type, None);
+        assert_eq!(d.data, b"a b,c");
+        assert!(parse("data:;base64,a*b").is_err());
+        assert!(parse("data:text/plain").is_err());
+        assert!(parse("http://x").is_err());
+
+        let sf = SingleFileSource::DataUri("data:application/json,{}".to_string());
+        assert_eq!(sf.fetch().unwrap(), b"{}");
+        let ds = DataSource::FileMap(HashMap::from([("a".to_string(), sf)]));
+        let fc = ds.get_file_content_typed(Path::new("a")).unwrap();
+        assert_eq!(fc.content_type.as_deref(), Some("application/json"));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::*;
+use reqwest::dns::{Name, Resolve, Resolving};
+use std::sync::{Arc, RwLock};
+
+/// HttpSource 使用的域名解析器. 系统解析器不可靠时, 可以包装一个实现了
+/// [`reqwest::dns::Resolve`] 的解析器. 开启 hickory feature 时可以用 [`DnsResolver::doh`]
+/// 和 [`DnsResolver::dot`] 构造 DoH / DoT 解析器
+#[derive(Clone)]
+pub struct DnsResolver(pub Arc<dyn Resolve>);
+
+impl DnsResolver {
+    pub fn new<R: Resolve + 'static>(r: R) -> Self {
+        Self(Arc::new(r))
+    }
+}
+
+impl std::fmt::Debug for DnsResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DnsResolver")
+    }
+}
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        self.0.resolve(name)
+    }
+}
+
+/// 基于 hickory-resolver 的解析器
+#[cfg(feature = "hickory")]
+struct Hickory(hickory_resolver::TokioAsyncResolver);
+
+#[cfg(feature = "hickory")]
+impl Resolve for Hickory {
+    fn resolve(&self, name: Name) -> Resolving {
+        let r = self.0.clone();
+        Box::pin(async move {
+            let ips = r.lookup_ip(name.as_str()).await?;
+            // 端口由 reqwest 按 url 设置
+            let addrs: Vec<_> = ips
+                .iter()
+                .map(|ip| std::net::SocketAddr::new(ip, 0))
+                .collect();
+            Ok(Box::new(addrs.into_iter())
+                as Box<dyn Iterator<Item = std::net::SocketAddr> + Send>)
+        })
+    }
+}
+
+#[cfg(feature = "hickory")]
+impl DnsResolver {
+    /// 按 hickory-resolver 的配置解析, 如 `ResolverConfig::cloudflare_https()`
+    pub fn hickory(
+        config: hickory_resolver::config::ResolverConfig,
+        opts: hickory_resolver::config::ResolverOpts,
+    ) -> Self {
+        Self::new(Hickory(hickory_resolver::TokioAsyncResolver::tokio(
+            config, opts,
+        )))
+    }
+
+    /// 通过 DNS over HTTPS 查询 `servers` 的 443 端口, `tls_name` 为服务器证书中的名称,
+    /// 如 `DnsResolver::doh(&["1.1.1.1".parse()?], "cloudflare-dns.com")`
+    pub fn doh(servers: &[std::net::IpAddr], tls_name: &str) -> Self {
+        use hickory_resolver::config::*;
+        let group = NameServerConfigGroup::from_ips_https(servers, 443, tls_name.to_string(), true);
+        Self::hickory(
+            ResolverConfig::from_parts(None, Vec::new(), group),
+            ResolverOpts::default(),
+        )
+    }
+
+    /// 通过 DNS over TLS 查询 `servers` 的 853 端口, `tls_name` 与 [`Self::doh`] 相同
+    pub fn dot(servers: &[std::net::IpAddr], tls_name: &str) -> Self {
+        use hickory_resolver::config::*;
+        let group = NameServerConfigGroup::from_ips_tls(servers, 853, tls_name.to_string(), true);
+        Self::hickory(
+            ResolverConfig::from_parts(None, Vec::new(), group),
+            ResolverOpts::default(),
+        )
+    }
+}
+
+static DEFAULT_RESOLVER: RwLock<Option<DnsResolver>> = RwLock::new(None);
+
+/// 设置所有未指定 `dns_resolver` 的 HttpSource 使用的解析器, None 表示使用系统解析器
+pub fn set_default_dns_resolver(r: Option<DnsResolver>) {
+    *DEFAULT_RESOLVER.write().unwrap_or_else(|e| e.into_inner()) = r;
+}
+
+impl HttpSource {
+    fn resolver(&self) -> Option<DnsResolver> {
+        self.dns_resolver.clone().or_else(|| {
+            DEFAULT_RESOLVER
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone()
+        })
+    }
+
+    pub(crate) fn blocking_client_builder(&self) -> reqwest::blocking::ClientBuilder {
+        let cb = reqwest::blocking::ClientBuilder::new();
+        match self.resolver() {
+            Some(r) => cb.dns_resolver(Arc::new(r)),
+            None => cb,
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn async_client_builder(&self) -> reqwest::ClientBuilder {
+        let cb = reqwest::ClientBuilder::new();
+        match self.resolver() {
+            Some(r) => cb.dns_resolver(Arc::new(r)),
+            None => cb,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+
+    struct Fixed(SocketAddr);
+
+    impl Resolve for Fixed {
+        fn resolve(&self, _name: Name) -> Resolving {
+            let a = self.0;
+            Box::pin(async move {
+                Ok(Box::new(std::iter::once(a)) as Box<dyn Iterator<Item = SocketAddr> + Send>)
+            })
+        }
+    }
+
+    #[test]
+    fn test_custom_resolver() {
+        let l = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = l.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut s, _) = l.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = s.read(&mut buf).unwrap();
+            s.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .unwrap();
+        });
+
+        let hs = HttpSource {
+            url: format!("http://rules.invalid:{}/a", addr.port()),
+            dns_resolver: Some(DnsResolver::new(Fixed(addr))),
+            ..Default::default()
+        };
+        assert_eq!(hs.fetch().unwrap(), b"ok");
+        server.join().unwrap();
+    }
+
+    /// hosts 文件中的名称不经过 DoH / DoT 服务器, 不需要网络
+    #[cfg(feature = "hickory")]
+    #[tokio::test]
+    async fn test_hickory_hosts_file() {
+        let localhost: std::net::IpAddr = "127.0.0.1".parse().unwrap();
+        for r in [
+            DnsResolver::doh(&[localhost], "localhost"),
+            DnsResolver::dot(&[localhost], "localhost"),
+        ] {
+            let addrs: Vec<_> = r
+                .resolve("localhost".parse().unwrap())
+                .await
+                .unwrap()
+                .collect();
+            assert!(addrs.iter().any(|a| a.ip().is_loopback()));
+        }
+    }
+}
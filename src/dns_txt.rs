@@ -0,0 +1,338 @@
+//! 以域名的 TXT 记录作为文件内容, 适合放置很小的 (签名过的) 启动配置.
+//!
+//! 直接向 DNS 服务器发送 UDP 查询, 响应被截断时改用 TCP. 一条记录中的多个字符串
+//! (每个最多 255 字节) 总是按顺序拼接; 内容需要拆成多条记录时, 见 [`DnsTxtSource::chunked`]
+
+use crate::*;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
+
+const TYPE_TXT: u16 = 16;
+const CLASS_IN: u16 = 1;
+const FLAG_TC: u16 = 0x0200;
+const RCODE_NXDOMAIN: u16 = 3;
+
+#[derive(Debug, Clone)]
+pub struct DnsTxtSource {
+    pub name: String,
+    /// 为空时使用 `/etc/resolv.conf` 中的第一个 nameserver
+    pub server: Option<SocketAddr>,
+    pub timeout: Duration,
+    /// 为 true 时每条记录以 `序号:` 开头 (如 `0:...`, `1:...`), 按序号拼接各条记录,
+    /// 序号必须从 0 开始连续. 为 false 时必须恰好有一条记录
+    pub chunked: bool,
+}
+
+impl DnsTxtSource {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.trim_end_matches('.').to_string(),
+            server: None,
+            timeout: Duration::from_secs(5),
+            chunked: false,
+        }
+    }
+
+    pub fn with_server(mut self, server: SocketAddr) -> Self {
+        self.server = Some(server);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_chunked(mut self) -> Self {
+        self.chunked = true;
+        self
+    }
+
+    fn server(&self) -> Result<SocketAddr, FetchError> {
+        if let Some(s) = self.server {
+            return Ok(s);
+        }
+        let conf = std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default();
+        conf.lines()
+            .filter_map(|l| l.trim().strip_prefix("nameserver"))
+            .find_map(|a| a.trim().parse::<std::net::IpAddr>().ok())
+            .map(|ip| SocketAddr::new(ip, 53))
+            .ok_or(FetchError::Unsupported("dns_txt without nameserver"))
+    }
+
+    /// 所有 TXT 记录, 每条记录的字符串已拼接
+    pub fn records(&self) -> Result<Vec<Vec<u8>>, FetchError> {
+        let server = self.server()?;
+        let id = query_id();
+        let q = encode_query(&self.name, id)?;
+        let udp = UdpSocket::bind(if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        })?;
+        udp.set_read_timeout(Some(self.timeout))?;
+        udp.send_to(&q, server)?;
+        let mut buf = vec![0; 4096];
+        loop {
+            let (n, from) = udp.recv_from(&mut buf)?;
+            if from != server || n < 2 || buf[..2] != id.to_be_bytes() {
+                continue;
+            }
+            if let Some(r) = parse_response(&buf[..n], id, &self.name)? {
+                return Ok(r);
+            }
+            break;
+        }
+        debug!(
+            "dns response for {} truncated, retrying over tcp",
+            self.name
+        );
+        let mut tcp = TcpStream::connect_timeout(&server, self.timeout)?;
+        tcp.set_read_timeout(Some(self.timeout))?;
+        tcp.set_write_timeout(Some(self.timeout))?;
+        let mut msg = (q.len() as u16).to_be_bytes().to_vec();
+        msg.extend_from_slice(&q);
+        tcp.write_all(&msg)?;
+        let mut len = [0; 2];
+        tcp.read_exact(&mut len)?;
+        let mut resp = vec![0; u16::from_be_bytes(len) as usize];
+        tcp.read_exact(&mut resp)?;
+        parse_response(&resp, id, &self.name)?
+            .ok_or_else(|| FetchError::Invalid(format!("{}: truncated tcp response", self.name)))
+    }
+
+    fn join(&self, records: Vec<Vec<u8>>) -> Result<Vec<u8>, FetchError> {
+        let invalid = |m: &str| FetchError::Invalid(format!("TXT {}: {m}", self.name));
+        if records.is_empty() {
+            return Err(FetchError::not_found(&self.name));
+        }
+        if !self.chunked {
+            return match <[Vec<u8>; 1]>::try_from(records) {
+                Ok([r]) => Ok(r),
+                Err(rs) => Err(invalid(&format!("{} records, expected 1", rs.len()))),
+            };
+        }
+        let mut chunks = records
+            .into_iter()
+            .map(|r| {
+                let i = r.iter().position(|b| *b == b':')?;
+                let n = std::str::from_utf8(&r[..i]).ok()?.parse::<usize>().ok()?;
+                Some((n, r[i + 1..].to_vec()))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("chunk without `index:` prefix"))?;
+        chunks.sort_by_key(|(n, _)| *n);
+        if chunks.iter().enumerate().any(|(i, (n, _))| i != *n) {
+            return Err(invalid("missing or duplicate chunk"));
+        }
+        Ok(chunks.into_iter().flat_map(|(_, d)| d).collect())
+    }
+}
+
+fn query_id() -> u16 {
+    static N: AtomicU64 = AtomicU64::new(0);
+    let t = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u64;
+    (t ^ N.fetch_add(0x9e37, Ordering::Relaxed)) as u16
+}
+
+/// 带 RD 标志的 TXT 查询
+fn encode_query(name: &str, id: u16) -> Result<Vec<u8>, FetchError> {
+    let mut q = id.to_be_bytes().to_vec();
+    q.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(FetchError::Invalid(format!("invalid domain name `{name}`")));
+        }
+        q.push(label.len() as u8);
+        q.extend_from_slice(label.as_bytes());
+    }
+    q.push(0);
+    q.extend_from_slice(&TYPE_TXT.to_be_bytes());
+    q.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(q)
+}
+
+fn u16_at(d: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*d.get(pos)?, *d.get(pos + 1)?]))
+}
+
+/// 跳过 (可能压缩的) 域名, 返回其后的位置
+fn skip_name(d: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let l = *d.get(pos)?;
+        if l & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        }
+        if l == 0 {
+            return Some(pos + 1);
+        }
+        pos += 1 + l as usize;
+    }
+}
+
+/// 解析响应中的 TXT 记录, 响应被截断时返回 None
+fn parse_response(d: &[u8], id: u16, name: &str) -> Result<Option<Vec<Vec<u8>>>, FetchError> {
+    let malformed = || FetchError::Invalid(format!("malformed dns response for {name}"));
+    let (rid, flags) = (u16_at(d, 0), u16_at(d, 2));
+    let (Some(rid), Some(flags)) = (rid, flags) else {
+        return Err(malformed());
+    };
+    if rid != id || flags & 0x8000 == 0 {
+        return Err(malformed());
+    }
+    if flags & FLAG_TC != 0 {
+        return Ok(None);
+    }
+    match flags & 0xf {
+        0 => {}
+        RCODE_NXDOMAIN => return Err(FetchError::not_found(name)),
+        rcode => {
+            return Err(FetchError::I(io::Error::other(format!(
+                "dns query for {name} failed with rcode {rcode}"
+            ))))
+        }
+    }
+    let qd = u16_at(d, 4).ok_or_else(malformed)?;
+    let an = u16_at(d, 6).ok_or_else(malformed)?;
+    let mut pos = 12;
+    for _ in 0..qd {
+        pos = skip_name(d, pos).ok_or_else(malformed)? + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..an {
+        pos = skip_name(d, pos).ok_or_else(malformed)?;
+        let ty = u16_at(d, pos).ok_or_else(malformed)?;
+        let len = u16_at(d, pos + 8).ok_or_else(malformed)? as usize;
+        pos += 10;
+        let rdata = d.get(pos..pos + len).ok_or_else(malformed)?;
+        pos += len;
+        if ty != TYPE_TXT {
+            continue;
+        }
+        let mut r = Vec::new();
+        let mut i = 0;
+        while i < rdata.len() {
+            let l = rdata[i] as usize;
+            r.extend_from_slice(rdata.get(i + 1..i + 1 + l).ok_or_else(malformed)?);
+            i += 1 + l;
+        }
+        records.push(r);
+    }
+    Ok(Some(records))
+}
+
+impl SyncSource for DnsTxtSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.join(self.records()?)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for DnsTxtSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        let s = self.clone();
+        tokio::task::spawn_blocking(move || s.fetch())
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// 对查询 `q` 的响应, 包含 `records` 中的 TXT 记录
+    fn response(q: &[u8], flags: u16, records: &[&[&[u8]]]) -> Vec<u8> {
+        let mut r = q[..2].to_vec();
+        r.extend_from_slice(&flags.to_be_bytes());
+        r.extend_from_slice(&[0, 1]);
+        r.extend_from_slice(&(records.len() as u16).to_be_bytes());
+        r.extend_from_slice(&[0, 0, 0, 0]);
+        r.extend_from_slice(&q[12..]);
+        for strings in records {
+            let rdata: Vec<u8> = strings
+                .iter()
+                .flat_map(|s| std::iter::once(s.len() as u8).chain(s.iter().copied()))
+                .collect();
+            r.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60]);
+            r.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            r.extend_from_slice(&rdata);
+        }
+        r
+    }
+
+    /// 按查询的域名返回记录: `trunc.test` 的 UDP 响应被截断, 需通过 TCP 读取
+    fn serve() -> SocketAddr {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = udp.local_addr().unwrap();
+        let tcp = TcpListener::bind(addr).unwrap();
+        let answer = |q: &[u8]| {
+            let name = String::from_utf8_lossy(&q[12..]).to_string();
+            let big = [b'x'; 255];
+            if name.contains("single") {
+                response(q, 0x8180, &[&[b"hello ", b"world"]])
+            } else if name.contains("chunks") {
+                response(q, 0x8180, &[&[b"1:b"], &[b"0:a"], &[b"2:", b"c"]])
+            } else if name.contains("trunc") {
+                response(q, 0x8180, &[&[&big, &big]])
+            } else {
+                response(q, 0x8183, &[])
+            }
+        };
+        std::thread::spawn(move || {
+            let mut buf = [0; 512];
+            loop {
+                let (n, from) = udp.recv_from(&mut buf).unwrap();
+                let q = &buf[..n];
+                let r = if String::from_utf8_lossy(q).contains("trunc") {
+                    response(q, 0x8380, &[])
+                } else {
+                    answer(q)
+                };
+                udp.send_to(&r, from).unwrap();
+            }
+        });
+        std::thread::spawn(move || {
+            for s in tcp.incoming() {
+                let mut s = s.unwrap();
+                let mut len = [0; 2];
+                s.read_exact(&mut len).unwrap();
+                let mut q = vec![0; u16::from_be_bytes(len) as usize];
+                s.read_exact(&mut q).unwrap();
+                let r = answer(&q);
+                s.write_all(&(r.len() as u16).to_be_bytes()).unwrap();
+                s.write_all(&r).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_dns_txt_source() {
+        let addr = serve();
+        let s = |name: &str| DnsTxtSource::new(name).with_server(addr);
+        assert_eq!(s("single.test.").fetch().unwrap(), b"hello world");
+        assert_eq!(s("chunks.test").with_chunked().fetch().unwrap(), b"abc");
+        assert!(matches!(
+            s("chunks.test").fetch().unwrap_err(),
+            FetchError::Invalid(_)
+        ));
+        assert_eq!(s("trunc.test").fetch().unwrap().len(), 510);
+        assert!(s("missing.test").fetch().unwrap_err().is_not_found());
+        assert!(s("bad..name").fetch().is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_dns_txt_source_async() {
+        let s = DnsTxtSource::new("single.test").with_server(serve());
+        assert_eq!(s.fetch_async().await.unwrap(), b"hello world");
+    }
+}
@@ -0,0 +1,75 @@
+//! 编译时嵌入到程序中的文件, 见 [`DataSource::Embedded`] 和 [`embed_files!`].
+//!
+//! 需要在运行时覆盖默认文件时, 用 [`overlay::OverlaySource`] 把 `DataSource::Folders`
+//! 等放在嵌入文件的上层
+
+use crate::*;
+
+/// 文件名 (以 `/` 分隔) 和内容, 通常由 [`embed_files!`] 生成
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmbeddedFiles(pub &'static [(&'static str, &'static [u8])]);
+
+impl EmbeddedFiles {
+    pub fn get(&self, file_name: &Path) -> Option<(&'static str, &'static [u8])> {
+        let p = normalize_separators(file_name);
+        let p = p.to_string_lossy();
+        let p = p.trim_start_matches("./");
+        self.0.iter().find(|(n, _)| *n == p).copied()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.0.iter().map(|(n, _)| n.to_string()).collect()
+    }
+}
+
+/// 用 `include_bytes!` 嵌入 `dir` 目录中列出的文件. `dir` 与 `include_bytes!` 一样
+/// 相对于调用所在的源文件, 也可以用 `concat!(env!("CARGO_MANIFEST_DIR"), "/assets")`
+///
+/// ```ignore
+/// let ds = DataSource::Embedded(embed_files!("../assets", ["config.json", "rules/a.json"]));
+/// ```
+#[macro_export]
+macro_rules! embed_files {
+    ($dir:expr, [$($name:literal),* $(,)?]) => {
+        $crate::embedded::EmbeddedFiles(&[
+            $(($name, include_bytes!(concat!($dir, "/", $name)) as &[u8])),*
+        ])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overlay::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_embedded() {
+        let ds = DataSource::Embedded(embed_files!(".", ["embedded.rs", "base64.rs"]));
+        assert_eq!(ds.list_files().unwrap(), vec!["embedded.rs", "base64.rs"]);
+        let (d, p) = ds.get_file_content(Path::new("./embedded.rs")).unwrap();
+        assert!(d.starts_with(b"//!"));
+        assert_eq!(p.as_deref(), Some("embedded.rs"));
+        assert!(matches!(
+            ds.get_file_content_ref(Path::new("embedded.rs")).unwrap().0,
+            Cow::Borrowed(_)
+        ));
+        assert!(ds
+            .get_file_content(Path::new("lib.rs"))
+            .unwrap_err()
+            .is_not_found());
+
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("embedded.rs"), "override").unwrap();
+        let os = OverlaySource::new(vec![
+            OverlayLayer::new(DataSource::Folders(vec![temp_dir
+                .path()
+                .to_string_lossy()
+                .to_string()])),
+            OverlayLayer::new(ds),
+        ]);
+        let (d, _) = os.get_file_content(Path::new("embedded.rs")).unwrap();
+        assert_eq!(d, b"override");
+        assert!(os.get_file_content(Path::new("base64.rs")).is_ok());
+    }
+}
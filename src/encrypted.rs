@@ -0,0 +1,206 @@
+use crate::*;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// 读取时解密的来源, 用于通过 http 等分发加密的配置包.
+///
+/// 使用 AES-256-GCM, 数据格式为 12 字节 nonce 后接密文和 tag (与 [`encrypt`] 的输出相同).
+/// 密钥从 `key` 读取, 可以是 32 字节原始数据 (按原样使用, 空白也是密钥的一部分)
+/// 或 64 个十六进制字符 (忽略首尾空白, 以便从带换行的文件读取)
+#[derive(Debug)]
+pub struct EncryptedSource {
+    pub inner: Box<SingleFileSource>,
+    pub key: Box<SingleFileSource>,
+}
+
+impl EncryptedSource {
+    pub fn new(inner: SingleFileSource, key: SingleFileSource) -> Self {
+        Self {
+            inner: Box::new(inner),
+            key: Box::new(key),
+        }
+    }
+
+    /// 内部来源的路径, 去掉 `.enc` 后缀, 以便按原始文件名推断 content type
+    pub fn path(&self) -> Option<String> {
+        self.inner
+            .get_path()
+            .map(|p| p.strip_suffix(".enc").map(str::to_string).unwrap_or(p))
+    }
+}
+
+fn parse_key(k: &[u8]) -> Result<LessSafeKey, FetchError> {
+    let invalid = || FetchError::Invalid("invalid AES-256 key".to_string());
+    let t = k.trim_ascii();
+    // 原始密钥不去除空白, 否则以空白开头或结尾的随机密钥会被截断
+    let raw: Vec<u8> = if k.len() == 32 {
+        k.to_vec()
+    } else if t.len() == 64 {
+        hex::decode(t).ok_or_else(invalid)?
+    } else {
+        return Err(invalid());
+    };
+    let k = UnboundKey::new(&AES_256_GCM, &raw).map_err(|_| invalid())?;
+    Ok(LessSafeKey::new(k))
+}
+
+/// 解密 [`encrypt`] 的输出, 密钥错误或数据被篡改时返回 [`FetchError::Invalid`]
+pub fn decrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, FetchError> {
+    open(&parse_key(key)?, data, b"")
+}
+
+pub(crate) fn check_key(key: &[u8]) -> Result<(), FetchError> {
+    parse_key(key).map(drop)
+}
+
+/// 加密缓存文件, 缓存名作为附加数据, 使一个缓存文件不能被替换成同一密钥加密的另一个
+pub(crate) fn encrypt_cache(key: &[u8], data: &[u8], name: &str) -> Result<Vec<u8>, FetchError> {
+    seal(&parse_key(key)?, data, name.as_bytes())
+}
+
+/// 解密 [`encrypt_cache`] 的输出, 密钥无效时返回错误, 数据无法解密或缓存名不符时返回 None
+pub(crate) fn decrypt_cache(
+    key: &[u8],
+    data: &[u8],
+    name: &str,
+) -> Result<Option<Vec<u8>>, FetchError> {
+    Ok(open(&parse_key(key)?, data, name.as_bytes()).ok())
+}
+
+fn open(k: &LessSafeKey, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, FetchError> {
+    if data.len() < NONCE_LEN {
+        return Err(FetchError::Invalid("encrypted data too short".to_string()));
+    }
+    let (n, c) = data.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(n)
+        .map_err(|_| FetchError::Invalid("invalid nonce".to_string()))?;
+    let mut buf = c.to_vec();
+    let plain = k
+        .open_in_place(nonce, Aad::from(aad), &mut buf)
+        .map_err(|_| FetchError::Invalid("decryption failed".to_string()))?;
+    Ok(plain.to_vec())
+}
+
+/// 用随机 nonce 加密, 返回 nonce 与密文拼接的结果
+pub fn encrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, FetchError> {
+    seal(&parse_key(key)?, data, b"")
+}
+
+fn seal(k: &LessSafeKey, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, FetchError> {
+    let mut n = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut n)
+        .map_err(|_| io::Error::other("random nonce"))?;
+    let mut buf = data.to_vec();
+    k.seal_in_place_append_tag(Nonce::assume_unique_for_key(n), Aad::from(aad), &mut buf)
+        .map_err(|_| io::Error::other("encryption failed"))?;
+    let mut r = n.to_vec();
+    r.append(&mut buf);
+    Ok(r)
+}
+
+impl SyncSource for EncryptedSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        decrypt(&self.key.fetch()?, &self.inner.fetch()?)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for EncryptedSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        let key = self.key.fetch_async().await?;
+        decrypt(&key, &self.inner.fetch_async().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_source() {
+        let key = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff\n";
+        let c = encrypt(key.as_bytes(), b"secret config").unwrap();
+        let es = EncryptedSource::new(
+            SingleFileSource::Inline(c.clone()),
+            SingleFileSource::Inline(key.as_bytes().to_vec()),
+        );
+        assert_eq!(es.fetch().unwrap(), b"secret config");
+
+        let es = EncryptedSource::new(
+            SingleFileSource::Inline(c),
+            SingleFileSource::Inline([7u8; 32].to_vec()),
+        );
+        assert!(matches!(es.fetch(), Err(FetchError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        // 64 字节但不是十六进制, 包括多字节字符
+        assert!(parse_key("é".repeat(32).as_bytes()).is_err());
+        assert!(parse_key(&[b'g'; 64]).is_err());
+        assert!(parse_key(&[b'+'; 64]).is_err());
+        // 原始密钥中的空白不会被去除
+        let k = [b' '; 32];
+        let c = encrypt(&k, b"x").unwrap();
+        assert_eq!(decrypt(&k, &c).unwrap(), b"x");
+        assert!(parse_key(&[b'a'; 33]).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().to_string_lossy();
+        let key = [1u8; 32];
+        let fc = FileCache::in_dir(&dir, Some(60))
+            .with_encryption_key(&key)
+            .resolve("a")
+            .into_owned();
+        assert_eq!(
+            format!("{:?}", fc.encryption_key.as_ref().unwrap()),
+            "CacheKey(..)"
+        );
+        let cf = fc.cache_file_path.clone().unwrap();
+        let s = SingleFileSource::Inline(b"token=abc".to_vec());
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"token=abc");
+        let stored = std::fs::read(&cf).unwrap();
+        assert_eq!(
+            decrypt_cache(&key, &stored, "a").unwrap().unwrap(),
+            b"token=abc"
+        );
+        assert!(decrypt(&key, &stored).is_err());
+        assert_eq!(fc.read_cache_file().unwrap(), b"token=abc");
+
+        // 同一密钥加密的其它缓存不能替换进来
+        let other = FileCache::in_dir(&dir, Some(60))
+            .with_encryption_key(&key)
+            .resolve("b")
+            .into_owned();
+        assert!(other.write_cache_file(b"token=other"));
+        std::fs::copy(other.cache_file_path.as_ref().unwrap(), &cf).unwrap();
+        assert!(fc.read_cache_file().unwrap_err().is_cache_corrupt());
+        assert!(fc.write_cache_file(b"token=abc"));
+        let stored = std::fs::read(&cf).unwrap();
+
+        // 换了密钥的缓存被丢弃, 重新读取后用新密钥写入
+        let fc2 = FileCache {
+            encryption_key: Some(CacheKey([2u8; 32].to_vec())),
+            ..fc.clone()
+        };
+        assert!(fc2.read_cache_file().unwrap_err().is_cache_corrupt());
+        assert!(!Path::new(&cf).exists());
+        std::fs::write(&cf, &stored).unwrap();
+        let s = SingleFileSource::Inline(b"token=def".to_vec());
+        assert_eq!(fetch_with_cache(&fc2, &s).unwrap(), b"token=def");
+        assert!(fc.read_cache_file().unwrap_err().is_cache_corrupt());
+
+        // 密钥无效时不写入缓存
+        let bad = FileCache::in_dir(&dir, Some(60))
+            .with_encryption_key(b"short")
+            .resolve("b")
+            .into_owned();
+        assert!(!bad.write_cache_file(b"x"));
+        assert!(check_key(b"short").is_err());
+    }
+}
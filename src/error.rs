@@ -0,0 +1,284 @@
+use std::io;
+
+/// http 请求相关的错误
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum HttpError {
+    #[cfg(feature = "reqwest")]
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("`{url}` exceeds size limit: {size} > {limit} bytes")]
+    SizeLimit {
+        url: String,
+        size: u64,
+        limit: usize,
+    },
+    #[error("unexpected status {status} from `{url}`")]
+    Status { url: String, status: u16 },
+    /// 连接在响应体读完之前断开, 收到的内容少于 Content-Length
+    #[error("`{url}` truncated: received {received} of {expected} bytes")]
+    Truncated {
+        url: String,
+        expected: u64,
+        received: u64,
+    },
+}
+
+/// 缓存文件相关的错误
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum CacheError {
+    #[error("no cache file")]
+    Missing,
+    #[error("cache file `{path}`: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("clock error: {0}")]
+    Clock(#[from] std::time::SystemTimeError),
+    /// 缓存内容与元数据中记录的 sha256 不一致, 见 [`FileCache::verify_checksum`]
+    #[error("cache file `{path}` does not match its recorded checksum")]
+    Corrupt { path: String },
+}
+
+/// 读取 tar 等归档时的错误
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ArchiveError {
+    #[error("read archive: {0}")]
+    Io(#[from] io::Error),
+    #[error("decompress ({format}): {reason}")]
+    Decompress {
+        format: &'static str,
+        reason: String,
+    },
+    /// 解压后的数据超过上限, 见 [`crate::MAX_DECOMPRESSED_SIZE`]
+    #[error("decompress ({format}): output exceeds {limit} bytes")]
+    TooLarge { format: &'static str, limit: u64 },
+}
+
+/// 按名称查找文件时的错误
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum LookupError {
+    #[error("not found `{0}`")]
+    NotFound(String),
+    #[error("`{name}` not found in directories `{dirs:?}`")]
+    NotInDirectories { name: String, dirs: Vec<String> },
+    #[error("is a directory `{0}`")]
+    IsDirectory(String),
+    #[error("permission denied `{0}`")]
+    Denied(String),
+}
+
+/// 获取数据时的错误.
+///
+/// 新的来源可能会增加变体, 因此标记为 `non_exhaustive`. 不需要区分具体来源时,
+/// 可以用 [`FetchError::kind`] 等方法代替直接匹配
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum FetchError {
+    #[error(transparent)]
+    Http(#[from] HttpError),
+    #[error(transparent)]
+    Cache(#[from] CacheError),
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+    #[error("io err: {0}")]
+    I(#[from] io::Error),
+    #[error("circuit open")]
+    CircuitOpen,
+    #[error("not supported: {0}")]
+    Unsupported(&'static str),
+    /// 来源需要的 feature 没有开启, 见 [`SingleFileSource::Http`] 等
+    #[error("feature `{0}` is not enabled")]
+    FeatureDisabled(&'static str),
+    #[error("invalid content: {0}")]
+    Invalid(String),
+    /// 系统密钥库返回的错误, 见 [`crate::keyring::KeyringEntry`]
+    #[cfg(feature = "keyring")]
+    #[error("keyring: {0}")]
+    Keyring(::keyring::Error),
+    /// 多个调用者共享的同一个错误, 见 [`crate::single_flight::SingleFlight`]
+    #[error("{0}")]
+    Shared(std::sync::Arc<FetchError>),
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for FetchError {
+    fn from(value: reqwest::Error) -> Self {
+        FetchError::Http(HttpError::Request(value))
+    }
+}
+
+impl From<std::time::SystemTimeError> for FetchError {
+    fn from(value: std::time::SystemTimeError) -> Self {
+        FetchError::Cache(CacheError::Clock(value))
+    }
+}
+
+/// [`FetchError`] 的大致分类, 不随来源种类的增加而变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FetchErrorKind {
+    NotFound,
+    IsDirectory,
+    PermissionDenied,
+    /// 网络请求失败或返回了错误的状态码
+    Network,
+    TooLarge,
+    /// 缓存文件读取失败
+    Cache,
+    /// 归档读取或解压失败
+    Archive,
+    Unavailable,
+    Unsupported,
+    InvalidContent,
+    Io,
+}
+
+impl FetchError {
+    pub fn not_found(name: impl Into<String>) -> Self {
+        FetchError::Lookup(LookupError::NotFound(name.into()))
+    }
+
+    pub fn kind(&self) -> FetchErrorKind {
+        match self {
+            FetchError::Http(HttpError::SizeLimit { .. })
+            | FetchError::Archive(ArchiveError::TooLarge { .. }) => FetchErrorKind::TooLarge,
+            FetchError::Http(_) => FetchErrorKind::Network,
+            FetchError::Cache(_) => FetchErrorKind::Cache,
+            FetchError::Archive(_) => FetchErrorKind::Archive,
+            FetchError::Lookup(LookupError::NotFound(_) | LookupError::NotInDirectories { .. }) => {
+                FetchErrorKind::NotFound
+            }
+            FetchError::Lookup(LookupError::IsDirectory(_)) => FetchErrorKind::IsDirectory,
+            FetchError::Lookup(LookupError::Denied(_)) => FetchErrorKind::PermissionDenied,
+            FetchError::I(e) => match e.kind() {
+                io::ErrorKind::NotFound => FetchErrorKind::NotFound,
+                io::ErrorKind::IsADirectory => FetchErrorKind::IsDirectory,
+                io::ErrorKind::PermissionDenied => FetchErrorKind::PermissionDenied,
+                io::ErrorKind::Unsupported => FetchErrorKind::Unsupported,
+                io::ErrorKind::InvalidData => FetchErrorKind::InvalidContent,
+                _ => FetchErrorKind::Io,
+            },
+            FetchError::CircuitOpen => FetchErrorKind::Unavailable,
+            FetchError::Unsupported(_) | FetchError::FeatureDisabled(_) => {
+                FetchErrorKind::Unsupported
+            }
+            FetchError::Invalid(_) => FetchErrorKind::InvalidContent,
+            #[cfg(feature = "keyring")]
+            FetchError::Keyring(e) => match e {
+                ::keyring::Error::NoEntry => FetchErrorKind::NotFound,
+                ::keyring::Error::PlatformFailure(_) | ::keyring::Error::NoStorageAccess(_) => {
+                    FetchErrorKind::Unavailable
+                }
+                ::keyring::Error::BadEncoding(_) => FetchErrorKind::InvalidContent,
+                _ => FetchErrorKind::Io,
+            },
+            FetchError::Shared(e) => e.kind(),
+        }
+    }
+
+    /// 出错的本地路径或查找的文件名, 如果有的话
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            FetchError::Cache(CacheError::Io { path, .. } | CacheError::Corrupt { path }) => {
+                Some(path)
+            }
+            FetchError::Lookup(
+                LookupError::NotFound(p) | LookupError::IsDirectory(p) | LookupError::Denied(p),
+            ) => Some(p),
+            FetchError::Lookup(LookupError::NotInDirectories { name, .. }) => Some(name),
+            FetchError::Shared(e) => e.path(),
+            _ => None,
+        }
+    }
+
+    /// 出错的请求 url, 如果有的话
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            #[cfg(feature = "reqwest")]
+            FetchError::Http(HttpError::Request(e)) => e.url().map(|u| u.as_str()),
+            FetchError::Http(
+                HttpError::SizeLimit { url, .. }
+                | HttpError::Status { url, .. }
+                | HttpError::Truncated { url, .. },
+            ) => Some(url),
+            FetchError::Shared(e) => e.url(),
+            _ => None,
+        }
+    }
+
+    /// 是否表示文件不存在
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == FetchErrorKind::NotFound
+    }
+
+    /// 是否为损坏的缓存, 此时缓存文件已被删除, 应当重新读取来源
+    pub fn is_cache_corrupt(&self) -> bool {
+        match self {
+            FetchError::Cache(CacheError::Corrupt { .. }) => true,
+            FetchError::Shared(e) => e.is_cache_corrupt(),
+            _ => false,
+        }
+    }
+}
+
+impl From<FetchError> for io::Error {
+    fn from(value: FetchError) -> Self {
+        if let FetchError::I(e) = value {
+            return e;
+        }
+        let kind = match value.kind() {
+            FetchErrorKind::NotFound => io::ErrorKind::NotFound,
+            FetchErrorKind::IsDirectory => io::ErrorKind::IsADirectory,
+            FetchErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+            FetchErrorKind::Unsupported => io::ErrorKind::Unsupported,
+            FetchErrorKind::InvalidContent => io::ErrorKind::InvalidData,
+            FetchErrorKind::Archive
+                if matches!(value, FetchError::Archive(ArchiveError::Decompress { .. })) =>
+            {
+                io::ErrorKind::InvalidData
+            }
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_kind_and_accessors() {
+        let e = FetchError::not_found("a.txt");
+        assert_eq!(e.kind(), FetchErrorKind::NotFound);
+        assert_eq!(e.path(), Some("a.txt"));
+        assert_eq!(e.url(), None);
+
+        let e = FetchError::Shared(Arc::new(
+            HttpError::SizeLimit {
+                url: "http://a/b".to_string(),
+                size: 10,
+                limit: 1,
+            }
+            .into(),
+        ));
+        assert_eq!(e.kind(), FetchErrorKind::TooLarge);
+        assert_eq!(e.url(), Some("http://a/b"));
+
+        let e = FetchError::I(io::Error::from(io::ErrorKind::NotFound));
+        assert!(e.is_not_found());
+        assert_eq!(
+            io::Error::from(FetchError::CircuitOpen).kind(),
+            io::ErrorKind::Other
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! etcd v3 键值来源. 通过 etcd 自带的 gRPC 网关 (`POST /v3/kv/range`, JSON) 读取, 不需要 gRPC 客户端
+
+use crate::*;
+use serde_json::{json, Value};
+
+/// etcd 中的一个键值对
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyValue {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    pub mod_revision: i64,
+}
+
+/// 到 etcd 的连接. `http.url` 为 endpoint, 如 `http://127.0.0.1:2379`,
+/// 代理和自定义请求头的设置与 [`HttpSource`] 相同
+#[derive(Debug, Clone, Default)]
+pub struct EtcdClient {
+    pub http: HttpSource,
+}
+
+/// 前缀范围的结束键: 最后一个不是 0xff 的字节加一. 全为 0xff 或为空时返回 `\0`, 即到最后
+pub fn prefix_range_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(b) = end.pop() {
+        if b < 0xff {
+            end.push(b + 1);
+            return end;
+        }
+    }
+    vec![0]
+}
+
+fn range_body(key: &[u8], range_end: Option<&[u8]>, keys_only: bool) -> String {
+    let mut v = json!({ "key": base64::encode(key) });
+    if let Some(e) = range_end {
+        v["range_end"] = base64::encode(e).into();
+        v["sort_order"] = "ASCEND".into();
+        v["sort_target"] = "KEY".into();
+    }
+    if keys_only {
+        v["keys_only"] = true.into();
+    }
+    v.to_string()
+}
+
+/// 解析 range 的响应. 没有匹配的键时 etcd 会省略 `kvs`
+fn parse_range_response(body: &[u8]) -> Result<Vec<KeyValue>, FetchError> {
+    let invalid = |e: &str| FetchError::Invalid(format!("etcd: {e}"));
+    let v: Value = serde_json::from_slice(body).map_err(|e| invalid(&e.to_string()))?;
+    let Some(kvs) = v.get("kvs") else {
+        return Ok(Vec::new());
+    };
+    let kvs = kvs
+        .as_array()
+        .ok_or_else(|| invalid("kvs is not an array"))?;
+    let b64 = |kv: &Value, k: &str| match kv.get(k).and_then(Value::as_str) {
+        Some(s) => base64::decode(s).ok_or_else(|| invalid("invalid base64")),
+        None => Ok(Vec::new()),
+    };
+    kvs.iter()
+        .map(|kv| {
+            // int64 在 JSON 中编码为字符串
+            let mod_revision = match kv.get("mod_revision") {
+                Some(Value::String(s)) => s.parse().unwrap_or_default(),
+                Some(n) => n.as_i64().unwrap_or_default(),
+                None => 0,
+            };
+            Ok(KeyValue {
+                key: b64(kv, "key")?,
+                value: b64(kv, "value")?,
+                mod_revision,
+            })
+        })
+        .collect()
+}
+
+impl EtcdClient {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            http: HttpSource {
+                url: endpoint.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// 开启认证时, 使用 `/v3/auth/authenticate` 得到的 token
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.http
+            .custom_request_headers
+            .get_or_insert_with(Vec::new)
+            .push(("Authorization".to_string(), token.to_string()));
+        self
+    }
+
+    fn range_url(&self) -> String {
+        format!("{}/v3/kv/range", self.http.url.trim_end_matches('/'))
+    }
+
+    fn check_status(&self, status: reqwest::StatusCode) -> Result<(), FetchError> {
+        match status {
+            s if s.is_success() => Ok(()),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Err(FetchError::Lookup(LookupError::Denied(self.range_url())))
+            }
+            s => Err(HttpError::Status {
+                url: self.range_url(),
+                status: s.as_u16(),
+            }
+            .into()),
+        }
+    }
+
+    /// 读取 `[key, range_end)` 中的键, `range_end` 为 None 时只读取 `key`
+    pub fn range(
+        &self,
+        key: &[u8],
+        range_end: Option<&[u8]>,
+        keys_only: bool,
+    ) -> Result<Vec<KeyValue>, FetchError> {
+        let hs = &self.http;
+        let mut cb = hs.blocking_client_builder();
+        if hs.should_use_proxy {
+            cb = hs.set_proxy(cb)?;
+        }
+        let mut rb = cb
+            .build()?
+            .post(self.range_url())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(range_body(key, range_end, keys_only));
+        for (k, v) in hs.custom_request_headers.iter().flatten() {
+            rb = rb.header(k, v);
+        }
+        let r = rb.send()?;
+        self.check_status(r.status())?;
+        parse_range_response(&read_body(r)?)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn range_async(
+        &self,
+        key: &[u8],
+        range_end: Option<&[u8]>,
+        keys_only: bool,
+    ) -> Result<Vec<KeyValue>, FetchError> {
+        let hs = &self.http;
+        let mut cb = hs.async_client_builder();
+        if hs.should_use_proxy {
+            cb = hs.set_proxy_async(cb)?;
+        }
+        let mut rb = cb
+            .build()?
+            .post(self.range_url())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(range_body(key, range_end, keys_only));
+        for (k, v) in hs.custom_request_headers.iter().flatten() {
+            rb = rb.header(k, v);
+        }
+        let r = rb.send().await?;
+        self.check_status(r.status())?;
+        parse_range_response(&read_body_async(r).await?)
+    }
+
+    /// 读取单个键的值
+    pub fn get(&self, key: &str) -> Result<Vec<u8>, FetchError> {
+        let kv = self.range(key.as_bytes(), None, false)?;
+        kv.into_iter()
+            .next()
+            .map(|kv| kv.value)
+            .ok_or_else(|| FetchError::not_found(key))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn get_async(&self, key: &str) -> Result<Vec<u8>, FetchError> {
+        let kv = self.range_async(key.as_bytes(), None, false).await?;
+        kv.into_iter()
+            .next()
+            .map(|kv| kv.value)
+            .ok_or_else(|| FetchError::not_found(key))
+    }
+}
+
+/// etcd 中的单个键
+#[derive(Debug, Clone, Default)]
+pub struct EtcdSource {
+    pub client: EtcdClient,
+    pub key: String,
+}
+
+impl EtcdSource {
+    pub fn new(client: EtcdClient, key: &str) -> Self {
+        Self {
+            client,
+            key: key.to_string(),
+        }
+    }
+}
+
+impl SyncSource for EtcdSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.client.get(&self.key)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for EtcdSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        self.client.get_async(&self.key).await
+    }
+}
+
+/// 以 `prefix` 下的键作为目录, 文件名即去掉前缀的键.
+/// 如 prefix 为 `/routing/` 时, `rules/a.json` 对应键 `/routing/rules/a.json`
+#[derive(Debug, Clone, Default)]
+pub struct EtcdFolderSource {
+    pub client: EtcdClient,
+    pub prefix: String,
+}
+
+impl EtcdFolderSource {
+    pub fn new(client: EtcdClient, prefix: &str) -> Self {
+        Self {
+            client,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    pub fn key(&self, file_name: &Path) -> String {
+        let p = normalize_separators(file_name);
+        let p = p.to_string_lossy();
+        format!("{}{}", self.prefix, p.trim_start_matches("./"))
+    }
+}
+
+impl SyncFolderSource for EtcdFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let k = self.key(file_name);
+        Ok((self.client.get(&k)?, Some(k)))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY.with_listable()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        let p = self.prefix.as_bytes();
+        let kvs = self.client.range(p, Some(&prefix_range_end(p)), true)?;
+        Ok(kvs
+            .into_iter()
+            .filter_map(|kv| {
+                let name = kv.key.strip_prefix(p)?;
+                (!name.is_empty()).then(|| String::from_utf8_lossy(name).to_string())
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for EtcdFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let k = self.key(file_name);
+        Ok((self.client.get_async(&k).await?, Some(k)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// 只实现了 `/v3/kv/range` 的 etcd
+    fn serve(kvs: BTreeMap<Vec<u8>, Vec<u8>>) -> String {
+        let l = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", l.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let mut r = BufReader::new(&s);
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    r.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_ascii_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(l) = line.strip_prefix("content-length: ") {
+                        len = l.parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                r.read_exact(&mut body).unwrap();
+                let req: Value = serde_json::from_slice(&body).unwrap();
+                let d = |k: &str| {
+                    req.get(k)
+                        .map(|v| base64::decode(v.as_str().unwrap()).unwrap())
+                };
+                let key = d("key").unwrap();
+                let found: Vec<Value> = match d("range_end") {
+                    Some(end) => kvs
+                        .range(key..end)
+                        .map(|(k, _)| json!({ "key": base64::encode(k), "mod_revision": "3" }))
+                        .collect(),
+                    None => kvs
+                        .get(&key)
+                        .map(|v| json!({ "key": base64::encode(&key), "value": base64::encode(v) }))
+                        .into_iter()
+                        .collect(),
+                };
+                let mut resp = json!({ "header": { "revision": "3" } });
+                if !found.is_empty() {
+                    resp["kvs"] = found.into();
+                }
+                let resp = resp.to_string();
+                write!(
+                    s,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{resp}",
+                    resp.len()
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_etcd_helpers() {
+        assert_eq!(prefix_range_end(b"/routing/"), b"/routing0");
+        assert_eq!(prefix_range_end(b"a\xff\xff"), b"b");
+        assert_eq!(prefix_range_end(b""), b"\0");
+        let kv = parse_range_response(
+            br#"{"kvs":[{"key":"L3Iv","value":"","mod_revision":"7"}],"count":"1"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            kv,
+            vec![KeyValue {
+                key: b"/r/".to_vec(),
+                value: Vec::new(),
+                mod_revision: 7
+            }]
+        );
+        assert!(parse_range_response(br#"{"header":{}}"#)
+            .unwrap()
+            .is_empty());
+        assert!(parse_range_response(b"<html>").is_err());
+    }
+
+    #[test]
+    fn test_etcd_folder_source() {
+        let kvs = [
+            ("/routing/rules/a.json", "{}"),
+            ("/routing/geo.dat", "geo"),
+            ("/routing0", "outside"),
+            ("/other", "x"),
+        ];
+        let url = serve(
+            kvs.iter()
+                .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+                .collect(),
+        );
+        let client = EtcdClient::new(&url).with_token("t");
+        let fs = EtcdFolderSource::new(client.clone(), "/routing/");
+        assert_eq!(fs.list_files().unwrap(), vec!["geo.dat", "rules/a.json"]);
+        let r = fs.get_file_content(Path::new("./rules/a.json")).unwrap();
+        assert_eq!(
+            r,
+            (b"{}".to_vec(), Some("/routing/rules/a.json".to_string()))
+        );
+        assert!(fs
+            .get_file_content(Path::new("nope"))
+            .unwrap_err()
+            .is_not_found());
+
+        let s = EtcdSource::new(client, "/other");
+        assert_eq!(s.fetch().unwrap(), b"x");
+        let ds = DataSource::Sync(Box::new(fs));
+        assert_eq!(ds.read_to_string("geo.dat").unwrap(), "geo");
+    }
+}
@@ -0,0 +1,229 @@
+use crate::overlay::OverlaySource;
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// 会在这里读取文件, 解析到此为止
+    Hit,
+    Miss,
+    /// 是目录, 会继续尝试下一个
+    IsDirectory,
+    /// 被 whiteout 隐藏, 解析到此为止并返回 not found
+    Hidden,
+    /// 需要读取数据才能确定 (tar, 自定义来源等)
+    Unknown,
+}
+
+/// [`DataSource::explain`] 中的一个候选
+#[derive(Debug, Clone)]
+pub struct ResolveStep {
+    pub candidate: String,
+    pub outcome: StepOutcome,
+}
+
+impl ResolveStep {
+    fn new(candidate: impl Into<String>, outcome: StepOutcome) -> Self {
+        Self {
+            candidate: candidate.into(),
+            outcome,
+        }
+    }
+}
+
+pub(crate) fn describe(sf: &SingleFileSource) -> String {
+    match sf {
+        SingleFileSource::Http(hs, fc) => match &sf.resolve_cache(fc).cache_file_path {
+            Some(cf) => format!("http {} (cache {cf})", hs.url),
+            None => format!("http {}", hs.url),
+        },
+        SingleFileSource::FilePath(p) => format!("file {p}"),
+        SingleFileSource::Inline(v) => format!("inline {} bytes", v.len()),
+        SingleFileSource::DataUri(u) => format!("data uri {} chars", u.len()),
+        SingleFileSource::InlineBase64(s) => format!("inline base64 {} chars", s.len()),
+        SingleFileSource::Env(name) => format!("env {name}"),
+        SingleFileSource::Annotated(s, _) => describe(s),
+        SingleFileSource::Generated(g, _) => format!("{g:?}"),
+        #[cfg(feature = "s3")]
+        SingleFileSource::S3(s, fc) => match &sf.resolve_cache(fc).cache_file_path {
+            Some(cf) => format!("s3 {} (cache {cf})", s.url()),
+            None => format!("s3 {}", s.url()),
+        },
+        #[cfg(feature = "azure")]
+        SingleFileSource::AzureBlob(s, fc) => match &sf.resolve_cache(fc).cache_file_path {
+            Some(cf) => format!("azure blob {} (cache {cf})", s.url()),
+            None => format!("azure blob {}", s.url()),
+        },
+        #[cfg(feature = "gcs")]
+        SingleFileSource::Gcs(s, fc) => match &sf.resolve_cache(fc).cache_file_path {
+            Some(cf) => format!("gcs {} (cache {cf})", s.url()),
+            None => format!("gcs {}", s.url()),
+        },
+        #[cfg(feature = "keyring")]
+        SingleFileSource::Keyring(k) => format!("keyring {}/{}", k.service, k.user),
+        #[cfg(feature = "dns_txt")]
+        SingleFileSource::DnsTxt(s) => format!("dns txt {}", s.name),
+        #[cfg(feature = "sftp")]
+        SingleFileSource::Sftp(s) => format!("sftp {}:{}", s.remote.host, s.path),
+        #[cfg(feature = "encrypted")]
+        SingleFileSource::Encrypted(e) => format!(
+            "encrypted {} (key {})",
+            describe(&e.inner),
+            describe(&e.key)
+        ),
+        SingleFileSource::Validated(s, _) => format!("validated {}", describe(s)),
+        SingleFileSource::Concat(c) => format!(
+            "concat [{}]",
+            c.parts.iter().map(describe).collect::<Vec<_>>().join(", ")
+        ),
+        #[cfg(feature = "json_merge")]
+        SingleFileSource::JsonMerge(m) => format!(
+            "json merge [{}]",
+            m.parts.iter().map(describe).collect::<Vec<_>>().join(", ")
+        ),
+        #[cfg(feature = "extract")]
+        SingleFileSource::Extract(e) => match &e.path {
+            extract::ExtractPath::JsonPointer(p) => format!("json `{p}` of {}", describe(&e.inner)),
+            extract::ExtractPath::TomlTable(p) => format!("toml [{p}] of {}", describe(&e.inner)),
+        },
+    }
+}
+
+/// 最后一步是否结束了解析
+fn stops(steps: &[ResolveStep]) -> bool {
+    steps
+        .last()
+        .is_some_and(|s| matches!(s.outcome, StepOutcome::Hit | StepOutcome::Hidden))
+}
+
+impl DataSource {
+    /// 按顺序列出读取 `file_name` 时会尝试的候选, 以及解析会在哪里停止, 不读取任何数据.
+    /// 用于排查 "为什么读到了错误的文件"
+    pub fn explain(&self, file_name: &Path) -> Vec<ResolveStep> {
+        let mut steps = Vec::new();
+        match self {
+            DataSource::StdReadFile => {
+                let o = if file_name.is_dir() {
+                    StepOutcome::IsDirectory
+                } else if file_name.exists() {
+                    StepOutcome::Hit
+                } else {
+                    StepOutcome::Miss
+                };
+                steps.push(ResolveStep::new(file_name.to_string_lossy(), o));
+            }
+            DataSource::Folders(dirs) => {
+                for dir in dirs {
+                    let p = folder_path(Path::new(dir), file_name);
+                    let o = if p.is_dir() {
+                        StepOutcome::IsDirectory
+                    } else if p.exists() {
+                        StepOutcome::Hit
+                    } else {
+                        StepOutcome::Miss
+                    };
+                    steps.push(ResolveStep::new(p.to_string_lossy(), o));
+                    if o == StepOutcome::Hit {
+                        break;
+                    }
+                }
+            }
+            DataSource::TarInMemory(_) => steps.push(ResolveStep::new(
+                format!("tar in memory: {}", file_name.to_string_lossy()),
+                StepOutcome::Unknown,
+            )),
+            DataSource::TarFile(tf) => steps.push(ResolveStep::new(
+                format!("tar {}: {}", tf.0, file_name.to_string_lossy()),
+                StepOutcome::Unknown,
+            )),
+            DataSource::FileMap(map) => {
+                let key = file_name.to_string_lossy();
+                match lookup_file_map(map, file_name) {
+                    Some(sf) => steps.push(ResolveStep::new(
+                        format!("key {key} -> {}", describe(sf)),
+                        StepOutcome::Hit,
+                    )),
+                    None => steps.push(ResolveStep::new(format!("key {key}"), StepOutcome::Miss)),
+                }
+            }
+            DataSource::Embedded(e) => {
+                let key = file_name.to_string_lossy();
+                let o = match e.get(file_name) {
+                    Some(_) => StepOutcome::Hit,
+                    None => StepOutcome::Miss,
+                };
+                steps.push(ResolveStep::new(format!("embedded {key}"), o));
+            }
+            DataSource::Sync(s) => {
+                steps.push(ResolveStep::new(format!("{s:?}"), StepOutcome::Unknown))
+            }
+            #[cfg(feature = "tokio")]
+            DataSource::Async(s) => {
+                steps.push(ResolveStep::new(format!("{s:?}"), StepOutcome::Unknown))
+            }
+        }
+        steps
+    }
+}
+
+impl OverlaySource {
+    /// 与 [`DataSource::explain`] 相同, 依次列出各层的候选, 候选前加上层的序号
+    pub fn explain(&self, file_name: &Path) -> Vec<ResolveStep> {
+        let mut steps = Vec::new();
+        for (i, l) in self.layers.iter().enumerate() {
+            if l.hides(file_name) {
+                steps.push(ResolveStep::new(
+                    format!("layer {i}: whiteout {}", file_name.to_string_lossy()),
+                    StepOutcome::Hidden,
+                ));
+                break;
+            }
+            let ls = l.source.explain(file_name);
+            steps.extend(ls.into_iter().map(|mut s| {
+                s.candidate = format!("layer {i}: {}", s.candidate);
+                s
+            }));
+            if stops(&steps) {
+                break;
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overlay::OverlayLayer;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_explain() {
+        let d1 = TempDir::new().unwrap();
+        let d2 = TempDir::new().unwrap();
+        std::fs::create_dir(d1.path().join("a.txt")).unwrap();
+        std::fs::write(d2.path().join("a.txt"), "a").unwrap();
+        let ds = DataSource::Folders(vec![
+            d1.path().to_string_lossy().to_string(),
+            d2.path().to_string_lossy().to_string(),
+        ]);
+        let outcomes: Vec<_> = ds
+            .explain(Path::new("a.txt"))
+            .into_iter()
+            .map(|s| s.outcome)
+            .collect();
+        assert_eq!(outcomes, vec![StepOutcome::IsDirectory, StepOutcome::Hit]);
+
+        let o = OverlaySource::new(vec![
+            OverlayLayer::new(DataSource::FileMap(HashMap::new())).with_whiteout("b.txt"),
+            OverlayLayer::new(ds),
+        ]);
+        let steps = o.explain(Path::new("a.txt"));
+        assert_eq!(steps.len(), 3);
+        assert!(steps[0].candidate.starts_with("layer 0: key a.txt"));
+        assert_eq!(steps[2].outcome, StepOutcome::Hit);
+
+        let steps = o.explain(Path::new("b.txt"));
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].outcome, StepOutcome::Hidden);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::*;
+
+/// [`DataSource::export`] 的输出位置
+pub enum ExportTarget<'a> {
+    /// 写成 tar 流
+    #[cfg(feature = "tar")]
+    Tar(&'a mut dyn io::Write),
+    /// 写到本地目录中, 保留子目录结构
+    Dir(&'a Path),
+}
+
+impl DataSource {
+    /// 把 `paths` 中的文件 (为 None 时为 [`SyncFolderSource::list_files`] 列出的所有文件)
+    /// 按各自在 DataSource 中解析到的内容导出, 返回导出的文件数.
+    ///
+    /// 可用于先把所有文件下载一次再离线运行, 或检查叠加的来源最终解析到的内容
+    pub fn export(
+        &self,
+        paths: Option<&[&str]>,
+        target: ExportTarget,
+    ) -> Result<usize, FetchError> {
+        let names = match paths {
+            Some(p) => p.iter().map(|s| s.to_string()).collect(),
+            None => {
+                self.capabilities().require_listable()?;
+                self.list_files()?
+            }
+        };
+        match target {
+            #[cfg(feature = "tar")]
+            ExportTarget::Tar(w) => {
+                let mut b = tar::Builder::new(w);
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)?
+                    .as_secs();
+                for n in &names {
+                    let (d, _) = self.get_file_content(Path::new(n))?;
+                    tar_builder::append_file(&mut b, n, &d, now)?;
+                }
+                b.finish()?;
+            }
+            ExportTarget::Dir(dir) => {
+                for n in &names {
+                    let rel = normalize_separators(Path::new(n)).into_owned();
+                    if rel
+                        .components()
+                        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+                    {
+                        warn!("export: skip unsafe path {n}");
+                        continue;
+                    }
+                    let (d, _) = self.get_file_content(&rel)?;
+                    let p = dir.join(&rel);
+                    if let Some(parent) = p.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&p, d).map_err(|e| local_io_error(e, &p))?;
+                }
+            }
+        }
+        Ok(names.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export() {
+        let ds = DataSource::FileMap(
+            vec![
+                ("a.txt".to_string(), SingleFileSource::Inline(b"a".to_vec())),
+                (
+                    "sub/b.txt".to_string(),
+                    SingleFileSource::Inline(b"b".to_vec()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let n = ds.export(None, ExportTarget::Dir(temp_dir.path())).unwrap();
+        assert_eq!(n, 2);
+
+        let exported = DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+        assert_eq!(exported.read_to_string("sub/b.txt").unwrap(), "b");
+        let mut files = exported.list_files().unwrap();
+        files.sort();
+        assert_eq!(files, vec!["a.txt", "sub/b.txt"]);
+
+        #[cfg(feature = "tar")]
+        {
+            let mut buf = Vec::new();
+            ds.export(Some(&["a.txt"]), ExportTarget::Tar(&mut buf))
+                .unwrap();
+            let t = DataSource::TarInMemory(buf);
+            assert_eq!(t.read_to_string("a.txt").unwrap(), "a");
+            assert_eq!(t.list_files().unwrap(), vec!["a.txt"]);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::*;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractPath {
+    /// RFC 6901 JSON Pointer, 如 `/outbounds/0`. 结果重新序列化为 JSON
+    JsonPointer(String),
+    /// TOML 表的键路径, 如 `dns.hosts`. 返回该表 (及其子表) 的原文, 子表的表头改为相对路径.
+    /// 按行处理, 只支持用 `[a.b]` 表头定义的表. 遇到多行字符串, 所选表中的内联表,
+    /// 路径上的表数组, 带引号的相关表头, 或用点分键 / 内联表定义所选表时返回 [`FetchError::Invalid`]
+    TomlTable(String),
+}
+
+/// 读取内层来源后只返回其中的一部分, 如大型远程 JSON 中的一节
+#[derive(Debug)]
+pub struct ExtractSource {
+    pub inner: Box<SingleFileSource>,
+    pub path: ExtractPath,
+}
+
+impl ExtractSource {
+    pub fn new(inner: SingleFileSource, path: ExtractPath) -> Self {
+        Self {
+            inner: Box::new(inner),
+            path,
+        }
+    }
+
+    pub fn json_pointer(inner: SingleFileSource, pointer: &str) -> Self {
+        Self::new(inner, ExtractPath::JsonPointer(pointer.to_string()))
+    }
+
+    pub fn toml_table(inner: SingleFileSource, key_path: &str) -> Self {
+        Self::new(inner, ExtractPath::TomlTable(key_path.to_string()))
+    }
+
+    fn not_found(&self) -> FetchError {
+        let p = match &self.path {
+            ExtractPath::JsonPointer(p) | ExtractPath::TomlTable(p) => p,
+        };
+        let name = self.inner.get_path().unwrap_or_default();
+        FetchError::not_found(format!("{name}#{p}"))
+    }
+
+    fn extract(&self, d: Vec<u8>) -> Result<Vec<u8>, FetchError> {
+        match &self.path {
+            ExtractPath::JsonPointer(p) => {
+                let v: Value = serde_json::from_slice(&d)
+                    .map_err(|e| FetchError::Invalid(format!("bad json: {e}")))?;
+                let v = v.pointer(p).ok_or_else(|| self.not_found())?;
+                serde_json::to_vec(v).map_err(|e| FetchError::I(io::Error::other(e)))
+            }
+            ExtractPath::TomlTable(p) => {
+                let s = std::str::from_utf8(&d)
+                    .map_err(|e| FetchError::Invalid(format!("bad toml: {e}")))?;
+                toml_table(s, p)?
+                    .map(String::into_bytes)
+                    .ok_or_else(|| self.not_found())
+            }
+        }
+    }
+}
+
+fn unsupported(path: &str, what: &str) -> FetchError {
+    FetchError::Invalid(format!("toml table {path}: {what} is not supported"))
+}
+
+/// 本行中 `[` 比 `]` 多出的个数, 忽略字符串和注释中的括号
+fn bracket_depth(line: &str) -> i32 {
+    let (mut depth, mut quote, mut escaped) = (0, None, false);
+    for c in line.chars() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '#' => break,
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {}
+            },
+        }
+    }
+    depth
+}
+
+/// `[a . b]` 或 `[[a.b]]` 的表名和是否为表数组, 不是表头时返回 None
+fn toml_header(line: &str) -> Option<(String, bool)> {
+    let l = line.trim();
+    let (inner, array) = match l.strip_prefix("[[") {
+        Some(r) => (r.split_once("]]")?.0, true),
+        None => (l.strip_prefix('[')?.split_once(']')?.0, false),
+    };
+    let name: Vec<&str> = inner.split('.').map(str::trim).collect();
+    Some((name.join("."), array))
+}
+
+fn toml_table(src: &str, path: &str) -> Result<Option<String>, FetchError> {
+    let path: Vec<&str> = path.split('.').map(str::trim).collect();
+    let path = path.join(".");
+    if src.contains("\"\"\"") || src.contains("'''") {
+        return Err(unsupported(&path, "multi-line string"));
+    }
+    if path.is_empty() {
+        return Ok(Some(src.to_string()));
+    }
+    let prefix = format!("{path}.");
+    // `name` 是所选表, 其父表或子表
+    let related = |name: &str| {
+        name == path || name.starts_with(&prefix) || path.starts_with(&format!("{name}."))
+    };
+    let (mut found, mut inside, mut depth) = (false, false, 0);
+    let mut table = String::new();
+    let mut out = String::new();
+    for line in src.lines() {
+        // 跨行数组的后续行
+        if depth > 0 {
+            depth += bracket_depth(line);
+            if inside {
+                out.push_str(line);
+                out.push('\n');
+            }
+            continue;
+        }
+        if let Some((name, array)) = toml_header(line) {
+            if name.contains(['"', '\'']) && related(&name.replace(['"', '\''], "")) {
+                return Err(unsupported(&path, "quoted table header"));
+            }
+            if array && (name == path || path.starts_with(&format!("{name}."))) {
+                return Err(unsupported(&path, "array of tables"));
+            }
+            table = name;
+            if table == path {
+                (found, inside) = (true, true);
+                continue;
+            }
+            inside = false;
+            if let Some(rel) = table.strip_prefix(&prefix) {
+                found = true;
+                inside = true;
+                let h = if array {
+                    format!("[[{rel}]]")
+                } else {
+                    format!("[{rel}]")
+                };
+                out.push_str(&h);
+                out.push('\n');
+                continue;
+            }
+        } else if let Some((key, value)) = line.split_once('=') {
+            let (key, value) = (key.trim(), value.trim_start());
+            if inside && value.starts_with('{') {
+                return Err(unsupported(&path, "inline table"));
+            }
+            if !inside && !key.starts_with('#') && !key.contains(['"', '\'']) {
+                let key: Vec<&str> = key.split('.').map(str::trim).collect();
+                let key = key.join(".");
+                let full = if table.is_empty() {
+                    key
+                } else {
+                    format!("{table}.{key}")
+                };
+                if related(&full) {
+                    return Err(unsupported(&path, "dotted key or inline table"));
+                }
+            }
+            depth = bracket_depth(value);
+        }
+        if inside {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    Ok(found.then_some(out))
+}
+
+impl SyncSource for ExtractSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.extract(self.inner.fetch()?)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for ExtractSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        self.extract(self.inner.fetch_async().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = "\
+title = \"x\"
+
+[dns]
+enable = true
+
+[dns.hosts]
+\"a.com\" = \"1.1.1.1\"
+
+[[dns.servers]]
+addr = \"8.8.8.8\"
+
+[dnsx]
+other = 1
+";
+
+    #[test]
+    fn test_extract_json() {
+        let inline =
+            || SingleFileSource::Inline(br#"{"dns":{"servers":["8.8.8.8",{"a":1}]}}"#.to_vec());
+        let e = ExtractSource::json_pointer(inline(), "/dns/servers/1");
+        assert_eq!(e.fetch().unwrap(), br#"{"a":1}"#);
+        let e = ExtractSource::json_pointer(inline(), "");
+        assert_eq!(e.fetch().unwrap(), inline().fetch().unwrap());
+        let sf = SingleFileSource::Extract(ExtractSource::json_pointer(inline(), "/nope"));
+        assert!(sf.fetch().unwrap_err().is_not_found());
+        let e = ExtractSource::json_pointer(SingleFileSource::Inline(b"{".to_vec()), "/a");
+        assert!(matches!(e.fetch(), Err(FetchError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_extract_toml() {
+        let e = ExtractSource::toml_table(SingleFileSource::Inline(TOML.into()), "dns");
+        assert_eq!(
+            String::from_utf8(e.fetch().unwrap()).unwrap(),
+            "enable = true\n\n[hosts]\n\"a.com\" = \"1.1.1.1\"\n\n[[servers]]\naddr = \"8.8.8.8\"\n\n"
+        );
+        assert_eq!(
+            toml_table(TOML, " dns . hosts ").unwrap().unwrap(),
+            "\"a.com\" = \"1.1.1.1\"\n\n"
+        );
+        assert!(toml_table(TOML, "nope").unwrap().is_none());
+        assert_eq!(toml_table(TOML, "").unwrap().unwrap(), TOML);
+
+        // 跨行数组中以 `[` 开头的行不是表头
+        let src = "[a]\nx = [\n  [1, 2],\n  \"]\",\n]\n[b]\ny = 1\n";
+        assert_eq!(
+            toml_table(src, "a").unwrap().unwrap(),
+            "x = [\n  [1, 2],\n  \"]\",\n]\n"
+        );
+
+        // 子集之外的写法
+        for (src, path) in [
+            (TOML, "dns.servers"),
+            ("[[a]]\n[a.b]\nx = 1\n", "a.b"),
+            ("[a]\ns = \"\"\"\n[b]\n\"\"\"\n", "a"),
+            ("[a]\nb = { x = 1 }\n", "a"),
+            ("a.b = 1\n[a.c]\n", "a"),
+            ("a = { b = 1 }\n", "a.b"),
+            ("[a]\nb.c = 1\n", "a.b"),
+            ("[\"a.b\"]\nx = 1\n", "a"),
+        ] {
+            let e = ExtractSource::toml_table(SingleFileSource::Inline(src.into()), path);
+            assert!(
+                matches!(e.fetch(), Err(FetchError::Invalid(_))),
+                "{src:?} {path}"
+            );
+        }
+        let e = ExtractSource::toml_table(SingleFileSource::Inline(vec![0xff]), "a");
+        assert!(matches!(e.fetch(), Err(FetchError::Invalid(_))));
+    }
+}
@@ -5,21 +5,271 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full};
-use std::{convert::Infallible, path::Path, sync::Arc};
+use std::{
+    convert::Infallible,
+    path::Path,
+    sync::{atomic::AtomicU64, Arc},
+};
 use tower::{Service, ServiceBuilder};
 
+/// 在查找 DataSource 之前处理的路径, 见 [`DataSourceService::with_route`]
+#[derive(Clone, Debug)]
+pub enum RouteOverride {
+    /// 302 重定向到指定位置, 如 `/latest` 指向某个版本的路径
+    Redirect(String),
+    /// 改为读取 DataSource 中的另一个路径
+    Alias(String),
+    /// 直接返回固定内容
+    Inline {
+        body: Vec<u8>,
+        content_type: Option<String>,
+    },
+}
+
+impl RouteOverride {
+    /// 重定向的位置和 content type 会作为响应头, 需要是合法的 header 值
+    fn check(&self) -> Result<(), FetchError> {
+        let v = match self {
+            RouteOverride::Redirect(to) => to,
+            RouteOverride::Inline {
+                content_type: Some(t),
+                ..
+            } => t,
+            _ => return Ok(()),
+        };
+        header::HeaderValue::try_from(v.as_str())
+            .map(|_| ())
+            .map_err(|e| FetchError::Invalid(format!("route header value {v:?}: {e}")))
+    }
+}
+
+type ReloadObserverFn = dyn Fn(&config_diff::ConfigDiff) + Send + Sync;
+
+/// 见 [`DataSourceService::with_reload_observer`]
+#[derive(Clone)]
+struct ReloadObserver(Arc<ReloadObserverFn>);
+
+impl std::fmt::Debug for ReloadObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReloadObserver")
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct DataSourceService {
-    data_source: Arc<DataSource>,
+    /// 可以被 [`DataSourceService::reload`] 替换, 所有克隆共享
+    data_source: Arc<std::sync::RwLock<Arc<DataSource>>>,
     // 可添加更多配置项，例如默认 Content-Type
+    /// 设置后, 请求必须带有 [`sign_url`] 生成的有效签名
+    signing_key: Option<Arc<[u8]>>,
+    /// 是否允许通过 `?archive=tar` 下载某个前缀下的所有文件
+    allow_archive: bool,
+    /// 打包的最多文件数和 tar 的最大字节数, 超过时返回 413
+    archive_limits: (usize, usize),
+    /// 是否响应 `/__health`, 见 [`register_health_route`]
+    health_check: bool,
+    /// `/__health` 缓存的检查结果, 超过 `health_interval` 后在后台重新检查
+    health: Arc<std::sync::Mutex<HealthCache>>,
+    health_interval: std::time::Duration,
+    /// 是否响应 `/__manifest`, 返回所有可列出的文件的 [`manifest::Manifest`]
+    manifest: bool,
+    /// 最近一次成功读取文件的时间 (unix 秒), 0 表示还没有
+    last_success: Arc<AtomicU64>,
+    /// 以 JSON 返回错误: `{"error":"not_found","path":"...","code":404,"message":"..."}`
+    json_errors: bool,
+    /// 键为去掉 `/files/` 前缀后的路径
+    routes: Arc<HashMap<String, RouteOverride>>,
+    /// 镜像模式的上游, 见 [`DataSourceService::mirror`]
+    #[cfg(feature = "reqwest")]
+    mirror: Option<Arc<read_through::ReadThroughSource>>,
+    /// 设置后, 镜像和重新加载的管理接口需要 `Authorization: Bearer <token>`
+    admin_token: Option<Arc<str>>,
+    /// 是否响应 `/__reload`, 返回最近一次 [`DataSourceService::reload`] 的时间和配置变化.
+    /// 没有 `admin_token` 时不响应
+    reload_endpoint: bool,
+    last_reload: Arc<std::sync::Mutex<Option<(u64, config_diff::ConfigDiff)>>>,
+    reload_observer: Option<ReloadObserver>,
 }
 
 impl DataSourceService {
     pub fn new(data_source: DataSource) -> Self {
         Self {
-            data_source: Arc::new(data_source),
+            data_source: Arc::new(std::sync::RwLock::new(Arc::new(data_source))),
+            signing_key: None,
+            allow_archive: false,
+            archive_limits: (DEFAULT_ARCHIVE_MAX_FILES, DEFAULT_ARCHIVE_MAX_BYTES),
+            health_check: false,
+            health: Arc::default(),
+            health_interval: DEFAULT_HEALTH_INTERVAL,
+            manifest: false,
+            last_success: Arc::new(AtomicU64::new(0)),
+            json_errors: false,
+            routes: Arc::new(HashMap::new()),
+            #[cfg(feature = "reqwest")]
+            mirror: None,
+            admin_token: None,
+            reload_endpoint: false,
+            last_reload: Arc::new(std::sync::Mutex::new(None)),
+            reload_observer: None,
+        }
+    }
+
+    /// 换成新的 DataSource, 之后的请求 (包括所有克隆) 都从新的来源读取.
+    /// 返回配置的变化, 同时通知 [`Self::with_reload_observer`] 设置的回调
+    pub fn reload(&self, data_source: DataSource) -> config_diff::ConfigDiff {
+        let mut current = self.data_source.write().unwrap_or_else(|e| e.into_inner());
+        let diff = current.config_diff(&data_source);
+        *current = Arc::new(data_source);
+        drop(current);
+        *self.health.lock().unwrap_or_else(|e| e.into_inner()) = HealthCache::default();
+        *self.last_reload.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((unix_now(), diff.clone()));
+        if let Some(o) = &self.reload_observer {
+            (o.0)(&diff);
+        }
+        diff
+    }
+
+    pub fn with_reload_observer(mut self, f: Arc<ReloadObserverFn>) -> Self {
+        self.reload_observer = Some(ReloadObserver(f));
+        self
+    }
+
+    /// 需同时设置 [`Self::with_admin_token`], 否则 `/__reload` 按普通文件路径处理
+    pub fn with_reload_endpoint(mut self, enable: bool) -> Self {
+        self.reload_endpoint = enable;
+        self
+    }
+
+    /// 镜像模式: 文件从 `source` 读取 (本地缓存优先), 并提供管理接口
+    /// - `POST /__mirror/purge` 删除所有缓存, `POST /__mirror/purge/<path>` 删除一个文件的缓存
+    /// - `POST /__mirror/refresh/<path>` 立即从上游重新下载
+    ///
+    /// 管理接口需要 [`Self::with_admin_token`], 否则返回 403
+    #[cfg(feature = "reqwest")]
+    pub fn mirror(source: read_through::ReadThroughSource) -> Self {
+        let mut s = Self::new(DataSource::Async(Box::new(source.clone())));
+        s.mirror = Some(Arc::new(source));
+        s
+    }
+
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    /// 重定向位置或 content type 不是合法的 header 值时返回 [`FetchError::Invalid`]
+    pub fn with_route(mut self, path: &str, r: RouteOverride) -> Result<Self, FetchError> {
+        r.check()?;
+        Arc::make_mut(&mut self.routes).insert(path.trim_start_matches('/').to_string(), r);
+        Ok(self)
+    }
+
+    pub fn with_json_errors(mut self, enable: bool) -> Self {
+        self.json_errors = enable;
+        self
+    }
+
+    pub fn with_health_check(mut self, enable: bool) -> Self {
+        self.health_check = enable;
+        self
+    }
+
+    /// 重新检查配置的间隔, 默认为 [`DEFAULT_HEALTH_INTERVAL`]
+    pub fn with_health_check_interval(mut self, interval: std::time::Duration) -> Self {
+        self.health_interval = interval;
+        self
+    }
+
+    pub fn with_manifest(mut self, enable: bool) -> Self {
+        self.manifest = enable;
+        self
+    }
+
+    pub fn with_archive(mut self, allow: bool) -> Self {
+        self.allow_archive = allow;
+        self
+    }
+
+    /// 打包整个目录时的上限, 默认为 [`DEFAULT_ARCHIVE_MAX_FILES`] 和 [`DEFAULT_ARCHIVE_MAX_BYTES`]
+    pub fn with_archive_limits(mut self, max_files: usize, max_bytes: usize) -> Self {
+        self.archive_limits = (max_files, max_bytes);
+        self
+    }
+
+    pub fn with_signing_key(mut self, key: &[u8]) -> Self {
+        self.signing_key = Some(key.into());
+        self
+    }
+}
+
+fn hmac_key(key: &[u8]) -> ring::hmac::Key {
+    ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key)
+}
+
+/// `auth` 是否为 `Bearer <token>`. 没有设置口令时总是 false.
+/// 比较两者的 HMAC, 耗时与口令内容无关
+fn is_admin(token: Option<&str>, auth: Option<&header::HeaderValue>) -> bool {
+    let (Some(t), Some(auth)) = (token, auth) else {
+        return false;
+    };
+    let k = hmac_key(b"data-source admin token");
+    let tag = ring::hmac::sign(&k, format!("Bearer {t}").as_bytes());
+    ring::hmac::verify(&k, auth.as_bytes(), tag.as_ref()).is_ok()
+}
+
+/// 签名覆盖路径, 除 `expires` 和 `sig` 外的查询参数 (按原顺序) 和过期时间,
+/// 这样签名的链接不能再加上 `?archive=tar` 等参数
+fn sign_message(path: &str, query: &str, expires: u64) -> String {
+    format!("{path}\n{query}\n{expires}")
+}
+
+/// 为 `path` (如 `/files/private/a.json`, 可以带有查询参数, 如 `/files/private?archive=tar`)
+/// 生成在 `expires_at` 前有效的签名链接, 在查询参数后加上 `expires=..&sig=..`
+pub fn sign_url(key: &[u8], path: &str, expires_at: SystemTime) -> String {
+    let expires = expires_at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let tag = ring::hmac::sign(
+        &hmac_key(key),
+        sign_message(path, query, expires).as_bytes(),
+    );
+    let sep = if query.is_empty() { "" } else { "&" };
+    format!(
+        "{path}?{query}{sep}expires={expires}&sig={}",
+        hex::encode(tag.as_ref())
+    )
+}
+
+/// 检查请求中的签名是否有效且未过期, 其它查询参数也必须与签名时相同
+pub fn verify_signed_url(key: &[u8], path: &str, query: Option<&str>) -> bool {
+    let (mut expires, mut sig) = (None, None);
+    let mut rest = Vec::new();
+    for kv in query.unwrap_or_default().split('&') {
+        match kv.split_once('=') {
+            Some(("expires", v)) => expires = v.parse::<u64>().ok(),
+            Some(("sig", v)) => sig = Some(v),
+            _ if kv.is_empty() => {}
+            _ => rest.push(kv),
         }
     }
+    let (Some(expires), Some(sig)) = (expires, sig) else {
+        return false;
+    };
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    if expires < now {
+        return false;
+    }
+    let Some(sig) = hex::decode(sig.as_bytes()) else {
+        return false;
+    };
+    let msg = sign_message(path, &rest.join("&"), expires);
+    ring::hmac::verify(&hmac_key(key), msg.as_bytes(), &sig).is_ok()
 }
 
 impl<ReqBody> Service<Request<ReqBody>> for DataSourceService
@@ -38,56 +288,229 @@ where
     }
 
     fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
-        let data_source = self.data_source.clone();
+        let data_source = self
+            .data_source
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let signing_key = self.signing_key.clone();
+        let allow_archive = self.allow_archive;
+        let archive_limits = self.archive_limits;
+        let health_check = self.health_check;
+        let health = self.health.clone();
+        let health_interval = self.health_interval;
+        let manifest = self.manifest;
+        let last_success = self.last_success.clone();
+        let json_errors = self.json_errors;
+        let routes = self.routes.clone();
+        #[cfg(feature = "reqwest")]
+        let mirror = self.mirror.clone();
+        let admin_token = self.admin_token.clone();
+        let last_reload =
+            (self.reload_endpoint && self.admin_token.is_some()).then(|| self.last_reload.clone());
 
         Box::pin(async move {
-            // 只处理 GET/HEAD 请求
+            let authorized = is_admin(
+                admin_token.as_deref(),
+                req.headers().get(header::AUTHORIZATION),
+            );
+            #[cfg(feature = "reqwest")]
+            if let Some(m) = mirror {
+                if let Some(rest) = req.uri().path().strip_prefix(MIRROR_ADMIN_PATH) {
+                    if admin_token.is_none() {
+                        return Ok(json_error_response(
+                            StatusCode::FORBIDDEN,
+                            "forbidden",
+                            rest,
+                            "Mirror admin api requires an admin token",
+                        ));
+                    }
+                    return Ok(mirror_admin_response(&m, req.method(), authorized, rest).await);
+                }
+            }
+
+            // 只处理 GET/HEAD 请求. 服务不提供写入接口, 即使来源的 capabilities
+            // 为 writable, PUT 等请求也返回 405
             if !matches!(req.method(), &Method::GET | &Method::HEAD) {
-                let body =
-                    UnsyncBoxBody::new(Full::new(Bytes::from("Method not allowed")).map_err(
-                        |_| std::io::Error::new(std::io::ErrorKind::Other, "stream error"),
-                    ));
+                let allow = if data_source.capabilities().readable {
+                    "GET, HEAD"
+                } else {
+                    ""
+                };
+                if json_errors {
+                    let mut r = json_error_response(
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "method_not_allowed",
+                        req.uri().path(),
+                        "Method not allowed",
+                    );
+                    r.headers_mut()
+                        .insert(header::ALLOW, header::HeaderValue::from_static(allow));
+                    return Ok(r);
+                }
+                let body = UnsyncBoxBody::new(
+                    Full::new(Bytes::from("Method not allowed"))
+                        .map_err(|_| std::io::Error::other("stream error")),
+                );
                 return Ok(Response::builder()
                     .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .header(header::ALLOW, allow)
                     .body(body)
                     .unwrap());
             }
 
+            if health_check && req.uri().path() == HEALTH_PATH {
+                return Ok(
+                    health_response(data_source, &health, health_interval, &last_success).await,
+                );
+            }
+
+            if let Some(last_reload) = last_reload.filter(|_| req.uri().path() == RELOAD_PATH) {
+                if !authorized {
+                    return Ok(json_error_response(
+                        StatusCode::UNAUTHORIZED,
+                        "unauthorized",
+                        RELOAD_PATH,
+                        "missing or invalid admin token",
+                    ));
+                }
+                let last = last_reload
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clone();
+                return Ok(reload_response(last));
+            }
+
+            if let Some(key) = signing_key {
+                if !verify_signed_url(&key, req.uri().path(), req.uri().query()) {
+                    if json_errors {
+                        return Ok(json_error_response(
+                            StatusCode::FORBIDDEN,
+                            "invalid_signature",
+                            req.uri().path(),
+                            "Invalid or expired signature",
+                        ));
+                    }
+                    let body = UnsyncBoxBody::new(
+                        Full::new(Bytes::from("Invalid or expired signature"))
+                            .map_err(|_| std::io::Error::other("stream error")),
+                    );
+                    return Ok(Response::builder()
+                        .status(StatusCode::FORBIDDEN)
+                        .body(body)
+                        .unwrap());
+                }
+            }
+
+            if manifest && req.uri().path() == MANIFEST_PATH {
+                return Ok(manifest_response(data_source).await);
+            }
+
             let path = req.uri().path().trim_start_matches("/files/");
+
+            let aliased;
+            let path = match routes.get(path.trim_start_matches('/')) {
+                Some(RouteOverride::Redirect(to)) => {
+                    let body = UnsyncBoxBody::new(
+                        Full::new(Bytes::new()).map_err(|_| std::io::Error::other("stream error")),
+                    );
+                    return Ok(Response::builder()
+                        .status(StatusCode::FOUND)
+                        .header(header::LOCATION, to.as_str())
+                        .body(body)
+                        .unwrap());
+                }
+                Some(RouteOverride::Inline { body, content_type }) => {
+                    let mime = content_type.clone().unwrap_or_else(|| {
+                        mime_guess::from_path(path)
+                            .first_or_octet_stream()
+                            .to_string()
+                    });
+                    let body = UnsyncBoxBody::new(
+                        Full::new(Bytes::from(body.clone()))
+                            .map_err(|_| std::io::Error::other("stream error")),
+                    );
+                    return Ok(Response::builder()
+                        .header(header::CONTENT_TYPE, mime)
+                        .body(body)
+                        .unwrap());
+                }
+                Some(RouteOverride::Alias(to)) => {
+                    aliased = to.clone();
+                    aliased.as_str()
+                }
+                None => path,
+            };
+
+            // 在路由之后处理, 这样别名和重定向对打包下载同样有效
+            if allow_archive {
+                let format = req.uri().query().and_then(|q| {
+                    q.split('&')
+                        .find_map(|kv| kv.strip_prefix("archive="))
+                        .map(|f| f.to_string())
+                });
+                if let Some(format) = format {
+                    let prefix = path.trim_matches('/').to_string();
+                    return Ok(
+                        archive_response(data_source, prefix, &format, archive_limits).await,
+                    );
+                }
+            }
             let path = Path::new(path);
 
-            let result = data_source.get_file_content_async(path).await;
+            let ctx = fetch_context(req.headers());
+            let result = data_source.get_file_content_ctx_async(path, &ctx).await;
 
             // 构建响应
             match result {
-                Ok((content, _)) => {
-                    let mime = mime_guess::from_path(path).first_or_octet_stream();
-                    let body = UnsyncBoxBody::new(Full::new(Bytes::from(content)).map_err(|_| {
-                        std::io::Error::new(std::io::ErrorKind::Other, "stream error")
-                    }));
-                    let response = Response::builder()
-                        .header(header::CONTENT_TYPE, mime.to_string())
-                        .body(body)
-                        .unwrap();
+                Ok(fc) => {
+                    last_success.store(unix_now(), Ordering::Relaxed);
+                    // 来源没有给出 content type 或给出的值不能作为响应头时才根据扩展名猜测
+                    let mime = fc
+                        .content_type
+                        .as_deref()
+                        .and_then(|t| header_value(path, "content type", t))
+                        .unwrap_or_else(|| {
+                            mime_guess::from_path(path)
+                                .first_or_octet_stream()
+                                .to_string()
+                                .try_into()
+                                .expect("mime types are valid header values")
+                        });
+                    let body = UnsyncBoxBody::new(
+                        Full::new(Bytes::from(fc.data))
+                            .map_err(|_| std::io::Error::other("stream error")),
+                    );
+                    let mut response = Response::builder().header(header::CONTENT_TYPE, mime);
+                    if let Some(enc) = fc
+                        .content_encoding
+                        .as_deref()
+                        .and_then(|e| header_value(path, "content encoding", e))
+                    {
+                        response = response.header(header::CONTENT_ENCODING, enc);
+                    }
+                    let response = response.body(body).unwrap();
                     Ok(response)
                 }
                 Err(e) => {
-                    let status = match e {
-                        FetchError::NF | FetchError::NFD(_) => StatusCode::NOT_FOUND,
-                        FetchError::S => StatusCode::PAYLOAD_TOO_LARGE,
-                        _ => StatusCode::INTERNAL_SERVER_ERROR,
-                    };
+                    let status = status_code(&e);
+                    if json_errors {
+                        return Ok(json_error_response(
+                            status,
+                            error_code(&e),
+                            &path.to_string_lossy(),
+                            &e.to_string(),
+                        ));
+                    }
                     let body = UnsyncBoxBody::new(
                         Full::new(Bytes::from(
                             status.to_string()
                                 + "\n\n"
-                                + &path.to_string_lossy().to_string()
+                                + &path.to_string_lossy()
                                 + "\n\n"
                                 + &e.to_string(),
                         ))
-                        .map_err(|_| {
-                            std::io::Error::new(std::io::ErrorKind::Other, "stream error")
-                        }),
+                        .map_err(|_| std::io::Error::other("stream error")),
                     );
                     Ok(Response::builder().status(status).body(body).unwrap())
                 }
@@ -96,6 +519,408 @@ where
     }
 }
 
+/// 来源给出的 content type 等不是合法的 header 值时记录警告并忽略, 不影响响应
+fn header_value(path: &Path, what: &str, v: &str) -> Option<header::HeaderValue> {
+    header::HeaderValue::try_from(v)
+        .inspect_err(|e| warn!("ignoring {what} {v:?} of {}: {e}", path.display()))
+        .ok()
+}
+
+/// JSON 错误中的 `error` 字段, 取值是稳定的
+pub fn error_code(e: &FetchError) -> &'static str {
+    match e.kind() {
+        FetchErrorKind::NotFound => "not_found",
+        FetchErrorKind::IsDirectory => "is_directory",
+        FetchErrorKind::PermissionDenied => "forbidden",
+        FetchErrorKind::TooLarge => "too_large",
+        FetchErrorKind::Unavailable => "unavailable",
+        FetchErrorKind::Unsupported => "unsupported",
+        FetchErrorKind::InvalidContent => "invalid_content",
+        _ => "internal",
+    }
+}
+
+fn json_error_response(
+    status: StatusCode,
+    error: &str,
+    path: &str,
+    message: &str,
+) -> Response<UnsyncBoxBody<Bytes, std::io::Error>> {
+    let body = serde_json::json!({
+        "error": error,
+        "path": path,
+        "code": status.as_u16(),
+        "message": message,
+    });
+    let body = UnsyncBoxBody::new(
+        Full::new(Bytes::from(body.to_string())).map_err(|_| std::io::Error::other("stream error")),
+    );
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap()
+}
+
+const HEALTH_PATH: &str = "/__health";
+const MANIFEST_PATH: &str = "/__manifest";
+const RELOAD_PATH: &str = "/__reload";
+
+/// `{"reloaded_at":..,"added":[..],"removed":[..],"changed":[..]}`, 还没有重新加载过时
+/// `reloaded_at` 为 null, 其余为空
+fn reload_response(
+    last: Option<(u64, config_diff::ConfigDiff)>,
+) -> Response<UnsyncBoxBody<Bytes, std::io::Error>> {
+    let (at, diff) = match last {
+        Some((at, diff)) => (Some(at), diff),
+        None => (None, Default::default()),
+    };
+    let body = serde_json::json!({
+        "reloaded_at": at,
+        "added": diff.added,
+        "removed": diff.removed,
+        "changed": diff.changed,
+    });
+    let body = UnsyncBoxBody::new(
+        Full::new(Bytes::from(body.to_string())).map_err(|_| std::io::Error::other("stream error")),
+    );
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap()
+}
+
+async fn manifest_response(
+    data_source: Arc<DataSource>,
+) -> Response<UnsyncBoxBody<Bytes, std::io::Error>> {
+    let r = tokio::task::spawn_blocking(move || data_source.manifest())
+        .await
+        .unwrap_or_else(|e| Err(FetchError::I(io::Error::other(e))));
+    match r {
+        Ok(m) => {
+            let body = UnsyncBoxBody::new(
+                Full::new(Bytes::from(m.to_json()))
+                    .map_err(|_| std::io::Error::other("stream error")),
+            );
+            Response::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .unwrap()
+        }
+        Err(e) => json_error_response(
+            status_code(&e),
+            error_code(&e),
+            MANIFEST_PATH,
+            &e.to_string(),
+        ),
+    }
+}
+#[cfg(feature = "reqwest")]
+const MIRROR_ADMIN_PATH: &str = "/__mirror/";
+
+/// 处理镜像的管理接口, `rest` 为 `purge`, `purge/<path>` 或 `refresh/<path>`
+#[cfg(feature = "reqwest")]
+async fn mirror_admin_response(
+    m: &read_through::ReadThroughSource,
+    method: &Method,
+    authorized: bool,
+    rest: &str,
+) -> Response<UnsyncBoxBody<Bytes, std::io::Error>> {
+    if method != Method::POST {
+        return json_error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            rest,
+            "Method not allowed",
+        );
+    }
+    if !authorized {
+        return json_error_response(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            rest,
+            "Missing or invalid admin token",
+        );
+    }
+    let (action, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let r = match (action, path) {
+        ("purge", "") => m
+            .purge(None)
+            .await
+            .map(|n| serde_json::json!({ "purged": n })),
+        ("purge", p) => m
+            .purge(Some(Path::new(p)))
+            .await
+            .map(|n| serde_json::json!({ "purged": n })),
+        ("refresh", p) if !p.is_empty() => m
+            .refresh(Path::new(p))
+            .await
+            .map(|d| serde_json::json!({ "refreshed": p, "size": d.len() })),
+        _ => {
+            return json_error_response(
+                StatusCode::NOT_FOUND,
+                "not_found",
+                rest,
+                "Unknown mirror action",
+            )
+        }
+    };
+    match r {
+        Ok(v) => {
+            let body = UnsyncBoxBody::new(
+                Full::new(Bytes::from(v.to_string()))
+                    .map_err(|_| std::io::Error::other("stream error")),
+            );
+            Response::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .unwrap()
+        }
+        Err(e) => json_error_response(status_code(&e), error_code(&e), path, &e.to_string()),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub const DEFAULT_HEALTH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+struct HealthCache {
+    /// 最近一次检查的时间 (unix 秒) 和发现的问题
+    checked: Option<(u64, Vec<serde_json::Value>)>,
+    refreshing: bool,
+}
+
+/// 在阻塞线程中检查配置 (会读取 tar, 在缓存目录中写入探测文件), 结果存入 `cache`
+async fn refresh_health(data_source: Arc<DataSource>, cache: Arc<std::sync::Mutex<HealthCache>>) {
+    let issues = tokio::task::spawn_blocking(move || {
+        data_source
+            .validate(&[])
+            .issues
+            .iter()
+            .map(|i| serde_json::json!({ "target": i.target, "message": i.message }))
+            .collect()
+    })
+    .await
+    .unwrap_or_else(|e| vec![serde_json::json!({ "target": "", "message": e.to_string() })]);
+    let mut c = cache.lock().unwrap_or_else(|e| e.into_inner());
+    c.checked = Some((unix_now(), issues));
+    c.refreshing = false;
+}
+
+/// 返回最近一次检查配置 (见 [`DataSource::validate`]) 的结果, 有问题时返回 503,
+/// 可用作 k8s 的 readiness probe. 过期的缓存只会列出, 不影响状态.
+///
+/// 结果超过 `interval` 时在后台重新检查, 本次仍返回上一次的结果; 只有还没有结果时才等待检查完成
+async fn health_response(
+    data_source: Arc<DataSource>,
+    cache: &Arc<std::sync::Mutex<HealthCache>>,
+    interval: std::time::Duration,
+    last_success: &AtomicU64,
+) -> Response<UnsyncBoxBody<Bytes, std::io::Error>> {
+    let (checked, refresh) = {
+        let mut c = cache.lock().unwrap_or_else(|e| e.into_inner());
+        let fresh = c
+            .checked
+            .as_ref()
+            .is_some_and(|(t, _)| unix_now().saturating_sub(*t) < interval.as_secs());
+        let refresh = !fresh && !c.refreshing;
+        c.refreshing |= refresh;
+        (c.checked.clone(), refresh)
+    };
+    #[cfg(feature = "reqwest")]
+    let stale_caches = data_source.stale_caches();
+    #[cfg(not(feature = "reqwest"))]
+    let stale_caches: Vec<String> = Vec::new();
+    let (checked_at, issues) = match checked {
+        Some(c) => {
+            if refresh {
+                tokio::spawn(refresh_health(data_source, cache.clone()));
+            }
+            c
+        }
+        None => {
+            refresh_health(data_source, cache.clone()).await;
+            let c = cache.lock().unwrap_or_else(|e| e.into_inner());
+            c.checked.clone().unwrap_or_default()
+        }
+    };
+    let last_success = match last_success.load(Ordering::Relaxed) {
+        0 => None,
+        t => Some(t),
+    };
+    let body = serde_json::json!({
+        "status": if issues.is_empty() { "ok" } else { "unhealthy" },
+        "issues": issues,
+        "stale_caches": stale_caches,
+        "checked_unix": checked_at,
+        "last_success_unix": last_success,
+    });
+    let status = if issues.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = UnsyncBoxBody::new(
+        Full::new(Bytes::from(body.to_string())).map_err(|_| std::io::Error::other("stream error")),
+    );
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap()
+}
+
+pub const DEFAULT_ARCHIVE_MAX_FILES: usize = 10_000;
+pub const DEFAULT_ARCHIVE_MAX_BYTES: usize = 256 << 20;
+
+/// 超过 `limit` 字节时写入失败的缓冲区
+#[cfg(feature = "tar")]
+struct LimitedBuf {
+    buf: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+#[cfg(feature = "tar")]
+impl io::Write for LimitedBuf {
+    fn write(&mut self, d: &[u8]) -> io::Result<usize> {
+        if self.buf.len() + d.len() > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("archive size limit exceeded"));
+        }
+        self.buf.extend_from_slice(d);
+        Ok(d.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 把 `prefix` 下所有可列出的文件打包返回. 目前只支持 tar.
+/// 整个 tar 在内存中生成, 文件数或大小超过 `limits` 时返回 413
+async fn archive_response(
+    data_source: Arc<DataSource>,
+    prefix: String,
+    format: &str,
+    limits: (usize, usize),
+) -> Response<UnsyncBoxBody<Bytes, std::io::Error>> {
+    let text = |status: StatusCode, s: String| {
+        let body = UnsyncBoxBody::new(
+            Full::new(Bytes::from(s)).map_err(|_| std::io::Error::other("stream error")),
+        );
+        Response::builder().status(status).body(body).unwrap()
+    };
+    if format != "tar" {
+        return text(
+            StatusCode::BAD_REQUEST,
+            format!("unsupported archive format `{format}`"),
+        );
+    }
+    #[cfg(feature = "tar")]
+    {
+        let name = if prefix.is_empty() {
+            "archive".to_string()
+        } else {
+            prefix.replace('/', "_")
+        };
+        let (max_files, max_bytes) = limits;
+        // 内层的 Err 是超过上限的说明
+        let r =
+            tokio::task::spawn_blocking(move || -> Result<Result<Vec<u8>, String>, FetchError> {
+                data_source.capabilities().require_listable()?;
+                let files: Vec<String> = data_source
+                    .list_files()?
+                    .into_iter()
+                    .filter(|f| prefix.is_empty() || f.starts_with(&format!("{prefix}/")))
+                    .collect();
+                if files.len() > max_files {
+                    return Ok(Err(format!(
+                        "archive has {} files, more than {max_files}",
+                        files.len()
+                    )));
+                }
+                let names: Vec<&str> = files.iter().map(|f| f.as_str()).collect();
+                let mut w = LimitedBuf {
+                    buf: Vec::new(),
+                    limit: max_bytes,
+                    exceeded: false,
+                };
+                match data_source.export(Some(&names), crate::export::ExportTarget::Tar(&mut w)) {
+                    Err(_) if w.exceeded => {
+                        Ok(Err(format!("archive is larger than {max_bytes} bytes")))
+                    }
+                    r => r.map(|_| Ok(w.buf)),
+                }
+            })
+            .await
+            .unwrap_or_else(|e| Err(FetchError::I(io::Error::other(e))));
+        match r {
+            Ok(Err(s)) => text(StatusCode::PAYLOAD_TOO_LARGE, s),
+            Ok(Ok(buf)) => {
+                let body = UnsyncBoxBody::new(
+                    Full::new(Bytes::from(buf)).map_err(|_| std::io::Error::other("stream error")),
+                );
+                Response::builder()
+                    .header(header::CONTENT_TYPE, "application/x-tar")
+                    .header(
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{name}.tar\""),
+                    )
+                    .body(body)
+                    .unwrap()
+            }
+            Err(e) => text(status_code(&e), e.to_string()),
+        }
+    }
+    #[cfg(not(feature = "tar"))]
+    {
+        let _ = (data_source, prefix, limits);
+        text(
+            StatusCode::NOT_IMPLEMENTED,
+            "archive needs the tar feature".to_string(),
+        )
+    }
+}
+
+/// 从请求头中取得 [`FetchContext`]: `X-Tenant-Id`, `Accept-Language` 中的语言,
+/// 以及 `X-Request-Id`
+pub fn fetch_context(headers: &axum::http::HeaderMap) -> FetchContext {
+    let get = |k: &str| {
+        headers
+            .get(k)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let languages = get(header::ACCEPT_LANGUAGE.as_str())
+        .map(|v| crate::locale::parse_accept_language(&v))
+        .unwrap_or_default();
+    FetchContext {
+        tenant: get("x-tenant-id"),
+        locale: languages.first().cloned(),
+        languages,
+        trace_id: get("x-request-id"),
+    }
+}
+
+fn status_code(e: &FetchError) -> StatusCode {
+    match e.kind() {
+        FetchErrorKind::NotFound | FetchErrorKind::IsDirectory => StatusCode::NOT_FOUND,
+        FetchErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        FetchErrorKind::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        FetchErrorKind::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        FetchErrorKind::Unsupported => StatusCode::NOT_IMPLEMENTED,
+        FetchErrorKind::InvalidContent => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 use axum::extract::Path as AxumPath;
 
 pub async fn handle_file_request(
@@ -109,6 +934,17 @@ pub async fn handle_file_request(
     service.call(req).await.unwrap()
 }
 
+/// 把 `/__health` 交给 `service` 处理, `service` 需开启 [`DataSourceService::with_health_check`]
+pub fn register_health_route(app: axum::Router, service: DataSourceService) -> axum::Router {
+    app.route_service(
+        HEALTH_PATH,
+        ServiceBuilder::new().service_fn(move |req| {
+            let mut service = service.clone();
+            async move { service.call(req).await }
+        }),
+    )
+}
+
 pub fn register_data_source_route(
     app: axum::Router,
     path: &str,
@@ -123,3 +959,494 @@ pub fn register_data_source_route(
         }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_signed_url() {
+        let key = b"secret";
+        let url = sign_url(
+            key,
+            "/files/a.txt",
+            SystemTime::now() + Duration::from_secs(60),
+        );
+        let (path, query) = url.split_once('?').unwrap();
+        assert!(verify_signed_url(key, path, Some(query)));
+        assert!(!verify_signed_url(key, "/files/b.txt", Some(query)));
+        assert!(!verify_signed_url(b"other", path, Some(query)));
+        assert!(!verify_signed_url(key, path, None));
+
+        let url = sign_url(
+            key,
+            "/files/a.txt",
+            SystemTime::now() - Duration::from_secs(1),
+        );
+        let (path, query) = url.split_once('?').unwrap();
+        assert!(!verify_signed_url(key, path, Some(query)));
+
+        // 其它查询参数也在签名范围内
+        let url = sign_url(
+            key,
+            "/files/a.txt",
+            SystemTime::now() + Duration::from_secs(60),
+        );
+        let (path, query) = url.split_once('?').unwrap();
+        assert!(!verify_signed_url(
+            key,
+            path,
+            Some(&format!("{query}&archive=tar"))
+        ));
+        assert!(!verify_signed_url(
+            key,
+            path,
+            Some(&format!("archive=tar&{query}"))
+        ));
+        let url = sign_url(
+            key,
+            "/files/sub?archive=tar",
+            SystemTime::now() + Duration::from_secs(60),
+        );
+        let (path, query) = url.split_once('?').unwrap();
+        assert_eq!(path, "/files/sub");
+        assert!(query.starts_with("archive=tar&expires="));
+        assert!(verify_signed_url(key, path, Some(query)));
+        let query = query.replace("archive=tar", "archive=zip");
+        assert!(!verify_signed_url(key, path, Some(&query)));
+    }
+
+    #[cfg(feature = "tar")]
+    #[tokio::test]
+    async fn test_archive() {
+        let ds = DataSource::FileMap(
+            vec![
+                (
+                    "sub/a.txt".to_string(),
+                    SingleFileSource::Inline(b"a".to_vec()),
+                ),
+                (
+                    "other/b.txt".to_string(),
+                    SingleFileSource::Inline(b"b".to_vec()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let mut service = DataSourceService::new(ds).with_archive(true);
+        let req = Request::builder()
+            .uri("/files/sub?archive=tar")
+            .body(())
+            .unwrap();
+        let resp = service.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+
+        let t = DataSource::TarInMemory(body.to_vec());
+        assert_eq!(t.list_files().unwrap(), vec!["sub/a.txt"]);
+
+        // 别名在打包前解析; 签名的链接不能再加上 archive 参数
+        let key = b"secret";
+        let mut service = service
+            .with_route("latest", RouteOverride::Alias("sub".to_string()))
+            .unwrap()
+            .with_signing_key(key);
+        let expires = SystemTime::now() + std::time::Duration::from_secs(60);
+        let get = |p: &str| Request::builder().uri(p).body(()).unwrap();
+        let url = sign_url(key, "/files/latest?archive=tar", expires);
+        let resp = service.call(get(&url)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let t = DataSource::TarInMemory(body.to_vec());
+        assert_eq!(t.list_files().unwrap(), vec!["sub/a.txt"]);
+        let url = sign_url(key, "/files/sub", expires) + "&archive=tar";
+        let resp = service.call(get(&url)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let service = DataSourceService::new(DataSource::FileMap(
+            (0..3)
+                .map(|i| (format!("sub/{i}"), SingleFileSource::Inline(vec![0; 1024])))
+                .collect(),
+        ))
+        .with_archive(true);
+        for (max_files, max_bytes, status) in [
+            (3, 16 << 10, StatusCode::OK),
+            (2, 16 << 10, StatusCode::PAYLOAD_TOO_LARGE),
+            (3, 4 << 10, StatusCode::PAYLOAD_TOO_LARGE),
+        ] {
+            let mut service = service.clone().with_archive_limits(max_files, max_bytes);
+            let req = Request::builder()
+                .uri("/files/sub?archive=tar")
+                .body(())
+                .unwrap();
+            assert_eq!(service.call(req).await.unwrap().status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manifest_route() {
+        let ds = DataSource::FileMap(HashMap::from([(
+            "a.txt".to_string(),
+            SingleFileSource::Inline(b"a".to_vec()),
+        )]));
+        let mut service = DataSourceService::new(ds).with_manifest(true);
+        let req = Request::builder().uri("/__manifest").body(()).unwrap();
+        let resp = service.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let m = manifest::Manifest::from_json(&body).unwrap();
+        assert!(m.verify("a.txt", b"a"));
+
+        let mut service = DataSourceService::new(DataSource::StdReadFile).with_manifest(true);
+        let req = Request::builder().uri("/__manifest").body(()).unwrap();
+        let resp = service.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[tokio::test]
+    async fn test_write_methods_rejected() {
+        let mut service = DataSourceService::new(DataSource::StdReadFile);
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri("/files/a.txt")
+            .body(())
+            .unwrap();
+        let resp = service.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[header::ALLOW], "GET, HEAD");
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let map = |v: &[u8]| {
+            DataSource::FileMap(HashMap::from([(
+                "a.txt".to_string(),
+                SingleFileSource::Inline(v.to_vec()),
+            )]))
+        };
+        let diffs = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let d = diffs.clone();
+        let service = DataSourceService::new(map(b"old"))
+            .with_reload_endpoint(true)
+            .with_admin_token("t")
+            .with_reload_observer(Arc::new(move |diff| d.lock().unwrap().push(diff.clone())));
+        let mut clone = service.clone();
+
+        let get = |uri: &str, token: Option<&str>| {
+            let mut b = Request::builder().uri(uri);
+            if let Some(t) = token {
+                b = b.header(header::AUTHORIZATION, format!("Bearer {t}"));
+            }
+            b.body(()).unwrap()
+        };
+        let resp = clone.call(get("/__reload", Some("t"))).await.unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(v["reloaded_at"].is_null());
+
+        let diff = service.reload(map(b"new"));
+        assert_eq!(diff.changed, vec!["a.txt"]);
+        assert_eq!(diffs.lock().unwrap().len(), 1);
+        let resp = clone.call(get("/files/a.txt", None)).await.unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "new");
+
+        let resp = clone.call(get("/__reload", None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = clone.call(get("/__reload", Some("t"))).await.unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(v["reloaded_at"].is_u64());
+        assert_eq!(v["changed"][0], "a.txt");
+
+        // 没有口令时不提供配置变化
+        let mut open = DataSourceService::new(map(b"old")).with_reload_endpoint(true);
+        open.reload(map(b"new"));
+        let resp = open.call(get("/__reload", None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let mut service =
+            DataSourceService::new(DataSource::Folders(vec!["/nonexistent".to_string()]))
+                .with_health_check(true);
+        let req = Request::builder().uri("/__health").body(()).unwrap();
+        let resp = service.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["status"], "unhealthy");
+        assert_eq!(v["issues"][0]["target"], "/nonexistent");
+    }
+
+    #[tokio::test]
+    async fn test_health_cached() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("d");
+        let ds = || DataSource::Folders(vec![dir.to_string_lossy().to_string()]);
+        let probe = |mut service: DataSourceService| async move {
+            let req = Request::builder().uri("/__health").body(()).unwrap();
+            let body = service.call(req).await.unwrap().into_body();
+            let body = body.collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+        let cached = DataSourceService::new(ds()).with_health_check(true);
+        let refreshed = DataSourceService::new(ds())
+            .with_health_check(true)
+            .with_health_check_interval(std::time::Duration::ZERO);
+        assert_eq!(probe(cached.clone()).await["status"], "unhealthy");
+        assert_eq!(probe(refreshed.clone()).await["status"], "unhealthy");
+        std::fs::create_dir(&dir).unwrap();
+
+        // 间隔内返回上一次的结果, 不重新检查
+        let v = probe(cached.clone()).await;
+        assert_eq!(v["status"], "unhealthy");
+        assert!(v["checked_unix"].as_u64().unwrap() > 0);
+
+        // 过期时先返回上一次的结果, 在后台重新检查
+        assert_eq!(probe(refreshed.clone()).await["status"], "unhealthy");
+        let mut ok = false;
+        for _ in 0..100 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            if probe(refreshed.clone()).await["status"] == "ok" {
+                ok = true;
+                break;
+            }
+        }
+        assert!(ok);
+
+        // reload 后丢弃缓存的结果
+        cached.reload(ds());
+        assert_eq!(probe(cached).await["status"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_json_errors() {
+        let mut service =
+            DataSourceService::new(DataSource::FileMap(HashMap::new())).with_json_errors(true);
+        let req = Request::builder().uri("/files/a.txt").body(()).unwrap();
+        let resp = service.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["error"], "not_found");
+        assert_eq!(v["path"], "a.txt");
+        assert_eq!(v["code"], 404);
+    }
+
+    #[test]
+    fn test_status_code_denied() {
+        let e = local_io_error(std::io::ErrorKind::PermissionDenied.into(), Path::new("a"));
+        assert_eq!(status_code(&e), StatusCode::FORBIDDEN);
+        let e = FetchError::Lookup(LookupError::IsDirectory("a".to_string()));
+        assert_eq!(status_code(&e), StatusCode::NOT_FOUND);
+    }
+
+    /// 不可读的文件返回 403, root 不受权限限制时跳过
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_forbidden_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("secret.txt");
+        std::fs::write(&file, "s").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o000)).unwrap();
+        if std::fs::read(&file).is_ok() {
+            return;
+        }
+        let ds = DataSource::Folders(vec![dir.path().to_string_lossy().to_string()]);
+        let mut service = DataSourceService::new(ds);
+        let req = Request::builder()
+            .uri("/files/secret.txt")
+            .body(())
+            .unwrap();
+        let resp = service.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_route_overrides() {
+        let ds = DataSource::FileMap(
+            vec![(
+                "v2/a.txt".to_string(),
+                SingleFileSource::Inline(b"a".to_vec()),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let mut service = DataSourceService::new(ds)
+            .with_route("latest", RouteOverride::Redirect("/files/v2/".to_string()))
+            .unwrap()
+            .with_route("a.txt", RouteOverride::Alias("v2/a.txt".to_string()))
+            .unwrap()
+            .with_route(
+                "robots.txt",
+                RouteOverride::Inline {
+                    body: b"User-agent: *".to_vec(),
+                    content_type: None,
+                },
+            )
+            .unwrap();
+
+        // 不能作为响应头的值在注册时拒绝
+        let s = DataSourceService::new(DataSource::StdReadFile);
+        let bad = s.clone().with_route(
+            "r",
+            RouteOverride::Redirect("/a\r\nSet-Cookie: x".to_string()),
+        );
+        assert!(matches!(bad, Err(FetchError::Invalid(_))));
+        let bad = s.with_route(
+            "i",
+            RouteOverride::Inline {
+                body: Vec::new(),
+                content_type: Some("text/plain\x7f".to_string()),
+            },
+        );
+        assert!(matches!(bad, Err(FetchError::Invalid(_))));
+
+        let get = |p: &str| Request::builder().uri(p).body(()).unwrap();
+        let resp = service.call(get("/files/latest")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(resp.headers()[header::LOCATION], "/files/v2/");
+
+        let resp = service.call(get("/files/a.txt")).await.unwrap();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.as_ref(), b"a");
+
+        let resp = service.call(get("/files/robots.txt")).await.unwrap();
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/plain");
+
+        // 条目声明的值不能作为响应头时忽略, 不会 panic
+        let ds = DataSource::FileMap(HashMap::from([(
+            "a.json".to_string(),
+            SingleFileSource::Inline(b"{}".to_vec()).with_meta(FileMeta {
+                content_type: Some("text/plain\nx: y".to_string()),
+                content_encoding: Some("gzip\r".to_string()),
+                ..Default::default()
+            }),
+        )]));
+        let mut service = DataSourceService::new(ds);
+        let resp = service.call(get("/files/a.json")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/json");
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_mirror() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::atomic::AtomicUsize;
+
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let upstream = format!("http://{}/", l.local_addr().unwrap());
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = count.clone();
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let n = c.fetch_add(1, Ordering::SeqCst);
+                let lines: Vec<String> = BufReader::new(&s)
+                    .lines()
+                    .map(|l| l.unwrap())
+                    .take_while(|l| !l.is_empty())
+                    .collect();
+                let resp = if lines[0].starts_with("GET /a.txt ") {
+                    let body = format!("v{n}");
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_string()
+                };
+                s.write_all(resp.as_bytes()).unwrap();
+            }
+        });
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cache = temp_dir.path().join("cache");
+        let source =
+            read_through::ReadThroughSource::new(&upstream, &cache.to_string_lossy(), Some(3600));
+        let mut service = DataSourceService::mirror(source).with_admin_token("t");
+
+        let req = |m: Method, p: &str, token: Option<&str>| {
+            let mut b = Request::builder().method(m).uri(p);
+            if let Some(t) = token {
+                b = b.header(header::AUTHORIZATION, format!("Bearer {t}"));
+            }
+            b.body(()).unwrap()
+        };
+        let body = |resp: Response<UnsyncBoxBody<Bytes, std::io::Error>>| async {
+            resp.into_body().collect().await.unwrap().to_bytes()
+        };
+
+        for _ in 0..2 {
+            let resp = service
+                .call(req(Method::GET, "/files/a.txt", None))
+                .await
+                .unwrap();
+            assert_eq!(body(resp).await.as_ref(), b"v0");
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let resp = service
+            .call(req(Method::GET, "/files/b.txt", None))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let cached = std::fs::read_dir(&cache)
+            .unwrap()
+            .map(|e| e.unwrap().file_name());
+        let cached = cached.filter(|n| !gc::is_cache_sidecar(&n.to_string_lossy()));
+        assert_eq!(cached.count(), 1);
+
+        let resp = service
+            .call(req(Method::POST, "/__mirror/refresh/a.txt", Some("x")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = service
+            .call(req(Method::POST, "/__mirror/refresh/a.txt", Some("t")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = service
+            .call(req(Method::GET, "/files/a.txt", None))
+            .await
+            .unwrap();
+        assert_eq!(body(resp).await.as_ref(), b"v2");
+
+        let resp = service
+            .call(req(Method::POST, "/__mirror/purge", Some("t")))
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body(resp).await).unwrap();
+        assert_eq!(v["purged"], 1);
+        let resp = service
+            .call(req(Method::GET, "/files/a.txt", None))
+            .await
+            .unwrap();
+        assert_eq!(body(resp).await.as_ref(), b"v3");
+
+        // 没有设置口令时管理接口不可用
+        let source =
+            read_through::ReadThroughSource::new(&upstream, &cache.to_string_lossy(), Some(3600));
+        let mut open = DataSourceService::mirror(source);
+        for token in [None, Some("t"), Some("")] {
+            let resp = open
+                .call(req(Method::POST, "/__mirror/purge", token))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        }
+        let cached = std::fs::read_dir(&cache)
+            .unwrap()
+            .filter(|e| !gc::is_cache_sidecar(&e.as_ref().unwrap().file_name().to_string_lossy()));
+        assert_eq!(cached.count(), 1);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::versioned::VersionedFolderSource;
+use crate::*;
+use std::time::Duration;
+
+/// 保留策略, 各项条件同时生效, 满足任意一项的旧条目都会被删除
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// 最多保留最新的几个
+    pub keep_last: Option<usize>,
+    /// 删除修改时间早于此的条目
+    pub max_age: Option<Duration>,
+    /// 从新到旧累计, 超出此大小的条目被删除
+    pub max_bytes: Option<u64>,
+}
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    modified: SystemTime,
+    /// 最近一次使用的时间, 不早于修改时间
+    used: SystemTime,
+    size: u64,
+}
+
+fn size_of(p: &Path) -> io::Result<u64> {
+    let m = std::fs::metadata(p)?;
+    if !m.is_dir() {
+        return Ok(m.len());
+    }
+    let mut total = 0;
+    for e in std::fs::read_dir(p)? {
+        total += size_of(&e?.path())?;
+    }
+    Ok(total)
+}
+
+fn entries_of(dir: &Path, dirs: bool) -> Result<Vec<Entry>, FetchError> {
+    let mut v = Vec::new();
+    for e in std::fs::read_dir(dir).map_err(|e| local_io_error(e, dir))? {
+        let e = e?;
+        let m = e.metadata()?;
+        if m.is_dir() != dirs {
+            continue;
+        }
+        let modified = m.modified()?;
+        v.push(Entry {
+            name: e.file_name().to_string_lossy().to_string(),
+            path: e.path(),
+            modified,
+            used: m.accessed().map_or(modified, |a| a.max(modified)),
+            size: if dirs { size_of(&e.path())? } else { m.len() },
+        });
+    }
+    Ok(v)
+}
+
+/// `entries` 需已从新到旧排序. `protected` 中的条目不会被选中, 但计入数量和大小
+fn select_expired<'a>(
+    entries: &'a [Entry],
+    policy: &RetentionPolicy,
+    protected: Option<&str>,
+) -> Vec<&'a Entry> {
+    let now = SystemTime::now();
+    let mut bytes = 0;
+    let mut v = Vec::new();
+    for (i, e) in entries.iter().enumerate() {
+        bytes += e.size;
+        if protected == Some(e.name.as_str()) {
+            continue;
+        }
+        let too_many = policy.keep_last.is_some_and(|n| i >= n);
+        let too_old = policy.max_age.is_some_and(|a| {
+            now.duration_since(e.modified)
+                .is_ok_and(|elapsed| elapsed > a)
+        });
+        let too_big = policy.max_bytes.is_some_and(|b| bytes > b);
+        if too_many || too_old || too_big {
+            v.push(e);
+        }
+    }
+    v
+}
+
+impl VersionedFolderSource {
+    /// 按策略删除旧版本, 当前版本始终保留. 返回删除的版本
+    pub fn gc(&self, policy: &RetentionPolicy) -> Result<Vec<String>, FetchError> {
+        let current = self.current_version().ok();
+        let mut entries = entries_of(Path::new(&self.root), true)?;
+        // 版本名本身即按时间排序
+        entries.sort_by(|a, b| b.name.cmp(&a.name));
+        let mut removed = Vec::new();
+        for e in select_expired(&entries, policy, current.as_deref()) {
+            std::fs::remove_dir_all(&e.path).map_err(|err| local_io_error(err, &e.path))?;
+            removed.push(e.name.clone());
+        }
+        Ok(removed)
+    }
+}
+
+/// 按策略删除缓存目录中的旧文件 (按修改时间), 返回删除的文件名
+pub fn gc_cache_dir(dir: &Path, policy: &RetentionPolicy) -> Result<Vec<String>, FetchError> {
+    let mut entries = entries_of(dir, false)?;
+    entries.sort_by_key(|e| std::cmp::Reverse(e.modified));
+    let mut removed = Vec::new();
+    for e in select_expired(&entries, policy, None) {
+        std::fs::remove_file(&e.path).map_err(|err| local_io_error(err, &e.path))?;
+        removed.push(e.name.clone());
+    }
+    Ok(removed)
+}
+
+/// 缓存文件旁的 `.etag` 和 `.meta.json` 记录
+pub(crate) fn is_cache_sidecar(name: &str) -> bool {
+    name.ends_with(".etag") || name.ends_with(".meta.json")
+}
+
+/// 把文件的访问时间设为现在, 修改时间 (用于判断缓存是否过期) 不变.
+/// 显式设置, 不依赖文件系统的 atime 挂载选项
+pub(crate) fn touch_accessed(p: &Path) {
+    let r = std::fs::File::options()
+        .append(true)
+        .open(p)
+        .and_then(|f| f.set_times(std::fs::FileTimes::new().set_accessed(SystemTime::now())));
+    if let Err(e) = r {
+        debug!("Failed to update access time of {}: {e}", p.display());
+    }
+}
+
+/// 按最近使用时间从新到旧累计大小, 删除超出 `max_bytes` 的缓存文件及其 `.etag` 和 `.meta.json` 记录 (LRU).
+/// `keep` (如刚写入的文件) 不会被删除, 但计入大小. 返回删除的文件名
+pub fn evict_lru(
+    dir: &Path,
+    max_bytes: u64,
+    keep: Option<&str>,
+) -> Result<Vec<String>, FetchError> {
+    let mut entries = entries_of(dir, false)?;
+    entries.retain(|e| !is_cache_sidecar(&e.name));
+    entries.sort_by_key(|e| std::cmp::Reverse(e.used));
+    let policy = RetentionPolicy {
+        max_bytes: Some(max_bytes),
+        ..Default::default()
+    };
+    let mut removed = Vec::new();
+    for e in select_expired(&entries, &policy, keep) {
+        remove_cache_file(&e.path)?;
+        removed.push(e.name.clone());
+    }
+    Ok(removed)
+}
+
+/// 删除缓存文件及其 `.etag` 和 `.meta.json` 记录, 返回缓存文件是否存在
+pub(crate) fn remove_cache_file(cf: &Path) -> Result<bool, FetchError> {
+    let mut existed = false;
+    for ext in ["", ".etag", ".meta.json"] {
+        let p = PathBuf::from(format!("{}{ext}", cf.display()));
+        match std::fs::remove_file(&p) {
+            Ok(()) => existed |= ext.is_empty(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(local_io_error(e, &p)),
+        }
+    }
+    Ok(existed)
+}
+
+impl FileCache {
+    /// 删除缓存文件, 下次读取时不论是否过期都会从来源重新读取. 返回缓存文件是否存在.
+    /// 目录模式下需先 [`FileCache::resolve`], 或使用 [`FileCache::invalidate_all`]
+    pub fn invalidate(&self) -> Result<bool, FetchError> {
+        match &self.cache_file_path {
+            Some(cf) => remove_cache_file(Path::new(cf)),
+            None => Ok(false),
+        }
+    }
+
+    /// 目录模式下删除 `cache_dir` 中的所有缓存, 否则同 [`FileCache::invalidate`]. 返回删除的缓存文件数
+    pub fn invalidate_all(&self) -> Result<usize, FetchError> {
+        let Some(dir) = self.cache_dir.as_deref().map(Path::new) else {
+            return Ok(self.invalidate()?.into());
+        };
+        if !dir.exists() {
+            return Ok(0);
+        }
+        let mut n = 0;
+        for e in entries_of(dir, false)? {
+            if is_cache_sidecar(&e.name) {
+                // 缓存文件已删除时留下的记录
+                let _ = std::fs::remove_file(&e.path);
+            } else if remove_cache_file(&e.path)? {
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl DataSource {
+    /// 删除 FileMap 中所有条目的缓存文件, 之后的读取都会从来源重新读取, 用于立即应用上游的更新.
+    /// 不影响前面的 [`memory_cache::MemoryCache`]. 返回删除的缓存文件数.
+    ///
+    /// 只处理 FileMap 中直接带有 [`FileCache`] 的条目, 不处理 Concat 等组合条目的内层来源.
+    /// 其他变体 (包括 `Sync` / `Async` 包装的组合来源) 无法取得其中的缓存, 直接返回 0,
+    /// 这时需对各自的 [`FileCache`] 调用 [`FileCache::invalidate`]
+    pub fn invalidate_caches(&self) -> Result<usize, FetchError> {
+        let DataSource::FileMap(m) = self else {
+            return Ok(0);
+        };
+        let mut n = 0;
+        for s in m.values() {
+            if let Some(fc) = s.file_cache() {
+                n += usize::from(fc.invalidate()?);
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// 每隔 `interval` 对 `dir` 执行一次 [`gc_cache_dir`]
+#[cfg(feature = "tokio")]
+pub fn spawn_cache_gc(
+    dir: PathBuf,
+    policy: RetentionPolicy,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut t = tokio::time::interval(interval);
+        loop {
+            t.tick().await;
+            let (d, p) = (dir.clone(), policy.clone());
+            match tokio::task::spawn_blocking(move || gc_cache_dir(&d, &p)).await {
+                Ok(Ok(removed)) if !removed.is_empty() => {
+                    debug!("cache gc removed {} files", removed.len())
+                }
+                Ok(Err(e)) => warn!("cache gc failed: {e}"),
+                _ => {}
+            }
+        }
+    })
+}
+
+/// 每隔 `interval` 对 `vs` 执行一次 [`VersionedFolderSource::gc`]
+#[cfg(feature = "tokio")]
+pub fn spawn_version_gc(
+    vs: VersionedFolderSource,
+    policy: RetentionPolicy,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut t = tokio::time::interval(interval);
+        loop {
+            t.tick().await;
+            let (v, p) = (vs.clone(), policy.clone());
+            match tokio::task::spawn_blocking(move || v.gc(&p)).await {
+                Ok(Ok(removed)) if !removed.is_empty() => {
+                    debug!("version gc removed {removed:?}")
+                }
+                Ok(Err(e)) => warn!("version gc failed: {e}"),
+                _ => {}
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_version_gc() {
+        let temp_dir = TempDir::new().unwrap();
+        for v in ["001", "002", "003", "004"] {
+            let d = temp_dir.path().join(v);
+            std::fs::create_dir(&d).unwrap();
+            std::fs::write(d.join("a.txt"), "0123456789").unwrap();
+        }
+        let vs = VersionedFolderSource::new(&temp_dir.path().to_string_lossy());
+        vs.set_current("001").unwrap();
+
+        let removed = vs
+            .gc(&RetentionPolicy {
+                keep_last: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(removed, vec!["002"]);
+        assert_eq!(vs.versions().unwrap(), vec!["001", "003", "004"]);
+
+        let removed = vs
+            .gc(&RetentionPolicy {
+                max_bytes: Some(15),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(removed, vec!["003"]);
+    }
+
+    #[test]
+    fn test_cache_gc() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a"), "a").unwrap();
+        let removed = gc_cache_dir(
+            temp_dir.path(),
+            &RetentionPolicy {
+                max_age: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(removed, vec!["a"]);
+    }
+
+    #[test]
+    fn test_evict_lru() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_string_lossy().to_string();
+        let fc = |name: &str| {
+            FileCache::in_dir(&dir, None)
+                .with_max_dir_bytes(25)
+                .resolve(name)
+                .into_owned()
+        };
+        let t0 = SystemTime::now() - Duration::from_secs(100);
+        for (i, name) in ["a", "b", "c"].iter().enumerate() {
+            let p = temp_dir.path().join(name);
+            std::fs::write(&p, "0123456789").unwrap();
+            let t = t0 + Duration::from_secs(i as u64 * 10);
+            let f = std::fs::File::options().append(true).open(&p).unwrap();
+            f.set_times(std::fs::FileTimes::new().set_accessed(t).set_modified(t))
+                .unwrap();
+        }
+        std::fs::write(temp_dir.path().join("a.etag"), "x").unwrap();
+        // 读取 a 后 b 成为最久未使用的
+        assert_eq!(fc("a").read_cache_file().unwrap(), b"0123456789");
+        assert!(fc("d").write_cache_file(b"0123456789"));
+        let mut left: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|n| !n.ends_with(".meta.json"))
+            .collect();
+        left.sort();
+        assert_eq!(left, vec!["a", "a.etag", "d"]);
+
+        assert_eq!(evict_lru(temp_dir.path(), 0, Some("d")).unwrap(), vec!["a"]);
+        assert!(!temp_dir.path().join("a.etag").exists());
+    }
+
+    #[test]
+    fn test_invalidate() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counting(AtomicUsize);
+        impl SyncSource for Counting {
+            fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+                let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(n.to_string().into_bytes())
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = FileCache::in_dir(&temp_dir.path().to_string_lossy(), None);
+        let fc = dir.resolve("a");
+        let s = Counting(AtomicUsize::new(0));
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"1");
+        std::fs::write(temp_dir.path().join("a.etag"), "x").unwrap();
+        // 没有过期时间, 一直使用缓存
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"1");
+        assert!(fc.invalidate().unwrap());
+        assert!(!temp_dir.path().join("a.etag").exists());
+        assert!(!fc.invalidate().unwrap());
+        // 删除后没有过期时间的缓存也会重新读取
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"2");
+
+        assert!(dir.resolve("b").write_cache_file(b"b"));
+        std::fs::write(temp_dir.path().join("c.etag"), "x").unwrap();
+        assert_eq!(dir.invalidate_all().unwrap(), 2);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), b"3");
+
+        let ds = DataSource::FileMap(HashMap::from([(
+            "a".to_string(),
+            SingleFileSource::Http(
+                HttpSource {
+                    url: "https://example.com/a".to_string(),
+                    ..Default::default()
+                },
+                dir.clone(),
+            ),
+        )]));
+        let DataSource::FileMap(m) = &ds else {
+            unreachable!()
+        };
+        let fc = m["a"].file_cache().unwrap().into_owned();
+        assert!(fc.write_cache_file(b"a"));
+        assert_eq!(ds.invalidate_caches().unwrap(), 1);
+        assert_eq!(fc.is_cache_timeout().unwrap(), None);
+
+        // 其他变体不处理
+        assert!(fc.write_cache_file(b"a"));
+        let wrapped = DataSource::Sync(Box::new(ds));
+        assert_eq!(wrapped.invalidate_caches().unwrap(), 0);
+        let folders = DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+        assert_eq!(folders.invalidate_caches().unwrap(), 0);
+        assert_eq!(fc.is_cache_timeout().unwrap(), Some(false));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::*;
+use std::sync::Arc;
+
+#[cfg(feature = "tokio")]
+type GenFuture = futures::future::BoxFuture<'static, Result<Vec<u8>, FetchError>>;
+
+/// 按需生成文件内容的函数, 用于 [`SingleFileSource::Generated`], 如渲染模板或拼接其它条目
+#[derive(Clone)]
+pub enum Generator {
+    Sync(Arc<dyn Fn() -> Result<Vec<u8>, FetchError> + Send + Sync>),
+    #[cfg(feature = "tokio")]
+    Async(Arc<dyn Fn() -> GenFuture + Send + Sync>),
+}
+
+impl Generator {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn() -> Result<Vec<u8>, FetchError> + Send + Sync + 'static,
+    {
+        Self::Sync(Arc::new(f))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub fn new_async<F, Fut>(f: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Vec<u8>, FetchError>> + Send + 'static,
+    {
+        use futures::FutureExt;
+        Self::Async(Arc::new(move || f().boxed()))
+    }
+}
+
+impl std::fmt::Debug for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Generator::Sync(_) => f.write_str("Generator::Sync"),
+            #[cfg(feature = "tokio")]
+            Generator::Async(_) => f.write_str("Generator::Async"),
+        }
+    }
+}
+
+impl SyncSource for Generator {
+    /// 异步的生成函数会阻塞当前线程直到完成
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        match self {
+            Generator::Sync(f) => f(),
+            #[cfg(feature = "tokio")]
+            Generator::Async(f) => futures::executor::block_on(f()),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for Generator {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        match self {
+            Generator::Sync(f) => f(),
+            Generator::Async(f) => f().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_generated_with_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = count.clone();
+        let g = Generator::new(move || {
+            let n = c.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("gen {n}").into_bytes())
+        });
+        let fc = FileCache {
+            update_interval_seconds: Some(3600),
+            cache_file_path: Some(temp_dir.path().join("g").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let ds = DataSource::FileMap(
+            vec![("g".to_string(), SingleFileSource::Generated(g, fc))]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(ds.read_to_string("g").unwrap(), "gen 0");
+        assert_eq!(ds.read_to_string("g").unwrap(), "gen 0");
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_generated_async() {
+        let g = Generator::new_async(|| async { Ok(b"async".to_vec()) });
+        let sf = SingleFileSource::Generated(g, FileCache::default());
+        assert_eq!(sf.fetch_async().await.unwrap(), b"async");
+    }
+}
@@ -0,0 +1,174 @@
+use crate::*;
+use std::process::{Command, Output};
+
+/// 从 git 仓库 (普通克隆或 bare 仓库均可) 的指定 ref 中读取文件, 如固定到某个 commit 的
+/// `rules/geoip.dat`. 通过调用 `git` 命令实现, 需要系统中安装了 git
+#[derive(Debug, Clone)]
+pub struct GitFolderSource {
+    pub repo: PathBuf,
+    /// 分支, tag 或 commit
+    pub rev: String,
+}
+
+impl GitFolderSource {
+    pub fn new(repo: impl Into<PathBuf>, rev: &str) -> Self {
+        Self {
+            repo: repo.into(),
+            rev: rev.to_string(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut c = Command::new("git");
+        c.arg("-C").arg(&self.repo);
+        c
+    }
+
+    /// `rev:path` 形式的对象名
+    fn object(&self, file_name: &Path) -> String {
+        let p = normalize_separators(file_name);
+        let p = p.to_string_lossy();
+        let p = p.trim_start_matches("./").trim_start_matches('/');
+        format!("{}:{p}", self.rev)
+    }
+
+    /// 把 rev 解析为 commit id, 返回固定到该 commit 的来源, 这样分支之后的提交不会影响读取结果
+    pub fn pinned(&self) -> Result<Self, FetchError> {
+        let o = self
+            .command()
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{}^{{commit}}", self.rev))
+            .output()?;
+        if !o.status.success() {
+            return Err(FetchError::not_found(&self.rev));
+        }
+        Ok(Self {
+            repo: self.repo.clone(),
+            rev: String::from_utf8_lossy(&o.stdout).trim().to_string(),
+        })
+    }
+
+    /// `cat-file blob` 失败时确定原因
+    fn blob_error(&self, object: &str, type_output: io::Result<Output>) -> FetchError {
+        match type_output {
+            Ok(o) if o.status.success() && o.stdout.starts_with(b"tree") => {
+                FetchError::Lookup(LookupError::IsDirectory(object.to_string()))
+            }
+            Ok(_) => FetchError::not_found(object),
+            Err(e) => FetchError::I(e),
+        }
+    }
+}
+
+impl SyncFolderSource for GitFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let object = self.object(file_name);
+        let o = self
+            .command()
+            .args(["cat-file", "blob"])
+            .arg(&object)
+            .output()?;
+        if !o.status.success() {
+            let t = self
+                .command()
+                .args(["cat-file", "-t"])
+                .arg(&object)
+                .output();
+            return Err(self.blob_error(&object, t));
+        }
+        Ok((o.stdout, Some(file_name.to_string_lossy().to_string())))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY.with_listable()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        let o = self
+            .command()
+            .args(["ls-tree", "-r", "-z", "--name-only"])
+            .arg(&self.rev)
+            .output()?;
+        if !o.status.success() {
+            return Err(FetchError::not_found(&self.rev));
+        }
+        Ok(o.stdout
+            .split(|b| *b == 0)
+            .filter(|s| !s.is_empty())
+            .map(|s| String::from_utf8_lossy(s).to_string())
+            .collect())
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for GitFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let object = self.object(file_name);
+        let o = tokio::process::Command::from(self.command())
+            .args(["cat-file", "blob"])
+            .arg(&object)
+            .output()
+            .await?;
+        if !o.status.success() {
+            let t = tokio::process::Command::from(self.command())
+                .args(["cat-file", "-t"])
+                .arg(&object)
+                .output()
+                .await;
+            return Err(self.blob_error(&object, t));
+        }
+        Ok((o.stdout, Some(file_name.to_string_lossy().to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let s = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(s.status.success(), "{}", String::from_utf8_lossy(&s.stderr));
+    }
+
+    #[test]
+    fn test_git_folder_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let d = temp_dir.path();
+        git(d, &["init", "-q"]);
+        std::fs::create_dir(d.join("rules")).unwrap();
+        std::fs::write(d.join("rules/geoip.dat"), b"v1").unwrap();
+        git(d, &["add", "."]);
+        git(d, &["commit", "-qm", "1"]);
+
+        let gs = GitFolderSource::new(d, "HEAD").pinned().unwrap();
+        std::fs::write(d.join("rules/geoip.dat"), b"v2").unwrap();
+        git(d, &["commit", "-qam", "2"]);
+
+        let r = gs.get_file_content(Path::new("rules/geoip.dat")).unwrap();
+        assert_eq!(r.0, b"v1");
+        let r = GitFolderSource::new(d, "HEAD")
+            .get_file_content(Path::new("rules/geoip.dat"))
+            .unwrap();
+        assert_eq!(r.0, b"v2");
+        assert!(matches!(
+            gs.get_file_content(Path::new("rules")),
+            Err(FetchError::Lookup(LookupError::IsDirectory(_)))
+        ));
+        assert!(gs
+            .get_file_content(Path::new("nope"))
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(gs.list_files().unwrap(), vec!["rules/geoip.dat"]);
+    }
+}
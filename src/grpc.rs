@@ -0,0 +1,681 @@
+//! 以 gRPC 文件服务 (`proto/file_service.proto`) 作为来源.
+//!
+//! 连接由 [`GrpcChannel`] 提供, 这里只负责消息的编解码和状态码的转换. 开启 tonic feature 时
+//! 可以使用基于 tonic 的 [`TonicChannel`]; 直接基于 HTTP/2 实现 channel 时可以用
+//! [`encode_frame`] 和 [`decode_frames`] 处理帧
+
+use crate::*;
+use futures::stream::{BoxStream, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 服务定义
+pub const FILE_SERVICE_PROTO: &str = include_str!("../proto/file_service.proto");
+
+pub const GET_FILE: &str = "/data_source.v1.FileService/GetFile";
+pub const READ_FILE: &str = "/data_source.v1.FileService/ReadFile";
+pub const LIST_FILES: &str = "/data_source.v1.FileService/ListFiles";
+
+/// 到文件服务的连接. 请求和响应都是编码后的 protobuf 消息, 不含 gRPC 的 5 字节帧头.
+/// 非 OK 的状态应使用 [`status_error`] 转换
+#[async_trait::async_trait]
+pub trait GrpcChannel: std::fmt::Debug + Send + Sync {
+    async fn unary(
+        &self,
+        method: &str,
+        request: Vec<u8>,
+        deadline: Option<Duration>,
+    ) -> Result<Vec<u8>, FetchError>;
+
+    /// 服务端流式调用, 返回的流中每项为一条消息
+    async fn server_streaming(
+        &self,
+        _method: &str,
+        _request: Vec<u8>,
+        _deadline: Option<Duration>,
+    ) -> Result<BoxStream<'static, Result<Vec<u8>, FetchError>>, FetchError> {
+        Err(FetchError::Unsupported("grpc server streaming"))
+    }
+}
+
+/// gRPC 状态码转换为 FetchError, `name` 为请求的文件名
+pub fn status_error(code: u32, message: &str, name: &str) -> FetchError {
+    match code {
+        5 => FetchError::not_found(name),
+        7 | 16 => FetchError::Lookup(LookupError::Denied(name.to_string())),
+        4 => io::Error::new(io::ErrorKind::TimedOut, message.to_string()).into(),
+        12 => FetchError::Unsupported("grpc method"),
+        _ => io::Error::other(format!("grpc status {code}: {message}")).into(),
+    }
+}
+
+/// 加上 gRPC 帧头 (不压缩)
+pub fn encode_frame(msg: &[u8]) -> Vec<u8> {
+    let mut v = Vec::with_capacity(msg.len() + 5);
+    v.push(0);
+    v.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    v.extend_from_slice(msg);
+    v
+}
+
+/// 拆分响应体中的各条消息, 不支持压缩的消息
+pub fn decode_frames(mut body: &[u8]) -> Result<Vec<Vec<u8>>, FetchError> {
+    let invalid = |m: &str| FetchError::Invalid(format!("grpc frame: {m}"));
+    let mut v = Vec::new();
+    while !body.is_empty() {
+        if body.len() < 5 {
+            return Err(invalid("truncated header"));
+        }
+        if body[0] != 0 {
+            return Err(invalid("compressed message"));
+        }
+        let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+        let msg = body
+            .get(5..5 + len)
+            .ok_or_else(|| invalid("truncated message"))?;
+        v.push(msg.to_vec());
+        body = &body[5 + len..];
+    }
+    Ok(v)
+}
+
+fn put_varint(v: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        v.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    v.push(n as u8);
+}
+
+fn get_varint(d: &mut &[u8]) -> Option<u64> {
+    let mut n = 0u64;
+    for i in 0..10 {
+        let (&b, rest) = d.split_first()?;
+        *d = rest;
+        n |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// 长度前缀的字段 (string / bytes)
+fn put_bytes_field(v: &mut Vec<u8>, field: u64, d: &[u8]) {
+    put_varint(v, field << 3 | 2);
+    put_varint(v, d.len() as u64);
+    v.extend_from_slice(d);
+}
+
+/// 消息中所有长度前缀的字段, 其它类型的字段被跳过
+fn bytes_fields(mut d: &[u8]) -> Result<Vec<(u64, &[u8])>, FetchError> {
+    let invalid = || FetchError::Invalid("grpc: malformed protobuf message".to_string());
+    let mut v = Vec::new();
+    while !d.is_empty() {
+        let key = get_varint(&mut d).ok_or_else(invalid)?;
+        match key & 7 {
+            0 => {
+                get_varint(&mut d).ok_or_else(invalid)?;
+            }
+            1 | 5 => {
+                let n = if key & 7 == 1 { 8 } else { 4 };
+                d = d.get(n..).ok_or_else(invalid)?;
+            }
+            2 => {
+                let len = get_varint(&mut d).ok_or_else(invalid)? as usize;
+                let (f, rest) = (d.get(..len).ok_or_else(invalid)?, &d[len..]);
+                v.push((key >> 3, f));
+                d = rest;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(v)
+}
+
+pub fn encode_get_file_request(name: &str) -> Vec<u8> {
+    let mut v = Vec::new();
+    put_bytes_field(&mut v, 1, name.as_bytes());
+    v
+}
+
+pub fn encode_get_file_response(data: &[u8], path: Option<&str>) -> Vec<u8> {
+    let mut v = Vec::new();
+    put_bytes_field(&mut v, 1, data);
+    if let Some(p) = path {
+        put_bytes_field(&mut v, 2, p.as_bytes());
+    }
+    v
+}
+
+pub fn encode_list_files_response(names: &[String]) -> Vec<u8> {
+    let mut v = Vec::new();
+    for n in names {
+        put_bytes_field(&mut v, 1, n.as_bytes());
+    }
+    v
+}
+
+pub fn decode_get_file_request(d: &[u8]) -> Result<String, FetchError> {
+    let mut name = String::new();
+    for (f, v) in bytes_fields(d)? {
+        if f == 1 {
+            name = String::from_utf8_lossy(v).to_string();
+        }
+    }
+    Ok(name)
+}
+
+/// 返回 (data, path), 空的 path 视为没有
+pub fn decode_get_file_response(d: &[u8]) -> Result<(Vec<u8>, Option<String>), FetchError> {
+    let (mut data, mut path) = (Vec::new(), None);
+    for (f, v) in bytes_fields(d)? {
+        match f {
+            1 => data.extend_from_slice(v),
+            2 if !v.is_empty() => path = Some(String::from_utf8_lossy(v).to_string()),
+            _ => {}
+        }
+    }
+    Ok((data, path))
+}
+
+pub fn decode_list_files_response(d: &[u8]) -> Result<Vec<String>, FetchError> {
+    Ok(bytes_fields(d)?
+        .into_iter()
+        .filter(|(f, _)| *f == 1)
+        .map(|(_, v)| String::from_utf8_lossy(v).to_string())
+        .collect())
+}
+
+/// 透传已编码消息的 codec, tonic 只负责帧和 HTTP/2
+#[cfg(feature = "tonic")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawCodec;
+
+#[cfg(feature = "tonic")]
+impl tonic::codec::Codec for RawCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = RawCodec;
+    type Decoder = RawCodec;
+
+    fn encoder(&mut self) -> RawCodec {
+        RawCodec
+    }
+
+    fn decoder(&mut self) -> RawCodec {
+        RawCodec
+    }
+}
+
+#[cfg(feature = "tonic")]
+impl tonic::codec::Encoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = tonic::Status;
+
+    fn encode(
+        &mut self,
+        item: Vec<u8>,
+        dst: &mut tonic::codec::EncodeBuf<'_>,
+    ) -> Result<(), tonic::Status> {
+        bytes::BufMut::put_slice(dst, &item);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tonic")]
+impl tonic::codec::Decoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = tonic::Status;
+
+    fn decode(
+        &mut self,
+        src: &mut tonic::codec::DecodeBuf<'_>,
+    ) -> Result<Option<Vec<u8>>, tonic::Status> {
+        let n = bytes::Buf::remaining(src);
+        Ok(Some(bytes::Buf::copy_to_bytes(src, n).to_vec()))
+    }
+}
+
+/// 基于 tonic 的连接. 截止时间通过 `grpc-timeout` 头告知服务端, 客户端也会在超时后放弃
+#[cfg(feature = "tonic")]
+#[derive(Debug, Clone)]
+pub struct TonicChannel {
+    pub channel: tonic::transport::Channel,
+}
+
+#[cfg(feature = "tonic")]
+impl TonicChannel {
+    pub fn new(channel: tonic::transport::Channel) -> Self {
+        Self { channel }
+    }
+
+    /// 第一次调用时才建立连接, `url` 如 `http://127.0.0.1:50051`
+    pub fn connect_lazy(url: &str) -> Result<Self, FetchError> {
+        let ep = tonic::transport::Endpoint::from_shared(url.to_string())
+            .map_err(|e| FetchError::Invalid(format!("grpc endpoint {url}: {e}")))?;
+        Ok(Self::new(ep.connect_lazy()))
+    }
+
+    /// 准备好的客户端, 请求 (带有截止时间) 和方法路径
+    async fn prepare(
+        &self,
+        method: &str,
+        request: Vec<u8>,
+        deadline: Option<Duration>,
+    ) -> Result<
+        (
+            tonic::client::Grpc<tonic::transport::Channel>,
+            tonic::Request<Vec<u8>>,
+            tonic::codegen::http::uri::PathAndQuery,
+        ),
+        FetchError,
+    > {
+        let path = tonic::codegen::http::uri::PathAndQuery::try_from(method)
+            .map_err(|e| FetchError::Invalid(format!("grpc method {method}: {e}")))?;
+        let mut g = tonic::client::Grpc::new(self.channel.clone());
+        g.ready().await.map_err(io::Error::other)?;
+        let mut req = tonic::Request::new(request);
+        if let Some(d) = deadline {
+            req.set_timeout(d);
+        }
+        Ok((g, req, path))
+    }
+}
+
+/// 请求中的文件名, 用于把 NOT_FOUND 等状态转换为对应的错误
+#[cfg(feature = "tonic")]
+fn tonic_status_error(s: tonic::Status, request: &[u8]) -> FetchError {
+    let name = decode_get_file_request(request).unwrap_or_default();
+    status_error(s.code() as u32, s.message(), &name)
+}
+
+/// 在 `deadline` 内完成 `f`, 超时与服务端返回 DEADLINE_EXCEEDED 相同
+#[cfg(feature = "tonic")]
+async fn within<T>(
+    deadline: Option<Duration>,
+    f: impl std::future::Future<Output = Result<T, FetchError>>,
+) -> Result<T, FetchError> {
+    match deadline {
+        Some(d) => tokio::time::timeout(d, f)
+            .await
+            .unwrap_or_else(|_| Err(status_error(4, "deadline exceeded", ""))),
+        None => f.await,
+    }
+}
+
+#[cfg(feature = "tonic")]
+#[async_trait::async_trait]
+impl GrpcChannel for TonicChannel {
+    async fn unary(
+        &self,
+        method: &str,
+        request: Vec<u8>,
+        deadline: Option<Duration>,
+    ) -> Result<Vec<u8>, FetchError> {
+        within(deadline, async {
+            let (mut g, req, path) = self.prepare(method, request.clone(), deadline).await?;
+            g.unary(req, path, RawCodec)
+                .await
+                .map(|r| r.into_inner())
+                .map_err(|s| tonic_status_error(s, &request))
+        })
+        .await
+    }
+
+    /// 截止时间对整个流有效, 超时后流以 DEADLINE_EXCEEDED 对应的错误结束
+    async fn server_streaming(
+        &self,
+        method: &str,
+        request: Vec<u8>,
+        deadline: Option<Duration>,
+    ) -> Result<BoxStream<'static, Result<Vec<u8>, FetchError>>, FetchError> {
+        let end = deadline.map(|d| tokio::time::Instant::now() + d);
+        let s = within(deadline, async {
+            let (mut g, req, path) = self.prepare(method, request.clone(), deadline).await?;
+            g.server_streaming(req, path, RawCodec)
+                .await
+                .map(|r| r.into_inner())
+                .map_err(|s| tonic_status_error(s, &request))
+        })
+        .await?;
+        let s = s.map(move |r| r.map_err(|s| tonic_status_error(s, &request)));
+        let Some(end) = end else {
+            return Ok(s.boxed());
+        };
+        let s = s.boxed();
+        Ok(futures::stream::unfold(Some(s), move |s| async move {
+            let mut s = s?;
+            match tokio::time::timeout_at(end, s.next()).await {
+                Ok(Some(m)) => Some((m, Some(s))),
+                Ok(None) => None,
+                Err(_) => Some((Err(status_error(4, "deadline exceeded", "")), None)),
+            }
+        })
+        .boxed())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcFolderSource {
+    pub channel: Arc<dyn GrpcChannel>,
+    /// 每次调用的截止时间
+    pub deadline: Option<Duration>,
+    /// 使用流式的 `ReadFile` 代替 `GetFile`, 适合超过消息大小限制的大文件
+    pub streaming: bool,
+}
+
+impl GrpcFolderSource {
+    pub fn new(channel: Arc<dyn GrpcChannel>) -> Self {
+        Self {
+            channel,
+            deadline: None,
+            streaming: false,
+        }
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    pub async fn list_files_async(&self) -> Result<Vec<String>, FetchError> {
+        let r = self
+            .channel
+            .unary(LIST_FILES, Vec::new(), self.deadline)
+            .await?;
+        decode_list_files_response(&r)
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncFolderSource for GrpcFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let name = normalize_separators(file_name)
+            .to_string_lossy()
+            .to_string();
+        let req = encode_get_file_request(&name);
+        if !self.streaming {
+            let r = self.channel.unary(GET_FILE, req, self.deadline).await?;
+            return decode_get_file_response(&r);
+        }
+        let mut s = self
+            .channel
+            .server_streaming(READ_FILE, req, self.deadline)
+            .await?;
+        let (mut data, mut path) = (Vec::new(), None);
+        while let Some(msg) = s.next().await {
+            let (d, p) = decode_get_file_response(&msg?)?;
+            data.extend_from_slice(&d);
+            path = path.or(p);
+        }
+        Ok((data, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在进程内用 DataSource 实现的文件服务, 流式读取时每块 2 字节
+    #[derive(Debug)]
+    struct LocalChannel(DataSource);
+
+    impl LocalChannel {
+        fn get(&self, request: &[u8]) -> Result<(Vec<u8>, Option<String>), FetchError> {
+            let name = decode_get_file_request(request)?;
+            match self.0.get_file_content(Path::new(&name)) {
+                Err(e) if e.is_not_found() => Err(status_error(5, "not found", &name)),
+                r => r,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GrpcChannel for LocalChannel {
+        async fn unary(
+            &self,
+            method: &str,
+            request: Vec<u8>,
+            deadline: Option<Duration>,
+        ) -> Result<Vec<u8>, FetchError> {
+            if deadline.is_some_and(|d| d.is_zero()) {
+                return Err(status_error(4, "deadline exceeded", ""));
+            }
+            match method {
+                GET_FILE => {
+                    let (d, p) = self.get(&request)?;
+                    Ok(encode_get_file_response(&d, p.as_deref()))
+                }
+                LIST_FILES => Ok(encode_list_files_response(&self.0.list_files()?)),
+                _ => Err(status_error(12, "unimplemented", method)),
+            }
+        }
+
+        async fn server_streaming(
+            &self,
+            _method: &str,
+            request: Vec<u8>,
+            _deadline: Option<Duration>,
+        ) -> Result<BoxStream<'static, Result<Vec<u8>, FetchError>>, FetchError> {
+            let (d, p) = self.get(&request)?;
+            let chunks: Vec<_> = d
+                .chunks(2)
+                .enumerate()
+                .map(|(i, c)| {
+                    let p = if i == 0 { p.as_deref() } else { None };
+                    Ok(encode_get_file_response(c, p))
+                })
+                .collect();
+            Ok(futures::stream::iter(chunks).boxed())
+        }
+    }
+
+    /// 用 LocalChannel 实现的 tonic 服务端. 读取 `slow.txt` 时, 单次调用等待 10 秒才返回,
+    /// 流式调用发送第一块后不再发送
+    #[cfg(feature = "tonic")]
+    #[derive(Debug, Clone)]
+    struct TonicFileService(Arc<LocalChannel>);
+
+    /// tonic 的服务接口以 Status 作为错误类型
+    #[cfg(feature = "tonic")]
+    #[allow(clippy::result_large_err)]
+    mod tonic_server {
+        use super::*;
+        use tonic::codegen::{http, Body, BoxFuture, Context, Poll, Service, StdError};
+
+        fn to_status(e: FetchError) -> tonic::Status {
+            match e.is_not_found() {
+                true => tonic::Status::not_found(e.to_string()),
+                false => tonic::Status::internal(e.to_string()),
+            }
+        }
+
+        fn is_slow(request: &[u8]) -> bool {
+            decode_get_file_request(request).is_ok_and(|n| n == "slow.txt")
+        }
+
+        struct Unary(Arc<LocalChannel>, &'static str);
+
+        impl tonic::server::UnaryService<Vec<u8>> for Unary {
+            type Response = Vec<u8>;
+            type Future = BoxFuture<tonic::Response<Vec<u8>>, tonic::Status>;
+
+            fn call(&mut self, req: tonic::Request<Vec<u8>>) -> Self::Future {
+                let (c, method) = (self.0.clone(), self.1);
+                Box::pin(async move {
+                    let req = req.into_inner();
+                    if is_slow(&req) {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                    }
+                    c.unary(method, req, None)
+                        .await
+                        .map(tonic::Response::new)
+                        .map_err(to_status)
+                })
+            }
+        }
+
+        struct ServerStreaming(Arc<LocalChannel>);
+
+        impl tonic::server::ServerStreamingService<Vec<u8>> for ServerStreaming {
+            type Response = Vec<u8>;
+            type ResponseStream = BoxStream<'static, Result<Vec<u8>, tonic::Status>>;
+            type Future = BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+
+            fn call(&mut self, req: tonic::Request<Vec<u8>>) -> Self::Future {
+                let c = self.0.clone();
+                Box::pin(async move {
+                    let req = req.into_inner();
+                    let slow = is_slow(&req);
+                    let s = c
+                        .server_streaming(READ_FILE, req, None)
+                        .await
+                        .map_err(to_status)?
+                        .map(|m| m.map_err(to_status));
+                    let s = match slow {
+                        true => s.take(1).chain(futures::stream::pending()).boxed(),
+                        false => s.boxed(),
+                    };
+                    Ok(tonic::Response::new(s))
+                })
+            }
+        }
+
+        impl tonic::server::NamedService for TonicFileService {
+            const NAME: &'static str = "data_source.v1.FileService";
+        }
+
+        impl<B> Service<http::Request<B>> for TonicFileService
+        where
+            B: Body + Send + 'static,
+            B::Error: Into<StdError> + Send + 'static,
+        {
+            type Response = http::Response<tonic::body::BoxBody>;
+            type Error = std::convert::Infallible;
+            type Future = BoxFuture<Self::Response, Self::Error>;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: http::Request<B>) -> Self::Future {
+                let c = self.0.clone();
+                Box::pin(async move {
+                    let mut g = tonic::server::Grpc::new(RawCodec);
+                    Ok(match req.uri().path() {
+                        GET_FILE => g.unary(Unary(c, GET_FILE), req).await,
+                        LIST_FILES => g.unary(Unary(c, LIST_FILES), req).await,
+                        _ => g.server_streaming(ServerStreaming(c), req).await,
+                    })
+                })
+            }
+        }
+    }
+
+    #[cfg(feature = "tonic")]
+    #[tokio::test]
+    async fn test_tonic_channel() {
+        let ds = DataSource::FileMap(HashMap::from([
+            (
+                "a.txt".to_string(),
+                SingleFileSource::Inline(b"hello".to_vec()),
+            ),
+            (
+                "slow.txt".to_string(),
+                SingleFileSource::Inline(b"slow".to_vec()),
+            ),
+        ]));
+        let l = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", l.local_addr().unwrap());
+        let incoming = tonic::transport::server::TcpIncoming::from_listener(l, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TonicFileService(Arc::new(LocalChannel(ds))))
+                .serve_with_incoming(incoming),
+        );
+
+        let channel: Arc<dyn GrpcChannel> = Arc::new(TonicChannel::connect_lazy(&url).unwrap());
+        let fs = GrpcFolderSource::new(channel.clone()).with_deadline(Duration::from_secs(5));
+        let mut names = fs.list_files_async().await.unwrap();
+        names.sort();
+        assert_eq!(names, vec!["a.txt", "slow.txt"]);
+        let r = fs.get_file_content_async(Path::new("a.txt")).await.unwrap();
+        assert_eq!(r.0, b"hello");
+        let e = fs.get_file_content_async(Path::new("b.txt")).await;
+        assert!(e.unwrap_err().is_not_found());
+
+        let streaming = fs.clone().with_streaming(true);
+        let r = streaming
+            .get_file_content_async(Path::new("a.txt"))
+            .await
+            .unwrap();
+        assert_eq!(r.0, b"hello");
+        let e = streaming.get_file_content_async(Path::new("b.txt")).await;
+        assert!(e.unwrap_err().is_not_found());
+
+        // 截止时间对单次调用和整个流都有效
+        let started = std::time::Instant::now();
+        let fs = fs.with_deadline(Duration::from_millis(200));
+        let e = fs.get_file_content_async(Path::new("slow.txt")).await;
+        assert_eq!(e.unwrap_err().kind(), FetchErrorKind::Io);
+        let e = fs
+            .with_streaming(true)
+            .get_file_content_async(Path::new("slow.txt"))
+            .await;
+        assert_eq!(e.unwrap_err().kind(), FetchErrorKind::Io);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        assert!(TonicChannel::connect_lazy("not a url").is_err());
+    }
+
+    #[test]
+    fn test_codec() {
+        let msg = encode_get_file_response(&[0; 300], Some("a/b"));
+        let frames = decode_frames(&[encode_frame(&msg), encode_frame(b"")].concat()).unwrap();
+        assert_eq!(frames, vec![msg.clone(), Vec::new()]);
+        let (d, p) = decode_get_file_response(&frames[0]).unwrap();
+        assert_eq!((d.len(), p.as_deref()), (300, Some("a/b")));
+        assert!(decode_frames(&encode_frame(&msg)[..10]).is_err());
+        assert_eq!(
+            decode_get_file_request(&encode_get_file_request("x.json")).unwrap(),
+            "x.json"
+        );
+        assert!(decode_get_file_response(&[0x0a, 5, 1]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_folder_source() {
+        let ds = DataSource::FileMap(HashMap::from([(
+            "a.txt".to_string(),
+            SingleFileSource::Inline(b"hello".to_vec()),
+        )]));
+        let channel: Arc<dyn GrpcChannel> = Arc::new(LocalChannel(ds));
+        let fs = GrpcFolderSource::new(channel.clone());
+        assert_eq!(fs.list_files_async().await.unwrap(), vec!["a.txt"]);
+        let r = fs.get_file_content_async(Path::new("a.txt")).await.unwrap();
+        assert_eq!(r.0, b"hello");
+
+        let fs = fs.with_streaming(true);
+        let ds = DataSource::Async(Box::new(fs.clone()));
+        let r = ds.get_file_content_async(Path::new("a.txt")).await.unwrap();
+        assert_eq!(r.0, b"hello");
+        assert!(ds
+            .get_file_content_async(Path::new("b.txt"))
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        let fs = GrpcFolderSource::new(channel).with_deadline(Duration::ZERO);
+        let e = fs.get_file_content_async(Path::new("a.txt")).await;
+        assert_eq!(e.unwrap_err().kind(), FetchErrorKind::Io);
+    }
+}
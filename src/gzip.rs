@@ -0,0 +1,82 @@
+//! gzip 解压与压缩, 用于直接读取和生成 `.tar.gz`. 通过 flate2 实现
+
+use crate::*;
+use std::io::Write;
+
+pub fn is_gzip(d: &[u8]) -> bool {
+    d.starts_with(&[0x1f, 0x8b])
+}
+
+/// 解压 gzip 数据, 支持多个 member 连接在一起的情况. 会校验 CRC32 和长度,
+/// 解压后超过 [`MAX_DECOMPRESSED_SIZE`] 时返回 [`ArchiveError::TooLarge`]
+pub fn gunzip(d: &[u8]) -> Result<Vec<u8>, FetchError> {
+    gunzip_with_limit(d, MAX_DECOMPRESSED_SIZE)
+}
+
+pub fn gunzip_with_limit(d: &[u8], limit: u64) -> Result<Vec<u8>, FetchError> {
+    read_decompressed(flate2::read::MultiGzDecoder::new(d), "gzip", limit)
+}
+
+/// 压缩为 gzip 格式, 头部不含文件名和时间, 相同输入得到相同输出
+pub fn gzip(d: &[u8]) -> Vec<u8> {
+    let mut e = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    e.write_all(d).expect("write to Vec");
+    e.finish().expect("write to Vec")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gunzip() {
+        // printf 'hello hello hello\n' | gzip -n
+        let gz = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00, 0x3b, 0x7c, 0x8a, 0xdf, 0x12, 0x00, 0x00,
+            0x00,
+        ];
+        assert_eq!(gunzip(&gz).unwrap(), b"hello hello hello\n");
+        let mut bad = gz;
+        bad[20] ^= 1;
+        assert!(gunzip(&bad).is_err());
+        assert!(gunzip(b"\x1f\x8bjunk").is_err());
+        let two = [&gz[..], &gz[..]].concat();
+        assert_eq!(gunzip(&two).unwrap(), b"hello hello hello\n".repeat(2));
+
+        let d = b"abcabcabcabc-xyz-".repeat(1000);
+        let gz = gzip(&d);
+        assert!(gz.len() < d.len() / 10);
+        assert_eq!(gzip(&d), gz);
+        assert_eq!(gunzip(&gz).unwrap(), d);
+        assert_eq!(gunzip(&gzip(b"")).unwrap(), b"");
+
+        // 解压后的大小有上限
+        let bomb = gzip(&vec![0; 1 << 20]);
+        assert!(bomb.len() < 2 << 10);
+        assert_eq!(gunzip_with_limit(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        let e = gunzip_with_limit(&bomb, (1 << 20) - 1).unwrap_err();
+        assert_eq!(e.kind(), FetchErrorKind::TooLarge);
+    }
+
+    #[test]
+    fn test_tar_gz() {
+        let mut b = tar::Builder::new(Vec::new());
+        let mut h = tar::Header::new_gnu();
+        h.set_size(5);
+        h.set_cksum();
+        b.append_data(&mut h, "rules/geoip.dat", &b"geoip"[..])
+            .unwrap();
+        let t = b.into_inner().unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let gz = temp_dir.path().join("a.tar.gz");
+        std::fs::write(&gz, gzip(&t)).unwrap();
+
+        let ds = DataSource::TarInMemory(std::fs::read(&gz).unwrap());
+        assert_eq!(ds.read_to_string("rules/geoip.dat").unwrap(), "geoip");
+        assert_eq!(ds.list_files().unwrap(), vec!["rules/geoip.dat"]);
+        let ds = DataSource::TarFile(TarFile(gz.to_string_lossy().to_string()));
+        assert_eq!(ds.read_to_string("rules/geoip.dat").unwrap(), "geoip");
+    }
+}
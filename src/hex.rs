@@ -0,0 +1,55 @@
+//! 小写十六进制的编码与解码, 以及 URL 中 `%XX` 的解码
+
+#[cfg_attr(not(any(feature = "cas", feature = "s3")), allow(dead_code))]
+pub(crate) fn encode(d: &[u8]) -> String {
+    d.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn nibble(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|v| v as u8)
+}
+
+/// 大小写均可, 长度为奇数或有非法字符时返回 None
+pub(crate) fn decode(s: &[u8]) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    s.chunks(2)
+        .map(|c| Some(nibble(c[0])? << 4 | nibble(c[1])?))
+        .collect()
+}
+
+/// 解码 `%XX`, 不合法的 `%` 原样保留
+pub(crate) fn percent_decode(s: &str) -> Vec<u8> {
+    let b = s.as_bytes();
+    let mut r = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' {
+            if let Some(v) = b.get(i + 1..i + 3).and_then(decode) {
+                r.push(v[0]);
+                i += 3;
+                continue;
+            }
+        }
+        r.push(b[i]);
+        i += 1;
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(encode(b"\x00\xab/"), "00ab2f");
+        assert_eq!(decode(b"00AB2f").unwrap(), b"\x00\xab/");
+        assert!(decode(b"abc").is_none());
+        assert!(decode(b"zz").is_none());
+        assert!(decode("é".as_bytes()).is_none());
+        assert_eq!(percent_decode("a%20b%2"), b"a b%2");
+        assert_eq!(percent_decode("%e4%b8%ad%zz%é"), "中%zz%é".as_bytes());
+    }
+}
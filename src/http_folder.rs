@@ -0,0 +1,217 @@
+use crate::*;
+
+/// 把请求的文件名拼接到 `base.url` 后面, 通过 http 获取文件.
+///
+/// `prefix_headers` 可以为不同的路径前缀附加不同的请求头 (例如 `private/` 加上 token,
+/// `public/` 不加), 所有匹配的前缀的请求头都会按配置顺序加在 `base` 的请求头之后.
+///
+/// 设置了 `index_file` 时, 可以通过这个文件 (每行一个文件名) 列出所有文件.
+///
+/// 设置了 `cache_dir` 时, 响应按 [`HttpSource::cache_key`] 缓存在这个目录中
+#[derive(Clone, Debug, Default)]
+pub struct HttpFolderSource {
+    pub base: HttpSource,
+    pub prefix_headers: Vec<(String, Vec<(String, String)>)>,
+    pub index_file: Option<String>,
+    pub cache_dir: Option<String>,
+    pub update_interval_seconds: Option<u64>,
+}
+
+impl HttpFolderSource {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base: HttpSource {
+                url: base_url.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn with_cache_dir(mut self, dir: &str, update_interval_seconds: Option<u64>) -> Self {
+        self.cache_dir = Some(dir.to_string());
+        self.update_interval_seconds = update_interval_seconds;
+        self
+    }
+
+    fn cache_for(&self, s: &HttpSource) -> Option<FileCache> {
+        self.cache_dir
+            .as_ref()
+            .map(|d| FileCache::for_request(d, s, self.update_interval_seconds))
+    }
+
+    pub fn with_index_file(mut self, index_file: &str) -> Self {
+        self.index_file = Some(index_file.to_string());
+        self
+    }
+
+    pub fn with_prefix_headers(mut self, prefix: &str, headers: Vec<(String, String)>) -> Self {
+        self.prefix_headers.push((prefix.to_string(), headers));
+        self
+    }
+
+    /// 生成请求 file_name 所用的 HttpSource. 文件名含有 `..` 时返回 None
+    pub fn source_for(&self, file_name: &Path) -> Option<HttpSource> {
+        let name = normalize_separators(file_name)
+            .to_string_lossy()
+            .to_string();
+        let name = name.trim_start_matches('/');
+        // url 解析时会去掉制表符和换行, `\` 和 `%2e` 也会被当作 `/` 和 `.`,
+        // 路径到 `?` 或 `#` 为止
+        let path: String = name
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .replace(['\t', '\n', '\r'], "");
+        if path.split(['/', '\\']).any(is_dot_dot) {
+            return None;
+        }
+
+        let mut s = self.base.clone();
+        s.url = format!("{}/{}", self.base.url.trim_end_matches('/'), name);
+        for (prefix, headers) in &self.prefix_headers {
+            if name.starts_with(prefix.as_str()) {
+                s.custom_request_headers
+                    .get_or_insert_with(Vec::new)
+                    .extend(headers.iter().cloned());
+            }
+        }
+        Some(s)
+    }
+}
+
+fn is_dot_dot(c: &str) -> bool {
+    c.to_ascii_lowercase().replace("%2e", ".") == ".."
+}
+
+impl SyncFolderSource for HttpFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let s = self
+            .source_for(file_name)
+            .ok_or_else(|| FetchError::not_found(file_name.to_string_lossy()))?;
+        match self.cache_for(&s) {
+            Some(fc) => fetch_with_cache(&fc, &s).map(|d| (d, Some(s.url))),
+            None => s.fetch().map(|d| (d, Some(s.url))),
+        }
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        let s = self
+            .source_for(file_name)
+            .ok_or_else(|| FetchError::not_found(file_name.to_string_lossy()))?;
+        if self.cache_dir.is_some() {
+            return self.get_file_content(file_name).map(FileContent::from);
+        }
+        let (data, content_type) = s.fetch_typed()?;
+        Ok(FileContent {
+            data,
+            path: Some(s.url),
+            content_type,
+            ..Default::default()
+        })
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        let c = capabilities::Capabilities::READ_ONLY;
+        if self.index_file.is_some() {
+            c.with_listable()
+        } else {
+            c
+        }
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        let index = self
+            .index_file
+            .as_ref()
+            .ok_or(FetchError::Unsupported("list_files without index_file"))?;
+        let (d, _) = self.get_file_content(Path::new(index))?;
+        Ok(String::from_utf8_lossy(&d)
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| l.to_string())
+            .collect())
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for HttpFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let s = self
+            .source_for(file_name)
+            .ok_or_else(|| FetchError::not_found(file_name.to_string_lossy()))?;
+        match self.cache_for(&s) {
+            Some(fc) => fetch_with_cache_async(&fc, &s)
+                .await
+                .map(|d| (d, Some(s.url))),
+            None => s.fetch_async().await.map(|d| (d, Some(s.url))),
+        }
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        let s = self
+            .source_for(file_name)
+            .ok_or_else(|| FetchError::not_found(file_name.to_string_lossy()))?;
+        if self.cache_dir.is_some() {
+            return self
+                .get_file_content_async(file_name)
+                .await
+                .map(FileContent::from);
+        }
+        let (data, content_type) = s.fetch_typed_async().await?;
+        Ok(FileContent {
+            data,
+            path: Some(s.url),
+            content_type,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_headers() {
+        let hf = HttpFolderSource::new("https://example.com/files/").with_prefix_headers(
+            "private/",
+            vec![("Authorization".to_string(), "Bearer t".to_string())],
+        );
+
+        let s = hf.source_for(Path::new("private/a.json")).unwrap();
+        assert_eq!(s.url, "https://example.com/files/private/a.json");
+        assert_eq!(s.custom_request_headers.unwrap().len(), 1);
+
+        let s = hf.source_for(Path::new("public/a.json")).unwrap();
+        assert!(s.custom_request_headers.is_none());
+
+        assert!(hf.source_for(Path::new("../etc/passwd")).is_none());
+        assert!(hf.source_for(Path::new("a\\..\\..\\etc")).is_none());
+        assert!(hf.source_for(Path::new("a/%2E./b")).is_none());
+        assert!(hf.source_for(Path::new("%2e.?x")).is_none());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let hf = HttpFolderSource::new("https://example.com/files/")
+            .with_prefix_headers("a", vec![("X-Tenant".to_string(), "1".to_string())]);
+        let a = hf.source_for(Path::new("a.json")).unwrap();
+        let b = hf.source_for(Path::new("b.json")).unwrap();
+        let mut a2 = a.clone();
+        a2.custom_request_headers = Some(vec![("x-tenant".to_string(), "2".to_string())]);
+
+        assert_eq!(a.cache_key().len(), 16);
+        assert_ne!(a.cache_key(), b.cache_key());
+        assert_ne!(a.cache_key(), a2.cache_key());
+        assert_eq!(a.cache_key(), a.clone().cache_key());
+    }
+}
@@ -0,0 +1,311 @@
+//! 按 CID 从 IPFS 网关或本地节点读取内容.
+//!
+//! 只有 raw 编码、sha2-256 摘要的 CIDv1 (如 `ipfs add --cid-version=1 --raw-leaves`
+//! 添加的单块文件) 的内容可以直接校验, 读到的内容与 CID 不符时返回 [`FetchError::Invalid`].
+//! 其它 CID (包括 `Qm` 开头的 CIDv0) 的内容是 dag-pb 编码后分块计算摘要的,
+//! 默认信任网关返回的内容, 设置 `require_verified` 后则拒绝读取
+
+use crate::*;
+
+const CODEC_RAW: u64 = 0x55;
+const MULTIHASH_SHA2_256: u64 = 0x12;
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+#[derive(Debug, Clone, Default)]
+pub struct IpfsSource {
+    pub cid: String,
+    /// 网关或节点 API 的地址, 代理和请求头等设置与 [`HttpSource`] 相同
+    pub http: HttpSource,
+    /// 为 true 时 `http.url` 是节点的 RPC API 地址 (如 `http://127.0.0.1:5001`),
+    /// 使用 `POST /api/v0/cat`, 否则是网关地址, 使用 `GET /ipfs/<cid>`
+    pub use_api: bool,
+    /// 拒绝读取无法校验的 CID
+    pub require_verified: bool,
+}
+
+/// RFC 4648 base32, 小写, 无填充
+fn base32_encode(d: &[u8]) -> String {
+    let mut r = String::new();
+    let (mut buf, mut bits) = (0u32, 0);
+    for &b in d {
+        buf = (buf << 8) | b as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            r.push(BASE32[(buf >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        r.push(BASE32[(buf << (5 - bits)) as usize & 31] as char);
+    }
+    r
+}
+
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut r = Vec::new();
+    let (mut buf, mut bits) = (0u32, 0);
+    for c in s.bytes() {
+        let v = BASE32.iter().position(|&b| b == c.to_ascii_lowercase())? as u32;
+        buf = (buf << 5) | v;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            r.push((buf >> bits) as u8);
+        }
+    }
+    Some(r)
+}
+
+fn read_varint(d: &mut &[u8]) -> Option<u64> {
+    let mut v = 0u64;
+    for i in 0..9 {
+        let (&b, rest) = d.split_first()?;
+        *d = rest;
+        v |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+/// 数据的 CIDv1 (raw 编码, sha2-256), 以 base32 表示
+pub fn raw_cid(data: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, data);
+    let mut b = vec![1, CODEC_RAW as u8, MULTIHASH_SHA2_256 as u8, 32];
+    b.extend_from_slice(digest.as_ref());
+    format!("b{}", base32_encode(&b))
+}
+
+/// 可以直接校验内容的 CID 返回其 sha256 (十六进制), 其它合法的 CID 返回 None
+pub fn raw_sha256_of_cid(cid: &str) -> Result<Option<String>, FetchError> {
+    let invalid = || FetchError::Invalid(format!("invalid cid `{cid}`"));
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        return Ok(None);
+    }
+    let b = cid
+        .strip_prefix('b')
+        .and_then(base32_decode)
+        .ok_or_else(invalid)?;
+    let mut d = b.as_slice();
+    if read_varint(&mut d) != Some(1) {
+        return Err(invalid());
+    }
+    let codec = read_varint(&mut d).ok_or_else(invalid)?;
+    let hash = read_varint(&mut d).ok_or_else(invalid)?;
+    let len = read_varint(&mut d).ok_or_else(invalid)?;
+    if d.len() as u64 != len {
+        return Err(invalid());
+    }
+    Ok((codec == CODEC_RAW && hash == MULTIHASH_SHA2_256).then(|| hex::encode(d)))
+}
+
+impl IpfsSource {
+    pub fn new(cid: &str, gateway: &str) -> Self {
+        Self {
+            cid: cid.to_string(),
+            http: HttpSource {
+                url: gateway.to_string(),
+                ..Default::default()
+            },
+            use_api: false,
+            require_verified: false,
+        }
+    }
+
+    /// 改为通过节点的 RPC API 读取
+    pub fn with_api(mut self, api: &str) -> Self {
+        self.http.url = api.to_string();
+        self.use_api = true;
+        self
+    }
+
+    pub fn with_require_verified(mut self) -> Self {
+        self.require_verified = true;
+        self
+    }
+
+    pub fn url(&self) -> String {
+        let base = self.http.url.trim_end_matches('/');
+        if self.use_api {
+            format!("{base}/api/v0/cat?arg={}", self.cid)
+        } else {
+            format!("{base}/ipfs/{}", self.cid)
+        }
+    }
+
+    /// 读取前检查 CID, 返回用于校验的摘要
+    fn expected_sha256(&self) -> Result<Option<String>, FetchError> {
+        let h = raw_sha256_of_cid(&self.cid)?;
+        if h.is_none() && self.require_verified {
+            return Err(FetchError::Unsupported("unverifiable cid"));
+        }
+        Ok(h)
+    }
+
+    fn check(
+        &self,
+        status: reqwest::StatusCode,
+        expected: Option<String>,
+        d: Vec<u8>,
+    ) -> Result<Vec<u8>, FetchError> {
+        match status {
+            s if s.is_success() => {}
+            reqwest::StatusCode::NOT_FOUND => return Err(FetchError::not_found(&self.cid)),
+            s => {
+                return Err(HttpError::Status {
+                    url: self.url(),
+                    status: s.as_u16(),
+                }
+                .into())
+            }
+        }
+        if expected.is_some_and(|h| h != cas::sha256_hex(&d)) {
+            return Err(FetchError::Invalid(format!(
+                "content of `{}` does not match its cid",
+                self.cid
+            )));
+        }
+        Ok(d)
+    }
+}
+
+impl SyncSource for IpfsSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        let expected = self.expected_sha256()?;
+        let hs = &self.http;
+        let r = if self.use_api {
+            let mut cb = hs.blocking_client_builder();
+            if hs.should_use_proxy {
+                cb = hs.set_proxy(cb)?;
+            }
+            let mut rb = cb.build()?.post(self.url());
+            for (k, v) in hs.custom_request_headers.iter().flatten() {
+                rb = rb.header(k, v);
+            }
+            rb.send()?
+        } else {
+            HttpSource {
+                url: self.url(),
+                ..hs.clone()
+            }
+            .send()?
+        };
+        let status = r.status();
+        self.check(status, expected, read_body(r)?)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for IpfsSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        let expected = self.expected_sha256()?;
+        let hs = &self.http;
+        let _permit = acquire_fetch_permit().await;
+        let r = if self.use_api {
+            let mut cb = hs.async_client_builder();
+            if hs.should_use_proxy {
+                cb = hs.set_proxy_async(cb)?;
+            }
+            let mut rb = cb.build()?.post(self.url());
+            for (k, v) in hs.custom_request_headers.iter().flatten() {
+                rb = rb.header(k, v);
+            }
+            rb.send().await?
+        } else {
+            HttpSource {
+                url: self.url(),
+                ..hs.clone()
+            }
+            .send_async()
+            .await?
+        };
+        let status = r.status();
+        self.check(status, expected, read_body_async(r).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+
+    /// 网关和 API 都返回 `body`, 其它路径返回 404
+    fn serve(cid: String, body: &'static str) -> String {
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", l.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let req = BufReader::new(&s).lines().next().unwrap().unwrap();
+                let ok = req == format!("GET /ipfs/{cid} HTTP/1.1")
+                    || req == format!("POST /api/v0/cat?arg={cid} HTTP/1.1");
+                let (status, body) = if ok {
+                    ("200 OK", body)
+                } else {
+                    ("404 Not Found", "")
+                };
+                write!(
+                    s,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_cid() {
+        let cid = raw_cid(b"hello");
+        assert!(cid.starts_with("bafkrei"));
+        assert_eq!(
+            raw_sha256_of_cid(&cid).unwrap(),
+            Some(cas::sha256_hex(b"hello"))
+        );
+        assert_eq!(
+            raw_sha256_of_cid("QmWATWQ7fVPP2EFGu71UkfnqhYXDYH566qy47CnJDgvs8u").unwrap(),
+            None
+        );
+        assert!(raw_sha256_of_cid("bnot-base32").is_err());
+        assert!(raw_sha256_of_cid("zabc").is_err());
+    }
+
+    #[test]
+    fn test_ipfs_source() {
+        let cid = raw_cid(b"hello");
+        let url = serve(cid.clone(), "hello");
+        assert_eq!(IpfsSource::new(&cid, &url).fetch().unwrap(), b"hello");
+        let api = IpfsSource::new(&cid, "").with_api(&url);
+        assert_eq!(api.fetch().unwrap(), b"hello");
+
+        let wrong = raw_cid(b"other");
+        let url = serve(wrong.clone(), "hello");
+        let e = IpfsSource::new(&wrong, &url).fetch().unwrap_err();
+        assert_eq!(e.kind(), FetchErrorKind::InvalidContent);
+        assert!(IpfsSource::new(&cid, &url)
+            .fetch()
+            .unwrap_err()
+            .is_not_found());
+
+        let v0 = "QmWATWQ7fVPP2EFGu71UkfnqhYXDYH566qy47CnJDgvs8u";
+        let url = serve(v0.to_string(), "hello");
+        assert_eq!(IpfsSource::new(v0, &url).fetch().unwrap(), b"hello");
+        let e = IpfsSource::new(v0, &url)
+            .with_require_verified()
+            .fetch()
+            .unwrap_err();
+        assert_eq!(e.kind(), FetchErrorKind::Unsupported);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_ipfs_source_async() {
+        let cid = raw_cid(b"hello");
+        let url = serve(cid.clone(), "hello");
+        let s = IpfsSource::new(&cid, "").with_api(&url);
+        assert_eq!(s.fetch_async().await.unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::*;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+static JOURNAL: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// 设置后, 每次上游请求和缓存决定都会以一行 JSON 追加到该文件中, 用于事后追查
+/// 某个文件是何时、从哪里得到的.
+///
+/// 请求记录: `{"ts":毫秒,"kind":"fetch","url":..,"status":..,"bytes":..,"duration_ms":..,"sha256":..,"error":..}`
+///
+/// 缓存记录: `{"ts":毫秒,"kind":"cache","cache":缓存文件,"decision":"hit"|"miss"|"expired"|"stale"}`
+pub fn set_fetch_journal(path: Option<PathBuf>) {
+    *JOURNAL.lock().unwrap_or_else(|e| e.into_inner()) = path;
+}
+
+fn json_str(s: &str) -> String {
+    let mut r = String::with_capacity(s.len() + 2);
+    r.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            '\n' => r.push_str("\\n"),
+            '\r' => r.push_str("\\r"),
+            '\t' => r.push_str("\\t"),
+            c if (c as u32) < 0x20 => r.push_str(&format!("\\u{:04x}", c as u32)),
+            c => r.push(c),
+        }
+    }
+    r.push('"');
+    r
+}
+
+fn opt<T: ToString>(v: Option<T>) -> String {
+    v.map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string())
+}
+
+fn append(fields: &str) {
+    let journal = JOURNAL.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(p) = journal.as_ref() else {
+        return;
+    };
+    let ts = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let r = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(p)
+        .and_then(|mut f| f.write_all(format!("{{\"ts\":{ts},{fields}}}\n").as_bytes()));
+    if let Err(e) = r {
+        warn!("Failed to write fetch journal: {e}");
+    }
+}
+
+pub(crate) fn is_enabled() -> bool {
+    JOURNAL.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// 目前只有 http 请求会记录
+#[cfg_attr(not(feature = "reqwest"), allow(dead_code))]
+pub(crate) fn record_fetch(
+    url: &str,
+    status: Option<u16>,
+    r: Result<&[u8], &FetchError>,
+    started: Instant,
+) {
+    if !is_enabled() {
+        return;
+    }
+    let (bytes, digest, error) = match r {
+        Ok(d) => (
+            Some(d.len()),
+            Some(json_str(&crate::cas::sha256_hex(d))),
+            None,
+        ),
+        Err(e) => (None, None, Some(json_str(&e.to_string()))),
+    };
+    append(&format!(
+        "\"kind\":\"fetch\",\"url\":{},\"status\":{},\"bytes\":{},\"duration_ms\":{},\"sha256\":{},\"error\":{}",
+        json_str(url),
+        opt(status),
+        opt(bytes),
+        started.elapsed().as_millis(),
+        opt(digest),
+        opt(error)
+    ));
+}
+
+pub(crate) fn record_cache(fc: &FileCache, decision: &str) {
+    let Some(cf) = &fc.cache_file_path else {
+        return;
+    };
+    if !is_enabled() {
+        return;
+    }
+    append(&format!(
+        "\"kind\":\"cache\",\"cache\":{},\"decision\":{}",
+        json_str(cf),
+        json_str(decision)
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_journal() {
+        let temp_dir = TempDir::new().unwrap();
+        let j = temp_dir.path().join("journal.jsonl");
+        let g = generated::Generator::new(|| Ok(b"x".to_vec()));
+        let fc = FileCache {
+            update_interval_seconds: Some(3600),
+            cache_file_path: Some(temp_dir.path().join("c").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        set_fetch_journal(Some(j.clone()));
+        fetch_with_cache(&fc, &g).unwrap();
+        fetch_with_cache(&fc, &g).unwrap();
+        record_fetch("http://a/\"b\"", Some(200), Ok(b"x"), Instant::now());
+        set_fetch_journal(None);
+
+        let s = std::fs::read_to_string(j).unwrap();
+        // 其它测试可能同时写入
+        let dir = temp_dir.path().to_string_lossy().to_string();
+        let lines: Vec<_> = s
+            .lines()
+            .filter(|l| l.contains(&dir) || l.contains("http://a/"))
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("\"decision\":\"miss\""));
+        assert!(lines[1].contains("\"decision\":\"hit\""));
+        assert!(lines[2].contains("\"url\":\"http://a/\\\"b\\\"\",\"status\":200,\"bytes\":1"));
+    }
+}
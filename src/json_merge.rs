@@ -0,0 +1,89 @@
+use crate::*;
+use serde_json::Value;
+
+/// 读取多个 JSON 文件并按顺序深度合并, 后面的优先级更高, 如默认配置加环境覆盖.
+///
+/// 两边都是对象时逐个键递归合并, 否则后面的值直接替换前面的值 (包括数组).
+/// 结果重新序列化为 JSON. 只支持 JSON, 某一部分不是合法 JSON 时返回 [`FetchError::Invalid`]
+#[derive(Debug, Default)]
+pub struct JsonMergeSource {
+    pub parts: Vec<SingleFileSource>,
+}
+
+impl JsonMergeSource {
+    pub fn new(parts: Vec<SingleFileSource>) -> Self {
+        Self { parts }
+    }
+}
+
+pub fn deep_merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(b), Value::Object(o)) => {
+            for (k, v) in o {
+                match b.get_mut(&k) {
+                    Some(bv) => deep_merge(bv, v),
+                    None => {
+                        b.insert(k, v);
+                    }
+                }
+            }
+        }
+        (b, o) => *b = o,
+    }
+}
+
+fn merge_parts(parts: Vec<Vec<u8>>) -> Result<Vec<u8>, FetchError> {
+    let mut merged = Value::Null;
+    for (i, p) in parts.into_iter().enumerate() {
+        let v: Value = serde_json::from_slice(&p)
+            .map_err(|e| FetchError::Invalid(format!("bad json in part {i}: {e}")))?;
+        if merged.is_null() {
+            merged = v;
+        } else {
+            deep_merge(&mut merged, v);
+        }
+    }
+    serde_json::to_vec(&merged).map_err(|e| FetchError::I(io::Error::other(e)))
+}
+
+impl SyncSource for JsonMergeSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        let parts = self
+            .parts
+            .iter()
+            .map(|p| p.fetch())
+            .collect::<Result<Vec<_>, _>>()?;
+        merge_parts(parts)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for JsonMergeSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        let parts =
+            futures::future::try_join_all(self.parts.iter().map(|p| p.fetch_async())).await?;
+        merge_parts(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let m = JsonMergeSource::new(vec![
+            SingleFileSource::Inline(br#"{"a":1,"b":{"c":1,"d":[1,2]}}"#.to_vec()),
+            SingleFileSource::Inline(br#"{"b":{"c":2,"d":[3]},"e":true}"#.to_vec()),
+        ]);
+        let v: Value = serde_json::from_slice(&m.fetch().unwrap()).unwrap();
+        assert_eq!(v, serde_json::json!({"a":1,"b":{"c":2,"d":[3]},"e":true}));
+
+        let bad = JsonMergeSource::new(vec![
+            SingleFileSource::Inline(b"{}".to_vec()),
+            SingleFileSource::Inline(b"a = 1".to_vec()),
+        ]);
+        assert!(matches!(bad.fetch(), Err(FetchError::Invalid(_))));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::*;
+
+/// 系统密钥库中的一项, 用于 [`SingleFileSource::Keyring`], 使 API token 等不必保存在磁盘上.
+///
+/// 通过 keyring crate 读取: Linux 上为 Secret Service (GNOME Keyring / KWallet),
+/// macOS 上为钥匙串, Windows 上为凭据管理器. 密钥库被锁定或无法访问时返回
+/// [`FetchError::Keyring`], 只有确实没有这一项时才是 not found. 读到的值不会写入任何缓存
+#[derive(Debug, Clone)]
+pub struct KeyringEntry {
+    pub service: String,
+    pub user: String,
+}
+
+impl KeyringEntry {
+    pub fn new(service: &str, user: &str) -> Self {
+        Self {
+            service: service.to_string(),
+            user: user.to_string(),
+        }
+    }
+
+    /// 读取值并生成 `Authorization: Bearer <token>` 请求头, 用于 [`HttpSource::custom_request_headers`]
+    pub fn bearer_header(&self) -> Result<(String, String), FetchError> {
+        let t = self.fetch()?;
+        Ok((
+            "Authorization".to_string(),
+            format!("Bearer {}", String::from_utf8_lossy(&t)),
+        ))
+    }
+}
+
+impl SyncSource for KeyringEntry {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        ::keyring::Entry::new(&self.service, &self.user)
+            .and_then(|e| e.get_secret())
+            .map_err(FetchError::Keyring)
+    }
+}
+
+/// 平台的密钥库接口是阻塞的, 在阻塞线程中读取
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for KeyringEntry {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        let e = self.clone();
+        tokio::task::spawn_blocking(move || e.fetch())
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyring_errors() {
+        ::keyring::set_default_credential_builder(::keyring::mock::default_credential_builder());
+        let e = KeyringEntry::new("data-source-test", "u")
+            .fetch()
+            .unwrap_err();
+        assert!(e.is_not_found());
+
+        // 密钥库不可用不是 not found
+        let e = FetchError::Keyring(::keyring::Error::NoStorageAccess("locked".into()));
+        assert_eq!(e.kind(), FetchErrorKind::Unavailable);
+        let e = FetchError::Keyring(::keyring::Error::PlatformFailure("dbus".into()));
+        assert_eq!(e.kind(), FetchErrorKind::Unavailable);
+    }
+}
@@ -0,0 +1,169 @@
+//! 条目很多 (由大型清单生成, 数万项) 的 FileMap 全部常驻内存时, 内存随条目数线性增长.
+//! [`LazyFileMap`] 把 `文件名 -> uri` 的对应关系放在磁盘上的索引中 (如
+//! [`sqlite::SqliteFolderSource`] 的一张表, 或每个文件内容为 uri 的目录),
+//! 读取时才解析为 [`SingleFileSource`], 内存中只保留最近使用的有限个条目
+
+use crate::*;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct HotSet {
+    entries: HashMap<String, (Arc<SingleFileSource>, u64)>,
+    tick: u64,
+}
+
+#[derive(Debug)]
+pub struct LazyFileMap {
+    /// 文件内容为条目的 uri, 见 [`SingleFileSource::from_uri`]
+    pub index: Box<dyn SyncFolderSource + Send + Sync>,
+    /// 内存中最多保留的条目数, 超出时移除最久未使用的条目
+    pub max_hot: usize,
+    hot: Mutex<HotSet>,
+}
+
+impl LazyFileMap {
+    pub fn new(index: impl SyncFolderSource + Send + Sync + 'static, max_hot: usize) -> Self {
+        Self {
+            index: Box::new(index),
+            max_hot,
+            hot: Mutex::default(),
+        }
+    }
+
+    /// 当前在内存中的条目数
+    pub fn hot_len(&self) -> usize {
+        self.hot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+
+    fn entry(&self, file_name: &Path) -> Result<Arc<SingleFileSource>, FetchError> {
+        let key = normalize_separators(file_name)
+            .to_string_lossy()
+            .to_string();
+        {
+            let mut hot = self.hot.lock().unwrap_or_else(|e| e.into_inner());
+            hot.tick += 1;
+            let tick = hot.tick;
+            if let Some((s, t)) = hot.entries.get_mut(&key) {
+                *t = tick;
+                return Ok(s.clone());
+            }
+        }
+        let (uri, _) = self.index.get_file_content(file_name)?;
+        let uri = String::from_utf8(uri)
+            .map_err(|_| FetchError::Invalid(format!("index entry `{key}` is not utf-8")))?;
+        let s = Arc::new(SingleFileSource::from_uri(uri.trim())?);
+        if self.max_hot == 0 {
+            return Ok(s);
+        }
+        let mut hot = self.hot.lock().unwrap_or_else(|e| e.into_inner());
+        while hot.entries.len() >= self.max_hot {
+            let Some(oldest) = hot
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, t))| *t)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            hot.entries.remove(&oldest);
+        }
+        let tick = hot.tick;
+        hot.entries.insert(key, (s.clone(), tick));
+        Ok(s)
+    }
+
+    /// 把 `(文件名, uri)` 写入 SQLite 索引, 表不存在时创建
+    #[cfg(feature = "sqlite")]
+    pub fn write_sqlite_index<'a>(
+        index: &sqlite::SqliteFolderSource,
+        entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<(), FetchError> {
+        index.create_table()?;
+        for (name, uri) in entries {
+            index.put(name, uri.as_bytes(), None)?;
+        }
+        Ok(())
+    }
+}
+
+impl SyncFolderSource for LazyFileMap {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let s = self.entry(file_name)?;
+        let d = s.fetch()?;
+        Ok((d, s.get_path()))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.index.capabilities().read_only()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        self.index.list_files()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lazy_file_map() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = temp_dir.path().join("index");
+        std::fs::create_dir_all(index.join("rules")).unwrap();
+        for (i, c) in ["a", "b", "c"].iter().enumerate() {
+            std::fs::write(index.join(format!("{c}.json")), format!("data:,{i}\n")).unwrap();
+        }
+        let file = temp_dir.path().join("geo.dat");
+        std::fs::write(&file, "geo").unwrap();
+        std::fs::write(
+            index.join("rules/geo.dat"),
+            format!("file://{}", file.display()),
+        )
+        .unwrap();
+
+        let m = LazyFileMap::new(
+            DataSource::Folders(vec![index.to_string_lossy().to_string()]),
+            2,
+        );
+        assert_eq!(m.get_file_content(Path::new("a.json")).unwrap().0, b"0");
+        assert_eq!(m.get_file_content(Path::new("b.json")).unwrap().0, b"1");
+        assert_eq!(m.get_file_content(Path::new("a.json")).unwrap().0, b"0");
+        assert_eq!(m.get_file_content(Path::new("c.json")).unwrap().0, b"2");
+        assert_eq!(m.hot_len(), 2);
+        {
+            let hot = m.hot.lock().unwrap();
+            assert!(hot.entries.contains_key("a.json"));
+            assert!(!hot.entries.contains_key("b.json"));
+        }
+        let (d, p) = m.get_file_content(Path::new("rules/geo.dat")).unwrap();
+        assert_eq!(d, b"geo");
+        assert_eq!(p.unwrap(), file.to_string_lossy());
+        assert_eq!(m.hot_len(), 2);
+        assert!(m
+            .get_file_content(Path::new("nope.json"))
+            .unwrap_err()
+            .is_not_found());
+
+        let ds = DataSource::Sync(Box::new(m));
+        assert_eq!(ds.read_to_string("b.json").unwrap(), "1");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_lazy_file_map_sqlite() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = sqlite::SqliteFolderSource::new(temp_dir.path().join("index.db"), "entries");
+        LazyFileMap::write_sqlite_index(&index, [("a.json", "data:,a"), ("b.json", "data:,b")])
+            .unwrap();
+        let m = LazyFileMap::new(index, 1);
+        assert_eq!(m.get_file_content(Path::new("b.json")).unwrap().0, b"b");
+        assert_eq!(m.list_files().unwrap(), vec!["a.json", "b.json"]);
+        assert!(m.capabilities().listable && !m.capabilities().writable);
+    }
+}
@@ -1,93 +1,507 @@
+#[cfg(feature = "azure")]
+pub mod azure;
+mod base64;
+#[cfg(feature = "cache_meta")]
+pub mod cache_meta;
+pub mod canary;
+pub mod capabilities;
+#[cfg(feature = "cas")]
+pub mod cas;
+pub mod circuit_breaker;
+pub mod concat;
+pub mod config_diff;
+#[cfg(feature = "consul")]
+pub mod consul;
+pub mod data_uri;
+#[cfg(feature = "reqwest")]
+pub mod dns;
+#[cfg(feature = "dns_txt")]
+pub mod dns_txt;
+pub mod embedded;
+#[cfg(feature = "encrypted")]
+pub mod encrypted;
+mod error;
+#[cfg(feature = "etcd")]
+pub mod etcd;
+pub mod explain;
+pub mod export;
+#[cfg(feature = "extract")]
+pub mod extract;
 #[cfg(feature = "file_server")]
 pub mod file_server;
-
-use std::{collections::HashMap, io, path::Path, time::SystemTime};
+pub mod gc;
+#[cfg(feature = "gcs")]
+pub mod gcs;
+pub mod generated;
+pub mod git;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "flate2")]
+pub mod gzip;
+mod hex;
+#[cfg(feature = "reqwest")]
+pub mod http_folder;
+#[cfg(feature = "ipfs")]
+pub mod ipfs;
+#[cfg(feature = "journal")]
+pub mod journal;
+#[cfg(feature = "json_merge")]
+pub mod json_merge;
+#[cfg(feature = "keyring")]
+pub mod keyring;
+pub mod lazy_map;
+pub mod locale;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod memory_cache;
+pub mod migrate;
+pub mod mirror;
+#[cfg(feature = "reqwest")]
+pub mod mirror_http;
+pub mod mirror_set;
+pub mod overlay;
+pub mod platform;
+#[cfg(feature = "tokio")]
+pub mod polling;
+#[cfg(feature = "reqwest")]
+pub mod proxy;
+#[cfg(feature = "reqwest")]
+pub mod range_cache;
+#[cfg(feature = "tokio")]
+#[cfg(feature = "reqwest")]
+pub mod read_through;
+#[cfg(feature = "tokio")]
+#[cfg(feature = "reqwest")]
+pub mod refresh;
+pub mod resolver;
+#[cfg(feature = "reqwest")]
+pub mod revalidate;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(feature = "sftp")]
+pub mod sftp;
+#[cfg(feature = "tokio")]
+pub mod single_flight;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "tokio")]
+pub mod swr;
+pub mod tags;
+#[cfg(feature = "tar")]
+pub mod tar_builder;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "unicode")]
+pub mod unicode;
+#[cfg(feature = "reqwest")]
+pub mod unix_socket;
+pub mod uri;
+pub mod validate;
+pub mod validator;
+pub mod versioned;
+#[cfg(feature = "tokio")]
+pub mod warmup;
+#[cfg(feature = "reqwest")]
+pub mod webdav;
+pub mod win_path;
+#[cfg(feature = "zstd")]
+pub mod zstd;
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::SystemTime,
+};
 
 use log::{debug, warn};
 
-#[derive(thiserror::Error, Debug)]
-pub enum FetchError {
-    #[cfg(feature = "reqwest")]
-    #[error("reqwest err")]
-    R(#[from] reqwest::Error),
-    #[error("io err")]
-    I(#[from] io::Error),
-    #[error("time err")]
-    T(#[from] std::time::SystemTimeError),
-    #[error("size limit exceed")]
-    S,
-    #[error("no cache file")]
-    NC,
-    #[error("not found")]
-    NF,
-    #[error("not found in directories `{0:?}`")]
-    NFD(Vec<String>),
-}
-
-impl From<FetchError> for io::Error {
-    fn from(value: FetchError) -> Self {
-        match value {
-            FetchError::R(error) => io::Error::other(error),
-            FetchError::I(error) => error,
-            FetchError::T(error) => io::Error::other(error),
-            FetchError::S => io::Error::other(value.to_string()),
-            FetchError::NC => io::Error::other(value.to_string()),
-            FetchError::NF => io::Error::new(io::ErrorKind::NotFound, ""),
-            FetchError::NFD(_) => io::Error::other(value.to_string()),
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
+pub use capabilities::capability_report;
+pub use error::*;
+
+#[derive(Debug, Clone, Default)]
 pub struct FileCache {
     pub update_interval_seconds: Option<u64>,
     pub cache_file_path: Option<String>,
+    /// 将过期时间提前 `[0, jitter_seconds]` 秒 (按缓存路径固定分配),
+    /// 避免大量相同间隔的条目同时过期、集中刷新
+    pub jitter_seconds: Option<u64>,
+    /// 没有 `cache_file_path` 时, 缓存到这个目录中按来源 url 的哈希命名的文件,
+    /// 这样一份设置可以用于多个远程条目, 见 [`FileCache::resolve`]
+    pub cache_dir: Option<String>,
+    /// 目录模式下缓存目录的总大小上限, 每次写入后按最近使用时间淘汰其它缓存文件, 见 [`gc::evict_lru`]
+    pub max_dir_bytes: Option<u64>,
+    /// 缓存过期时先返回旧缓存, 在后台刷新 (只对异步读取有效), 见 [`swr`]
+    pub stale_while_revalidate: bool,
+    /// 读取来源失败时, 改为返回不超过这个时间 (从缓存文件的修改时间算起) 的旧缓存, 包括已过期的
+    pub stale_if_error_seconds: Option<u64>,
+    /// 返回缓存前检查内容与元数据中记录的 sha256 (需开启 cache_meta feature),
+    /// 不一致或没有记录时视为没有缓存, 重新读取来源
+    pub verify_checksum: bool,
+    /// 加密缓存文件 (需开启 encrypted feature, 格式同 [`encrypted`] 模块, 以缓存文件名作为附加数据),
+    /// 无法解密的缓存视为没有缓存, 重新读取来源. 因此改名 (如 [`migrate::migrate_cache`]) 后需重新读取
+    pub encryption_key: Option<CacheKey>,
+    /// 用 zstd 压缩缓存文件 (需开启 zstd feature), 读取时按文件开头识别, 关闭后仍能读取旧缓存
+    pub compress: bool,
+}
+
+/// 压缩后的缓存文件开头, 之后是 zstd 数据. 与来源本身就是 zstd 的数据区分开.
+/// 没有开启 zstd feature 时也用于识别无法读取的压缩缓存
+pub(crate) const COMPRESSED_CACHE_MAGIC: &[u8] = b"DSCZ\x01";
+
+/// 缓存加密密钥: 32 字节原始数据或 64 个十六进制字符. Debug 输出不包含密钥
+#[derive(Clone, PartialEq, Eq)]
+pub struct CacheKey(pub Vec<u8>);
+
+impl std::fmt::Debug for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CacheKey(..)")
+    }
+}
+
+/// 写入缓存时已知的来源信息, 记录在缓存元数据中
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(not(feature = "cache_meta"), allow(dead_code))]
+pub(crate) struct SourceInfo<'a> {
+    pub url: Option<&'a str>,
+    pub etag: Option<&'a str>,
+    pub content_type: Option<&'a str>,
+}
+
+impl<'a> SourceInfo<'a> {
+    #[cfg_attr(not(feature = "reqwest"), allow(dead_code))]
+    pub fn url(url: &'a str) -> Self {
+        Self {
+            url: Some(url),
+            ..Default::default()
+        }
+    }
 }
 
 impl FileCache {
+    /// 缓存到 `dir` 中, 文件名由各个条目的 url 决定
+    pub fn in_dir(dir: &str, update_interval_seconds: Option<u64>) -> Self {
+        Self {
+            update_interval_seconds,
+            cache_dir: Some(dir.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_stale_while_revalidate(mut self) -> Self {
+        self.stale_while_revalidate = true;
+        self
+    }
+
+    pub fn with_stale_if_error(mut self, max_stale_seconds: u64) -> Self {
+        self.stale_if_error_seconds = Some(max_stale_seconds);
+        self
+    }
+
+    pub fn with_verify_checksum(mut self) -> Self {
+        self.verify_checksum = true;
+        self
+    }
+
+    pub fn with_compression(mut self) -> Self {
+        self.compress = true;
+        self
+    }
+
+    pub fn with_encryption_key(mut self, key: &[u8]) -> Self {
+        self.encryption_key = Some(CacheKey(key.to_vec()));
+        self
+    }
+
+    pub fn with_max_dir_bytes(mut self, max_dir_bytes: u64) -> Self {
+        self.max_dir_bytes = Some(max_dir_bytes);
+        self
+    }
+
+    /// 按 `name` 确定 `cache_dir` 中的缓存文件. 已有 `cache_file_path` 或没有 `cache_dir` 时不变
+    pub fn resolve(&self, name: &str) -> Cow<'_, FileCache> {
+        match (&self.cache_file_path, &self.cache_dir) {
+            (None, Some(dir)) => Cow::Owned(FileCache {
+                cache_file_path: Some(Path::new(dir).join(name).to_string_lossy().to_string()),
+                ..self.clone()
+            }),
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// 目录模式下写入前创建缓存目录
+    fn create_cache_dir(&self) {
+        let Some(cf) = self.cache_dir.as_ref().and(self.cache_file_path.as_ref()) else {
+            return;
+        };
+        if let Some(parent) = Path::new(cf).parent() {
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                warn!("Failed to create cache dir: {err}");
+            }
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter_seconds: u64) -> Self {
+        self.jitter_seconds = Some(jitter_seconds);
+        self
+    }
+
+    /// 应用 jitter 后的实际更新间隔
+    pub fn effective_interval(&self) -> Option<u64> {
+        let interval = self.update_interval_seconds?;
+        let splay = match (self.jitter_seconds, &self.cache_file_path) {
+            (Some(j), Some(cf)) if j > 0 => stable_hash(cf) % (j + 1),
+            _ => 0,
+        };
+        Some(interval.saturating_sub(splay))
+    }
+
     pub fn read_cache_file(&self) -> Result<Vec<u8>, FetchError> {
         let cf = self.cache_file_path.as_ref().unwrap();
-        let s: Vec<u8> = std::fs::read(cf)?;
-        Ok(s)
+        let d = read_local_file(Path::new(cf))?;
+        self.check_cache(&d)?;
+        let d = self.open_cache(d)?;
+        self.mark_used();
+        Ok(d)
     }
 
     #[cfg(feature = "tokio")]
     pub async fn read_cache_file_async(&self) -> Result<Vec<u8>, FetchError> {
         let cf = self.cache_file_path.as_ref().unwrap();
+        let d = read_local_file_async(Path::new(cf)).await?;
+        let d = if self.verify_checksum
+            || self.encryption_key.is_some()
+            || d.starts_with(COMPRESSED_CACHE_MAGIC)
+        {
+            // 校验, 解密和解压都要处理整个文件, 放到阻塞线程中, 不占用异步执行器
+            let fc = self.clone();
+            tokio::task::spawn_blocking(move || {
+                fc.check_cache(&d)?;
+                fc.open_cache(d)
+            })
+            .await
+            .map_err(io::Error::other)??
+        } else {
+            self.open_cache(d)?
+        };
+        self.mark_used();
+        Ok(d)
+    }
+
+    /// 按 `verify_checksum` 检查读到的缓存文件. 没有开启 cache_meta feature 时无法检查, 返回错误而不是跳过
+    #[cfg_attr(not(feature = "cache_meta"), allow(unused_variables))]
+    fn check_cache(&self, d: &[u8]) -> Result<(), FetchError> {
+        #[cfg(feature = "cache_meta")]
+        return self.verify_cache(d);
+        #[cfg(not(feature = "cache_meta"))]
+        if self.verify_checksum {
+            Err(FetchError::FeatureDisabled("cache_meta"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 按 `compress` 压缩, 再按 `encryption_key` 加密要写入缓存文件的内容.
+    /// 没有开启对应的 feature 时返回错误, 不写入缓存
+    pub(crate) fn seal_cache<'a>(&self, d: &'a [u8]) -> Result<Cow<'a, [u8]>, FetchError> {
+        let d = if self.compress {
+            #[cfg(feature = "zstd")]
+            {
+                zstd::pack_cache(d).unwrap_or_else(|e| {
+                    warn!("Failed to compress cache file: {e}");
+                    Cow::Borrowed(d)
+                })
+            }
+            #[cfg(not(feature = "zstd"))]
+            return Err(FetchError::FeatureDisabled("zstd"));
+        } else {
+            Cow::Borrowed(d)
+        };
+        match &self.encryption_key {
+            None => Ok(d),
+            #[cfg(feature = "encrypted")]
+            Some(k) => encrypted::encrypt_cache(&k.0, &d, &self.cache_name()).map(Cow::Owned),
+            #[cfg(not(feature = "encrypted"))]
+            Some(_) => Err(FetchError::FeatureDisabled("encrypted")),
+        }
+    }
 
-        let content = tokio::fs::read(cf).await?;
-        Ok(content)
+    /// 解密并解压读到的缓存文件, 无法解密 (密钥已更换或内容被改动) 或解压时按损坏处理
+    fn open_cache(&self, d: Vec<u8>) -> Result<Vec<u8>, FetchError> {
+        let d = match &self.encryption_key {
+            None => d,
+            #[cfg(feature = "encrypted")]
+            Some(k) => encrypted::decrypt_cache(&k.0, &d, &self.cache_name())?
+                .ok_or_else(|| self.discard_corrupt())?,
+            #[cfg(not(feature = "encrypted"))]
+            Some(_) => return Err(FetchError::FeatureDisabled("encrypted")),
+        };
+        #[cfg(feature = "zstd")]
+        let d = zstd::unpack_cache(d)?.ok_or_else(|| self.discard_corrupt())?;
+        #[cfg(not(feature = "zstd"))]
+        if d.starts_with(COMPRESSED_CACHE_MAGIC) {
+            return Err(FetchError::FeatureDisabled("zstd"));
+        }
+        Ok(d)
+    }
+
+    /// 缓存文件名 (目录模式下即缓存键), 加密时作为附加数据
+    #[cfg(feature = "encrypted")]
+    fn cache_name(&self) -> String {
+        let cf = Path::new(self.cache_file_path.as_ref().unwrap());
+        cf.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// 删除损坏的缓存文件及其 ETag 和元数据, 返回 [`CacheError::Corrupt`]
+    #[cfg(any(feature = "cache_meta", feature = "encrypted", feature = "zstd"))]
+    pub(crate) fn discard_corrupt(&self) -> FetchError {
+        let cf = self.cache_file_path.clone().unwrap();
+        warn!("cache file {cf} is corrupt, discarding");
+        match gc::remove_cache_file(Path::new(&cf)) {
+            Ok(_) => CacheError::Corrupt { path: cf }.into(),
+            Err(e) => e,
+        }
+    }
+
+    /// 有大小上限时记录缓存文件的使用时间
+    fn mark_used(&self) {
+        if self.max_dir_bytes.is_some() {
+            gc::touch_accessed(Path::new(self.cache_file_path.as_ref().unwrap()));
+        }
+    }
+
+    /// 写入后按 `max_dir_bytes` 淘汰目录中的其它缓存文件
+    fn enforce_max_dir_bytes(&self) {
+        let (Some(dir), Some(max)) = (&self.cache_dir, self.max_dir_bytes) else {
+            return;
+        };
+        let cf = Path::new(self.cache_file_path.as_ref().unwrap());
+        let keep = cf.file_name().map(|n| n.to_string_lossy());
+        match gc::evict_lru(Path::new(dir), max, keep.as_deref()) {
+            Ok(removed) if !removed.is_empty() => {
+                debug!("cache dir over limit, evicted {} files", removed.len())
+            }
+            Err(e) => warn!("Failed to evict cache files: {e}"),
+            _ => {}
+        }
     }
 
     pub fn write_cache_file(&self, bytes: &[u8]) -> bool {
+        self.write_cache_file_from(bytes, &SourceInfo::default())
+    }
+
+    /// 写入缓存, 开启 cache_meta feature 时同时记录 `info`, 见 [`cache_meta`]
+    #[cfg_attr(not(feature = "cache_meta"), allow(unused_variables))]
+    pub(crate) fn write_cache_file_from(&self, bytes: &[u8], info: &SourceInfo) -> bool {
         let cf = self.cache_file_path.as_ref().unwrap();
-        if let Err(err) = std::fs::write(cf, bytes) {
+        let bytes = match self.seal_cache(bytes) {
+            Ok(b) => b,
+            Err(err) => {
+                warn!("Failed to write cache file: {err}");
+                return false;
+            }
+        };
+        self.create_cache_dir();
+        if let Err(err) = std::fs::write(cf, &bytes) {
             warn!("Failed to write cache file: {err}");
             false
         } else {
+            #[cfg(feature = "cache_meta")]
+            self.save_metadata(&bytes, info);
+            self.enforce_max_dir_bytes();
             true
         }
     }
 
     #[cfg(feature = "tokio")]
     pub async fn write_cache_file_async(&self, bytes: &[u8]) -> bool {
+        self.write_cache_file_from_async(bytes, &SourceInfo::default())
+            .await
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn write_cache_file_from_async(
+        &self,
+        bytes: &[u8],
+        info: &SourceInfo<'_>,
+    ) -> bool {
+        match self.try_write_cache_file_from_async(bytes, info).await {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("Failed to write cache file: {err}");
+                false
+            }
+        }
+    }
+
+    /// 与 `write_cache_file_from_async` 相同, 失败时返回原因
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(not(feature = "cache_meta"), allow(unused_variables))]
+    pub(crate) async fn try_write_cache_file_from_async(
+        &self,
+        bytes: &[u8],
+        info: &SourceInfo<'_>,
+    ) -> Result<(), FetchError> {
         let cf = self.cache_file_path.as_ref().unwrap();
-        if let Err(err) = tokio::fs::write(cf, bytes).await {
-            warn!("Failed to write cache file: {err}");
-            false
+        let bytes = if self.compress || self.encryption_key.is_some() {
+            let (fc, b) = (self.clone(), bytes.to_vec());
+            let sealed =
+                tokio::task::spawn_blocking(move || fc.seal_cache(&b).map(Cow::into_owned))
+                    .await
+                    .map_err(io::Error::other)??;
+            Cow::Owned(sealed)
         } else {
-            true
+            self.seal_cache(bytes)?
+        };
+        self.create_cache_dir();
+        tokio::fs::write(cf, &bytes)
+            .await
+            .map_err(|source| CacheError::Io {
+                path: cf.clone(),
+                source,
+            })?;
+        #[cfg(feature = "cache_meta")]
+        self.save_metadata(&bytes, info);
+        self.enforce_max_dir_bytes();
+        Ok(())
+    }
+
+    /// 读取来源失败时按 `stale_if_error_seconds` 使用旧缓存, 不能使用时返回原错误
+    pub(crate) fn fallback_on_error(&self, e: FetchError) -> Result<Vec<u8>, FetchError> {
+        let (Some(max), Some(cf)) = (self.stale_if_error_seconds, &self.cache_file_path) else {
+            return Err(e);
+        };
+        let age = std::fs::metadata(cf)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| SystemTime::now().duration_since(t).ok());
+        if age.is_none_or(|a| a.as_secs() > max) {
+            return Err(e);
         }
+        warn!("{e}, serving stale cache");
+        #[cfg(feature = "journal")]
+        journal::record_cache(self, "stale");
+        self.read_cache_file().or(Err(e))
     }
 
     /// 检查缓存文件是否超时
     pub fn is_cache_timeout(&self) -> Result<Option<bool>, FetchError> {
         if let Some(cf) = &self.cache_file_path {
-            if std::fs::exists(cf)? {
+            let io_err = |source| CacheError::Io {
+                path: cf.clone(),
+                source,
+            };
+            if std::fs::exists(cf).map_err(io_err)? {
                 let mut expired = false;
-                if let Some(interval) = self.update_interval_seconds {
-                    let metadata = std::fs::metadata(cf)?;
-                    let last_modified = metadata.modified()?;
+                if let Some(interval) = self.effective_interval() {
+                    let last_modified = std::fs::metadata(cf)
+                        .and_then(|m| m.modified())
+                        .map_err(io_err)?;
                     let elapsed = SystemTime::now().duration_since(last_modified)?.as_secs();
                     expired = elapsed > interval;
                 }
@@ -100,6 +514,151 @@ impl FileCache {
     }
 }
 
+#[cfg(feature = "reqwest")]
+impl FileCache {
+    /// 缓存到 `dir` 中以 [`HttpSource::cache_key`] 命名的文件,
+    /// 这样同一 url 不同请求头的响应不会互相覆盖
+    pub fn for_request(dir: &str, s: &HttpSource, update_interval_seconds: Option<u64>) -> Self {
+        Self {
+            update_interval_seconds,
+            cache_file_path: Some(
+                Path::new(dir)
+                    .join(s.cache_key())
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+/// url 和 (已排序的) 请求头的哈希, 用作目录模式下的缓存文件名
+pub(crate) fn url_cache_key(url: &str, headers: &[(String, &str)]) -> String {
+    let mut s = format!("{url}\n");
+    for (k, v) in headers {
+        s.push_str(&format!("{k}: {v}\n"));
+    }
+    format!("{:016x}", stable_hash(&s))
+}
+
+impl SingleFileSource {
+    /// 远程条目 (http, s3, azure, gcs) 的缓存设置, `cache_dir` 已按条目解析为具体的文件
+    pub fn file_cache(&self) -> Option<Cow<'_, FileCache>> {
+        match self {
+            SingleFileSource::Http(_, fc) => Some(self.resolve_cache(fc)),
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(_, fc) => Some(self.resolve_cache(fc)),
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(_, fc) => Some(self.resolve_cache(fc)),
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(_, fc) => Some(self.resolve_cache(fc)),
+            #[cfg(feature = "encrypted")]
+            SingleFileSource::Encrypted(e) => e.inner.file_cache(),
+            SingleFileSource::Annotated(s, _) | SingleFileSource::Validated(s, _) => s.file_cache(),
+            _ => None,
+        }
+    }
+
+    /// 目录模式下的缓存文件名: Http 为 [`HttpSource::cache_key`], 其他远程条目为 url 的哈希.
+    /// Generated 没有 url, 不支持目录模式
+    pub(crate) fn resolve_cache<'a>(&self, fc: &'a FileCache) -> Cow<'a, FileCache> {
+        let name = match self {
+            #[cfg(feature = "reqwest")]
+            SingleFileSource::Http(hs, _) => hs.cache_key(),
+            #[cfg(not(feature = "reqwest"))]
+            SingleFileSource::Http(hs, _) => url_cache_key(&hs.url, &[]),
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(s, _) => url_cache_key(&s.url(), &[]),
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(s, _) => url_cache_key(&s.url(), &[]),
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(s, _) => url_cache_key(&s.url(), &[]),
+            _ => return Cow::Borrowed(fc),
+        };
+        fc.resolve(&name)
+    }
+}
+
+/// FNV-1a, 保证不同进程和版本间结果一致
+pub(crate) fn stable_hash(s: &str) -> u64 {
+    stable_hash_bytes(s.as_bytes())
+}
+
+pub(crate) fn stable_hash_bytes(d: &[u8]) -> u64 {
+    d.iter().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// UTC 时间的年, 月, 日和当天的秒数, 用于请求签名和 http 头中的日期
+#[cfg(feature = "reqwest")]
+pub(crate) fn utc_civil(t: SystemTime) -> (i64, i64, i64, i64) {
+    let secs = t
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(m <= 2), m, d, rem)
+}
+
+/// `Sun, 06 Nov 1994 08:49:37 GMT` 格式的时间, 用于 http 头
+#[cfg(feature = "reqwest")]
+pub(crate) fn rfc1123_date(t: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (y, m, d, rem) = utc_civil(t);
+    let days = t
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400;
+    format!(
+        "{}, {d:02} {} {y} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        MONTHS[m as usize - 1],
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// 解析 [`rfc1123_date`] 格式的时间, 返回 unix 时间戳 (秒). 不检查星期
+#[cfg(feature = "reqwest")]
+pub(crate) fn parse_rfc1123_date(s: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let mut it = s.split_whitespace().skip(1);
+    let d: i64 = it.next()?.parse().ok()?;
+    let m = it.next()?;
+    let m = MONTHS.iter().position(|&v| v == m)? as i64 + 1;
+    let y: i64 = it.next()?.parse().ok()?;
+    let mut hms = it.next()?.split(':').map(|v| v.parse::<i64>().ok());
+    let (hh, mm, ss) = (hms.next()??, hms.next()??, hms.next()??);
+    if it.next() != Some("GMT") || !(1..=31).contains(&d) {
+        return None;
+    }
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u64::try_from(days * 86400 + hh * 3600 + mm * 60 + ss).ok()
+}
+
 #[cfg(feature = "tokio")]
 #[async_trait::async_trait]
 pub trait AsyncSource: Send + Sync {
@@ -110,33 +669,110 @@ pub trait SyncSource {
     fn fetch(&self) -> Result<Vec<u8>, FetchError>;
 }
 
+#[cfg(feature = "journal")]
+pub(crate) fn cache_decision(timeout: Option<bool>) -> &'static str {
+    match timeout {
+        Some(false) => "hit",
+        Some(true) => "expired",
+        None => "miss",
+    }
+}
+
 #[cfg(feature = "tokio")]
 pub async fn fetch_with_cache_async(
     fc: &FileCache,
     s: &dyn AsyncSource,
 ) -> Result<Vec<u8>, FetchError> {
-    if fc.is_cache_timeout()?.is_some_and(|timeout| !timeout) {
-        fc.read_cache_file_async().await
-    } else {
-        let d = s.fetch_async().await?;
-        if fc.cache_file_path.is_some() {
-            fc.write_cache_file_async(&d).await;
+    fetch_with_cache_from_async(fc, s, &SourceInfo::default()).await
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) async fn fetch_with_cache_from_async(
+    fc: &FileCache,
+    s: &dyn AsyncSource,
+    info: &SourceInfo<'_>,
+) -> Result<Vec<u8>, FetchError> {
+    let timeout = fc.is_cache_timeout()?;
+    #[cfg(feature = "journal")]
+    journal::record_cache(fc, cache_decision(timeout));
+    if timeout.is_some_and(|timeout| !timeout) {
+        match fc.read_cache_file_async().await {
+            Err(e) if e.is_cache_corrupt() => {}
+            r => return r,
         }
-        Ok(d)
     }
+    let d = match s.fetch_async().await {
+        Ok(d) => d,
+        Err(e) => return fc.fallback_on_error(e),
+    };
+    if fc.cache_file_path.is_some() {
+        fc.write_cache_file_from_async(&d, info).await;
+    }
+    Ok(d)
 }
 pub fn fetch_with_cache(fc: &FileCache, s: &dyn SyncSource) -> Result<Vec<u8>, FetchError> {
-    if fc.is_cache_timeout()?.is_some_and(|timeout| !timeout) {
-        fc.read_cache_file()
-    } else {
-        let d = s.fetch()?;
-        if fc.cache_file_path.is_some() {
-            fc.write_cache_file(&d);
+    fetch_with_cache_from(fc, s, &SourceInfo::default())
+}
+
+/// 与 [`fetch_with_cache`] 相同, 写入缓存时同时记录 `info`
+pub(crate) fn fetch_with_cache_from(
+    fc: &FileCache,
+    s: &dyn SyncSource,
+    info: &SourceInfo,
+) -> Result<Vec<u8>, FetchError> {
+    let timeout = fc.is_cache_timeout()?;
+    #[cfg(feature = "journal")]
+    journal::record_cache(fc, cache_decision(timeout));
+    if timeout.is_some_and(|timeout| !timeout) {
+        match fc.read_cache_file() {
+            Err(e) if e.is_cache_corrupt() => {}
+            r => return r,
+        }
+    }
+    let d = match s.fetch() {
+        Ok(d) => d,
+        Err(e) => return fc.fallback_on_error(e),
+    };
+    if fc.cache_file_path.is_some() {
+        fc.write_cache_file_from(&d, info);
+    }
+    Ok(d)
+}
+
+/// 读到的文件内容, 以及可能的路径, content type 和 content encoding
+#[derive(Debug, Clone, Default)]
+pub struct FileContent {
+    pub data: Vec<u8>,
+    pub path: Option<String>,
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    /// 条目和来源的标签, 见 [`FileMeta::tags`] 和 [`tags::TaggedSource`]
+    pub tags: Vec<(String, String)>,
+}
+
+impl From<(Vec<u8>, Option<String>)> for FileContent {
+    fn from((data, path): (Vec<u8>, Option<String>)) -> Self {
+        Self {
+            data,
+            path,
+            content_type: None,
+            content_encoding: None,
+            tags: Vec::new(),
         }
-        Ok(d)
     }
 }
 
+/// 单次请求的上下文, 自定义来源可以据此为不同请求返回不同的文件,
+/// 如为法语用户提供 `fr/` 下的文件
+#[derive(Debug, Clone, Default)]
+pub struct FetchContext {
+    pub tenant: Option<String>,
+    pub locale: Option<String>,
+    /// 按优先级排列的可接受语言, 如来自 Accept-Language, 见 [`locale::LocaleSource`]
+    pub languages: Vec<String>,
+    pub trace_id: Option<String>,
+}
+
 #[cfg(feature = "tokio")]
 #[async_trait::async_trait]
 pub trait AsyncFolderSource: std::fmt::Debug {
@@ -144,6 +780,41 @@ pub trait AsyncFolderSource: std::fmt::Debug {
         &self,
         file_name: &std::path::Path,
     ) -> Result<(Vec<u8>, Option<String>), FetchError>;
+
+    /// 与 get_file_content_async 相同, 来源知道 content type 时 (如 http 响应) 会一并返回
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &std::path::Path,
+    ) -> Result<FileContent, FetchError>
+    where
+        Self: Sync,
+    {
+        self.get_file_content_async(file_name)
+            .await
+            .map(FileContent::from)
+    }
+
+    /// 与 get_file_content_typed_async 相同, 带有请求上下文. 默认忽略上下文
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &std::path::Path,
+        _ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError>
+    where
+        Self: Sync,
+    {
+        self.get_file_content_typed_async(file_name).await
+    }
+}
+
+/// [`SyncFolderSource::list_entries`] 返回的一项, 来源不知道的字段为 None
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListEntry {
+    pub name: String,
+    pub size: Option<u64>,
+    /// unix 时间戳 (秒)
+    pub mtime: Option<u64>,
+    pub etag: Option<String>,
 }
 
 pub trait SyncFolderSource: std::fmt::Debug {
@@ -151,9 +822,48 @@ pub trait SyncFolderSource: std::fmt::Debug {
         &self,
         file_name: &std::path::Path,
     ) -> Result<(Vec<u8>, Option<String>), FetchError>;
+
+    /// 与 get_file_content 相同, 来源知道 content type 时 (如 http 响应) 会一并返回
+    fn get_file_content_typed(
+        &self,
+        file_name: &std::path::Path,
+    ) -> Result<FileContent, FetchError> {
+        self.get_file_content(file_name).map(FileContent::from)
+    }
+
+    /// 与 get_file_content_typed 相同, 带有请求上下文. 默认忽略上下文
+    fn get_file_content_ctx(
+        &self,
+        file_name: &std::path::Path,
+        _ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        self.get_file_content_typed(file_name)
+    }
+
+    /// 列出所有可读取的文件名 (以 `/` 分隔), 不支持列出时返回 [`FetchError::Unsupported`]
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        Err(FetchError::Unsupported("list_files"))
+    }
+
+    /// 与 list_files 相同, 同时返回列出时已知的大小, mtime 和 etag, 默认都为 None
+    fn list_entries(&self) -> Result<Vec<ListEntry>, FetchError> {
+        Ok(self
+            .list_files()?
+            .into_iter()
+            .map(|name| ListEntry {
+                name,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    /// 来源的能力, 默认只可读. 实现了 list_files 的来源应同时返回 listable
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY
+    }
 }
 
-#[cfg(feature = "tar")]
+/// 未开启 tar feature 时仍然存在, 读取时返回 [`FetchError::FeatureDisabled`]
 #[derive(Clone, Debug, Default)]
 pub struct TarFile(pub String);
 
@@ -163,9 +873,79 @@ impl SyncFolderSource for TarFile {
         &self,
         file_name: &std::path::Path,
     ) -> Result<(Vec<u8>, Option<String>), FetchError> {
-        let f = std::fs::File::open(&self.0)?;
-        get_file_from_tar_by_reader(file_name, f)
+        get_file_from_tar_by_reader(file_name, open_tar_file(&self.0)?)
+    }
+}
+
+/// 压缩的 tar 或缓存解压后最多的字节数, 防止很小的压缩包展开后耗尽内存
+pub const MAX_DECOMPRESSED_SIZE: u64 = 1 << 30;
+
+/// 读完解压流, 超过 `limit` 字节时返回 [`ArchiveError::TooLarge`]
+#[cfg(any(feature = "flate2", feature = "zstd"))]
+pub(crate) fn read_decompressed(
+    r: impl std::io::Read,
+    format: &'static str,
+    limit: u64,
+) -> Result<Vec<u8>, FetchError> {
+    use std::io::Read;
+    let mut out = Vec::new();
+    r.take(limit.saturating_add(1))
+        .read_to_end(&mut out)
+        .map_err(|e| ArchiveError::Decompress {
+            format,
+            reason: e.to_string(),
+        })?;
+    if out.len() as u64 > limit {
+        return Err(ArchiveError::TooLarge { format, limit }.into());
+    }
+    Ok(out)
+}
+
+/// 开启 flate2 / zstd feature 时, 压缩的 tar 数据按开头的字节识别并先解压, 否则直接借用.
+/// 解压后的大小不超过 [`MAX_DECOMPRESSED_SIZE`]
+#[cfg(feature = "tar")]
+pub fn decompress_tar(d: &[u8]) -> Result<Cow<'_, [u8]>, FetchError> {
+    #[cfg(feature = "flate2")]
+    if gzip::is_gzip(d) {
+        return gzip::gunzip(d).map(Cow::Owned);
+    }
+    #[cfg(feature = "zstd")]
+    if zstd::is_zstd(d) {
+        return zstd::decompress(d).map(Cow::Owned);
+    }
+    Ok(Cow::Borrowed(d))
+}
+
+/// 根据开头的字节判断 tar 数据是否需要解压
+#[cfg(feature = "tar")]
+fn is_compressed_tar(_magic: &[u8]) -> bool {
+    #[cfg(feature = "flate2")]
+    if gzip::is_gzip(_magic) {
+        return true;
+    }
+    #[cfg(feature = "zstd")]
+    if zstd::is_zstd(_magic) {
+        return true;
+    }
+    false
+}
+
+/// 打开 tar 文件, 压缩的文件会整个读入内存并解压
+#[cfg(feature = "tar")]
+pub(crate) fn open_tar_file(p: &str) -> Result<Box<dyn std::io::Read>, FetchError> {
+    use std::io::{Read, Seek};
+    let mut f = std::fs::File::open(p).map_err(|e| local_io_error(e, Path::new(p)))?;
+    let mut magic = Vec::with_capacity(4);
+    (&mut f).take(4).read_to_end(&mut magic)?;
+    f.rewind()?;
+    if !is_compressed_tar(&magic) {
+        return Ok(Box::new(f));
     }
+    let mut d = Vec::new();
+    f.read_to_end(&mut d)?;
+    Ok(Box::new(std::io::Cursor::new(
+        decompress_tar(&d)?.into_owned(),
+    )))
 }
 #[cfg(feature = "tokio-tar")]
 #[async_trait::async_trait]
@@ -174,11 +954,163 @@ impl AsyncFolderSource for TarFile {
         &self,
         file_name: &std::path::Path,
     ) -> Result<(Vec<u8>, Option<String>), FetchError> {
-        let f = tokio::fs::File::open(&self.0).await?;
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+        let mut f = tokio::fs::File::open(&self.0)
+            .await
+            .map_err(|e| local_io_error(e, Path::new(&self.0)))?;
+        let mut magic = Vec::with_capacity(4);
+        (&mut f).take(4).read_to_end(&mut magic).await?;
+        f.rewind().await?;
+        if is_compressed_tar(&magic) {
+            let mut d = Vec::new();
+            f.read_to_end(&mut d).await?;
+            return get_file_from_tar_in_memory(file_name, &d);
+        }
         get_file_from_tar_by_reader_async(file_name, f).await
     }
 }
 
+/// 同时进行的上游请求数的上限, 同步和异步的请求共用同一个计数.
+///
+/// 同步的调用方在 [`Condvar`](std::sync::Condvar) 上等待, 不会在 tokio 的 worker 线程上
+/// `block_on`, 所以在 current_thread 运行时中也不会死锁
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct FetchLimiter {
+    sem: std::sync::Arc<tokio::sync::Semaphore>,
+    lock: std::sync::Mutex<()>,
+    released: std::sync::Condvar,
+}
+
+/// [`FetchLimiter`] 的一个许可, drop 时归还并唤醒等待中的同步调用方
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct FetchPermit {
+    permit: Option<tokio::sync::OwnedSemaphorePermit>,
+    limiter: std::sync::Arc<FetchLimiter>,
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for FetchPermit {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        let _g = self.limiter.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.limiter.released.notify_all();
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl FetchLimiter {
+    pub fn new(n: usize) -> std::sync::Arc<Self> {
+        std::sync::Arc::new(Self {
+            sem: std::sync::Arc::new(tokio::sync::Semaphore::new(n)),
+            lock: std::sync::Mutex::new(()),
+            released: std::sync::Condvar::new(),
+        })
+    }
+
+    pub async fn acquire(self: &std::sync::Arc<Self>) -> FetchPermit {
+        let permit = self.sem.clone().acquire_owned().await.ok();
+        FetchPermit {
+            permit,
+            limiter: self.clone(),
+        }
+    }
+
+    /// 阻塞当前线程直到拿到许可
+    pub fn acquire_blocking(self: &std::sync::Arc<Self>) -> FetchPermit {
+        let mut g = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match self.sem.clone().try_acquire_owned() {
+                Ok(p) => {
+                    return FetchPermit {
+                        permit: Some(p),
+                        limiter: self.clone(),
+                    }
+                }
+                Err(tokio::sync::TryAcquireError::Closed) => {
+                    return FetchPermit {
+                        permit: None,
+                        limiter: self.clone(),
+                    }
+                }
+                Err(tokio::sync::TryAcquireError::NoPermits) => {
+                    g = self.released.wait(g).unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+static FETCH_LIMITER: std::sync::RwLock<Option<std::sync::Arc<FetchLimiter>>> =
+    std::sync::RwLock::new(None);
+
+/// 限制同时进行的上游 http 请求数 (所有 HttpSource 共享), None 表示不限制.
+///
+/// 避免 file server 在短时间内收到大量未缓存的请求时, 同时打开过多上游连接
+#[cfg(feature = "tokio")]
+pub fn set_max_concurrent_fetches(n: Option<usize>) {
+    let mut l = FETCH_LIMITER.write().unwrap_or_else(|e| e.into_inner());
+    *l = n.map(FetchLimiter::new);
+}
+
+#[cfg(feature = "tokio")]
+fn fetch_limiter() -> Option<std::sync::Arc<FetchLimiter>> {
+    FETCH_LIMITER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// 按 [`set_max_concurrent_fetches`] 的设置获取一个许可, 不限制时返回 None
+#[cfg(feature = "tokio")]
+pub async fn acquire_fetch_permit() -> Option<FetchPermit> {
+    Some(fetch_limiter()?.acquire().await)
+}
+
+/// 与 [`acquire_fetch_permit`] 相同, 供同步的请求使用
+#[cfg(feature = "tokio")]
+pub fn acquire_fetch_permit_blocking() -> Option<FetchPermit> {
+    Some(fetch_limiter()?.acquire_blocking())
+}
+
+/// 未开启 reqwest feature 时的占位, 只有基本字段, 读取时返回 [`FetchError::FeatureDisabled`].
+/// 这样构造 [`SingleFileSource::Http`] 的代码在不同 feature 组合下都能编译
+#[cfg(not(feature = "reqwest"))]
+#[derive(Clone, Debug, Default)]
+pub struct HttpSource {
+    pub url: String,
+    pub proxy: Option<String>,
+    pub custom_request_headers: Option<Vec<(String, String)>>,
+    pub should_use_proxy: bool,
+    pub size_limit_bytes: Option<usize>,
+}
+
+impl HttpSource {
+    /// `file://` url 对应的本地路径. 读取这样的 HttpSource 时直接读本地文件, 不经过缓存.
+    /// 只支持本机 (主机名为空或 `localhost`), 其它 url 返回 None
+    pub fn local_path(&self) -> Option<PathBuf> {
+        if !self.url.get(..7)?.eq_ignore_ascii_case("file://") {
+            return None;
+        }
+        let rest = &self.url[7..];
+        let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+        if !rest.starts_with('/') {
+            return None;
+        }
+        let rest = rest.split(['?', '#']).next().unwrap_or_default();
+        let p = String::from_utf8(hex::percent_decode(rest)).ok()?;
+        // file:///C:/a 对应 C:/a
+        #[cfg(windows)]
+        let p = match p.as_bytes() {
+            [b'/', d, b':', ..] if d.is_ascii_alphabetic() => p[1..].to_string(),
+            _ => p,
+        };
+        Some(PathBuf::from(p))
+    }
+}
+
 #[cfg(feature = "reqwest")]
 #[derive(Clone, Debug, Default)]
 pub struct HttpSource {
@@ -187,6 +1119,10 @@ pub struct HttpSource {
     pub custom_request_headers: Option<Vec<(String, String)>>,
     pub should_use_proxy: bool,
     pub size_limit_bytes: Option<usize>,
+    /// 按主机名选择代理, 优先于 `proxy` 和 `should_use_proxy`, 见 [`proxy::ProxyRule`]
+    pub proxy_rules: Vec<proxy::ProxyRule>,
+    /// 为 None 时使用 [`dns::set_default_dns_resolver`] 设置的解析器或系统解析器
+    pub dns_resolver: Option<dns::DnsResolver>,
 }
 
 #[cfg(feature = "reqwest")]
@@ -215,9 +1151,55 @@ impl HttpSource {
     }
 }
 #[cfg(feature = "reqwest")]
-impl SyncSource for HttpSource {
-    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
-        let mut cb = reqwest::blocking::ClientBuilder::new();
+impl HttpSource {
+    /// 缓存文件名: 对 url 加一个换行, 再按名称 (小写) 排序后逐个加上
+    /// `name: value` 和换行得到的字符串, 取 64 位 FNV-1a 哈希的 16 位十六进制表示.
+    /// 所有 `custom_request_headers` 都参与计算
+    pub fn cache_key(&self) -> String {
+        let mut headers: Vec<(String, &str)> = self
+            .custom_request_headers
+            .iter()
+            .flatten()
+            .map(|(k, v)| (k.to_ascii_lowercase(), v.as_str()))
+            .collect();
+        headers.sort();
+        url_cache_key(&self.url, &headers)
+    }
+
+    /// 与 fetch 相同, 同时返回响应的 Content-Type
+    pub fn fetch_typed(&self) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        if let Some(p) = self.local_path() {
+            return Ok((read_local_file(&p)?, None));
+        }
+        if let Some((sock, path)) = unix_socket::parse_unix_url(&self.url) {
+            return unix_socket::fetch(self, &sock, &path);
+        }
+        if let Some(s) = self.apply_proxy_rules() {
+            return s.fetch_typed();
+        }
+        #[cfg(feature = "tokio")]
+        let _permit = acquire_fetch_permit_blocking();
+
+        #[cfg(feature = "journal")]
+        let started = std::time::Instant::now();
+        let r = self.send().and_then(|r| {
+            let status = r.status().as_u16();
+            let ct = content_type_of(r.headers());
+            Ok((read_body(r)?, ct, status))
+        });
+        #[cfg(feature = "journal")]
+        journal::record_fetch(
+            &self.url,
+            r.as_ref().ok().map(|r| r.2),
+            r.as_ref().map(|r| r.0.as_slice()),
+            started,
+        );
+        r.map(|(v, ct, _)| (v, ct))
+    }
+
+    /// 发出请求 (必要时回退到代理) 并检查 Content-Length, 不处理 proxy_rules
+    pub(crate) fn send(&self) -> Result<reqwest::blocking::Response, FetchError> {
+        let mut cb = self.blocking_client_builder();
         if self.should_use_proxy {
             cb = self.set_proxy(cb)?;
         }
@@ -227,26 +1209,89 @@ impl SyncSource for HttpSource {
             Ok(r) => r,
             Err(e) => {
                 if !self.should_use_proxy && self.proxy.is_some() {
-                    let mut cb = reqwest::blocking::ClientBuilder::new();
+                    let mut cb = self.blocking_client_builder();
                     cb = self.set_proxy(cb)?;
                     let c = cb.build()?;
                     self.get(c)?
                 } else {
-                    return Err(FetchError::R(e));
+                    return Err(e.into());
                 }
             }
         };
         if let Some(sl) = self.size_limit_bytes {
             if let Some(s) = r.content_length() {
                 if s as usize > sl {
-                    return Err(FetchError::S);
+                    return Err(HttpError::SizeLimit {
+                        url: self.url.clone(),
+                        size: s,
+                        limit: sl,
+                    }
+                    .into());
                 }
             }
         }
-        let b = r.bytes()?;
-        let v = b.to_vec();
+        Ok(r)
+    }
+}
 
-        Ok(v)
+/// 读取的响应体短于 Content-Length 时 (连接中途断开) 返回 [`HttpError::Truncated`],
+/// 不把不完整的内容当作结果
+#[cfg(feature = "reqwest")]
+fn check_body_length<E: Into<FetchError>>(
+    url: &str,
+    expected: Option<u64>,
+    received: usize,
+    err: Option<E>,
+) -> Result<(), FetchError> {
+    match expected {
+        Some(expected) if (received as u64) < expected => Err(HttpError::Truncated {
+            url: url.to_string(),
+            expected,
+            received: received as u64,
+        }
+        .into()),
+        _ => err.map_or(Ok(()), |e| Err(e.into())),
+    }
+}
+
+#[cfg(feature = "reqwest")]
+pub(crate) fn read_body(mut r: reqwest::blocking::Response) -> Result<Vec<u8>, FetchError> {
+    use std::io::Read;
+    let expected = r.content_length();
+    let mut d = Vec::new();
+    let err = r.read_to_end(&mut d).err();
+    check_body_length(r.url().as_str(), expected, d.len(), err)?;
+    Ok(d)
+}
+
+#[cfg(feature = "tokio")]
+#[cfg(feature = "reqwest")]
+pub(crate) async fn read_body_async(mut r: reqwest::Response) -> Result<Vec<u8>, FetchError> {
+    let expected = r.content_length();
+    let mut d = Vec::new();
+    let err = loop {
+        match r.chunk().await {
+            Ok(Some(c)) => d.extend_from_slice(&c),
+            Ok(None) => break None,
+            Err(e) => break Some(e),
+        }
+    };
+    check_body_length(r.url().as_str(), expected, d.len(), err)?;
+    Ok(d)
+}
+
+#[cfg(feature = "reqwest")]
+fn content_type_of(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+#[cfg(feature = "reqwest")]
+impl SyncSource for HttpSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.fetch_typed().map(|r| r.0)
     }
 }
 
@@ -276,10 +1321,43 @@ impl HttpSource {
 
 #[cfg(feature = "tokio")]
 #[cfg(feature = "reqwest")]
-#[async_trait::async_trait]
-impl AsyncSource for HttpSource {
-    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
-        let client_builder = reqwest::ClientBuilder::new();
+impl HttpSource {
+    /// 与 fetch_async 相同, 同时返回响应的 Content-Type
+    pub async fn fetch_typed_async(&self) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        if let Some(p) = self.local_path() {
+            return Ok((read_local_file_async(&p).await?, None));
+        }
+        if let Some((sock, path)) = unix_socket::parse_unix_url(&self.url) {
+            return unix_socket::fetch_async(self, &sock, &path).await;
+        }
+        if let Some(s) = self.apply_proxy_rules() {
+            return Box::pin(s.fetch_typed_async()).await;
+        }
+        let _permit = acquire_fetch_permit().await;
+
+        #[cfg(feature = "journal")]
+        let started = std::time::Instant::now();
+        let r = async {
+            let response = self.send_async().await?;
+            let status = response.status().as_u16();
+            let ct = content_type_of(response.headers());
+            let bytes = read_body_async(response).await?;
+            Ok::<_, FetchError>((bytes, ct, status))
+        }
+        .await;
+        #[cfg(feature = "journal")]
+        journal::record_fetch(
+            &self.url,
+            r.as_ref().ok().map(|r| r.2),
+            r.as_ref().map(|r| r.0.as_slice()),
+            started,
+        );
+        r.map(|(v, ct, _)| (v, ct))
+    }
+
+    /// 发出请求 (必要时回退到代理) 并检查 Content-Length, 不处理 proxy_rules
+    pub(crate) async fn send_async(&self) -> Result<reqwest::Response, FetchError> {
+        let client_builder = self.async_client_builder();
         let client_builder = if self.should_use_proxy {
             self.set_proxy_async(client_builder)?
         } else {
@@ -292,26 +1370,37 @@ impl AsyncSource for HttpSource {
             Ok(r) => r,
             Err(e) => {
                 if !self.should_use_proxy && self.proxy.is_some() {
-                    let mut cb = reqwest::ClientBuilder::new();
+                    let mut cb = self.async_client_builder();
                     cb = self.set_proxy_async(cb)?;
                     let c = cb.build()?;
                     self.get_async(c).await?
                 } else {
-                    return Err(FetchError::R(e));
+                    return Err(e.into());
                 }
             }
         };
         if let Some(size_limit) = self.size_limit_bytes {
             if let Some(content_length) = response.content_length() {
                 if content_length as usize > size_limit {
-                    return Err(FetchError::S);
+                    return Err(HttpError::SizeLimit {
+                        url: self.url.clone(),
+                        size: content_length,
+                        limit: size_limit,
+                    }
+                    .into());
                 }
             }
         }
+        Ok(response)
+    }
+}
 
-        let bytes = response.bytes().await?.to_vec();
-
-        Ok(bytes)
+#[cfg(feature = "tokio")]
+#[cfg(feature = "reqwest")]
+#[async_trait::async_trait]
+impl AsyncSource for HttpSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        self.fetch_typed_async().await.map(|r| r.0)
     }
 }
 
@@ -321,12 +1410,69 @@ pub trait GetPath {
     }
 }
 
+/// 为 FileMap 中的条目显式指定的 content type 和 content encoding
+#[derive(Debug, Clone, Default)]
+pub struct FileMeta {
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    /// 如 `("team", "infra")`, 读取时带到 [`FileContent::tags`] 和 [`tags::stats`] 中
+    pub tags: Vec<(String, String)>,
+}
+
+impl FileMeta {
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
 #[derive(Debug)]
 pub enum SingleFileSource {
-    #[cfg(feature = "reqwest")]
+    /// 未开启 reqwest feature 时读取返回 [`FetchError::FeatureDisabled`]
     Http(HttpSource, FileCache),
     FilePath(String),
     Inline(Vec<u8>),
+    /// `data:` URI, 读取时解码, 见 [`data_uri`]
+    DataUri(String),
+    /// 标准 base64 编码的内容, 读取时解码, 忽略其中的空白. 用于在 TOML/JSON 配置中写入二进制数据
+    InlineBase64(String),
+    /// 环境变量的值, 读取时才获取, 变量不存在时返回 NotFound
+    Env(String),
+    /// 带有显式 content type / encoding 的来源, 如没有扩展名的 `geoip`
+    Annotated(Box<SingleFileSource>, FileMeta),
+    /// 读取时才生成的内容, 可以像 Http 一样用 FileCache 缓存
+    Generated(generated::Generator, FileCache),
+    /// 多个来源拼接成的文件
+    Concat(concat::ConcatSource),
+    /// 多个 JSON 文件深度合并后的结果
+    #[cfg(feature = "json_merge")]
+    JsonMerge(json_merge::JsonMergeSource),
+    /// 内层来源中的一部分, 如 JSON 中的一节
+    #[cfg(feature = "extract")]
+    Extract(extract::ExtractSource),
+    /// 读到的内容需通过检查, 否则使用旧缓存或返回 [`FetchError::Invalid`]
+    Validated(Box<SingleFileSource>, validator::Validator),
+    /// S3 兼容存储中的对象, 可以像 Http 一样用 FileCache 缓存
+    #[cfg(feature = "s3")]
+    S3(s3::S3Source, FileCache),
+    /// Azure Blob Storage 中的 blob, 可以像 Http 一样用 FileCache 缓存
+    #[cfg(feature = "azure")]
+    AzureBlob(azure::AzureBlobSource, FileCache),
+    /// Google Cloud Storage 中的对象, 可以像 Http 一样用 FileCache 缓存
+    #[cfg(feature = "gcs")]
+    Gcs(gcs::GcsSource, FileCache),
+    /// 系统密钥库中的值, 不会缓存到磁盘
+    #[cfg(feature = "keyring")]
+    Keyring(keyring::KeyringEntry),
+    /// 域名的 TXT 记录, 不会缓存到磁盘
+    #[cfg(feature = "dns_txt")]
+    DnsTxt(dns_txt::DnsTxtSource),
+    /// 读取时解密的来源
+    #[cfg(feature = "encrypted")]
+    Encrypted(encrypted::EncryptedSource),
+    /// 通过 SFTP 读取的远程文件
+    #[cfg(feature = "sftp")]
+    Sftp(sftp::SftpSource),
 }
 impl Default for SingleFileSource {
     fn default() -> Self {
@@ -334,13 +1480,75 @@ impl Default for SingleFileSource {
     }
 }
 
+impl SingleFileSource {
+    pub fn with_meta(self, meta: FileMeta) -> Self {
+        Self::Annotated(Box::new(self), meta)
+    }
+
+    pub fn meta(&self) -> Option<&FileMeta> {
+        match self {
+            SingleFileSource::Annotated(_, m) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Inline 条目的数据
+    pub fn inline_data(&self) -> Option<&[u8]> {
+        match self {
+            SingleFileSource::Inline(v) => Some(v),
+            SingleFileSource::Annotated(s, _) => s.inline_data(),
+            _ => None,
+        }
+    }
+
+    fn file_content(&self, data: Vec<u8>) -> FileContent {
+        let mut fc = FileContent::from((data, self.get_path()));
+        if let Some(m) = self.meta() {
+            fc.content_type.clone_from(&m.content_type);
+            fc.content_encoding.clone_from(&m.content_encoding);
+            fc.tags.clone_from(&m.tags);
+        } else if let SingleFileSource::DataUri(u) = self {
+            fc.content_type = data_uri::parse(u).ok().and_then(|d| d.media_type);
+        }
+        fc
+    }
+}
+
 impl GetPath for SingleFileSource {
     fn get_path(&self) -> Option<String> {
         match self {
-            #[cfg(feature = "reqwest")]
             SingleFileSource::Http(http_source, _fc) => Some(http_source.url.clone()),
             SingleFileSource::FilePath(p) => Some(p.clone()),
             SingleFileSource::Inline(_ec) => None,
+            SingleFileSource::DataUri(_) => None,
+            SingleFileSource::InlineBase64(_) => None,
+            SingleFileSource::Env(name) => Some(format!("env://{name}")),
+            SingleFileSource::Annotated(s, _) => s.get_path(),
+            SingleFileSource::Generated(..) => None,
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(s, _) => Some(format!("s3://{}/{}", s.bucket, s.key)),
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(s, _) => Some(s.url()),
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(s, _) => Some(format!("gs://{}/{}", s.bucket, s.object)),
+            #[cfg(feature = "keyring")]
+            SingleFileSource::Keyring(_) => None,
+            #[cfg(feature = "dns_txt")]
+            SingleFileSource::DnsTxt(s) => Some(format!("dns-txt://{}", s.name)),
+            #[cfg(feature = "sftp")]
+            SingleFileSource::Sftp(s) => Some(format!(
+                "sftp://{}/{}",
+                s.remote.host,
+                s.path.trim_start_matches('/')
+            )),
+            #[cfg(feature = "encrypted")]
+            SingleFileSource::Encrypted(e) => e.path(),
+            SingleFileSource::Concat(_) => None,
+            #[cfg(feature = "json_merge")]
+            SingleFileSource::JsonMerge(_) => None,
+            #[cfg(feature = "extract")]
+            SingleFileSource::Extract(e) => e.inner.get_path(),
+            SingleFileSource::Validated(s, _) => s.get_path(),
         }
     }
 }
@@ -349,34 +1557,312 @@ impl GetPath for SingleFileSource {
 #[async_trait::async_trait]
 impl AsyncSource for SingleFileSource {
     async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        #[cfg(feature = "reqwest")]
+        if let Some(d) = self.fetch_stale_async().await {
+            return Ok(d);
+        }
         match self {
+            SingleFileSource::Http(http_source, _) if http_source.local_path().is_some() => {
+                read_local_file_async(&http_source.local_path().unwrap()).await
+            }
             #[cfg(feature = "reqwest")]
             SingleFileSource::Http(http_source, fc) => {
-                fetch_with_cache_async(fc, http_source).await
+                revalidate::fetch_http_with_cache_async(&self.resolve_cache(fc), http_source).await
             }
-            SingleFileSource::FilePath(f) => {
-                let s: Vec<u8> = tokio::fs::read(f).await?;
-                Ok(s)
+            #[cfg(not(feature = "reqwest"))]
+            SingleFileSource::Http(..) => Err(FetchError::FeatureDisabled("reqwest")),
+            SingleFileSource::FilePath(f) => read_local_file_async(Path::new(f)).await,
+            SingleFileSource::Inline(v) => Ok(v.clone()),
+            SingleFileSource::DataUri(u) => data_uri::parse(u).map(|d| d.data),
+            SingleFileSource::InlineBase64(s) => decode_inline_base64(s),
+            SingleFileSource::Env(name) => read_env(name),
+            SingleFileSource::Annotated(s, _) => s.fetch_async().await,
+            SingleFileSource::Generated(g, fc) => fetch_with_cache_async(fc, g).await,
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(s, fc) => {
+                let url = s.url();
+                fetch_with_cache_from_async(&self.resolve_cache(fc), s, &SourceInfo::url(&url))
+                    .await
+            }
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(s, fc) => {
+                let url = s.url();
+                fetch_with_cache_from_async(&self.resolve_cache(fc), s, &SourceInfo::url(&url))
+                    .await
+            }
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(s, fc) => {
+                let url = s.url();
+                fetch_with_cache_from_async(&self.resolve_cache(fc), s, &SourceInfo::url(&url))
+                    .await
+            }
+            #[cfg(feature = "keyring")]
+            SingleFileSource::Keyring(k) => k.fetch_async().await,
+            #[cfg(feature = "dns_txt")]
+            SingleFileSource::DnsTxt(s) => s.fetch_async().await,
+            #[cfg(feature = "sftp")]
+            SingleFileSource::Sftp(s) => s.fetch_async().await,
+            #[cfg(feature = "encrypted")]
+            SingleFileSource::Encrypted(e) => e.fetch_async().await,
+            SingleFileSource::Concat(c) => c.fetch_async().await,
+            #[cfg(feature = "json_merge")]
+            SingleFileSource::JsonMerge(m) => m.fetch_async().await,
+            #[cfg(feature = "extract")]
+            SingleFileSource::Extract(e) => e.fetch_async().await,
+            SingleFileSource::Validated(s, v) => s.fetch_validated_async(v).await,
+        }
+    }
+}
+
+impl SyncSource for SingleFileSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        match self {
+            SingleFileSource::Http(http_source, _) if http_source.local_path().is_some() => {
+                read_local_file(&http_source.local_path().unwrap())
+            }
+            #[cfg(feature = "reqwest")]
+            SingleFileSource::Http(http_source, fc) => {
+                revalidate::fetch_http_with_cache(&self.resolve_cache(fc), http_source)
             }
+            #[cfg(not(feature = "reqwest"))]
+            SingleFileSource::Http(..) => Err(FetchError::FeatureDisabled("reqwest")),
+            SingleFileSource::FilePath(f) => read_local_file(Path::new(f)),
             SingleFileSource::Inline(v) => Ok(v.clone()),
+            SingleFileSource::DataUri(u) => data_uri::parse(u).map(|d| d.data),
+            SingleFileSource::InlineBase64(s) => decode_inline_base64(s),
+            SingleFileSource::Env(name) => read_env(name),
+            SingleFileSource::Annotated(s, _) => s.fetch(),
+            SingleFileSource::Generated(g, fc) => fetch_with_cache(fc, g),
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(s, fc) => {
+                let url = s.url();
+                fetch_with_cache_from(&self.resolve_cache(fc), s, &SourceInfo::url(&url))
+            }
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(s, fc) => {
+                let url = s.url();
+                fetch_with_cache_from(&self.resolve_cache(fc), s, &SourceInfo::url(&url))
+            }
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(s, fc) => {
+                let url = s.url();
+                fetch_with_cache_from(&self.resolve_cache(fc), s, &SourceInfo::url(&url))
+            }
+            #[cfg(feature = "keyring")]
+            SingleFileSource::Keyring(k) => k.fetch(),
+            #[cfg(feature = "dns_txt")]
+            SingleFileSource::DnsTxt(s) => s.fetch(),
+            #[cfg(feature = "sftp")]
+            SingleFileSource::Sftp(s) => s.fetch(),
+            #[cfg(feature = "encrypted")]
+            SingleFileSource::Encrypted(e) => e.fetch(),
+            SingleFileSource::Concat(c) => c.fetch(),
+            #[cfg(feature = "json_merge")]
+            SingleFileSource::JsonMerge(m) => m.fetch(),
+            #[cfg(feature = "extract")]
+            SingleFileSource::Extract(e) => e.fetch(),
+            SingleFileSource::Validated(s, v) => s.fetch_validated(v),
+        }
+    }
+}
+
+/// 把本地文件操作的 io 错误转为 FetchError, 权限不足时返回 [`LookupError::Denied`],
+/// 是目录时返回 [`LookupError::IsDirectory`], 其它错误保留原来的 kind, 并在信息中加上路径
+pub fn local_io_error(e: io::Error, p: &Path) -> FetchError {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => {
+            FetchError::Lookup(LookupError::Denied(p.to_string_lossy().to_string()))
+        }
+        io::ErrorKind::IsADirectory => {
+            FetchError::Lookup(LookupError::IsDirectory(p.to_string_lossy().to_string()))
+        }
+        k => FetchError::I(io::Error::new(k, format!("{}: {e}", p.display()))),
+    }
+}
+
+/// 文件的 mtime, unix 时间戳 (秒)
+pub(crate) fn unix_mtime(m: &std::fs::Metadata) -> Option<u64> {
+    let t = m.modified().ok()?;
+    Some(t.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs())
+}
+
+pub(crate) fn decode_inline_base64(s: &str) -> Result<Vec<u8>, FetchError> {
+    let s: String = s.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    base64::decode(&s).ok_or_else(|| FetchError::Invalid("bad inline base64".to_string()))
+}
+
+/// 非 UTF-8 的值按 lossy 转换
+fn read_env(name: &str) -> Result<Vec<u8>, FetchError> {
+    std::env::var_os(name)
+        .map(|v| v.to_string_lossy().into_owned().into_bytes())
+        .ok_or_else(|| FetchError::not_found(name))
+}
+
+/// 读取本地文件, 路径是目录时返回 [`LookupError::IsDirectory`],
+/// 权限不足时 (包括所在目录不可访问) 返回 [`LookupError::Denied`].
+/// 不预先检查文件是否存在, 直接按读取时的 io 错误判断
+pub fn read_local_file(p: &Path) -> Result<Vec<u8>, FetchError> {
+    let lp = win_path::local_path(p);
+    std::fs::read(&lp).map_err(|e| read_error(e, p, &lp))
+}
+
+#[cfg(feature = "tokio")]
+pub async fn read_local_file_async(p: &Path) -> Result<Vec<u8>, FetchError> {
+    let lp = win_path::local_path(p);
+    tokio::fs::read(&lp)
+        .await
+        .map_err(|e| read_error(e, p, &lp))
+}
+
+/// Windows 上打开目录返回 PermissionDenied, 此时再检查是否为目录
+fn read_error(e: io::Error, p: &Path, lp: &Path) -> FetchError {
+    if e.kind() == io::ErrorKind::PermissionDenied && lp.is_dir() {
+        return FetchError::Lookup(LookupError::IsDirectory(p.to_string_lossy().to_string()));
+    }
+    local_io_error(e, p)
+}
+
+/// Folders 中的一个目录里没有这个文件, 应继续查找下一个目录
+fn is_folder_miss(e: &FetchError) -> bool {
+    match e {
+        FetchError::I(e) => matches!(
+            e.kind(),
+            io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+        ),
+        _ => false,
+    }
+}
+
+thread_local! {
+    /// 只在 [`DataSource::with_separator_retry`] 关闭重试的来源读取期间为 false
+    static SEPARATOR_RETRY: std::cell::Cell<bool> = const { std::cell::Cell::new(true) };
+}
+
+/// 读取期间关闭 `\` 的转换, drop 时恢复
+struct NoSeparatorRetryGuard(bool);
+
+impl NoSeparatorRetryGuard {
+    fn new() -> Self {
+        Self(SEPARATOR_RETRY.replace(false))
+    }
+}
+
+impl Drop for NoSeparatorRetryGuard {
+    fn drop(&mut self) {
+        SEPARATOR_RETRY.set(self.0);
+    }
+}
+
+/// 见 [`DataSource::with_separator_retry`]
+#[derive(Debug)]
+struct NoSeparatorRetry(DataSource);
+
+impl SyncFolderSource for NoSeparatorRetry {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let _g = NoSeparatorRetryGuard::new();
+        self.0.get_file_content(file_name)
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        let _g = NoSeparatorRetryGuard::new();
+        self.0.get_file_content_typed(file_name)
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let _g = NoSeparatorRetryGuard::new();
+        self.0.get_file_content_ctx(file_name, ctx)
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        SyncFolderSource::list_files(&self.0)
+    }
+
+    fn list_entries(&self) -> Result<Vec<ListEntry>, FetchError> {
+        self.0.list_entries()
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.0.capabilities()
+    }
+}
+
+/// 把路径中的 `\` 替换为 `/`, 这样 `certs\server.pem` 这样的配置在 Folders, tar 和 FileMap
+/// 中都能找到对应的文件. 这些来源先按原名称查找, 原名称找不到时才使用转换后的名称.
+/// 用 [`DataSource::with_separator_retry`] 关闭的来源读取时原样返回
+pub fn normalize_separators(p: &Path) -> Cow<'_, Path> {
+    let s = p.to_string_lossy();
+    if SEPARATOR_RETRY.with(|r| r.get()) && s.contains('\\') {
+        return Cow::Owned(PathBuf::from(s.replace('\\', "/")));
+    }
+    Cow::Borrowed(p)
+}
+
+/// 按 unicode 规范化的设置 (需要 unicode feature) 转换文件名
+#[cfg(feature = "unicode")]
+fn normalize_unicode(p: &Path) -> Cow<'_, Path> {
+    unicode::normalize_path(p)
+}
+
+#[cfg(not(feature = "unicode"))]
+fn normalize_unicode(p: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(p)
+}
+
+/// 处理分隔符和 unicode 规范化后的名称, 用于与 FileMap 和 tar 中的名称比较
+fn normalize_name(p: &Path) -> PathBuf {
+    normalize_unicode(&normalize_separators(p)).into_owned()
+}
+
+#[cfg(feature = "tar")]
+fn paths_match(a: &Path, b: &Path) -> bool {
+    a == b || normalize_name(a) == normalize_name(b)
+}
+
+/// Folders 中 `dir` 下 `file_name` 的路径. 规范化后的文件不存在时使用原名称.
+/// 名称中的 `\` 在非 Windows 系统上可能是文件名的一部分, 这样的文件存在时优先使用
+pub(crate) fn folder_path(dir: &Path, file_name: &Path) -> PathBuf {
+    let n = normalize_unicode(file_name);
+    if cfg!(not(windows)) && n.to_string_lossy().contains('\\') && n.is_relative() {
+        let literal = win_path::local_path(&dir.join(&n)).into_owned();
+        if literal.exists() {
+            return literal;
         }
     }
+    let p = win_path::join_local(dir, &n);
+    if matches!(n, Cow::Owned(_)) && !p.exists() {
+        return win_path::join_local(dir, file_name);
+    }
+    p
 }
 
-impl SyncSource for SingleFileSource {
-    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
-        match self {
-            #[cfg(feature = "reqwest")]
-            SingleFileSource::Http(http_source, fc) => fetch_with_cache(fc, http_source),
-            SingleFileSource::FilePath(f) => {
-                let s: Vec<u8> = std::fs::read(f)?;
-                Ok(s)
-            }
-            SingleFileSource::Inline(v) => Ok(v.clone()),
-        }
+/// StdReadFile 读取的路径, 与 [`folder_path`] 相同, 但不处理分隔符
+fn std_read_path(file_name: &Path) -> Cow<'_, Path> {
+    match normalize_unicode(file_name) {
+        Cow::Owned(p) if p.exists() => Cow::Owned(p),
+        _ => Cow::Borrowed(file_name),
     }
 }
 
+fn lookup_file_map<'a>(
+    map: &'a HashMap<String, SingleFileSource>,
+    file_name: &Path,
+) -> Option<&'a SingleFileSource> {
+    let key = file_name.to_string_lossy();
+    if let Some(sf) = map.get(key.as_ref()) {
+        return Some(sf);
+    }
+    let nk = normalize_name(file_name);
+    let nk = nk.to_string_lossy();
+    if nk == key {
+        return None;
+    }
+    map.get(nk.as_ref())
+}
+
 /// Defines where to get the content of the requested file name.
 ///
 /// 很多配置中 都要再加载其他外部文件,
@@ -388,21 +1874,50 @@ pub enum DataSource {
     StdReadFile,
     ///从指定的一组路径来寻找文件
     Folders(Vec<String>),
-    /// 从一个 已放到内存中的 tar 中 寻找文件
-    #[cfg(feature = "tar")]
+    /// 从一个 已放到内存中的 tar 中 寻找文件. 开启 flate2 / zstd feature 时也可以是
+    /// `.tar.gz` / `.tar.zst`. 未开启 tar feature 时读取返回 [`FetchError::FeatureDisabled`]
     TarInMemory(Vec<u8>),
-    #[cfg(feature = "tar")]
     TarFile(TarFile),
 
-    /// 与其它方式不同，FileMap 存储名称的映射表, 无需遍历目录
+    /// 与其它方式不同，FileMap 存储名称的映射表, 无需遍历目录.
+    /// 请求的名称找不到时按规范化后的名称 (`/` 分隔, 见 [`normalize_separators`]) 查找,
+    /// 因此键也应是规范化的形式, 可以用 [`DataSource::file_map`] 构造
     FileMap(HashMap<String, SingleFileSource>),
 
+    /// 编译时嵌入的文件, 读取时不复制, 见 [`embed_files!`]
+    Embedded(embedded::EmbeddedFiles),
+
     Sync(Box<dyn SyncFolderSource + Send + Sync>),
     #[cfg(feature = "tokio")]
     Async(Box<dyn AsyncFolderSource + Send + Sync>),
 }
 
 impl DataSource {
+    /// 构造 FileMap, 键按请求名称的规则规范化 (`\` 转为 `/`, 以及 unicode 规范化的设置)
+    pub fn file_map(entries: impl IntoIterator<Item = (String, SingleFileSource)>) -> Self {
+        DataSource::FileMap(
+            entries
+                .into_iter()
+                .map(|(k, v)| {
+                    (
+                        normalize_name(Path::new(&k)).to_string_lossy().to_string(),
+                        v,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// 是否在原名称找不到时把名称中的 `\` 视为 `/` 重试 (见 [`normalize_separators`]), 默认开启.
+    /// 关闭后 `a\b` 只匹配名称本身含有 `\` 的文件, 返回包装后的 [`DataSource::Sync`],
+    /// 其中的来源总是以同步的方式读取
+    pub fn with_separator_retry(self, enable: bool) -> Self {
+        if enable {
+            return self;
+        }
+        DataSource::Sync(Box::new(NoSeparatorRetry(self)))
+    }
+
     pub fn insert_current_working_dir(&mut self) -> io::Result<()> {
         if let DataSource::Folders(ref mut v) = self {
             v.push(std::env::current_dir()?.to_string_lossy().to_string())
@@ -417,10 +1932,97 @@ impl DataSource {
         let r = SyncFolderSource::get_file_content(self, file_name.as_ref())?;
         Ok(String::from_utf8_lossy(r.0.as_slice()).to_string())
     }
+
+    /// 与 get_file_content 相同, 但 FileMap 中的 Inline 条目和 TarInMemory 中的文件
+    /// 会直接借用已在内存中的数据, 不再复制
+    pub fn get_file_content_ref(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Cow<'_, [u8]>, Option<String>), FetchError> {
+        match self {
+            #[cfg(feature = "tar")]
+            DataSource::TarInMemory(tar_binary) => {
+                get_file_ref_from_tar_in_memory(file_name, tar_binary)
+            }
+            DataSource::FileMap(map) => match lookup_file_map(map, file_name) {
+                Some(sf) => match sf.inline_data() {
+                    Some(d) => Ok((Cow::Borrowed(d), sf.get_path())),
+                    None => sf.fetch().map(|d| (Cow::Owned(d), sf.get_path())),
+                },
+                None => Err(FetchError::not_found(file_name.to_string_lossy())),
+            },
+            DataSource::Embedded(e) => match e.get(file_name) {
+                Some((n, d)) => Ok((Cow::Borrowed(d), Some(n.to_string()))),
+                None => Err(FetchError::not_found(file_name.to_string_lossy())),
+            },
+            _ => self
+                .get_file_content(file_name)
+                .map(|(d, p)| (Cow::Owned(d), p)),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn get_file_content_ref_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Cow<'_, [u8]>, Option<String>), FetchError> {
+        match self {
+            #[cfg(feature = "tar")]
+            DataSource::TarInMemory(_) => self.get_file_content_ref(file_name),
+            DataSource::FileMap(map) => match lookup_file_map(map, file_name) {
+                Some(sf) => match sf.inline_data() {
+                    Some(d) => Ok((Cow::Borrowed(d), sf.get_path())),
+                    None => sf
+                        .fetch_async()
+                        .await
+                        .map(|d| (Cow::Owned(d), sf.get_path())),
+                },
+                None => Err(FetchError::not_found(file_name.to_string_lossy())),
+            },
+            _ => self
+                .get_file_content_async(file_name)
+                .await
+                .map(|(d, p)| (Cow::Owned(d), p)),
+        }
+    }
 }
 #[cfg(feature = "tokio")]
 #[async_trait::async_trait]
 impl AsyncFolderSource for DataSource {
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        match self {
+            DataSource::Async(source) => source.get_file_content_ctx_async(file_name, ctx).await,
+            DataSource::Sync(source) => source.get_file_content_ctx(file_name, ctx),
+            _ => self.get_file_content_typed_async(file_name).await,
+        }
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        match self {
+            DataSource::Async(source) => source.get_file_content_typed_async(file_name).await,
+            DataSource::Sync(source) => source.get_file_content_typed(file_name),
+            DataSource::FileMap(map) => match lookup_file_map(map, file_name) {
+                Some(sf) => tags::observe(
+                    sf.meta().map_or(&[], |m| &m.tags),
+                    file_name,
+                    sf.fetch_async().await.map(|d| sf.file_content(d)),
+                ),
+                None => Err(FetchError::not_found(file_name.to_string_lossy())),
+            },
+            _ => self
+                .get_file_content_async(file_name)
+                .await
+                .map(FileContent::from),
+        }
+    }
+
     /// 返回读到的 数据。可能还会返回 成功找到的路径
     async fn get_file_content_async(
         &self,
@@ -436,37 +2038,158 @@ impl AsyncFolderSource for DataSource {
             }
             #[cfg(feature = "tokio-tar")]
             DataSource::TarFile(tf) => tf.get_file_content_async(file_name).await,
+            #[cfg(all(feature = "tar", not(feature = "tokio-tar")))]
+            DataSource::TarFile(tf) => tf.get_file_content(file_name),
+            #[cfg(not(feature = "tar"))]
+            DataSource::TarInMemory(_) | DataSource::TarFile(_) => {
+                Err(FetchError::FeatureDisabled("tar"))
+            }
 
             DataSource::Folders(possible_addrs) => {
+                let mut dir_hit = None;
                 for dir in possible_addrs {
-                    let real_file_name = std::path::Path::new(dir).join(file_name);
-
-                    if real_file_name.exists() {
-                        return Ok(tokio::fs::read(&real_file_name)
-                            .await
-                            .map(|v| (v, Some(dir.to_owned())))?);
+                    let real_file_name = folder_path(Path::new(dir), file_name);
+                    match read_local_file_async(&real_file_name).await {
+                        Ok(v) => return Ok((v, Some(dir.to_owned()))),
+                        Err(FetchError::Lookup(LookupError::IsDirectory(_))) => {
+                            dir_hit.get_or_insert(real_file_name);
+                        }
+                        Err(e) if is_folder_miss(&e) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                match dir_hit {
+                    Some(d) => Err(FetchError::Lookup(LookupError::IsDirectory(
+                        d.to_string_lossy().to_string(),
+                    ))),
+                    None => Err(LookupError::NotInDirectories {
+                        name: file_name.to_string_lossy().to_string(),
+                        dirs: possible_addrs.clone(),
                     }
+                    .into()),
                 }
-                Err(FetchError::NFD(possible_addrs.clone()))
             }
             DataSource::StdReadFile => {
-                let s: Vec<u8> = tokio::fs::read(file_name).await?;
+                let s: Vec<u8> = read_local_file_async(&std_read_path(file_name)).await?;
                 Ok((s, None))
             }
 
-            DataSource::FileMap(map) => {
-                let r = map.get(&file_name.to_string_lossy().to_string());
+            DataSource::FileMap(map) => match lookup_file_map(map, file_name) {
+                Some(sf) => sf.fetch_async().await.map(|d| (d, sf.get_path())),
+                None => Err(FetchError::not_found(file_name.to_string_lossy())),
+            },
+            DataSource::Embedded(_) => self
+                .get_file_content_ref(file_name)
+                .map(|(d, p)| (d.into_owned(), p)),
+        }
+    }
+}
+
+impl SyncFolderSource for DataSource {
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        match self {
+            DataSource::Sync(source) => source.get_file_content_ctx(file_name, ctx),
+            #[cfg(feature = "tokio")]
+            DataSource::Async(source) => tokio::runtime::Handle::current()
+                .block_on(source.get_file_content_ctx_async(file_name, ctx)),
+            _ => self.get_file_content_typed(file_name),
+        }
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        DataSource::capabilities(self)
+    }
 
-                match r {
-                    Some(sf) => sf.fetch_async().await.map(|d| (d, sf.get_path())),
-                    None => Err(FetchError::NF),
+    /// Folders 中同名的文件只列出一次, StdReadFile 和 Async 不支持列出
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        match self {
+            DataSource::Folders(dirs) => {
+                let mut v = Vec::new();
+                for d in dirs {
+                    if Path::new(d).is_dir() {
+                        list_dir_files(Path::new(d), "", &mut v)?;
+                    }
                 }
+                let mut seen = std::collections::HashSet::new();
+                v.retain(|f| seen.insert(f.clone()));
+                Ok(v)
+            }
+            #[cfg(feature = "tar")]
+            DataSource::TarInMemory(v) => list_tar_files(std::io::Cursor::new(decompress_tar(v)?)),
+            #[cfg(feature = "tar")]
+            DataSource::TarFile(tf) => list_tar_files(open_tar_file(&tf.0)?),
+            #[cfg(not(feature = "tar"))]
+            DataSource::TarInMemory(_) | DataSource::TarFile(_) => {
+                Err(FetchError::FeatureDisabled("tar"))
             }
+            DataSource::FileMap(map) => Ok(map.keys().cloned().collect()),
+            DataSource::Embedded(e) => Ok(e.names()),
+            DataSource::Sync(source) => source.list_files(),
+            _ => Err(FetchError::Unsupported("list_files")),
+        }
+    }
+
+    /// Folders 返回本地文件的大小和 mtime
+    fn list_entries(&self) -> Result<Vec<ListEntry>, FetchError> {
+        match self {
+            DataSource::Folders(dirs) => {
+                let mut seen = std::collections::HashSet::new();
+                let mut r = Vec::new();
+                for d in dirs {
+                    let d = Path::new(d);
+                    if !d.is_dir() {
+                        continue;
+                    }
+                    let mut v = Vec::new();
+                    list_dir_files(d, "", &mut v)?;
+                    for name in v {
+                        if !seen.insert(name.clone()) {
+                            continue;
+                        }
+                        let m = std::fs::metadata(d.join(&name)).ok();
+                        r.push(ListEntry {
+                            size: m.as_ref().map(|m| m.len()),
+                            mtime: m.as_ref().and_then(unix_mtime),
+                            name,
+                            etag: None,
+                        });
+                    }
+                }
+                Ok(r)
+            }
+            DataSource::Sync(source) => source.list_entries(),
+            _ => Ok(SyncFolderSource::list_files(self)?
+                .into_iter()
+                .map(|name| ListEntry {
+                    name,
+                    ..Default::default()
+                })
+                .collect()),
+        }
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        match self {
+            DataSource::Sync(source) => source.get_file_content_typed(file_name),
+            #[cfg(feature = "tokio")]
+            DataSource::Async(source) => tokio::runtime::Handle::current()
+                .block_on(source.get_file_content_typed_async(file_name)),
+            DataSource::FileMap(map) => match lookup_file_map(map, file_name) {
+                Some(sf) => tags::observe(
+                    sf.meta().map_or(&[], |m| &m.tags),
+                    file_name,
+                    sf.fetch().map(|d| sf.file_content(d)),
+                ),
+                None => Err(FetchError::not_found(file_name.to_string_lossy())),
+            },
+            _ => self.get_file_content(file_name).map(FileContent::from),
         }
     }
-}
 
-impl SyncFolderSource for DataSource {
     /// 返回读到的 数据。可能还会返回 成功找到的路径
     fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
         match self {
@@ -483,34 +2206,75 @@ impl SyncFolderSource for DataSource {
             }
             #[cfg(feature = "tar")]
             DataSource::TarFile(tf) => tf.get_file_content(file_name),
+            #[cfg(not(feature = "tar"))]
+            DataSource::TarInMemory(_) | DataSource::TarFile(_) => {
+                Err(FetchError::FeatureDisabled("tar"))
+            }
 
             DataSource::Folders(possible_addrs) => {
+                let mut dir_hit = None;
                 for dir in possible_addrs {
-                    let real_file_name = std::path::Path::new(dir).join(file_name);
-
-                    if real_file_name.exists() {
-                        return Ok(
-                            std::fs::read(&real_file_name).map(|v| (v, Some(dir.to_owned())))?
-                        );
+                    let real_file_name = folder_path(Path::new(dir), file_name);
+                    match read_local_file(&real_file_name) {
+                        Ok(v) => return Ok((v, Some(dir.to_owned()))),
+                        Err(FetchError::Lookup(LookupError::IsDirectory(_))) => {
+                            dir_hit.get_or_insert(real_file_name);
+                        }
+                        Err(e) if is_folder_miss(&e) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                match dir_hit {
+                    Some(d) => Err(FetchError::Lookup(LookupError::IsDirectory(
+                        d.to_string_lossy().to_string(),
+                    ))),
+                    None => Err(LookupError::NotInDirectories {
+                        name: file_name.to_string_lossy().to_string(),
+                        dirs: possible_addrs.clone(),
                     }
+                    .into()),
                 }
-                Err(FetchError::NFD(possible_addrs.clone()))
             }
             DataSource::StdReadFile => {
-                let s: Vec<u8> = std::fs::read(file_name)?;
+                let s: Vec<u8> = read_local_file(&std_read_path(file_name))?;
                 Ok((s, None))
             }
 
-            DataSource::FileMap(map) => {
-                let r = map.get(&file_name.to_string_lossy().to_string());
+            DataSource::FileMap(map) => match lookup_file_map(map, file_name) {
+                Some(sf) => sf.fetch().map(|d| (d, sf.get_path())),
+                None => Err(FetchError::not_found(file_name.to_string_lossy())),
+            },
+            DataSource::Embedded(_) => self
+                .get_file_content_ref(file_name)
+                .map(|(d, p)| (d.into_owned(), p)),
+        }
+    }
+}
+
+fn list_dir_files(dir: &Path, prefix: &str, v: &mut Vec<String>) -> Result<(), FetchError> {
+    for e in std::fs::read_dir(dir).map_err(|e| local_io_error(e, dir))? {
+        let e = e?;
+        let name = format!("{prefix}{}", e.file_name().to_string_lossy());
+        if e.file_type()?.is_dir() {
+            list_dir_files(&e.path(), &format!("{name}/"), v)?;
+        } else {
+            v.push(name);
+        }
+    }
+    Ok(())
+}
 
-                match r {
-                    Some(sf) => sf.fetch().map(|d| (d, sf.get_path())),
-                    None => Err(FetchError::NF),
-                }
-            }
+#[cfg(feature = "tar")]
+fn list_tar_files<R: std::io::Read>(r: R) -> Result<Vec<String>, FetchError> {
+    let mut a = tar::Archive::new(r);
+    let mut v = Vec::new();
+    for e in a.entries()? {
+        let e = e?;
+        if e.header().entry_type().is_file() {
+            v.push(e.path()?.to_string_lossy().replace('\\', "/"));
         }
     }
+    Ok(v)
 }
 
 #[cfg(feature = "tokio-tar")]
@@ -524,24 +2288,26 @@ where
 {
     let mut a = tokio_tar::Archive::new(reader);
 
-    let mut es = a.entries().unwrap();
+    let mut es = a.entries().map_err(ArchiveError::Io)?;
 
     use futures::StreamExt;
     use tokio::io::AsyncReadExt;
 
     while let Some(file) = es.next().await {
-        let mut f = file.unwrap();
-        let p = f.path().unwrap();
-        if p.eq(file_name_in_tar.as_ref()) {
-            debug!("found {}", file_name_in_tar.as_ref().to_str().unwrap());
+        let mut f = file.map_err(ArchiveError::Io)?;
+        let p = f.path().map_err(ArchiveError::Io)?;
+        if paths_match(&p, file_name_in_tar.as_ref()) {
+            debug!("found {}", file_name_in_tar.as_ref().display());
             let ps = p.to_string_lossy().to_string();
             let mut result = vec![];
 
-            f.read_to_end(&mut result).await?;
+            f.read_to_end(&mut result).await.map_err(ArchiveError::Io)?;
             return Ok((result, Some(ps)));
         }
     }
-    Err(FetchError::NF)
+    Err(FetchError::not_found(
+        file_name_in_tar.as_ref().to_string_lossy(),
+    ))
 }
 #[cfg(feature = "tar")]
 pub fn get_file_from_tar_by_reader<P, R>(
@@ -556,60 +2322,227 @@ where
 
     let mut e = a
         .entries()
-        .unwrap()
+        .map_err(ArchiveError::Io)?
         .find(|a| {
-            a.as_ref()
-                .is_ok_and(|b| b.path().is_ok_and(|c| c == file_name_in_tar.as_ref()))
+            a.as_ref().is_ok_and(|b| {
+                b.path()
+                    .is_ok_and(|c| paths_match(&c, file_name_in_tar.as_ref()))
+            })
         })
-        .ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::NotFound,
-                format!(
-                    "get_file_from_tar: can't find the file, {}",
-                    file_name_in_tar.as_ref().to_str().unwrap()
-                ),
-            )
-        })??;
+        .ok_or_else(|| FetchError::not_found(file_name_in_tar.as_ref().to_string_lossy()))?
+        .map_err(ArchiveError::Io)?;
 
-    debug!("found {}", file_name_in_tar.as_ref().to_str().unwrap());
+    debug!("found {}", file_name_in_tar.as_ref().display());
 
     let mut result = vec![];
     use std::io::Read;
-    e.read_to_end(&mut result)?;
+    e.read_to_end(&mut result).map_err(ArchiveError::Io)?;
     Ok((
         result,
-        Some(e.path().unwrap().to_str().unwrap().to_string()),
+        Some(
+            e.path()
+                .map_err(ArchiveError::Io)?
+                .to_string_lossy()
+                .to_string(),
+        ),
     ))
 }
 #[cfg(feature = "tar")]
 pub fn get_file_from_tar_in_memory<P>(
     file_name_in_tar: P,
-    tar_binary: &Vec<u8>,
+    tar_binary: &[u8],
 ) -> Result<(Vec<u8>, Option<String>), FetchError>
 where
     P: AsRef<std::path::Path>,
 {
     debug!(
         "finding {} from tar, tar whole size is {}",
-        file_name_in_tar.as_ref().to_str().unwrap(),
+        file_name_in_tar.as_ref().display(),
         tar_binary.len()
     );
-    let r = std::io::Cursor::new(tar_binary);
-    get_file_from_tar_by_reader(file_name_in_tar, r)
+    let t = decompress_tar(tar_binary)?;
+    get_file_from_tar_by_reader(file_name_in_tar, std::io::Cursor::new(t.as_ref()))
+}
+
+/// 与 get_file_from_tar_in_memory 相同, 但普通文件直接返回 tar_binary 中对应的片段
+#[cfg(feature = "tar")]
+pub fn get_file_ref_from_tar_in_memory<'a, P>(
+    file_name_in_tar: P,
+    tar_binary: &'a [u8],
+) -> Result<(Cow<'a, [u8]>, Option<String>), FetchError>
+where
+    P: AsRef<std::path::Path>,
+{
+    if let Cow::Owned(t) = decompress_tar(tar_binary)? {
+        return get_file_from_tar_by_reader(file_name_in_tar, std::io::Cursor::new(t))
+            .map(|(d, p)| (Cow::Owned(d), p));
+    }
+    let mut a = tar::Archive::new(std::io::Cursor::new(tar_binary));
+    for e in a.entries().map_err(ArchiveError::Io)? {
+        let e = e.map_err(ArchiveError::Io)?;
+        let p = e.path().map_err(ArchiveError::Io)?;
+        if !paths_match(&p, file_name_in_tar.as_ref()) {
+            continue;
+        }
+        let ps = p.to_string_lossy().to_string();
+        if e.header().entry_type().is_file() {
+            let start = e.raw_file_position() as usize;
+            let end = start.saturating_add(e.size() as usize);
+            if let Some(d) = tar_binary.get(start..end) {
+                return Ok((Cow::Borrowed(d), Some(ps)));
+            }
+        }
+        // 稀疏文件等无法直接借用, 回退到复制
+        let mut result = vec![];
+        use std::io::Read;
+        let mut e = e;
+        e.read_to_end(&mut result)?;
+        return Ok((Cow::Owned(result), Some(ps)));
+    }
+    Err(FetchError::not_found(
+        file_name_in_tar.as_ref().to_string_lossy(),
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::{self, File};
-    use std::io::Write;
+    use std::fs;
     use tempfile::TempDir;
 
     #[cfg(feature = "reqwest")]
     use reqwest::blocking::Client;
 
+    #[cfg(feature = "reqwest")]
     const URL: &str = "https://www.rust-lang.org";
 
+    #[cfg(not(feature = "reqwest"))]
+    #[cfg(not(feature = "tar"))]
+    #[test]
+    fn test_feature_disabled() {
+        let sf = SingleFileSource::Http(
+            HttpSource {
+                url: "http://a/b".to_string(),
+                ..Default::default()
+            },
+            FileCache::default(),
+        );
+        assert!(matches!(
+            sf.fetch(),
+            Err(FetchError::FeatureDisabled("reqwest"))
+        ));
+        let ds = DataSource::TarInMemory(Vec::new());
+        let e = ds.list_files().unwrap_err();
+        assert_eq!(e.kind(), FetchErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_cache_jitter() {
+        let fcs: Vec<_> = (0..20)
+            .map(|i| {
+                FileCache {
+                    update_interval_seconds: Some(600),
+                    cache_file_path: Some(format!("cache/{i}")),
+                    ..Default::default()
+                }
+                .with_jitter(300)
+            })
+            .collect();
+        let intervals: Vec<_> = fcs
+            .iter()
+            .map(|f| f.effective_interval().unwrap())
+            .collect();
+        assert!(intervals.iter().all(|i| (300..=600).contains(i)));
+        assert!(intervals.iter().any(|i| *i != intervals[0]));
+        assert_eq!(fcs[0].effective_interval(), fcs[0].effective_interval());
+    }
+
+    #[test]
+    fn test_stale_if_error() {
+        struct Failing;
+        impl SyncSource for Failing {
+            fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+                Err(FetchError::Unsupported("down"))
+            }
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .resolve("a")
+            .into_owned();
+        let cf = fc.cache_file_path.clone().unwrap();
+        assert!(fetch_with_cache(&fc.clone().with_stale_if_error(300), &Failing).is_err());
+        std::fs::write(&cf, "old").unwrap();
+        std::fs::File::options()
+            .append(true)
+            .open(&cf)
+            .unwrap()
+            .set_modified(SystemTime::now() - std::time::Duration::from_secs(120))
+            .unwrap();
+        assert!(fetch_with_cache(&fc, &Failing).is_err());
+        let d = fetch_with_cache(&fc.clone().with_stale_if_error(300), &Failing).unwrap();
+        assert_eq!(d, b"old");
+        // 超过可接受的时间
+        assert!(fetch_with_cache(&fc.with_stale_if_error(100), &Failing).is_err());
+    }
+
+    #[test]
+    fn test_cache_dir() {
+        let fc = FileCache::in_dir("cache", Some(60));
+        let http = |url: &str| {
+            let hs = HttpSource {
+                url: url.to_string(),
+                ..Default::default()
+            };
+            SingleFileSource::Http(hs, fc.clone())
+        };
+        let path = |url: &str| http(url).file_cache().unwrap().cache_file_path.clone();
+        let (a, b) = (
+            path("https://a.com/geoip.dat"),
+            path("https://b.com/geoip.dat"),
+        );
+        assert_ne!(a, b);
+        assert!(Path::new(&a.unwrap()).starts_with("cache"));
+        // 指定了缓存文件时不受 cache_dir 影响
+        let fc = FileCache {
+            cache_file_path: Some("x".to_string()),
+            ..fc
+        };
+        assert_eq!(fc.resolve("y").cache_file_path.as_deref(), Some("x"));
+        let g = SingleFileSource::Inline(Vec::new());
+        assert!(g.file_cache().is_none());
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_compress_disabled() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .resolve("a")
+            .into_owned();
+        assert!(!fc.clone().with_compression().write_cache_file(b"a"));
+        let cf = fc.cache_file_path.clone().unwrap();
+        std::fs::write(&cf, [COMPRESSED_CACHE_MAGIC, b"zstd"].concat()).unwrap();
+        assert!(matches!(
+            fc.read_cache_file(),
+            Err(FetchError::FeatureDisabled("zstd"))
+        ));
+    }
+
+    #[cfg(not(feature = "cache_meta"))]
+    #[test]
+    fn test_verify_checksum_disabled() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .with_verify_checksum()
+            .resolve("a")
+            .into_owned();
+        assert!(fc.write_cache_file(b"a"));
+        assert!(matches!(
+            fc.read_cache_file(),
+            Err(FetchError::FeatureDisabled("cache_meta"))
+        ));
+    }
+
     #[cfg(feature = "tokio")]
     #[cfg(feature = "reqwest")]
     #[tokio::test]
@@ -639,6 +2572,174 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    /// 声明 10 字节, 只发送 4 字节后断开连接
+    #[cfg(feature = "reqwest")]
+    fn serve_truncated() -> String {
+        use std::io::Write;
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/a", l.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let mut buf = [0; 1024];
+                let _ = std::io::Read::read(&mut s, &mut buf);
+                s.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabcd")
+                    .unwrap();
+            }
+        });
+        url
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[test]
+    fn test_truncated_body() {
+        let temp_dir = TempDir::new().unwrap();
+        let cf = temp_dir.path().join("cache");
+        let sf = SingleFileSource::Http(
+            HttpSource {
+                url: serve_truncated(),
+                ..Default::default()
+            },
+            FileCache {
+                update_interval_seconds: Some(60),
+                cache_file_path: Some(cf.to_string_lossy().into()),
+                ..Default::default()
+            },
+        );
+        let e = sf.fetch().unwrap_err();
+        assert!(
+            matches!(
+                e,
+                FetchError::Http(HttpError::Truncated {
+                    expected: 10,
+                    received: 4,
+                    ..
+                })
+            ),
+            "{e}"
+        );
+        assert!(!cf.exists());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_truncated_body_async() {
+        let hs = HttpSource {
+            url: serve_truncated(),
+            ..Default::default()
+        };
+        let e = hs.fetch_async().await.unwrap_err();
+        assert!(
+            matches!(e, FetchError::Http(HttpError::Truncated { .. })),
+            "{e}"
+        );
+        assert!(e.url().is_some());
+    }
+
+    #[test]
+    fn test_http_source_file_url() {
+        let temp_dir = TempDir::new().unwrap();
+        let f = temp_dir.path().join("a b.txt");
+        std::fs::write(&f, "local").unwrap();
+        let url = format!("file://{}", f.to_string_lossy().replace(' ', "%20"));
+        let hs = HttpSource {
+            url: url.clone(),
+            ..Default::default()
+        };
+        assert_eq!(hs.local_path().unwrap(), f);
+        let sf = SingleFileSource::Http(
+            hs,
+            FileCache {
+                cache_file_path: Some(temp_dir.path().join("cache").to_string_lossy().into()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(sf.fetch().unwrap(), b"local");
+        assert!(!temp_dir.path().join("cache").exists());
+
+        let local = |u: &str| {
+            HttpSource {
+                url: u.to_string(),
+                ..Default::default()
+            }
+            .local_path()
+        };
+        assert_eq!(local("FILE://localhost/a?x#y"), Some(PathBuf::from("/a")));
+        assert_eq!(local("file://host/a"), None);
+        assert_eq!(local("http://a/b"), None);
+    }
+
+    /// 同步和异步的请求共用限制, 在 current_thread 运行时中持有许可时同步调用方也不会死锁
+    #[cfg(all(feature = "tokio", feature = "reqwest"))]
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_fetch_limiter() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Arc;
+
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/a", l.local_addr().unwrap());
+        let open = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (o, pk) = (open.clone(), peak.clone());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let (o, pk) = (o.clone(), pk.clone());
+                std::thread::spawn(move || {
+                    pk.fetch_max(o.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    for line in BufReader::new(&s).lines() {
+                        if line.unwrap().is_empty() {
+                            break;
+                        }
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(30));
+                    o.fetch_sub(1, Ordering::SeqCst);
+                    s.write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nConnection: close\r\n\r\na",
+                    )
+                    .unwrap();
+                });
+            }
+        });
+
+        let limiter = FetchLimiter::new(2);
+        let held = limiter.acquire().await;
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let (limiter, url) = (limiter.clone(), url.clone());
+                std::thread::spawn(move || {
+                    let _p = limiter.acquire_blocking();
+                    HttpSource {
+                        url,
+                        ..Default::default()
+                    }
+                    .fetch()
+                    .unwrap()
+                })
+            })
+            .collect();
+        let fetch_async = || async {
+            let _p = limiter.acquire().await;
+            HttpSource {
+                url: url.clone(),
+                ..Default::default()
+            }
+            .fetch_async()
+            .await
+            .unwrap()
+        };
+        let (a, b) = futures::join!(fetch_async(), fetch_async());
+        assert_eq!((a, b), (b"a".to_vec(), b"a".to_vec()));
+        drop(held);
+        for t in threads {
+            assert_eq!(t.join().unwrap(), b"a");
+        }
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+        assert!((1..=2).contains(&peak.load(Ordering::SeqCst)));
+    }
+
     #[test]
     fn test_data_source_read_from_folders() {
         let temp_dir = TempDir::new().unwrap();
@@ -666,16 +2767,287 @@ mod tests {
         let content = data_source.read_to_string("config.json").unwrap();
         assert_eq!(content, "{\"key\": \"value\"}");
     }
-    use std::path::PathBuf;
+
+    #[test]
+    fn test_data_source_env() {
+        std::env::set_var("DATA_SOURCE_TEST_TOKEN", "secret");
+        let data_source = DataSource::FileMap(HashMap::from([
+            (
+                "token".to_string(),
+                SingleFileSource::Env("DATA_SOURCE_TEST_TOKEN".to_string()),
+            ),
+            (
+                "missing".to_string(),
+                SingleFileSource::Env("DATA_SOURCE_TEST_MISSING".to_string()),
+            ),
+        ]));
+        assert_eq!(data_source.read_to_string("token").unwrap(), "secret");
+        assert!(data_source
+            .get_file_content(Path::new("missing"))
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    fn test_inline_base64() {
+        let sf = SingleFileSource::InlineBase64("AGdl\n  b2lw/w==".to_string());
+        assert_eq!(sf.fetch().unwrap(), b"\0geoip\xff");
+        assert!(sf.get_path().is_none());
+        let bad = SingleFileSource::InlineBase64("a*b".to_string());
+        assert!(matches!(bad.fetch(), Err(FetchError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_data_source_folder_is_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+
+        let data_source = DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+
+        let r = data_source.read_to_string("sub");
+        assert!(matches!(
+            r,
+            Err(FetchError::Lookup(LookupError::IsDirectory(_)))
+        ));
+        let r = DataSource::StdReadFile.read_to_string(temp_dir.path());
+        assert!(matches!(
+            r,
+            Err(FetchError::Lookup(LookupError::IsDirectory(_)))
+        ));
+    }
+
+    #[test]
+    fn test_local_io_error() {
+        let p = Path::new("/srv/conf/a.json");
+        let e = local_io_error(io::ErrorKind::PermissionDenied.into(), p);
+        assert!(
+            matches!(&e, FetchError::Lookup(LookupError::Denied(n)) if n == "/srv/conf/a.json")
+        );
+        assert_eq!(e.kind(), FetchErrorKind::PermissionDenied);
+        let e = local_io_error(io::ErrorKind::IsADirectory.into(), p);
+        assert_eq!(e.kind(), FetchErrorKind::IsDirectory);
+        let e = local_io_error(io::ErrorKind::NotFound.into(), p);
+        assert!(e.is_not_found());
+        assert!(e.to_string().contains("/srv/conf/a.json"));
+    }
+
+    /// 权限不足时返回 Denied, 而不是因为无法 stat 而当作不存在. root 不受权限限制, 跳过
+    #[cfg(unix)]
+    #[test]
+    fn test_data_source_folder_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("secret.txt");
+        fs::write(&file, "s").unwrap();
+        fs::create_dir(temp_dir.path().join("locked")).unwrap();
+        fs::write(temp_dir.path().join("locked/a.txt"), "a").unwrap();
+        let mode = |p: &Path, m| fs::set_permissions(p, fs::Permissions::from_mode(m)).unwrap();
+        mode(&file, 0o000);
+        mode(&temp_dir.path().join("locked"), 0o000);
+        let readable = fs::read(&file).is_ok();
+
+        let ds = DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+        let r = (
+            ds.read_to_string("secret.txt"),
+            ds.read_to_string("locked/a.txt"),
+        );
+        mode(&temp_dir.path().join("locked"), 0o755);
+        if readable {
+            return;
+        }
+        for r in [r.0, r.1] {
+            assert_eq!(r.unwrap_err().kind(), FetchErrorKind::PermissionDenied);
+        }
+        assert!(ds.read_to_string("nope.txt").unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_file_map_backslash_lookup() {
+        let file_map = vec![(
+            "certs/server.pem".to_string(),
+            SingleFileSource::Inline(b"pem".to_vec()),
+        )]
+        .into_iter()
+        .collect();
+
+        let data_source = DataSource::FileMap(file_map);
+
+        let content = data_source.read_to_string("certs\\server.pem").unwrap();
+        assert_eq!(content, "pem");
+
+        // 构造时规范化键, 原名称相同的键优先
+        let data_source = DataSource::file_map([
+            (
+                "keys\\a.pem".to_string(),
+                SingleFileSource::Inline(b"a".to_vec()),
+            ),
+            ("b\\c".to_string(), SingleFileSource::Inline(b"c".to_vec())),
+        ]);
+        assert_eq!(data_source.read_to_string("keys/a.pem").unwrap(), "a");
+        assert_eq!(data_source.read_to_string("keys\\a.pem").unwrap(), "a");
+        let DataSource::FileMap(mut m) = data_source else {
+            unreachable!()
+        };
+        m.insert("b\\c".to_string(), SingleFileSource::Inline(b"x".to_vec()));
+        let data_source = DataSource::FileMap(m);
+        assert_eq!(data_source.read_to_string("b\\c").unwrap(), "x");
+        assert_eq!(data_source.read_to_string("b/c").unwrap(), "c");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_folder_literal_backslash() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("a")).unwrap();
+        std::fs::write(temp_dir.path().join("a/b"), "nested").unwrap();
+        let ds = DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+        assert_eq!(ds.read_to_string("a\\b").unwrap(), "nested");
+        std::fs::write(temp_dir.path().join("a\\b"), "literal").unwrap();
+        assert_eq!(ds.read_to_string("a\\b").unwrap(), "literal");
+    }
+
+    #[test]
+    fn test_separator_retry_option() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("a")).unwrap();
+        std::fs::write(temp_dir.path().join("a/b"), "nested").unwrap();
+        let folders = || DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+        let map = || {
+            DataSource::file_map([(
+                "certs/server.pem".to_string(),
+                SingleFileSource::Inline(b"pem".to_vec()),
+            )])
+        };
+
+        let (f, m) = (folders(), map());
+        assert_eq!(f.read_to_string("a\\b").unwrap(), "nested");
+        assert_eq!(m.read_to_string("certs\\server.pem").unwrap(), "pem");
+        let (f, m) = (f.with_separator_retry(true), m.with_separator_retry(true));
+        assert_eq!(f.read_to_string("a\\b").unwrap(), "nested");
+        assert_eq!(m.read_to_string("certs\\server.pem").unwrap(), "pem");
+
+        let (f, m) = (
+            folders().with_separator_retry(false),
+            map().with_separator_retry(false),
+        );
+        assert!(f.read_to_string("a\\b").unwrap_err().is_not_found());
+        assert!(m
+            .read_to_string("certs\\server.pem")
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(f.read_to_string("a/b").unwrap(), "nested");
+        assert_eq!(m.read_to_string("certs/server.pem").unwrap(), "pem");
+        #[cfg(not(windows))]
+        {
+            std::fs::write(temp_dir.path().join("a\\b"), "literal").unwrap();
+            assert_eq!(f.read_to_string("a\\b").unwrap(), "literal");
+        }
+
+        // 只影响关闭了重试的来源
+        assert_eq!(map().read_to_string("certs\\server.pem").unwrap(), "pem");
+    }
+
+    #[derive(Debug)]
+    struct Typed;
+
+    impl SyncFolderSource for Typed {
+        fn get_file_content(
+            &self,
+            _file_name: &Path,
+        ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+            Ok((b"{}".to_vec(), None))
+        }
+
+        fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+            let mut fc = FileContent::from(self.get_file_content(file_name)?);
+            fc.content_type = Some("application/json".to_string());
+            Ok(fc)
+        }
+    }
+
+    #[test]
+    fn test_data_source_content_type() {
+        let data_source = DataSource::Sync(Box::new(Typed));
+        let fc = data_source.get_file_content_typed(Path::new("a")).unwrap();
+        assert_eq!(fc.content_type.as_deref(), Some("application/json"));
+
+        let file_map = vec![(
+            "geoip".to_string(),
+            SingleFileSource::Inline(vec![0]).with_meta(FileMeta {
+                content_type: Some("application/octet-stream+mmdb".to_string()),
+                content_encoding: Some("gzip".to_string()),
+                ..Default::default()
+            }),
+        )]
+        .into_iter()
+        .collect();
+        let data_source = DataSource::FileMap(file_map);
+        let fc = data_source
+            .get_file_content_typed(Path::new("geoip"))
+            .unwrap();
+        assert_eq!(fc.data, vec![0]);
+        assert_eq!(
+            fc.content_type.as_deref(),
+            Some("application/octet-stream+mmdb")
+        );
+        assert_eq!(fc.content_encoding.as_deref(), Some("gzip"));
+        assert!(matches!(
+            data_source.get_file_content_typed(Path::new("a")),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+    }
+
+    #[derive(Debug)]
+    struct Localized;
+
+    impl SyncFolderSource for Localized {
+        fn get_file_content(
+            &self,
+            file_name: &Path,
+        ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+            Ok((file_name.to_string_lossy().as_bytes().to_vec(), None))
+        }
+
+        fn get_file_content_ctx(
+            &self,
+            file_name: &Path,
+            ctx: &FetchContext,
+        ) -> Result<FileContent, FetchError> {
+            match &ctx.locale {
+                Some(l) => self.get_file_content_typed(&Path::new(l).join(file_name)),
+                None => self.get_file_content_typed(file_name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_data_source_fetch_context() {
+        let data_source = DataSource::Sync(Box::new(Localized));
+        let ctx = FetchContext {
+            locale: Some("fr".to_string()),
+            ..Default::default()
+        };
+        let fc = data_source
+            .get_file_content_ctx(Path::new("a.json"), &ctx)
+            .unwrap();
+        assert_eq!(fc.data, b"fr/a.json");
+        let fc = data_source
+            .get_file_content_ctx(Path::new("a.json"), &FetchContext::default())
+            .unwrap();
+        assert_eq!(fc.data, b"a.json");
+    }
+
     #[cfg(feature = "tar")]
     fn gentar() -> (TempDir, PathBuf, &'static str, &'static str) {
         let temp_dir = TempDir::new().unwrap();
         let tar_path = temp_dir.path().join("test.tar");
 
-        let mut tar_builder = tar::Builder::new(File::create(&tar_path).unwrap());
+        let mut tar_builder = tar::Builder::new(fs::File::create(&tar_path).unwrap());
 
         let mut file = tempfile::NamedTempFile::new().unwrap();
         let c = "hello tar\n";
+        use std::io::Write;
         write!(file, "{}", c).unwrap();
         let file_path = file.path().to_owned();
 
@@ -701,6 +3073,26 @@ mod tests {
         assert_eq!(String::from_utf8_lossy(&content), c);
         assert_eq!(path.unwrap(), tfn);
     }
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_get_file_content_ref() {
+        let (_td, tar_path, tfn, c) = gentar();
+
+        let ds = DataSource::TarInMemory(fs::read(&tar_path).unwrap());
+        let (content, path) = ds.get_file_content_ref(Path::new(tfn)).unwrap();
+        assert!(matches!(content, Cow::Borrowed(_)));
+        assert_eq!(content.as_ref(), c.as_bytes());
+        assert_eq!(path.unwrap(), tfn);
+
+        let ds = DataSource::FileMap(
+            vec![("a".to_string(), SingleFileSource::Inline(b"a".to_vec()))]
+                .into_iter()
+                .collect(),
+        );
+        let (content, _) = ds.get_file_content_ref(Path::new("a")).unwrap();
+        assert!(matches!(content, Cow::Borrowed(b"a")));
+    }
+
     #[cfg(feature = "tokio-tar")]
     #[tokio::test]
     async fn test_get_file_from_tar_async() -> Result<(), FetchError> {
@@ -0,0 +1,194 @@
+use crate::*;
+
+/// 解析 Accept-Language, 按 q 值从高到低返回语言, 忽略 `*` 和 q=0 的项
+pub fn parse_accept_language(v: &str) -> Vec<String> {
+    let mut langs: Vec<(String, f32)> = v
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let lang = parts.next().filter(|l| !l.is_empty() && *l != "*")?;
+            let q = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            (q > 0.0).then(|| (lang.to_string(), q))
+        })
+        .collect();
+    langs.sort_by(|a, b| b.1.total_cmp(&a.1));
+    langs.into_iter().map(|l| l.0).collect()
+}
+
+/// 按语言依次尝试的文件名. 如 `messages.json` 与 `["fr-CA"]` 得到
+/// `messages.fr-CA.json`, `messages.fr.json`, `messages.json`
+pub fn localized_candidates(file_name: &Path, languages: &[String]) -> Vec<PathBuf> {
+    let stem = file_name
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let ext = file_name
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut tags: Vec<&str> = Vec::new();
+    for l in languages {
+        let mut t = l.as_str();
+        loop {
+            if !tags.contains(&t) {
+                tags.push(t);
+            }
+            match t.rfind('-') {
+                Some(i) => t = &t[..i],
+                None => break,
+            }
+        }
+    }
+
+    let mut v: Vec<PathBuf> = tags
+        .into_iter()
+        .map(|t| file_name.with_file_name(format!("{stem}.{t}{ext}")))
+        .collect();
+    v.push(file_name.to_path_buf());
+    v
+}
+
+/// 按 [`FetchContext::languages`] (为空时用 [`FetchContext::locale`]) 查找本地化的文件,
+/// 都找不到时返回原文件
+#[derive(Debug)]
+pub struct LocaleSource<S> {
+    pub inner: S,
+}
+
+impl<S> LocaleSource<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+fn languages_of(ctx: &FetchContext) -> &[String] {
+    if ctx.languages.is_empty() {
+        ctx.locale.as_slice()
+    } else {
+        &ctx.languages
+    }
+}
+
+impl<S: SyncFolderSource> SyncFolderSource for LocaleSource<S> {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.inner.get_file_content(file_name)
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.inner.get_file_content_typed(file_name)
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let candidates = localized_candidates(file_name, languages_of(ctx));
+        let (base, localized) = candidates.split_last().expect("base file");
+        for c in localized {
+            match self.inner.get_file_content_ctx(c, ctx) {
+                Err(e) if e.is_not_found() => continue,
+                r => return r,
+            }
+        }
+        self.inner.get_file_content_ctx(base, ctx)
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.inner.capabilities().read_only()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        self.inner.list_files()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl<S: AsyncFolderSource + Send + Sync> AsyncFolderSource for LocaleSource<S> {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.inner.get_file_content_async(file_name).await
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        self.inner.get_file_content_typed_async(file_name).await
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let candidates = localized_candidates(file_name, languages_of(ctx));
+        let (base, localized) = candidates.split_last().expect("base file");
+        for c in localized {
+            match self.inner.get_file_content_ctx_async(c, ctx).await {
+                Err(e) if e.is_not_found() => continue,
+                r => return r,
+            }
+        }
+        self.inner.get_file_content_ctx_async(base, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localized_candidates() {
+        let langs = parse_accept_language("en;q=0.5, fr-CA, *;q=0.1, de;q=0");
+        assert_eq!(langs, vec!["fr-CA", "en"]);
+
+        let c = localized_candidates(Path::new("i18n/messages.json"), &langs);
+        let c: Vec<_> = c.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        assert_eq!(
+            c,
+            vec![
+                "i18n/messages.fr-CA.json",
+                "i18n/messages.fr.json",
+                "i18n/messages.en.json",
+                "i18n/messages.json"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locale_source() {
+        let ls = LocaleSource::new(DataSource::FileMap(
+            vec![
+                (
+                    "m.json".to_string(),
+                    SingleFileSource::Inline(b"base".to_vec()),
+                ),
+                (
+                    "m.fr.json".to_string(),
+                    SingleFileSource::Inline(b"fr".to_vec()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ));
+        let ctx = FetchContext {
+            languages: vec!["fr-CA".to_string()],
+            ..Default::default()
+        };
+        let fc = ls.get_file_content_ctx(Path::new("m.json"), &ctx).unwrap();
+        assert_eq!(fc.data, b"fr");
+        let fc = ls
+            .get_file_content_ctx(Path::new("m.json"), &FetchContext::default())
+            .unwrap();
+        assert_eq!(fc.data, b"base");
+    }
+}
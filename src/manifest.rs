@@ -0,0 +1,162 @@
+//! 文件清单: 所有可列出的文件的大小和 sha256, 客户端据此只下载变化的文件并校验下载结果.
+//!
+//! JSON 格式: `{"files":[{"path":"a/b.json","size":12,"sha256":"..."}]}`, 按路径排序
+
+use crate::*;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    /// 小写十六进制
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+/// [`Manifest::diff`] 的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestDiff {
+    /// 新增或内容变化的文件
+    pub changed: Vec<String>,
+    /// 新清单中已不存在的文件
+    pub removed: Vec<String>,
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> String {
+        let files: Vec<Value> = self
+            .files
+            .iter()
+            .map(|e| json!({ "path": e.path, "size": e.size, "sha256": e.sha256 }))
+            .collect();
+        json!({ "files": files }).to_string()
+    }
+
+    pub fn from_json(d: &[u8]) -> Result<Self, FetchError> {
+        let invalid = |m: &str| FetchError::Invalid(format!("manifest: {m}"));
+        let v: Value = serde_json::from_slice(d).map_err(|e| invalid(&e.to_string()))?;
+        let files = v["files"]
+            .as_array()
+            .ok_or_else(|| invalid("missing files"))?
+            .iter()
+            .map(|f| {
+                Ok(ManifestEntry {
+                    path: f["path"]
+                        .as_str()
+                        .ok_or_else(|| invalid("missing path"))?
+                        .to_string(),
+                    size: f["size"].as_u64().ok_or_else(|| invalid("missing size"))?,
+                    sha256: f["sha256"]
+                        .as_str()
+                        .ok_or_else(|| invalid("missing sha256"))?
+                        .to_ascii_lowercase(),
+                })
+            })
+            .collect::<Result<Vec<_>, FetchError>>()?;
+        Ok(Self { files })
+    }
+
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.files.iter().find(|e| e.path == path)
+    }
+
+    /// `data` 是否与清单中 `path` 的大小和摘要一致
+    pub fn verify(&self, path: &str, data: &[u8]) -> bool {
+        self.get(path)
+            .is_some_and(|e| e.size == data.len() as u64 && e.sha256 == cas::sha256_hex(data))
+    }
+
+    /// 从 self 更新到 `newer` 需要下载和删除的文件
+    pub fn diff(&self, newer: &Manifest) -> ManifestDiff {
+        let old: HashMap<&str, &str> = self
+            .files
+            .iter()
+            .map(|e| (e.path.as_str(), e.sha256.as_str()))
+            .collect();
+        let new: HashMap<&str, &str> = newer
+            .files
+            .iter()
+            .map(|e| (e.path.as_str(), e.sha256.as_str()))
+            .collect();
+        ManifestDiff {
+            changed: newer
+                .files
+                .iter()
+                .filter(|e| old.get(e.path.as_str()) != Some(&e.sha256.as_str()))
+                .map(|e| e.path.clone())
+                .collect(),
+            removed: self
+                .files
+                .iter()
+                .filter(|e| !new.contains_key(e.path.as_str()))
+                .map(|e| e.path.clone())
+                .collect(),
+        }
+    }
+}
+
+impl DataSource {
+    /// 读取 [`SyncFolderSource::list_files`] 列出的所有文件, 生成清单
+    pub fn manifest(&self) -> Result<Manifest, FetchError> {
+        self.capabilities().require_listable()?;
+        let mut names = self.list_files()?;
+        names.sort();
+        names.dedup();
+        let files = names
+            .into_iter()
+            .map(|n| {
+                let (d, _) = self.get_file_content(Path::new(&n))?;
+                Ok(ManifestEntry {
+                    path: n,
+                    size: d.len() as u64,
+                    sha256: cas::sha256_hex(&d),
+                })
+            })
+            .collect::<Result<Vec<_>, FetchError>>()?;
+        Ok(Manifest { files })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let ds = DataSource::FileMap(HashMap::from([
+            ("b.txt".to_string(), SingleFileSource::Inline(b"b".to_vec())),
+            (
+                "a/x.json".to_string(),
+                SingleFileSource::Inline(b"{}".to_vec()),
+            ),
+        ]));
+        let m = ds.manifest().unwrap();
+        assert_eq!(m.files[0].path, "a/x.json");
+        assert_eq!(m.files[1].size, 1);
+        assert_eq!(
+            m.files[1].sha256,
+            "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d"
+        );
+        assert_eq!(Manifest::from_json(m.to_json().as_bytes()).unwrap(), m);
+        assert!(m.verify("b.txt", b"b"));
+        assert!(!m.verify("b.txt", b"c"));
+        assert!(!m.verify("c.txt", b"b"));
+
+        let mut newer = m.clone();
+        newer.files.remove(0);
+        newer.files[0].sha256 = cas::sha256_hex(b"c");
+        newer.files.push(ManifestEntry {
+            path: "c.txt".to_string(),
+            ..Default::default()
+        });
+        let d = m.diff(&newer);
+        assert_eq!(d.changed, vec!["b.txt", "c.txt"]);
+        assert_eq!(d.removed, vec!["a/x.json"]);
+        assert!(Manifest::from_json(b"{}").is_err());
+    }
+}
@@ -0,0 +1,402 @@
+//! 进程内的内存缓存, 可以放在任意 [`SyncSource`] / [`AsyncSource`] 或整个 [`DataSource`] 前面,
+//! 让 file server 中频繁读取的文件不必每次都读磁盘或网络.
+//! 与 [`resolver::DataResolver`] 自带的缓存不同, 一个 MemoryCache 可以通过 `Arc` 被多个来源共享
+//!
+//! ```ignore
+//! let cache = Arc::new(MemoryCache::new(Duration::from_secs(30)));
+//! let ds = DataSource::Sync(Box::new(cache.cached_folder(ds)));
+//! let rules = cache.cached_source("rules", http_source).with_ttl(Duration::from_secs(300));
+//! ```
+
+use crate::*;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct Entry {
+    content: Arc<FileContent>,
+    expires: Instant,
+}
+
+pub struct MemoryCache {
+    /// 没有单独指定 ttl 的条目的有效期
+    pub default_ttl: Duration,
+    /// 超过时淘汰最早过期的条目
+    pub max_entries: Option<usize>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl std::fmt::Debug for MemoryCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryCache")
+            .field("default_ttl", &self.default_ttl)
+            .field("max_entries", &self.max_entries)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl MemoryCache {
+    pub fn new(default_ttl: Duration) -> Self {
+        Self {
+            default_ttl,
+            max_entries: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 未过期的条目. 已过期的条目会被移除
+    pub fn get(&self, key: &str) -> Option<Arc<FileContent>> {
+        let mut m = self.lock();
+        match m.get(key) {
+            Some(e) if e.expires > Instant::now() => Some(e.content.clone()),
+            Some(_) => {
+                m.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: &str, content: FileContent) -> Arc<FileContent> {
+        self.insert_with_ttl(key, content, self.default_ttl)
+    }
+
+    pub fn insert_with_ttl(
+        &self,
+        key: &str,
+        content: FileContent,
+        ttl: Duration,
+    ) -> Arc<FileContent> {
+        let content = Arc::new(content);
+        let mut m = self.lock();
+        m.insert(
+            key.to_string(),
+            Entry {
+                content: content.clone(),
+                expires: Instant::now() + ttl,
+            },
+        );
+        if let Some(max) = self.max_entries {
+            while m.len() > max {
+                let Some(k) = m
+                    .iter()
+                    .min_by_key(|(_, e)| e.expires)
+                    .map(|(k, _)| k.clone())
+                else {
+                    break;
+                };
+                m.remove(&k);
+            }
+        }
+        content
+    }
+
+    /// 移除一个条目, 返回它是否存在
+    pub fn invalidate(&self, key: &str) -> bool {
+        self.lock().remove(key).is_some()
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// 移除所有已过期的条目, 返回移除的数量
+    pub fn purge_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut m = self.lock();
+        let n = m.len();
+        m.retain(|_, e| e.expires > now);
+        n - m.len()
+    }
+
+    /// 条目数, 包括已过期但还未移除的
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 以 `key` 缓存 `inner` 的内容
+    pub fn cached_source<S>(self: &Arc<Self>, key: &str, inner: S) -> CachedSource<S> {
+        CachedSource {
+            inner,
+            key: key.to_string(),
+            ttl: None,
+            cache: self.clone(),
+        }
+    }
+
+    /// 按文件名缓存 `inner` 中的文件
+    pub fn cached_folder(self: &Arc<Self>, inner: DataSource) -> CachedFolder {
+        CachedFolder {
+            inner,
+            prefix: String::new(),
+            cache: self.clone(),
+        }
+    }
+}
+
+/// 见 [`MemoryCache::cached_source`]. 读取失败时不缓存
+#[derive(Debug)]
+pub struct CachedSource<S> {
+    pub inner: S,
+    pub key: String,
+    /// 为 None 时使用 [`MemoryCache::default_ttl`]
+    pub ttl: Option<Duration>,
+    cache: Arc<MemoryCache>,
+}
+
+impl<S> CachedSource<S> {
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn cache(&self) -> &Arc<MemoryCache> {
+        &self.cache
+    }
+
+    fn store(&self, data: Vec<u8>) -> Vec<u8> {
+        let ttl = self.ttl.unwrap_or(self.cache.default_ttl);
+        let content = FileContent {
+            data,
+            ..Default::default()
+        };
+        self.cache
+            .insert_with_ttl(&self.key, content, ttl)
+            .data
+            .clone()
+    }
+}
+
+impl<S: SyncSource> SyncSource for CachedSource<S> {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        if let Some(c) = self.cache.get(&self.key) {
+            return Ok(c.data.clone());
+        }
+        self.inner.fetch().map(|d| self.store(d))
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl<S: AsyncSource> AsyncSource for CachedSource<S> {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        if let Some(c) = self.cache.get(&self.key) {
+            return Ok(c.data.clone());
+        }
+        let d = self.inner.fetch_async().await?;
+        Ok(self.store(d))
+    }
+}
+
+/// 见 [`MemoryCache::cached_folder`]. 带有租户或语言的请求可能得到不同的文件, 不经过缓存
+#[derive(Debug)]
+pub struct CachedFolder {
+    pub inner: DataSource,
+    /// 多个来源共享一个 MemoryCache 时用于区分各自的文件
+    pub prefix: String,
+    cache: Arc<MemoryCache>,
+}
+
+impl CachedFolder {
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn cache(&self) -> &Arc<MemoryCache> {
+        &self.cache
+    }
+
+    /// `file_name` 对应的缓存键, 可用于 [`MemoryCache::invalidate`]
+    pub fn key(&self, file_name: &Path) -> String {
+        format!(
+            "{}{}",
+            self.prefix,
+            normalize_separators(file_name).to_string_lossy()
+        )
+    }
+
+    fn cached(&self, file_name: &Path) -> Option<FileContent> {
+        self.cache.get(&self.key(file_name)).map(|c| (*c).clone())
+    }
+
+    fn store(&self, file_name: &Path, r: FileContent) -> FileContent {
+        self.cache.insert(&self.key(file_name), r.clone());
+        r
+    }
+}
+
+fn cacheable(ctx: &FetchContext) -> bool {
+    ctx.tenant.is_none() && ctx.locale.is_none() && ctx.languages.is_empty()
+}
+
+impl SyncFolderSource for CachedFolder {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed(file_name)
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        if let Some(c) = self.cached(file_name) {
+            return Ok(c);
+        }
+        let r = self.inner.get_file_content_typed(file_name)?;
+        Ok(self.store(file_name, r))
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        if cacheable(ctx) {
+            return self.get_file_content_typed(file_name);
+        }
+        self.inner.get_file_content_ctx(file_name, ctx)
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        self.inner.list_files()
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.inner.capabilities().read_only()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for CachedFolder {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed_async(file_name)
+            .await
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        if let Some(c) = self.cached(file_name) {
+            return Ok(c);
+        }
+        let r = self.inner.get_file_content_typed_async(file_name).await?;
+        Ok(self.store(file_name, r))
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        if cacheable(ctx) {
+            return self.get_file_content_typed_async(file_name).await;
+        }
+        self.inner.get_file_content_ctx_async(file_name, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct Counting(AtomicUsize);
+
+    impl SyncSource for Counting {
+        fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+            let n = self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(n.to_string().into_bytes())
+        }
+    }
+
+    #[test]
+    fn test_memory_cache_source() {
+        let cache = Arc::new(MemoryCache::new(Duration::from_secs(60)));
+        let s = cache.cached_source("a", Counting::default());
+        assert_eq!(s.fetch().unwrap(), b"0");
+        assert_eq!(s.fetch().unwrap(), b"0");
+        assert!(cache.invalidate("a"));
+        assert_eq!(s.fetch().unwrap(), b"1");
+
+        // 单独的 ttl
+        let s = cache
+            .cached_source("b", Counting::default())
+            .with_ttl(Duration::ZERO);
+        assert_eq!(s.fetch().unwrap(), b"0");
+        assert_eq!(s.fetch().unwrap(), b"1");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.purge_expired(), 1);
+        assert!(cache.get("a").is_some());
+    }
+
+    #[test]
+    fn test_memory_cache_max_entries() {
+        let cache = MemoryCache::new(Duration::from_secs(60)).with_max_entries(2);
+        cache.insert_with_ttl("short", FileContent::default(), Duration::from_secs(1));
+        cache.insert("a", FileContent::default());
+        cache.insert("b", FileContent::default());
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("short").is_none());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_memory_cache_folder() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let f = temp_dir.path().join("a.txt");
+        std::fs::write(&f, "1").unwrap();
+        let cache = Arc::new(MemoryCache::new(Duration::from_secs(60)));
+        let ds = DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+        let c = cache.cached_folder(ds).with_prefix("conf:");
+        assert_eq!(c.get_file_content(Path::new("a.txt")).unwrap().0, b"1");
+        std::fs::write(&f, "2").unwrap();
+        assert_eq!(c.get_file_content(Path::new("a.txt")).unwrap().0, b"1");
+        assert!(cache.get("conf:a.txt").is_some());
+
+        // 带租户的请求不使用缓存
+        let ctx = FetchContext {
+            tenant: Some("t".to_string()),
+            ..Default::default()
+        };
+        let r = c.get_file_content_ctx(Path::new("a.txt"), &ctx).unwrap();
+        assert_eq!(r.data, b"2");
+
+        assert!(cache.invalidate(&c.key(Path::new("a.txt"))));
+        assert_eq!(c.get_file_content(Path::new("a.txt")).unwrap().0, b"2");
+        assert!(c.get_file_content(Path::new("nope")).is_err());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_memory_cache_folder_async() {
+        let cache = Arc::new(MemoryCache::new(Duration::from_secs(60)));
+        let ds = DataSource::FileMap(HashMap::from([(
+            "a".to_string(),
+            SingleFileSource::Inline(b"x".to_vec()),
+        )]));
+        let c = cache.cached_folder(ds);
+        let r = c.get_file_content_async(Path::new("a")).await.unwrap();
+        assert_eq!(r.0, b"x");
+        assert_eq!(cache.get("a").unwrap().data, b"x");
+    }
+}
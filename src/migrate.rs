@@ -0,0 +1,132 @@
+//! 更换缓存位置 (如从逐个指定的缓存文件改为 [`FileCache::in_dir`] 的缓存目录) 时,
+//! 把已有的缓存移动到新位置, 避免部署后重新下载所有文件
+
+use crate::*;
+
+/// [`migrate_cache`] 的结果, 各项为 FileMap 中的键
+#[derive(Debug, Default)]
+pub struct MigrateReport {
+    pub moved: Vec<String>,
+    /// 旧缓存不存在, 或新位置已有缓存 (不会覆盖)
+    pub skipped: Vec<String>,
+    pub failed: Vec<(String, FetchError)>,
+}
+
+/// 对 `old` 和 `new` 两个 FileMap 中都存在且都有缓存文件的 http / s3 / azure / gcs 条目,
+/// 把旧缓存文件及其 `.etag` 和 `.meta.json` 记录移动到新的缓存路径. 修改时间保持不变,
+/// 因此未过期的缓存迁移后仍然有效. 可以在新配置生效前运行, 旧配置在迁移完成前照常读取
+pub fn migrate_cache(old: &DataSource, new: &DataSource) -> Result<MigrateReport, FetchError> {
+    let (DataSource::FileMap(old), DataSource::FileMap(new)) = (old, new) else {
+        return Err(FetchError::Unsupported("migrate_cache"));
+    };
+    let mut keys: Vec<&String> = old.keys().filter(|k| new.contains_key(*k)).collect();
+    keys.sort();
+
+    let mut report = MigrateReport::default();
+    for k in keys {
+        let from = old[k]
+            .file_cache()
+            .and_then(|fc| fc.cache_file_path.clone());
+        let to = new[k]
+            .file_cache()
+            .and_then(|fc| fc.cache_file_path.clone());
+        let (Some(from), Some(to)) = (from, to) else {
+            continue;
+        };
+        let (from, to) = (Path::new(&from), Path::new(&to));
+        if from == to {
+            continue;
+        }
+        if !from.is_file() || to.exists() {
+            report.skipped.push(k.clone());
+            continue;
+        }
+        let r = move_file(from, to).and_then(|_| {
+            for ext in ["etag", "meta.json"] {
+                let side = |p: &Path| PathBuf::from(format!("{}.{ext}", p.to_string_lossy()));
+                if side(from).is_file() {
+                    move_file(&side(from), &side(to))?;
+                }
+            }
+            Ok(())
+        });
+        match r {
+            Ok(()) => report.moved.push(k.clone()),
+            Err(e) => report.failed.push((k.clone(), e)),
+        }
+    }
+    Ok(report)
+}
+
+/// 不能重命名时 (如跨文件系统) 复制后删除, 并恢复修改时间
+fn move_file(from: &Path, to: &Path) -> Result<(), FetchError> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| local_io_error(e, parent))?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    let modified = std::fs::metadata(from)?.modified()?;
+    std::fs::copy(from, to).map_err(|e| local_io_error(e, to))?;
+    std::fs::File::options()
+        .append(true)
+        .open(to)?
+        .set_modified(modified)?;
+    std::fs::remove_file(from).map_err(|e| local_io_error(e, from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_migrate_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let http = |cf: &Path| {
+            SingleFileSource::Http(
+                HttpSource {
+                    url: "http://a/b".to_string(),
+                    ..Default::default()
+                },
+                FileCache {
+                    update_interval_seconds: Some(60),
+                    cache_file_path: Some(cf.to_string_lossy().to_string()),
+                    ..Default::default()
+                },
+            )
+        };
+        std::fs::write(dir.join("a.cache"), "a").unwrap();
+        std::fs::write(dir.join("a.cache.etag"), "\"1\"").unwrap();
+        std::fs::write(dir.join("b.cache"), "old b").unwrap();
+        std::fs::create_dir(dir.join("new")).unwrap();
+        std::fs::write(dir.join("new/b"), "new b").unwrap();
+        let modified = std::fs::metadata(dir.join("a.cache"))
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let old = DataSource::FileMap(HashMap::from([
+            ("a".to_string(), http(&dir.join("a.cache"))),
+            ("b".to_string(), http(&dir.join("b.cache"))),
+            ("c".to_string(), http(&dir.join("c.cache"))),
+        ]));
+        let new = DataSource::FileMap(HashMap::from([
+            ("a".to_string(), http(&dir.join("new/a"))),
+            ("b".to_string(), http(&dir.join("new/b"))),
+            ("c".to_string(), http(&dir.join("new/c"))),
+        ]));
+        let r = migrate_cache(&old, &new).unwrap();
+        assert_eq!(r.moved, vec!["a"]);
+        assert_eq!(r.skipped, vec!["b", "c"]);
+        assert!(r.failed.is_empty());
+        assert_eq!(std::fs::read(dir.join("new/a")).unwrap(), b"a");
+        assert_eq!(std::fs::read(dir.join("new/a.etag")).unwrap(), b"\"1\"");
+        assert_eq!(std::fs::read(dir.join("new/b")).unwrap(), b"new b");
+        assert!(!dir.join("a.cache").exists());
+        let m = std::fs::metadata(dir.join("new/a")).unwrap();
+        assert_eq!(m.modified().unwrap(), modified);
+        assert!(migrate_cache(&DataSource::StdReadFile, &new).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use crate::*;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    /// 同时读取的文件数
+    pub parallelism: usize,
+    /// 删除本地目录中来源里已不存在的文件
+    pub delete_extraneous: bool,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            parallelism: 4,
+            delete_extraneous: false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub deleted: Vec<String>,
+    pub failed: Vec<(String, FetchError)>,
+}
+
+/// 把可列出的来源 (见 [`SyncFolderSource::list_files`]) 同步到本地目录 `dir`.
+///
+/// 列出时来源给出了大小和 mtime (见 [`SyncFolderSource::list_entries`]) 且与本地文件相同时
+/// 不会读取该文件; 否则读取后与本地文件比较, 只有内容不同时才会写入. 写入后本地文件的 mtime
+/// 设为来源的 mtime, 下次同步时即可跳过.
+/// 单个文件读取失败不会中断同步, 会记录在 [`SyncReport::failed`] 中
+pub fn sync_to_folder(
+    source: &(dyn SyncFolderSource + Sync),
+    dir: &Path,
+    opts: &SyncOptions,
+) -> Result<SyncReport, FetchError> {
+    source.capabilities().require_listable()?;
+    let entries = source.list_entries()?;
+    let names: Vec<String> = entries.iter().map(|e| e.name.clone()).collect();
+    let by_name: HashMap<&str, &ListEntry> = entries.iter().map(|e| (e.name.as_str(), e)).collect();
+    sync_names(&names, dir, opts, &|_, _| {}, |n| {
+        sync_one(source, dir, by_name[n])
+    })
+}
+
+/// 以 `opts.parallelism` 个线程对每个文件调用 `f` (返回是否写入了文件), 每完成一个调用一次
+/// `progress(已完成数, 总数)`, 最后按需删除多余的本地文件
+fn sync_names(
+    names: &[String],
+    dir: &Path,
+    opts: &SyncOptions,
+    progress: &(dyn Fn(usize, usize) + Sync),
+    f: impl Fn(&str) -> Result<bool, FetchError> + Sync,
+) -> Result<SyncReport, FetchError> {
+    std::fs::create_dir_all(dir).map_err(|e| local_io_error(e, dir))?;
+
+    let report = Mutex::new(SyncReport::default());
+    let done = std::sync::atomic::AtomicUsize::new(0);
+    let total = names.len();
+    let chunk = total.div_ceil(opts.parallelism.max(1)).max(1);
+    std::thread::scope(|s| {
+        for part in names.chunks(chunk) {
+            let (report, done, f) = (&report, &done, &f);
+            s.spawn(move || {
+                for n in part {
+                    let r = f(n);
+                    let mut report = report.lock().unwrap_or_else(|e| e.into_inner());
+                    match r {
+                        Ok(true) => report.updated.push(n.clone()),
+                        Ok(false) => report.unchanged.push(n.clone()),
+                        Err(e) => report.failed.push((n.clone(), e)),
+                    }
+                    drop(report);
+                    progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+                }
+            });
+        }
+    });
+    let mut report = report.into_inner().unwrap_or_else(|e| e.into_inner());
+
+    if opts.delete_extraneous {
+        let keep: std::collections::HashSet<_> = names.iter().collect();
+        let local: Vec<_> = DataSource::Folders(vec![dir.to_string_lossy().to_string()])
+            .list_files()?
+            .into_iter()
+            .filter(|f| !keep.contains(f))
+            .collect();
+        for f in local {
+            let p = dir.join(&f);
+            std::fs::remove_file(&p).map_err(|e| local_io_error(e, &p))?;
+            report.deleted.push(f);
+        }
+    }
+    Ok(report)
+}
+
+/// 文件名对应的本地相对路径, 含有 `..` 或绝对路径时拒绝
+fn local_rel(name: &str) -> Result<PathBuf, FetchError> {
+    let rel = normalize_separators(Path::new(name)).into_owned();
+    if rel
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(FetchError::Lookup(LookupError::Denied(name.to_string())));
+    }
+    Ok(rel)
+}
+
+fn write_file(p: &Path, d: &[u8]) -> Result<(), FetchError> {
+    if let Some(parent) = p.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(p, d).map_err(|e| local_io_error(e, p))
+}
+
+fn sync_one(
+    source: &(dyn SyncFolderSource + Sync),
+    dir: &Path,
+    entry: &ListEntry,
+) -> Result<bool, FetchError> {
+    let rel = local_rel(&entry.name)?;
+    let p = dir.join(&rel);
+    if let (Some(size), Some(mtime)) = (entry.size, entry.mtime) {
+        let local = std::fs::metadata(&p).ok();
+        if local.is_some_and(|m| m.is_file() && m.len() == size && unix_mtime(&m) == Some(mtime)) {
+            return Ok(false);
+        }
+    }
+    let (d, _) = source.get_file_content(&rel)?;
+    let changed = !std::fs::read(&p).is_ok_and(|old| old == d);
+    if changed {
+        write_file(&p, &d)?;
+    }
+    if let Some(mtime) = entry.mtime {
+        let t = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(mtime);
+        std::fs::File::options()
+            .write(true)
+            .open(&p)
+            .and_then(|f| f.set_modified(t))
+            .map_err(|e| local_io_error(e, &p))?;
+    }
+    Ok(changed)
+}
+
+/// 从 [`file_server::DataSourceService`] 提供的服务同步到本地目录 `dir`:
+/// 下载 `{url}/__manifest`, 与本地文件的 sha256 比较, 只从 `{url}/files/<path>` 下载变化的文件,
+/// 下载的内容必须与清单一致. `progress(已完成数, 总数)` 在每个文件处理完后调用
+#[cfg(all(feature = "reqwest", feature = "manifest"))]
+pub fn sync_from_manifest(
+    url: &str,
+    dir: &Path,
+    opts: &SyncOptions,
+    progress: &(dyn Fn(usize, usize) + Sync),
+) -> Result<SyncReport, FetchError> {
+    let url = url.trim_end_matches('/');
+    let hs = HttpSource {
+        url: format!("{url}/__manifest"),
+        ..Default::default()
+    };
+    let r = hs.send()?;
+    if !r.status().is_success() {
+        return Err(HttpError::Status {
+            url: hs.url,
+            status: r.status().as_u16(),
+        }
+        .into());
+    }
+    let m = manifest::Manifest::from_json(&read_body(r)?)?;
+    let files = http_folder::HttpFolderSource::new(&format!("{url}/files/"));
+    let names: Vec<String> = m.files.iter().map(|e| e.path.clone()).collect();
+    sync_names(&names, dir, opts, progress, |n| {
+        let p = dir.join(local_rel(n)?);
+        if std::fs::read(&p).is_ok_and(|old| m.verify(n, &old)) {
+            return Ok(false);
+        }
+        let (d, _) = files.get_file_content(Path::new(n))?;
+        if !m.verify(n, &d) {
+            return Err(FetchError::Invalid(format!("{n}: sha256 mismatch")));
+        }
+        write_file(&p, &d)?;
+        Ok(true)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sync_to_folder() {
+        let ds = DataSource::FileMap(
+            vec![
+                ("a.txt".to_string(), SingleFileSource::Inline(b"a".to_vec())),
+                (
+                    "sub/b.txt".to_string(),
+                    SingleFileSource::Inline(b"b".to_vec()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+        std::fs::write(temp_dir.path().join("old.txt"), "old").unwrap();
+
+        let opts = SyncOptions {
+            delete_extraneous: true,
+            ..Default::default()
+        };
+        let r = sync_to_folder(&ds, temp_dir.path(), &opts).unwrap();
+        assert_eq!(r.updated, vec!["sub/b.txt"]);
+        assert_eq!(r.unchanged, vec!["a.txt"]);
+        assert_eq!(r.deleted, vec!["old.txt"]);
+        assert!(r.failed.is_empty());
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("sub/b.txt")).unwrap(),
+            b"b"
+        );
+    }
+
+    /// 记录读取次数的来源
+    #[derive(Debug)]
+    struct Counting(DataSource, AtomicUsize);
+
+    impl SyncFolderSource for Counting {
+        fn get_file_content(
+            &self,
+            file_name: &Path,
+        ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            self.0.get_file_content(file_name)
+        }
+
+        fn list_files(&self) -> Result<Vec<String>, FetchError> {
+            SyncFolderSource::list_files(&self.0)
+        }
+
+        fn list_entries(&self) -> Result<Vec<ListEntry>, FetchError> {
+            self.0.list_entries()
+        }
+
+        fn capabilities(&self) -> capabilities::Capabilities {
+            self.0.capabilities()
+        }
+    }
+
+    #[test]
+    fn test_sync_skips_unchanged_by_metadata() {
+        let src = TempDir::new().unwrap();
+        std::fs::create_dir(src.path().join("sub")).unwrap();
+        std::fs::write(src.path().join("a.txt"), "a").unwrap();
+        std::fs::write(src.path().join("sub/b.txt"), "b").unwrap();
+        let source = Counting(
+            DataSource::Folders(vec![src.path().to_string_lossy().to_string()]),
+            AtomicUsize::new(0),
+        );
+        let dst = TempDir::new().unwrap();
+        let opts = SyncOptions::default();
+
+        let r = sync_to_folder(&source, dst.path(), &opts).unwrap();
+        assert_eq!(r.updated.len(), 2);
+        assert_eq!(source.1.load(Ordering::Relaxed), 2);
+
+        // 大小和 mtime 都相同, 不再读取来源
+        let r = sync_to_folder(&source, dst.path(), &opts).unwrap();
+        assert_eq!(r.unchanged.len(), 2);
+        assert_eq!(source.1.load(Ordering::Relaxed), 2);
+
+        std::fs::write(src.path().join("a.txt"), "aa").unwrap();
+        let r = sync_to_folder(&source, dst.path(), &opts).unwrap();
+        assert_eq!(r.updated, vec!["a.txt"]);
+        assert_eq!(source.1.load(Ordering::Relaxed), 3);
+        assert_eq!(std::fs::read(dst.path().join("a.txt")).unwrap(), b"aa");
+    }
+
+    #[cfg(all(feature = "reqwest", feature = "manifest"))]
+    #[test]
+    fn test_sync_from_manifest() {
+        use std::io::{BufRead, BufReader, Write};
+
+        let ds = DataSource::FileMap(HashMap::from([
+            ("a.txt".to_string(), SingleFileSource::Inline(b"a".to_vec())),
+            (
+                "sub/b.txt".to_string(),
+                SingleFileSource::Inline(b"b".to_vec()),
+            ),
+            (
+                "bad.txt".to_string(),
+                SingleFileSource::Inline(b"x".to_vec()),
+            ),
+        ]));
+        let mut m = ds.manifest().unwrap();
+        m.files[1].sha256 = cas::sha256_hex(b"y");
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", l.local_addr().unwrap());
+        let requests = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let reqs = requests.clone();
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let line = BufReader::new(&s).lines().next().unwrap().unwrap();
+                let path = line.split(' ').nth(1).unwrap().to_string();
+                let body = match path.strip_prefix("/files/") {
+                    Some(p) => ds.get_file_content(Path::new(p)).unwrap().0,
+                    None => m.to_json().into_bytes(),
+                };
+                reqs.lock().unwrap().push(path);
+                write!(
+                    s,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                s.write_all(&body).unwrap();
+            }
+        });
+
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+        std::fs::write(temp_dir.path().join("old.txt"), "old").unwrap();
+        let opts = SyncOptions {
+            delete_extraneous: true,
+            ..Default::default()
+        };
+        let calls = Mutex::new(Vec::new());
+        let r = sync_from_manifest(&url, temp_dir.path(), &opts, &|done, total| {
+            calls.lock().unwrap().push((done, total))
+        })
+        .unwrap();
+        assert_eq!(r.updated, vec!["sub/b.txt"]);
+        assert_eq!(r.unchanged, vec!["a.txt"]);
+        assert_eq!(r.failed.len(), 1);
+        assert_eq!(r.failed[0].0, "bad.txt");
+        assert_eq!(r.deleted, vec!["old.txt"]);
+        let mut calls = calls.into_inner().unwrap();
+        calls.sort();
+        assert_eq!(calls, [(1, 3), (2, 3), (3, 3)]);
+        let mut reqs = requests.lock().unwrap().clone();
+        reqs.sort();
+        assert_eq!(reqs, ["/__manifest", "/files/bad.txt", "/files/sub/b.txt"]);
+    }
+}
@@ -0,0 +1,215 @@
+//! 同一个文件的多个下载地址 (如 geodata 的多个 CDN), 按顺序尝试直到成功.
+//! 多个完整数据源之间的切换见 [`mirror_set::MirrorSet`]
+
+use crate::*;
+use std::sync::Mutex;
+
+#[derive(Debug, Default)]
+pub struct MirrorHttpSource {
+    /// 按顺序尝试的 url
+    pub urls: Vec<String>,
+    /// 请求时使用的代理, 请求头, 大小限制等, 其 url 会被覆盖
+    pub http: HttpSource,
+    last_used: Mutex<Option<usize>>,
+}
+
+impl Clone for MirrorHttpSource {
+    fn clone(&self) -> Self {
+        Self {
+            urls: self.urls.clone(),
+            http: self.http.clone(),
+            last_used: Mutex::new(self.last_used()),
+        }
+    }
+}
+
+impl MirrorHttpSource {
+    pub fn new<S: AsRef<str>>(urls: &[S]) -> Self {
+        Self {
+            urls: urls.iter().map(|u| u.as_ref().to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_http(mut self, http: HttpSource) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// 上一次成功读取时使用的 url 在 `urls` 中的序号
+    pub fn last_used(&self) -> Option<usize> {
+        *self.last_used.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn source(&self, i: usize) -> HttpSource {
+        HttpSource {
+            url: self.urls[i].clone(),
+            ..self.http.clone()
+        }
+    }
+
+    /// 与 [`HttpSource::fetch`] 不同, 非 2xx 的响应也视为失败, 以便尝试下一个 url
+    fn fetch_one(&self, i: usize) -> Result<Vec<u8>, FetchError> {
+        let hs = self.source(i);
+        if !is_remote(&hs) {
+            return hs.fetch();
+        }
+        let hs = hs.apply_proxy_rules().unwrap_or(hs);
+        #[cfg(feature = "tokio")]
+        let _permit = acquire_fetch_permit_blocking();
+        let r = hs.send()?;
+        check_status(&hs, r.status())?;
+        read_body(r)
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn fetch_one_async(&self, i: usize) -> Result<Vec<u8>, FetchError> {
+        let hs = self.source(i);
+        if !is_remote(&hs) {
+            return hs.fetch_async().await;
+        }
+        let hs = hs.apply_proxy_rules().unwrap_or(hs);
+        let _permit = acquire_fetch_permit().await;
+        let r = hs.send_async().await?;
+        check_status(&hs, r.status())?;
+        read_body_async(r).await
+    }
+
+    fn done(
+        &self,
+        i: usize,
+        r: Result<Vec<u8>, FetchError>,
+    ) -> Result<(Vec<u8>, usize), FetchError> {
+        match r {
+            Ok(d) => {
+                *self.last_used.lock().unwrap_or_else(|e| e.into_inner()) = Some(i);
+                Ok((d, i))
+            }
+            Err(e) => {
+                debug!("mirror {} failed: {e}", self.urls[i]);
+                Err(e)
+            }
+        }
+    }
+
+    /// 依次尝试各个 url, 返回内容和成功的 url 的序号. 全部失败时返回最后一个错误
+    pub fn fetch_mirrored(&self) -> Result<(Vec<u8>, usize), FetchError> {
+        let mut last = FetchError::Unsupported("empty mirror list");
+        for i in 0..self.urls.len() {
+            match self.done(i, self.fetch_one(i)) {
+                Ok(r) => return Ok(r),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn fetch_mirrored_async(&self) -> Result<(Vec<u8>, usize), FetchError> {
+        let mut last = FetchError::Unsupported("empty mirror list");
+        for i in 0..self.urls.len() {
+            match self.done(i, self.fetch_one_async(i).await) {
+                Ok(r) => return Ok(r),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+}
+
+/// 本地文件和 unix socket 没有 http 状态, 直接用 fetch 读取
+fn is_remote(hs: &HttpSource) -> bool {
+    hs.local_path().is_none() && unix_socket::parse_unix_url(&hs.url).is_none()
+}
+
+fn check_status(hs: &HttpSource, status: reqwest::StatusCode) -> Result<(), FetchError> {
+    if status.is_success() {
+        return Ok(());
+    }
+    Err(HttpError::Status {
+        url: hs.url.clone(),
+        status: status.as_u16(),
+    }
+    .into())
+}
+
+impl SyncSource for MirrorHttpSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.fetch_mirrored().map(|(d, _)| d)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for MirrorHttpSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        self.fetch_mirrored_async().await.map(|(d, _)| d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+
+    /// 对每个请求返回 `status` 和 `body`
+    fn serve(status: &'static str, body: &'static str) -> String {
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/geoip.dat", l.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let lines = BufReader::new(&s).lines().map(|l| l.unwrap());
+                lines.take_while(|l| !l.is_empty()).for_each(drop);
+                write!(
+                    s,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    fn closed_port() -> String {
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/geoip.dat", l.local_addr().unwrap())
+    }
+
+    #[test]
+    fn test_mirror_http_source() {
+        let bad = serve("503 Service Unavailable", "busy");
+        let good = serve("200 OK", "geo");
+        let s = MirrorHttpSource::new(&[closed_port(), bad.clone(), good]);
+        assert_eq!(s.last_used(), None);
+        assert_eq!(s.fetch_mirrored().unwrap(), (b"geo".to_vec(), 2));
+        assert_eq!(s.last_used(), Some(2));
+        assert_eq!(s.fetch().unwrap(), b"geo");
+
+        let e = MirrorHttpSource::new(&[closed_port(), bad])
+            .fetch()
+            .unwrap_err();
+        assert!(
+            matches!(e, FetchError::Http(HttpError::Status { status: 503, .. })),
+            "{e:?}"
+        );
+        assert!(MirrorHttpSource::new::<&str>(&[]).fetch().is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_mirror_http_source_async() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cf = temp_dir.path().join("geoip.dat");
+        let s = MirrorHttpSource::new(&[closed_port(), serve("200 OK", "geo")]);
+        let fc = FileCache {
+            update_interval_seconds: Some(60),
+            cache_file_path: Some(cf.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        assert_eq!(fetch_with_cache_async(&fc, &s).await.unwrap(), b"geo");
+        assert_eq!(s.last_used(), Some(1));
+        assert_eq!(std::fs::read(&cf).unwrap(), b"geo");
+    }
+}
@@ -0,0 +1,283 @@
+use crate::*;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// [`MirrorSet`] 中的一个候选
+#[derive(Debug)]
+pub struct Mirror {
+    pub name: String,
+    pub source: DataSource,
+    /// 越小越优先, 只有同一优先级的候选都失败时才会尝试下一优先级
+    pub priority: u32,
+    /// 同一优先级内的权重
+    pub weight: u32,
+}
+
+impl Mirror {
+    pub fn new(name: &str, source: DataSource) -> Self {
+        Self {
+            name: name.to_string(),
+            source,
+            priority: 0,
+            weight: 1,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// 一个候选的健康状况
+#[derive(Debug, Clone, Default)]
+pub struct MirrorHealth {
+    pub consecutive_failures: u32,
+    /// 成功请求耗时的指数移动平均, 毫秒
+    pub latency_ewma_ms: f64,
+    last_failure: Option<Instant>,
+}
+
+/// 连续失败多少次后视为不健康
+const UNHEALTHY_FAILURES: u32 = 3;
+const EWMA_ALPHA: f64 = 0.3;
+
+/// 在多个镜像中读取文件. 按优先级分组, 组内按权重和健康状况 (连续失败次数, 耗时) 排序,
+/// 依次尝试直到成功. 不健康的候选在 `cooldown` 内排在最后
+#[derive(Debug)]
+pub struct MirrorSet {
+    pub mirrors: Vec<Mirror>,
+    pub cooldown: Duration,
+    health: Mutex<Vec<MirrorHealth>>,
+}
+
+impl MirrorSet {
+    pub fn new(mirrors: Vec<Mirror>) -> Self {
+        let health = vec![MirrorHealth::default(); mirrors.len()];
+        Self {
+            mirrors,
+            cooldown: Duration::from_secs(30),
+            health: Mutex::new(health),
+        }
+    }
+
+    pub fn health(&self) -> Vec<MirrorHealth> {
+        self.health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 本次请求尝试候选的顺序
+    pub fn order(&self) -> Vec<usize> {
+        let health = self.health();
+        let score = |i: usize| {
+            let h = &health[i];
+            let unhealthy = h.consecutive_failures >= UNHEALTHY_FAILURES
+                && h.last_failure.is_some_and(|t| t.elapsed() < self.cooldown);
+            let s = self.mirrors[i].weight as f64 * 0.5f64.powi(h.consecutive_failures as i32)
+                / (1.0 + h.latency_ewma_ms / 100.0);
+            (unhealthy, self.mirrors[i].priority, -s)
+        };
+        let mut v: Vec<usize> = (0..self.mirrors.len()).collect();
+        v.sort_by(|a, b| {
+            let (a, b) = (score(*a), score(*b));
+            (a.0, a.1).cmp(&(b.0, b.1)).then(a.2.total_cmp(&b.2))
+        });
+        v
+    }
+
+    fn record<T>(&self, i: usize, r: &Result<T, FetchError>, elapsed: Duration) {
+        let mut health = self.health.lock().unwrap_or_else(|e| e.into_inner());
+        let h = &mut health[i];
+        match r {
+            Ok(_) => {
+                let ms = elapsed.as_secs_f64() * 1000.0;
+                h.latency_ewma_ms = if h.latency_ewma_ms == 0.0 {
+                    ms
+                } else {
+                    EWMA_ALPHA * ms + (1.0 - EWMA_ALPHA) * h.latency_ewma_ms
+                };
+                h.consecutive_failures = 0;
+            }
+            // 文件不存在不代表镜像不健康
+            Err(e) if e.is_not_found() => {}
+            Err(_) => {
+                h.consecutive_failures += 1;
+                h.last_failure = Some(Instant::now());
+            }
+        }
+    }
+
+    /// 把健康状况保存到文件, 每行 `name\tconsecutive_failures\tlatency_ewma_ms`
+    pub fn save_health(&self, path: &Path) -> Result<(), FetchError> {
+        let health = self.health();
+        let s: String = self
+            .mirrors
+            .iter()
+            .zip(health.iter())
+            .map(|(m, h)| {
+                format!(
+                    "{}\t{}\t{}\n",
+                    m.name, h.consecutive_failures, h.latency_ewma_ms
+                )
+            })
+            .collect();
+        std::fs::write(path, s).map_err(|e| local_io_error(e, path))?;
+        Ok(())
+    }
+
+    /// 读取 [`MirrorSet::save_health`] 保存的健康状况, 按名称匹配, 忽略无法解析的行
+    pub fn load_health(&self, path: &Path) -> Result<(), FetchError> {
+        let s = String::from_utf8_lossy(&read_local_file(path)?).to_string();
+        let mut health = self.health.lock().unwrap_or_else(|e| e.into_inner());
+        for line in s.lines() {
+            let mut it = line.split('\t');
+            let (Some(name), Some(f), Some(l)) = (it.next(), it.next(), it.next()) else {
+                continue;
+            };
+            let (Ok(f), Ok(l)) = (f.parse(), l.parse()) else {
+                continue;
+            };
+            if let Some(i) = self.mirrors.iter().position(|m| m.name == name) {
+                health[i].consecutive_failures = f;
+                health[i].latency_ewma_ms = l;
+                if f >= UNHEALTHY_FAILURES {
+                    health[i].last_failure = Some(Instant::now());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SyncFolderSource for MirrorSet {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed(file_name)
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.get_file_content_ctx(file_name, &FetchContext::default())
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let mut last = FetchError::not_found(file_name.to_string_lossy());
+        for i in self.order() {
+            let start = Instant::now();
+            let r = self.mirrors[i].source.get_file_content_ctx(file_name, ctx);
+            self.record(i, &r, start.elapsed());
+            match r {
+                Ok(fc) => return Ok(fc),
+                Err(e) => {
+                    debug!("mirror {} failed: {e}", self.mirrors[i].name);
+                    last = e
+                }
+            }
+        }
+        Err(last)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for MirrorSet {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed_async(file_name)
+            .await
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        self.get_file_content_ctx_async(file_name, &FetchContext::default())
+            .await
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let mut last = FetchError::not_found(file_name.to_string_lossy());
+        for i in self.order() {
+            let start = Instant::now();
+            let r = self.mirrors[i]
+                .source
+                .get_file_content_ctx_async(file_name, ctx)
+                .await;
+            self.record(i, &r, start.elapsed());
+            match r {
+                Ok(fc) => return Ok(fc),
+                Err(e) => {
+                    debug!("mirror {} failed: {e}", self.mirrors[i].name);
+                    last = e
+                }
+            }
+        }
+        Err(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn inline(d: &[u8]) -> DataSource {
+        DataSource::FileMap(
+            vec![("a".to_string(), SingleFileSource::Inline(d.to_vec()))]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_mirror_set() {
+        let temp_dir = TempDir::new().unwrap();
+        // 读取目录会失败, 且不是 not found
+        let broken = DataSource::FileMap(
+            vec![(
+                "a".to_string(),
+                SingleFileSource::FilePath(temp_dir.path().to_string_lossy().to_string()),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let ms = MirrorSet::new(vec![
+            Mirror::new("broken", broken).with_weight(10),
+            Mirror::new("light", inline(b"light")).with_weight(1),
+            Mirror::new("backup", inline(b"backup")).with_priority(1),
+        ]);
+        assert_eq!(ms.order(), vec![0, 1, 2]);
+        for _ in 0..UNHEALTHY_FAILURES {
+            assert_eq!(ms.get_file_content(Path::new("a")).unwrap().0, b"light");
+        }
+        assert_eq!(ms.order(), vec![1, 2, 0]);
+
+        let p = temp_dir.path().join("health");
+        ms.save_health(&p).unwrap();
+        let ms2 = MirrorSet::new(vec![
+            Mirror::new("broken", inline(b"ok")).with_weight(10),
+            Mirror::new("light", inline(b"light")),
+        ]);
+        ms2.load_health(&p).unwrap();
+        assert_eq!(ms2.order(), vec![1, 0]);
+    }
+}
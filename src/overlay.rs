@@ -0,0 +1,171 @@
+use crate::*;
+use std::collections::HashSet;
+
+/// 叠加层中的一层. `whiteouts` 中的文件在这一层及其下面的所有层中都视为不存在,
+/// 用于在只读的 tar 等来源上模拟 "删除"
+#[derive(Debug, Default)]
+pub struct OverlayLayer {
+    pub source: DataSource,
+    pub whiteouts: HashSet<String>,
+}
+
+impl OverlayLayer {
+    pub fn new(source: DataSource) -> Self {
+        Self {
+            source,
+            whiteouts: HashSet::new(),
+        }
+    }
+
+    pub fn with_whiteout(mut self, file_name: &str) -> Self {
+        self.whiteouts.insert(file_name.replace('\\', "/"));
+        self
+    }
+
+    pub(crate) fn hides(&self, file_name: &Path) -> bool {
+        !self.whiteouts.is_empty()
+            && self
+                .whiteouts
+                .contains(normalize_separators(file_name).to_string_lossy().as_ref())
+    }
+}
+
+/// 按顺序在多个来源中查找文件, 前面的层优先. 某一层找不到时继续查找下一层,
+/// 遇到 whiteout 时直接返回 [`LookupError::NotFound`]
+#[derive(Debug, Default)]
+pub struct OverlaySource {
+    pub layers: Vec<OverlayLayer>,
+}
+
+impl OverlaySource {
+    pub fn new(layers: Vec<OverlayLayer>) -> Self {
+        Self { layers }
+    }
+}
+
+impl SyncFolderSource for OverlaySource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed(file_name)
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.get_file_content_ctx(file_name, &FetchContext::default())
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        for l in &self.layers {
+            if l.hides(file_name) {
+                return Err(FetchError::not_found(file_name.to_string_lossy()));
+            }
+            match l.source.get_file_content_ctx(file_name, ctx) {
+                Err(e) if e.is_not_found() => continue,
+                r => return r,
+            }
+        }
+        Err(FetchError::not_found(file_name.to_string_lossy()))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.layers
+            .iter()
+            .fold(
+                capabilities::Capabilities::READ_ONLY.with_listable(),
+                |c, l| c.intersect(l.source.capabilities()),
+            )
+            .read_only()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        let mut hidden = HashSet::new();
+        let mut v = Vec::new();
+        for l in &self.layers {
+            hidden.extend(l.whiteouts.iter().cloned());
+            for f in l.source.list_files()? {
+                if hidden.insert(f.clone()) {
+                    v.push(f);
+                }
+            }
+        }
+        Ok(v)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for OverlaySource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed_async(file_name)
+            .await
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        self.get_file_content_ctx_async(file_name, &FetchContext::default())
+            .await
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        for l in &self.layers {
+            if l.hides(file_name) {
+                return Err(FetchError::not_found(file_name.to_string_lossy()));
+            }
+            match l.source.get_file_content_ctx_async(file_name, ctx).await {
+                Err(e) if e.is_not_found() => continue,
+                r => return r,
+            }
+        }
+        Err(FetchError::not_found(file_name.to_string_lossy()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_map(entries: &[(&str, &[u8])]) -> DataSource {
+        DataSource::FileMap(
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), SingleFileSource::Inline(v.to_vec())))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_overlay_whiteout() {
+        let o = OverlaySource::new(vec![
+            OverlayLayer::new(file_map(&[("a", b"upper")])).with_whiteout("b"),
+            OverlayLayer::new(file_map(&[("a", b"lower"), ("b", b"b"), ("c", b"c")])),
+        ]);
+
+        assert_eq!(o.get_file_content(Path::new("a")).unwrap().0, b"upper");
+        assert!(matches!(
+            o.get_file_content(Path::new("b")),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+        assert_eq!(o.get_file_content(Path::new("c")).unwrap().0, b"c");
+        assert!(matches!(
+            o.get_file_content(Path::new("d")),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+
+        let mut files = o.list_files().unwrap();
+        files.sort();
+        assert_eq!(files, vec!["a", "c"]);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::*;
+
+/// 把请求的文件名中的 `{os}` 和 `{arch}` 替换为目标平台, 这样同一份 FileMap
+/// 可以描述各平台的文件, 如 `tool-{os}-{arch}` 对应 `tool-linux-x86_64` 等条目.
+///
+/// 默认使用当前平台 ([`std::env::consts::OS`], [`std::env::consts::ARCH`])
+#[derive(Debug)]
+pub struct PlatformSource<S> {
+    pub inner: S,
+    pub os: String,
+    pub arch: String,
+}
+
+impl<S> PlatformSource<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+
+    pub fn with_target(mut self, os: &str, arch: &str) -> Self {
+        self.os = os.to_string();
+        self.arch = arch.to_string();
+        self
+    }
+
+    /// 替换后的文件名
+    pub fn expand<'a>(&self, file_name: &'a Path) -> Cow<'a, Path> {
+        let s = file_name.to_string_lossy();
+        if !s.contains("{os}") && !s.contains("{arch}") {
+            return Cow::Borrowed(file_name);
+        }
+        Cow::Owned(PathBuf::from(
+            s.replace("{os}", &self.os).replace("{arch}", &self.arch),
+        ))
+    }
+}
+
+impl<S: SyncFolderSource> SyncFolderSource for PlatformSource<S> {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.inner.get_file_content(&self.expand(file_name))
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.inner.get_file_content_typed(&self.expand(file_name))
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        self.inner
+            .get_file_content_ctx(&self.expand(file_name), ctx)
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.inner.capabilities().read_only()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        self.inner.list_files()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl<S: AsyncFolderSource + Send + Sync> AsyncFolderSource for PlatformSource<S> {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let p = self.expand(file_name).into_owned();
+        self.inner.get_file_content_async(&p).await
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        let p = self.expand(file_name).into_owned();
+        self.inner.get_file_content_typed_async(&p).await
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let p = self.expand(file_name).into_owned();
+        self.inner.get_file_content_ctx_async(&p, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_source() {
+        let ps = PlatformSource::new(DataSource::FileMap(
+            vec![
+                (
+                    "tool-linux-x86_64".to_string(),
+                    SingleFileSource::Inline(b"linux".to_vec()),
+                ),
+                (
+                    "tool-windows-x86_64".to_string(),
+                    SingleFileSource::Inline(b"windows".to_vec()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ))
+        .with_target("windows", "x86_64");
+
+        let r = ps.get_file_content(Path::new("tool-{os}-{arch}")).unwrap();
+        assert_eq!(r.0, b"windows");
+        let r = ps.get_file_content(Path::new("tool-linux-x86_64")).unwrap();
+        assert_eq!(r.0, b"linux");
+    }
+}
@@ -0,0 +1,146 @@
+//! 定期重新读取一个 [`AsyncSource`], 只在内容 (按哈希比较) 变化时通知,
+//! 用于没有推送机制的远程配置
+
+use crate::*;
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::watch, task::JoinHandle};
+
+#[derive(Clone)]
+pub struct PollingSource {
+    pub source: Arc<dyn AsyncSource>,
+    pub interval: Duration,
+}
+
+impl std::fmt::Debug for PollingSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PollingSource")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PollingSource {
+    pub fn new(source: impl AsyncSource + 'static, interval: Duration) -> Self {
+        Self {
+            source: Arc::new(source),
+            interval,
+        }
+    }
+
+    /// 先读取一次作为初始值 (失败时返回错误), 之后每隔 `interval` 重新读取,
+    /// 内容变化时发送新内容. 读取失败时保留旧值. 所有 Receiver 都被丢弃后后台任务结束
+    pub async fn watch(&self) -> Result<watch::Receiver<Vec<u8>>, FetchError> {
+        let d = self.source.fetch_async().await?;
+        let hash = stable_hash_bytes(&d);
+        let (tx, rx) = watch::channel(d);
+        let s = self.clone();
+        tokio::spawn(async move {
+            s.poll(Some(hash), |d| tx.send(d).is_ok(), || tx.is_closed())
+                .await
+        });
+        Ok(rx)
+    }
+
+    /// 每次内容变化时 (包括第一次成功读取) 调用 `f`, 直到返回的任务被 abort
+    pub fn spawn_with_callback<F>(&self, mut f: F) -> JoinHandle<()>
+    where
+        F: FnMut(Vec<u8>) + Send + 'static,
+    {
+        let s = self.clone();
+        tokio::spawn(async move {
+            s.poll(
+                None,
+                move |d| {
+                    f(d);
+                    true
+                },
+                || false,
+            )
+            .await
+        })
+    }
+
+    /// `changed` 返回 false 或 `closed` 返回 true 时结束
+    async fn poll(
+        &self,
+        mut last: Option<u64>,
+        mut changed: impl FnMut(Vec<u8>) -> bool,
+        closed: impl Fn() -> bool,
+    ) {
+        let mut t = tokio::time::interval(self.interval);
+        t.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        if last.is_some() {
+            t.tick().await;
+        }
+        loop {
+            t.tick().await;
+            if closed() {
+                return;
+            }
+            match self.source.fetch_async().await {
+                Ok(d) => {
+                    let h = stable_hash_bytes(&d);
+                    if last == Some(h) {
+                        continue;
+                    }
+                    last = Some(h);
+                    if !changed(d) {
+                        return;
+                    }
+                }
+                Err(e) => warn!("polling fetch failed: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// 依次返回 `values` 中的内容, 用完后重复最后一个
+    struct Seq(Mutex<Vec<Result<&'static str, ()>>>);
+
+    #[async_trait::async_trait]
+    impl AsyncSource for Seq {
+        async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+            let mut v = self.0.lock().unwrap();
+            let r = if v.len() > 1 { v.remove(0) } else { v[0] };
+            r.map(|s| s.as_bytes().to_vec())
+                .map_err(|_| FetchError::Unsupported("seq"))
+        }
+    }
+
+    fn seq(values: &[Result<&'static str, ()>]) -> PollingSource {
+        PollingSource::new(Seq(Mutex::new(values.to_vec())), Duration::from_millis(5))
+    }
+
+    #[tokio::test]
+    async fn test_polling_watch() {
+        let s = seq(&[Ok("a"), Ok("a"), Err(()), Ok("b"), Ok("b"), Ok("c")]);
+        let mut rx = s.watch().await.unwrap();
+        assert_eq!(*rx.borrow_and_update(), b"a");
+        rx.changed().await.unwrap();
+        assert_eq!(*rx.borrow_and_update(), b"b");
+        rx.changed().await.unwrap();
+        assert_eq!(*rx.borrow_and_update(), b"c");
+        let r = tokio::time::timeout(Duration::from_millis(50), rx.changed()).await;
+        assert!(r.is_err());
+
+        assert!(seq(&[Err(())]).watch().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_polling_callback() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let h = seq(&[Err(()), Ok("a"), Ok("a"), Ok("b")]).spawn_with_callback(move |d| {
+            tx.send(d).unwrap();
+        });
+        assert_eq!(rx.recv().await.unwrap(), b"a");
+        assert_eq!(rx.recv().await.unwrap(), b"b");
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(rx.try_recv().is_err());
+        h.abort();
+    }
+}
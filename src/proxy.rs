@@ -0,0 +1,108 @@
+use crate::*;
+
+/// 按 url 的主机名决定是否使用代理. `pattern` 可以是完整主机名, `*.example.com`
+/// (匹配所有子域名) 或 `*` (匹配所有主机). `proxy` 为 None 表示直连
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyRule {
+    pub pattern: String,
+    pub proxy: Option<String>,
+}
+
+impl ProxyRule {
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let p = self.pattern.to_ascii_lowercase();
+        match p.strip_prefix('*') {
+            Some("") => true,
+            Some(suffix) if suffix.starts_with('.') => host.ends_with(suffix),
+            _ => host == p,
+        }
+    }
+}
+
+/// 解析规则文件, 每行 `<pattern> PROXY <proxy url>` 或 `<pattern> DIRECT`,
+/// 空行和 `#` 开头的行被忽略
+pub fn parse_proxy_rules(s: &str) -> Result<Vec<ProxyRule>, FetchError> {
+    let mut v = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let proxy = match parts.as_slice() {
+            [_, d] if d.eq_ignore_ascii_case("DIRECT") => None,
+            [_, p, url] if p.eq_ignore_ascii_case("PROXY") => Some(url.to_string()),
+            _ => {
+                return Err(FetchError::I(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid proxy rule at line {}: {line}", i + 1),
+                )))
+            }
+        };
+        v.push(ProxyRule {
+            pattern: parts[0].to_string(),
+            proxy,
+        });
+    }
+    Ok(v)
+}
+
+impl HttpSource {
+    /// 第一条匹配 url 主机名的规则. 返回 None 时按 `proxy` 和 `should_use_proxy` 处理
+    pub fn matching_proxy_rule(&self) -> Option<&ProxyRule> {
+        if self.proxy_rules.is_empty() {
+            return None;
+        }
+        let url = reqwest::Url::parse(&self.url).ok()?;
+        let host = url.host_str()?;
+        self.proxy_rules.iter().find(|r| r.matches(host))
+    }
+
+    /// 有匹配的规则时, 返回按规则设置好代理 (且不再回退) 的副本
+    pub(crate) fn apply_proxy_rules(&self) -> Option<HttpSource> {
+        let r = self.matching_proxy_rule()?;
+        Some(HttpSource {
+            proxy: r.proxy.clone(),
+            should_use_proxy: r.proxy.is_some(),
+            proxy_rules: Vec::new(),
+            ..self.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_rules() {
+        let rules = parse_proxy_rules(
+            "# overseas cdn only\n*.cdn.example.com PROXY http://127.0.0.1:8080\n* DIRECT\n",
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 2);
+
+        let hs = HttpSource {
+            url: "https://a.cdn.example.com/x".to_string(),
+            proxy: Some("http://other:1".to_string()),
+            proxy_rules: rules.clone(),
+            ..Default::default()
+        };
+        let s = hs.apply_proxy_rules().unwrap();
+        assert_eq!(s.proxy.as_deref(), Some("http://127.0.0.1:8080"));
+        assert!(s.should_use_proxy);
+
+        let hs = HttpSource {
+            url: "https://local.example.org/x".to_string(),
+            proxy: Some("http://other:1".to_string()),
+            proxy_rules: rules,
+            ..Default::default()
+        };
+        let s = hs.apply_proxy_rules().unwrap();
+        assert!(s.proxy.is_none());
+        assert!(!s.should_use_proxy);
+
+        assert!(parse_proxy_rules("a b c d").is_err());
+    }
+}
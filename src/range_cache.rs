@@ -0,0 +1,226 @@
+use crate::*;
+
+/// 远程大文件的分块缓存: 按 `block_size` 把文件分成固定大小的块, 通过 http Range 请求
+/// 只下载被读到的块, 并保存在 `dir/<cache_key>/<块序号>` 中.
+///
+/// 上游不支持 Range 时 (返回 200), 会从完整响应中截取对应的块
+#[derive(Clone, Debug)]
+pub struct RangeCache {
+    pub source: HttpSource,
+    pub dir: String,
+    pub block_size: u64,
+}
+
+impl RangeCache {
+    pub fn new(source: HttpSource, dir: &str, block_size: u64) -> Self {
+        Self {
+            source,
+            dir: dir.to_string(),
+            block_size: block_size.max(1),
+        }
+    }
+
+    fn block_path(&self, i: u64) -> PathBuf {
+        Path::new(&self.dir)
+            .join(self.source.cache_key())
+            .join(i.to_string())
+    }
+
+    fn range_header(&self, i: u64) -> String {
+        let start = i * self.block_size;
+        format!("bytes={}-{}", start, start + self.block_size - 1)
+    }
+
+    /// 把响应转为块的内容. 超出文件末尾时返回空
+    fn block_from_response(
+        &self,
+        i: u64,
+        status: u16,
+        body: Vec<u8>,
+    ) -> Result<Vec<u8>, FetchError> {
+        match status {
+            206 => Ok(body),
+            416 => Ok(Vec::new()),
+            200 => {
+                let start = ((i * self.block_size) as usize).min(body.len());
+                let end = (start + self.block_size as usize).min(body.len());
+                Ok(body[start..end].to_vec())
+            }
+            404 => Err(FetchError::not_found(&self.source.url)),
+            status => Err(HttpError::Status {
+                url: self.source.url.clone(),
+                status,
+            }
+            .into()),
+        }
+    }
+
+    fn store(&self, i: u64, d: &[u8]) {
+        let p = self.block_path(i);
+        let tmp = p.with_extension("tmp");
+        let r = std::fs::create_dir_all(p.parent().unwrap())
+            .and_then(|_| std::fs::write(&tmp, d))
+            .and_then(|_| std::fs::rename(&tmp, &p));
+        if let Err(e) = r {
+            warn!("Failed to write block cache file: {e}");
+        }
+    }
+
+    pub fn read_block(&self, i: u64) -> Result<Vec<u8>, FetchError> {
+        let p = self.block_path(i);
+        if p.exists() {
+            return read_local_file(&p);
+        }
+        let c = self.source.blocking_client_builder().build()?;
+        let mut rb = c
+            .get(&self.source.url)
+            .header(reqwest::header::RANGE, self.range_header(i));
+        for (k, v) in self.source.custom_request_headers.iter().flatten() {
+            rb = rb.header(k, v);
+        }
+        let r = rb.send()?;
+        let status = r.status().as_u16();
+        let d = self.block_from_response(i, status, read_body(r)?)?;
+        self.store(i, &d);
+        Ok(d)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn read_block_async(&self, i: u64) -> Result<Vec<u8>, FetchError> {
+        let p = self.block_path(i);
+        if p.exists() {
+            return read_local_file_async(&p).await;
+        }
+        let _permit = acquire_fetch_permit().await;
+        let c = self.source.async_client_builder().build()?;
+        let mut rb = c
+            .get(&self.source.url)
+            .header(reqwest::header::RANGE, self.range_header(i));
+        for (k, v) in self.source.custom_request_headers.iter().flatten() {
+            rb = rb.header(k, v);
+        }
+        let r = rb.send().await?;
+        let status = r.status().as_u16();
+        let d = self.block_from_response(i, status, read_body_async(r).await?)?;
+        self.store(i, &d);
+        Ok(d)
+    }
+
+    fn blocks(&self, offset: u64, len: u64) -> std::ops::Range<u64> {
+        if len == 0 {
+            return 0..0;
+        }
+        offset / self.block_size..(offset + len - 1) / self.block_size + 1
+    }
+
+    fn slice(&self, offset: u64, len: u64, first_block: u64, data: Vec<u8>) -> Vec<u8> {
+        let skip = ((offset - first_block * self.block_size) as usize).min(data.len());
+        let end = (skip + len as usize).min(data.len());
+        data[skip..end].to_vec()
+    }
+
+    /// 读取 `[offset, offset + len)`, 文件较短时返回的数据也较短
+    pub fn read_range(&self, offset: u64, len: u64) -> Result<Vec<u8>, FetchError> {
+        let blocks = self.blocks(offset, len);
+        let first = blocks.start;
+        let mut data = Vec::new();
+        for i in blocks {
+            let b = self.read_block(i)?;
+            let short = (b.len() as u64) < self.block_size;
+            data.extend(b);
+            if short {
+                break;
+            }
+        }
+        Ok(self.slice(offset, len, first, data))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn read_range_async(&self, offset: u64, len: u64) -> Result<Vec<u8>, FetchError> {
+        let blocks = self.blocks(offset, len);
+        let first = blocks.start;
+        let mut data = Vec::new();
+        for i in blocks {
+            let b = self.read_block_async(i).await?;
+            let short = (b.len() as u64) < self.block_size;
+            data.extend(b);
+            if short {
+                break;
+            }
+        }
+        Ok(self.slice(offset, len, first, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tempfile::TempDir;
+
+    /// 支持 Range 的最简单的 http 服务器, 返回请求次数的计数器
+    fn serve(content: &'static [u8]) -> (String, Arc<AtomicUsize>) {
+        let l = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/big", l.local_addr().unwrap());
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = count.clone();
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                c.fetch_add(1, Ordering::SeqCst);
+                let mut range = None;
+                for line in BufReader::new(&s).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(r) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        let (a, b) = r.split_once('-').unwrap();
+                        range = Some((a.parse::<usize>().unwrap(), b.parse::<usize>().unwrap()));
+                    }
+                }
+                let (start, end) = range.unwrap();
+                let resp = if start >= content.len() {
+                    b"HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+                } else {
+                    let body = &content[start..(end + 1).min(content.len())];
+                    let mut r = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    r.extend_from_slice(body);
+                    r
+                };
+                s.write_all(&resp).unwrap();
+            }
+        });
+        (url, count)
+    }
+
+    #[test]
+    fn test_range_cache() {
+        let (url, count) = serve(b"0123456789abcdef");
+        let temp_dir = TempDir::new().unwrap();
+        let rc = RangeCache::new(
+            HttpSource {
+                url,
+                ..Default::default()
+            },
+            &temp_dir.path().to_string_lossy(),
+            4,
+        );
+
+        assert_eq!(rc.read_range(2, 4).unwrap(), b"2345");
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(rc.read_range(4, 2).unwrap(), b"45");
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(rc.read_range(14, 10).unwrap(), b"ef");
+        assert_eq!(rc.read_range(20, 1).unwrap(), b"");
+    }
+}
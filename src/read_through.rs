@@ -0,0 +1,127 @@
+//! 读穿透的镜像: 优先返回本地缓存, 过期后从上游重新下载.
+//! 与 [`http_folder::HttpFolderSource`] 的缓存不同, 只缓存成功 (2xx) 的响应,
+//! 上游不可用时继续返回过期的缓存, 适合为离线环境中的机器做制品镜像.
+//! 配合 [`file_server::DataSourceService::mirror`] 使用
+
+use crate::*;
+
+#[derive(Debug, Clone)]
+pub struct ReadThroughSource {
+    /// 上游地址, 请求头等设置. 其中的 cache_dir 和 update_interval_seconds 决定缓存位置和过期时间
+    pub upstream: http_folder::HttpFolderSource,
+}
+
+impl ReadThroughSource {
+    /// `cache_dir` 应只用于这个镜像, [`Self::purge`] 会清空它
+    pub fn new(base_url: &str, cache_dir: &str, update_interval_seconds: Option<u64>) -> Self {
+        Self {
+            upstream: http_folder::HttpFolderSource::new(base_url)
+                .with_cache_dir(cache_dir, update_interval_seconds),
+        }
+    }
+
+    fn cache_dir(&self) -> &str {
+        self.upstream.cache_dir.as_deref().unwrap_or(".")
+    }
+
+    fn entry(&self, file_name: &Path) -> Result<(HttpSource, FileCache), FetchError> {
+        let s = self
+            .upstream
+            .source_for(file_name)
+            .ok_or_else(|| FetchError::not_found(file_name.to_string_lossy()))?;
+        let fc =
+            FileCache::for_request(self.cache_dir(), &s, self.upstream.update_interval_seconds);
+        Ok((s, fc))
+    }
+
+    /// 从上游下载并写入缓存. 404 时返回 NotFound, 其它非 2xx 状态返回 [`HttpError::Status`]
+    async fn download(&self, s: &HttpSource, fc: &FileCache) -> Result<Vec<u8>, FetchError> {
+        let s = s.apply_proxy_rules().unwrap_or_else(|| s.clone());
+        let _permit = acquire_fetch_permit().await;
+        let r = s.send_async().await?;
+        match r.status() {
+            st if st.is_success() => {
+                let content_type = r
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string());
+                let d = read_body_async(r).await?;
+                tokio::fs::create_dir_all(self.cache_dir()).await?;
+                let info = SourceInfo {
+                    content_type: content_type.as_deref(),
+                    ..SourceInfo::url(&s.url)
+                };
+                fc.write_cache_file_from_async(&d, &info).await;
+                Ok(d)
+            }
+            reqwest::StatusCode::NOT_FOUND => Err(FetchError::not_found(&s.url)),
+            st => Err(HttpError::Status {
+                url: s.url.clone(),
+                status: st.as_u16(),
+            }
+            .into()),
+        }
+    }
+
+    /// 删除 `file_name` 的缓存, 为 None 时删除所有缓存. 返回删除的文件数
+    pub async fn purge(&self, file_name: Option<&Path>) -> Result<usize, FetchError> {
+        let Some(file_name) = file_name else {
+            let mut n = 0;
+            let mut rd = match tokio::fs::read_dir(self.cache_dir()).await {
+                Ok(rd) => rd,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(e) = rd.next_entry().await? {
+                if e.file_type().await?.is_file() {
+                    tokio::fs::remove_file(e.path()).await?;
+                    if !gc::is_cache_sidecar(&e.file_name().to_string_lossy()) {
+                        n += 1;
+                    }
+                }
+            }
+            return Ok(n);
+        };
+        let (_, fc) = self.entry(file_name)?;
+        #[cfg(feature = "cache_meta")]
+        let _ = tokio::fs::remove_file(fc.meta_path().unwrap()).await;
+        match tokio::fs::remove_file(fc.cache_file_path.unwrap()).await {
+            Ok(()) => Ok(1),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 不论缓存是否过期, 立即从上游重新下载
+    pub async fn refresh(&self, file_name: &Path) -> Result<Vec<u8>, FetchError> {
+        let (s, fc) = self.entry(file_name)?;
+        self.download(&s, &fc).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncFolderSource for ReadThroughSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let (s, fc) = self.entry(file_name)?;
+        let timeout = fc.is_cache_timeout()?;
+        if timeout == Some(false) {
+            match fc.read_cache_file_async().await {
+                Err(e) if e.is_cache_corrupt() => {}
+                r => return r.map(|d| (d, Some(s.url))),
+            }
+        }
+        match self.download(&s, &fc).await {
+            Ok(d) => Ok((d, Some(s.url))),
+            // 上游出错 (而不是文件不存在) 时返回过期的缓存
+            Err(e) if timeout == Some(true) && !e.is_not_found() => {
+                warn!("serving stale cache for {}: {e}", s.url);
+                Ok((fc.read_cache_file_async().await?, Some(s.url)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
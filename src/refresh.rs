@@ -0,0 +1,173 @@
+use crate::*;
+use futures::StreamExt;
+
+/// 单个条目的刷新结果
+#[derive(Debug)]
+pub enum RefreshOutcome {
+    /// 下载到新内容并写入了缓存
+    Updated,
+    /// 服务器返回 304, 只更新了缓存文件的修改时间
+    Unchanged,
+    Failed(FetchError),
+}
+
+#[derive(Debug, Default)]
+pub struct RefreshReport {
+    /// 按名称排序
+    pub entries: Vec<(String, RefreshOutcome)>,
+}
+
+impl RefreshReport {
+    fn count(&self, f: impl Fn(&RefreshOutcome) -> bool) -> usize {
+        self.entries.iter().filter(|(_, o)| f(o)).count()
+    }
+    pub fn updated(&self) -> usize {
+        self.count(|o| matches!(o, RefreshOutcome::Updated))
+    }
+    pub fn unchanged(&self) -> usize {
+        self.count(|o| matches!(o, RefreshOutcome::Unchanged))
+    }
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, RefreshOutcome::Failed(_)))
+    }
+}
+
+/// 找到条目中带缓存文件的 Http 来源, 以及需要通过的检查
+fn http_entry(
+    s: &SingleFileSource,
+) -> Option<(
+    &HttpSource,
+    Cow<'_, FileCache>,
+    Option<&validator::Validator>,
+)> {
+    match s {
+        SingleFileSource::Http(hs, fc) => {
+            let fc = s.resolve_cache(fc);
+            fc.cache_file_path.is_some().then_some((hs, fc, None))
+        }
+        SingleFileSource::Annotated(s, _) => http_entry(s),
+        SingleFileSource::Validated(s, v) => {
+            http_entry(s).map(|(hs, fc, inner)| (hs, fc, inner.or(Some(v))))
+        }
+        _ => None,
+    }
+}
+
+async fn refresh_entry(
+    hs: &HttpSource,
+    fc: &FileCache,
+    v: Option<&validator::Validator>,
+) -> Result<RefreshOutcome, FetchError> {
+    let hs = hs.apply_proxy_rules().unwrap_or_else(|| hs.clone());
+    let cf = Path::new(fc.cache_file_path.as_ref().unwrap());
+    let etag_file = fc.etag_path().unwrap();
+
+    let mut req = hs.clone();
+    if cf.exists() {
+        if let Ok(etag) = tokio::fs::read_to_string(&etag_file).await {
+            req.custom_request_headers
+                .get_or_insert_with(Vec::new)
+                .push(("If-None-Match".to_string(), etag.trim().to_string()));
+        }
+    }
+
+    let _permit = acquire_fetch_permit().await;
+    let r = req.send_async().await?;
+    if r.status() == reqwest::StatusCode::NOT_MODIFIED {
+        std::fs::File::options()
+            .append(true)
+            .open(cf)?
+            .set_modified(SystemTime::now())?;
+        #[cfg(feature = "cache_meta")]
+        fc.touch_metadata();
+        return Ok(RefreshOutcome::Unchanged);
+    }
+    let r = r.error_for_status()?;
+    let etag = r
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    #[cfg(feature = "cache_meta")]
+    let content_type = r
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let d = read_body_async(r).await?;
+    if let Some(v) = v {
+        v.check(&d)?;
+    }
+    let d = fc.seal_cache(&d)?;
+    tokio::fs::write(cf, &d).await?;
+    #[cfg(feature = "cache_meta")]
+    fc.save_metadata(
+        &d,
+        &SourceInfo {
+            url: Some(&hs.url),
+            etag: etag.as_deref(),
+            content_type: content_type.as_deref(),
+        },
+    );
+    match etag {
+        Some(etag) => tokio::fs::write(&etag_file, etag).await?,
+        None => {
+            let _ = tokio::fs::remove_file(&etag_file).await;
+        }
+    }
+    Ok(RefreshOutcome::Updated)
+}
+
+impl DataSource {
+    /// 强制刷新 FileMap 中所有带缓存文件的 Http 条目, 不论缓存是否过期.
+    /// 若上次响应带有 ETag, 会发送 `If-None-Match`, 304 时保留原缓存.
+    /// 同时最多进行 `concurrency` 个请求. 其它种类的 DataSource 返回空报告
+    pub async fn refresh_all_async(&self, concurrency: usize) -> RefreshReport {
+        let DataSource::FileMap(m) = self else {
+            return RefreshReport::default();
+        };
+        let mut entries: Vec<(String, RefreshOutcome)> =
+            futures::stream::iter(m.iter().filter_map(|(k, s)| http_entry(s).map(|e| (k, e))))
+                .map(|(k, (hs, fc, v))| async move {
+                    let o = refresh_entry(hs, &fc, v)
+                        .await
+                        .unwrap_or_else(RefreshOutcome::Failed);
+                    (k.clone(), o)
+                })
+                .buffer_unordered(concurrency.max(1))
+                .collect()
+                .await;
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        RefreshReport { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_refresh_all_reports_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_string_lossy().to_string();
+        let hs = HttpSource {
+            url: "http://127.0.0.1:1/a".to_string(),
+            ..Default::default()
+        };
+        let fc = FileCache::for_request(&dir, &hs, None);
+        let ds = DataSource::FileMap(
+            vec![
+                ("a".to_string(), SingleFileSource::Http(hs, fc)),
+                ("b".to_string(), SingleFileSource::Inline(b"b".to_vec())),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let report = ds.refresh_all_async(4).await;
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.entries[0].0, "a");
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.updated() + report.unchanged(), 0);
+    }
+}
@@ -0,0 +1,517 @@
+//! 高层接口: 把 DataSource, 内存缓存策略, 转换, 限制和观察者放在一个对象中,
+//! 应用只需配置一次, 不必自己组合各种包装类型.
+//!
+//! ```ignore
+//! let r = DataResolver::new(DataSource::Folders(vec!["conf".into()]))
+//!     .with_cache_policy(CachePolicy { ttl: Some(Duration::from_secs(60)), max_entries: Some(256) })
+//!     .with_limits(Limits { max_file_size: Some(1 << 20), ..Default::default() })
+//!     .with_transform(|_, d| Ok(d))
+//!     .with_observer(|e| log::info!("{e:?}"));
+//! let rules = r.get_string("rules.json")?;
+//! ```
+
+use crate::*;
+use std::collections::HashSet;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub type TransformFn = dyn Fn(&str, Vec<u8>) -> Result<Vec<u8>, FetchError> + Send + Sync;
+pub type ObserverFn = dyn Fn(&ResolveEvent) + Send + Sync;
+/// 从文件内容中找出它引用的其它文件, 返回相对于来源根目录的路径
+pub type DepsExtractor = dyn Fn(&str, &[u8]) -> Vec<String> + Send + Sync;
+
+/// 路径和 (转换后的) 内容
+pub type Resolved = (String, Arc<Vec<u8>>);
+
+/// [`DataResolver::get_with_deps`] 同时读取的最大文件数
+const PREFETCH_CONCURRENCY: usize = 8;
+
+/// 内存缓存策略. `ttl` 为 None 时不缓存, 每次都从来源读取
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CachePolicy {
+    pub ttl: Option<Duration>,
+    /// 超过时淘汰最早读取的条目
+    pub max_entries: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// 来源返回的原始数据 (转换前) 的最大字节数
+    pub max_file_size: Option<usize>,
+    /// [`DataResolver::get_with_deps`] 最多加载的依赖数 (不含入口文件)
+    pub max_dependencies: Option<usize>,
+}
+
+/// 每次 get 的结果, 传给观察者
+#[derive(Debug)]
+pub struct ResolveEvent<'a> {
+    pub path: &'a str,
+    /// 是否来自内存缓存
+    pub cached: bool,
+    pub elapsed: Duration,
+    /// 成功时为 (转换后的) 字节数
+    pub result: Result<usize, &'a FetchError>,
+}
+
+struct Entry {
+    data: Arc<Vec<u8>>,
+    at: Instant,
+}
+
+/// 一个路径的订阅者, 以及上次读到的内容
+#[derive(Default)]
+struct Subscription {
+    last: Option<Arc<Vec<u8>>>,
+    senders: Vec<mpsc::Sender<Arc<Vec<u8>>>>,
+}
+
+pub struct DataResolver {
+    pub source: DataSource,
+    pub cache_policy: CachePolicy,
+    pub limits: Limits,
+    transforms: Vec<Arc<TransformFn>>,
+    observers: Vec<Arc<ObserverFn>>,
+    deps_extractor: Option<Arc<DepsExtractor>>,
+    memo: Mutex<HashMap<String, Entry>>,
+    subscriptions: Mutex<HashMap<String, Subscription>>,
+}
+
+impl std::fmt::Debug for DataResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataResolver")
+            .field("source", &self.source)
+            .field("cache_policy", &self.cache_policy)
+            .field("limits", &self.limits)
+            .field("transforms", &self.transforms.len())
+            .field("observers", &self.observers.len())
+            .field("deps_extractor", &self.deps_extractor.is_some())
+            .finish()
+    }
+}
+
+impl DataResolver {
+    pub fn new(source: DataSource) -> Self {
+        Self {
+            source,
+            cache_policy: CachePolicy::default(),
+            limits: Limits::default(),
+            transforms: Vec::new(),
+            observers: Vec::new(),
+            deps_extractor: None,
+            memo: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_cache_policy(mut self, p: CachePolicy) -> Self {
+        self.cache_policy = p;
+        self
+    }
+
+    pub fn with_limits(mut self, l: Limits) -> Self {
+        self.limits = l;
+        self
+    }
+
+    /// 追加一个转换, 按添加顺序依次作用于读到的内容, 缓存的是转换后的结果
+    pub fn with_transform(
+        mut self,
+        f: impl Fn(&str, Vec<u8>) -> Result<Vec<u8>, FetchError> + Send + Sync + 'static,
+    ) -> Self {
+        self.transforms.push(Arc::new(f));
+        self
+    }
+
+    pub fn with_observer(mut self, f: impl Fn(&ResolveEvent) + Send + Sync + 'static) -> Self {
+        self.observers.push(Arc::new(f));
+        self
+    }
+
+    /// 设置 [`Self::get_with_deps`] 使用的依赖提取函数, 作用于转换后的内容
+    pub fn with_deps_extractor(
+        mut self,
+        f: impl Fn(&str, &[u8]) -> Vec<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.deps_extractor = Some(Arc::new(f));
+        self
+    }
+
+    fn cached(&self, path: &str) -> Option<Arc<Vec<u8>>> {
+        let ttl = self.cache_policy.ttl?;
+        let memo = self.memo.lock().unwrap();
+        memo.get(path)
+            .filter(|e| e.at.elapsed() < ttl)
+            .map(|e| e.data.clone())
+    }
+
+    /// 检查大小, 依次转换, 写入缓存并通知订阅者
+    fn finish(&self, path: &str, data: Vec<u8>) -> Result<Arc<Vec<u8>>, FetchError> {
+        if let Some(limit) = self.limits.max_file_size {
+            if data.len() > limit {
+                return Err(HttpError::SizeLimit {
+                    url: path.to_string(),
+                    size: data.len() as u64,
+                    limit,
+                }
+                .into());
+            }
+        }
+        let mut data = data;
+        for t in &self.transforms {
+            data = t(path, data)?;
+        }
+        let data = Arc::new(data);
+        if self.cache_policy.ttl.is_some() {
+            let mut memo = self.memo.lock().unwrap();
+            memo.insert(
+                path.to_string(),
+                Entry {
+                    data: data.clone(),
+                    at: Instant::now(),
+                },
+            );
+            if let Some(max) = self.cache_policy.max_entries {
+                while memo.len() > max {
+                    let Some(oldest) = memo
+                        .iter()
+                        .min_by_key(|(_, e)| e.at)
+                        .map(|(k, _)| k.clone())
+                    else {
+                        break;
+                    };
+                    memo.remove(&oldest);
+                }
+            }
+        }
+        self.notify(path, &data);
+        Ok(data)
+    }
+
+    fn notify(&self, path: &str, data: &Arc<Vec<u8>>) {
+        let mut subs = self.subscriptions.lock().unwrap();
+        let Some(sub) = subs.get_mut(path) else {
+            return;
+        };
+        if sub.last.as_ref().is_some_and(|l| l == data) {
+            return;
+        }
+        sub.last = Some(data.clone());
+        sub.senders.retain(|s| s.send(data.clone()).is_ok());
+        if sub.senders.is_empty() {
+            subs.remove(path);
+        }
+    }
+
+    fn observe(
+        &self,
+        path: &str,
+        cached: bool,
+        started: Instant,
+        r: &Result<Arc<Vec<u8>>, FetchError>,
+    ) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let e = ResolveEvent {
+            path,
+            cached,
+            elapsed: started.elapsed(),
+            result: r.as_ref().map(|d| d.len()),
+        };
+        for o in &self.observers {
+            o(&e);
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Result<Arc<Vec<u8>>, FetchError> {
+        let started = Instant::now();
+        if let Some(d) = self.cached(path) {
+            let r = Ok(d);
+            self.observe(path, true, started, &r);
+            return r;
+        }
+        let r = SyncFolderSource::get_file_content(&self.source, Path::new(path))
+            .and_then(|(d, _)| self.finish(path, d));
+        self.observe(path, false, started, &r);
+        r
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn get_async(&self, path: &str) -> Result<Arc<Vec<u8>>, FetchError> {
+        let started = Instant::now();
+        if let Some(d) = self.cached(path) {
+            let r = Ok(d);
+            self.observe(path, true, started, &r);
+            return r;
+        }
+        let r = AsyncFolderSource::get_file_content_async(&self.source, Path::new(path))
+            .await
+            .and_then(|(d, _)| self.finish(path, d));
+        self.observe(path, false, started, &r);
+        r
+    }
+
+    /// 下一层需要读取的依赖, 去掉已经见过的. 超过 max_dependencies 时返回错误
+    fn next_level(
+        &self,
+        loaded: &[Resolved],
+        seen: &mut HashSet<String>,
+    ) -> Result<Vec<String>, FetchError> {
+        let Some(extract) = &self.deps_extractor else {
+            return Ok(Vec::new());
+        };
+        let mut next = Vec::new();
+        for (p, d) in loaded {
+            for dep in extract(p, d) {
+                if seen.insert(dep.clone()) {
+                    next.push(dep);
+                }
+            }
+        }
+        if let Some(max) = self.limits.max_dependencies {
+            // seen 中包含入口文件
+            if seen.len() - 1 > max {
+                return Err(FetchError::Invalid(format!(
+                    "{}: more than {max} dependencies",
+                    loaded[0].0
+                )));
+            }
+        }
+        Ok(next)
+    }
+
+    /// 读取 `path` 及其 (递归的) 依赖, 同一层的依赖并发读取并放入内存缓存.
+    /// 返回入口文件在前, 按层排列的所有文件; 任何一个读取失败时返回错误.
+    /// 没有设置 [`Self::with_deps_extractor`] 时只读取 `path`
+    pub fn get_with_deps(&self, path: &str) -> Result<Vec<Resolved>, FetchError> {
+        let mut seen = HashSet::from([path.to_string()]);
+        let mut all = vec![(path.to_string(), self.get(path)?)];
+        let mut level_start = 0;
+        loop {
+            let next = self.next_level(&all[level_start..], &mut seen)?;
+            if next.is_empty() {
+                return Ok(all);
+            }
+            level_start = all.len();
+            #[cfg(feature = "tokio")]
+            let handle = tokio::runtime::Handle::try_current().ok();
+            for chunk in next.chunks(PREFETCH_CONCURRENCY) {
+                let results: Vec<_> = std::thread::scope(|s| {
+                    let hs: Vec<_> = chunk
+                        .iter()
+                        .map(|p| {
+                            #[cfg(feature = "tokio")]
+                            let handle = handle.clone();
+                            s.spawn(move || {
+                                // Async 来源需要在 tokio 运行时的上下文中读取
+                                #[cfg(feature = "tokio")]
+                                let _g = handle.as_ref().map(|h| h.enter());
+                                self.get(p)
+                            })
+                        })
+                        .collect();
+                    hs.into_iter().map(|h| h.join().unwrap()).collect()
+                });
+                for (p, r) in chunk.iter().zip(results) {
+                    all.push((p.clone(), r?));
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn get_with_deps_async(&self, path: &str) -> Result<Vec<Resolved>, FetchError> {
+        use futures::StreamExt;
+        let mut seen = HashSet::from([path.to_string()]);
+        let mut all = vec![(path.to_string(), self.get_async(path).await?)];
+        let mut level_start = 0;
+        loop {
+            let next = self.next_level(&all[level_start..], &mut seen)?;
+            if next.is_empty() {
+                return Ok(all);
+            }
+            level_start = all.len();
+            let results: Vec<_> = futures::stream::iter(next.iter())
+                .map(|p| self.get_async(p))
+                .buffered(PREFETCH_CONCURRENCY)
+                .collect()
+                .await;
+            for (p, r) in next.into_iter().zip(results) {
+                all.push((p, r?));
+            }
+        }
+    }
+
+    /// 非 UTF-8 的内容会被有损转换, 与 [`DataSource::read_to_string`] 相同
+    pub fn get_string(&self, path: &str) -> Result<String, FetchError> {
+        self.get(path)
+            .map(|d| String::from_utf8_lossy(&d).to_string())
+    }
+
+    /// 订阅 `path` 的内容变化. 之后每次从来源读到与上次不同的内容时 (包括第一次),
+    /// 接收端都会收到新内容. 可以调用 [`Self::refresh`] 主动检查
+    pub fn subscribe(&self, path: &str) -> mpsc::Receiver<Arc<Vec<u8>>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_default()
+            .senders
+            .push(tx);
+        rx
+    }
+
+    /// 清空内存缓存
+    pub fn invalidate(&self) {
+        self.memo.lock().unwrap().clear();
+    }
+
+    /// 绕过内存缓存重新读取所有被订阅的路径, 返回读取失败的路径
+    pub fn refresh(&self) -> Vec<(String, FetchError)> {
+        let paths: Vec<String> = self.subscriptions.lock().unwrap().keys().cloned().collect();
+        let mut failed = Vec::new();
+        for p in paths {
+            self.memo.lock().unwrap().remove(&p);
+            if let Err(e) = self.get(&p) {
+                failed.push((p, e));
+            }
+        }
+        failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_data_resolver() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("a.txt"), "hello").unwrap();
+        std::fs::write(dir.join("big.txt"), "0123456789").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let ev = events.clone();
+        let r = DataResolver::new(DataSource::Folders(vec![dir.to_string_lossy().to_string()]))
+            .with_cache_policy(CachePolicy {
+                ttl: Some(Duration::from_secs(3600)),
+                max_entries: Some(1),
+            })
+            .with_limits(Limits {
+                max_file_size: Some(8),
+                ..Default::default()
+            })
+            .with_transform(|_, d| Ok(d.to_ascii_uppercase()))
+            .with_transform(|p, mut d| {
+                d.extend_from_slice(format!(" ({p})").as_bytes());
+                Ok(d)
+            })
+            .with_observer(move |e| {
+                ev.lock()
+                    .unwrap()
+                    .push((e.path.to_string(), e.cached, e.result.is_ok()))
+            });
+
+        assert_eq!(r.get_string("a.txt").unwrap(), "HELLO (a.txt)");
+        std::fs::write(dir.join("a.txt"), "changed").unwrap();
+        assert_eq!(r.get_string("a.txt").unwrap(), "HELLO (a.txt)");
+        let e = r.get("big.txt").unwrap_err();
+        assert_eq!(e.kind(), FetchErrorKind::TooLarge);
+        assert!(r.get("nope").unwrap_err().is_not_found());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ("a.txt".to_string(), false, true),
+                ("a.txt".to_string(), true, true),
+                ("big.txt".to_string(), false, false),
+                ("nope".to_string(), false, false),
+            ]
+        );
+
+        let rx = r.subscribe("a.txt");
+        assert!(r.refresh().is_empty());
+        assert_eq!(*rx.try_recv().unwrap(), b"CHANGED (a.txt)");
+        // 内容没有变化时不通知
+        r.refresh();
+        assert!(rx.try_recv().is_err());
+
+        // max_entries 为 1
+        std::fs::write(dir.join("b.txt"), "b").unwrap();
+        r.get("b.txt").unwrap();
+        assert!(!r.memo.lock().unwrap().contains_key("a.txt"));
+
+        std::fs::remove_file(dir.join("a.txt")).unwrap();
+        assert_eq!(r.refresh().len(), 1);
+    }
+
+    fn includes(_: &str, d: &[u8]) -> Vec<String> {
+        String::from_utf8_lossy(d)
+            .lines()
+            .filter_map(|l| l.strip_prefix("include "))
+            .map(|l| l.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_get_with_deps() {
+        let file_map = |files: &[(&str, &str)]| {
+            DataSource::FileMap(
+                files
+                    .iter()
+                    .map(|(k, v)| {
+                        (
+                            k.to_string(),
+                            SingleFileSource::Inline(v.as_bytes().to_vec()),
+                        )
+                    })
+                    .collect(),
+            )
+        };
+        let ds = file_map(&[
+            ("main.conf", "include a.conf\ninclude b.conf"),
+            ("a.conf", "include c.conf\ninclude main.conf"),
+            ("b.conf", "include c.conf"),
+            ("c.conf", "leaf"),
+        ]);
+        let r = DataResolver::new(ds)
+            .with_cache_policy(CachePolicy {
+                ttl: Some(Duration::from_secs(3600)),
+                max_entries: None,
+            })
+            .with_deps_extractor(includes);
+        let all = r.get_with_deps("main.conf").unwrap();
+        let names: Vec<_> = all.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(names, ["main.conf", "a.conf", "b.conf", "c.conf"]);
+        assert_eq!(*all[3].1, b"leaf");
+        assert_eq!(r.memo.lock().unwrap().len(), 4);
+
+        let r = r.with_limits(Limits {
+            max_dependencies: Some(2),
+            ..Default::default()
+        });
+        assert!(r.get_with_deps("main.conf").is_err());
+
+        let r =
+            DataResolver::new(file_map(&[("x", "include missing")])).with_deps_extractor(includes);
+        assert!(r.get_with_deps("x").unwrap_err().is_not_found());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_get_with_deps_async() {
+        let ds = DataSource::FileMap(HashMap::from([
+            (
+                "a".to_string(),
+                SingleFileSource::Inline(b"include b".to_vec()),
+            ),
+            ("b".to_string(), SingleFileSource::Inline(b"b".to_vec())),
+        ]));
+        let r = DataResolver::new(ds).with_deps_extractor(includes);
+        let all = r.get_with_deps_async("a").await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(*all[1].1, b"b");
+    }
+}
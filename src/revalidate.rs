@@ -0,0 +1,335 @@
+//! Http 条目的缓存过期后先向服务器验证: 带上 `If-None-Match` (上次响应的 ETag) 和
+//! `If-Modified-Since` (缓存文件的修改时间), 服务器返回 304 时只更新缓存文件的修改时间,
+//! 不重新下载. ETag 记录在缓存文件旁的 `<cache>.etag` 中, 与 [`DataSource::refresh_all_async`] 共用
+
+use crate::*;
+use reqwest::header::{CONTENT_TYPE, ETAG};
+
+impl FileCache {
+    /// 缓存文件对应的 etag 记录
+    pub fn etag_path(&self) -> Option<PathBuf> {
+        let cf = self.cache_file_path.as_ref()?;
+        Some(PathBuf::from(format!("{cf}.etag")))
+    }
+
+    /// 缓存文件存在时加上条件请求头. 已按 proxy_rules 设置代理
+    fn conditional_request(&self, hs: &HttpSource) -> HttpSource {
+        let mut req = hs.apply_proxy_rules().unwrap_or_else(|| hs.clone());
+        let cf = self.cache_file_path.as_ref().map(Path::new);
+        let Some(mtime) = cf.and_then(|cf| std::fs::metadata(cf).and_then(|m| m.modified()).ok())
+        else {
+            return req;
+        };
+        let headers = req.custom_request_headers.get_or_insert_with(Vec::new);
+        if let Some(etag) = self
+            .etag_path()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+        {
+            headers.push(("If-None-Match".to_string(), etag.trim().to_string()));
+        }
+        headers.push(("If-Modified-Since".to_string(), rfc1123_date(mtime)));
+        req
+    }
+
+    /// 304 时把缓存视为刚刚更新
+    fn touch_cache_file(&self) -> Result<(), FetchError> {
+        let cf = Path::new(self.cache_file_path.as_ref().unwrap());
+        std::fs::File::options()
+            .append(true)
+            .open(cf)
+            .and_then(|f| f.set_modified(SystemTime::now()))
+            .map_err(|e| local_io_error(e, cf))?;
+        #[cfg(feature = "cache_meta")]
+        self.touch_metadata();
+        Ok(())
+    }
+
+    /// 记录响应的 ETag, 没有时删除旧记录
+    fn save_etag(&self, etag: Option<&str>) {
+        let Some(p) = self.etag_path() else {
+            return;
+        };
+        let r = match etag {
+            Some(etag) => std::fs::write(&p, etag),
+            None => std::fs::remove_file(&p).or_else(|e| match e.kind() {
+                io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }),
+        };
+        if let Err(e) = r {
+            warn!("Failed to update etag file: {e}");
+        }
+    }
+
+    /// 是否自己处理这次请求: 有缓存文件, 缓存不存在或已过期, 且不是 unix socket
+    fn should_revalidate(&self, hs: &HttpSource) -> Result<bool, FetchError> {
+        if self.cache_file_path.is_none() || unix_socket::parse_unix_url(&hs.url).is_some() {
+            return Ok(false);
+        }
+        let timeout = self.is_cache_timeout()?;
+        #[cfg(feature = "journal")]
+        if timeout != Some(false) {
+            journal::record_cache(self, cache_decision(timeout));
+        }
+        Ok(timeout != Some(false))
+    }
+}
+
+fn header_of(r: &reqwest::header::HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    r.get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+fn source_info<'a>(
+    hs: &'a HttpSource,
+    etag: &'a Option<String>,
+    content_type: &'a Option<String>,
+) -> SourceInfo<'a> {
+    SourceInfo {
+        url: Some(&hs.url),
+        etag: etag.as_deref(),
+        content_type: content_type.as_deref(),
+    }
+}
+
+/// 设置了 stale_if_error_seconds 时 5xx 也视为读取失败, 以便使用旧缓存
+fn check_server_error(
+    fc: &FileCache,
+    hs: &HttpSource,
+    status: reqwest::StatusCode,
+) -> Result<(), FetchError> {
+    if fc.stale_if_error_seconds.is_none() || !status.is_server_error() {
+        return Ok(());
+    }
+    Err(HttpError::Status {
+        url: hs.url.clone(),
+        status: status.as_u16(),
+    }
+    .into())
+}
+
+/// Http 条目的 [`fetch_with_cache`], 过期的缓存先用条件请求验证, 304 时使用原缓存
+pub fn fetch_http_with_cache(fc: &FileCache, hs: &HttpSource) -> Result<Vec<u8>, FetchError> {
+    if !fc.should_revalidate(hs)? {
+        return fetch_with_cache_from(fc, hs, &SourceInfo::url(&hs.url));
+    }
+    revalidate(fc, hs).or_else(|e| fc.fallback_on_error(e))
+}
+
+fn revalidate(fc: &FileCache, hs: &HttpSource) -> Result<Vec<u8>, FetchError> {
+    let req = fc.conditional_request(hs);
+    #[cfg(feature = "tokio")]
+    let _permit = acquire_fetch_permit_blocking();
+    let r = req.send()?;
+    if r.status() == reqwest::StatusCode::NOT_MODIFIED {
+        debug!("{} not modified", hs.url);
+        fc.touch_cache_file()?;
+        return match fc.read_cache_file() {
+            // 缓存已删除, 不再带条件头
+            Err(e) if e.is_cache_corrupt() => revalidate(fc, hs),
+            r => r,
+        };
+    }
+    check_server_error(fc, hs, r.status())?;
+    let etag = header_of(r.headers(), ETAG).filter(|_| r.status().is_success());
+    let content_type = header_of(r.headers(), CONTENT_TYPE);
+    let d = read_body(r)?;
+    if fc.write_cache_file_from(&d, &source_info(hs, &etag, &content_type)) {
+        fc.save_etag(etag.as_deref());
+    }
+    Ok(d)
+}
+
+#[cfg(feature = "tokio")]
+pub async fn fetch_http_with_cache_async(
+    fc: &FileCache,
+    hs: &HttpSource,
+) -> Result<Vec<u8>, FetchError> {
+    if !fc.should_revalidate(hs)? {
+        return fetch_with_cache_from_async(fc, hs, &SourceInfo::url(&hs.url)).await;
+    }
+    match revalidate_async(fc, hs).await {
+        Err(e) => fc.fallback_on_error(e),
+        r => r,
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn revalidate_async(fc: &FileCache, hs: &HttpSource) -> Result<Vec<u8>, FetchError> {
+    let req = fc.conditional_request(hs);
+    let _permit = acquire_fetch_permit().await;
+    let r = req.send_async().await?;
+    if r.status() == reqwest::StatusCode::NOT_MODIFIED {
+        debug!("{} not modified", hs.url);
+        fc.touch_cache_file()?;
+        return match fc.read_cache_file_async().await {
+            Err(e) if e.is_cache_corrupt() => Box::pin(revalidate_async(fc, hs)).await,
+            r => r,
+        };
+    }
+    check_server_error(fc, hs, r.status())?;
+    let etag = header_of(r.headers(), ETAG).filter(|_| r.status().is_success());
+    let content_type = header_of(r.headers(), CONTENT_TYPE);
+    let d = read_body_async(r).await?;
+    let info = source_info(hs, &etag, &content_type);
+    if fc.write_cache_file_from_async(&d, &info).await {
+        fc.save_etag(etag.as_deref());
+    }
+    Ok(d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// ETag 为 `"v1"` 的服务器, 记录每个请求的条件头 (小写)
+    fn serve() -> (String, Arc<Mutex<Vec<Vec<String>>>>) {
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rules.dat", l.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let s2 = seen.clone();
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let cond: Vec<String> = BufReader::new(&s)
+                    .lines()
+                    .map(|l| l.unwrap())
+                    .take_while(|l| !l.is_empty())
+                    .map(|l| l.to_ascii_lowercase())
+                    .filter(|l| l.starts_with("if-"))
+                    .collect();
+                let matched = cond.iter().any(|l| l == "if-none-match: \"v1\"");
+                s2.lock().unwrap().push(cond);
+                let r = if matched {
+                    "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 2\r\nConnection: close\r\n\r\nv1"
+                        .to_string()
+                };
+                s.write_all(r.as_bytes()).unwrap();
+            }
+        });
+        (url, seen)
+    }
+
+    fn expire(cf: &Path) {
+        std::fs::File::options()
+            .append(true)
+            .open(cf)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(120))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_revalidate() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (url, seen) = serve();
+        let hs = HttpSource {
+            url,
+            ..Default::default()
+        };
+        let dir = temp_dir.path().to_string_lossy().to_string();
+        let fc = FileCache::for_request(&dir, &hs, Some(60));
+        let cf = PathBuf::from(fc.cache_file_path.clone().unwrap());
+        let sf = SingleFileSource::Http(hs, fc.clone());
+
+        assert_eq!(sf.fetch().unwrap(), b"v1");
+        assert_eq!(std::fs::read(fc.etag_path().unwrap()).unwrap(), b"\"v1\"");
+        // 未过期时不请求
+        assert_eq!(sf.fetch().unwrap(), b"v1");
+        assert_eq!(seen.lock().unwrap().len(), 1);
+
+        expire(&cf);
+        assert_eq!(sf.fetch().unwrap(), b"v1");
+        assert_eq!(fc.is_cache_timeout().unwrap(), Some(false));
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert!(seen[0].is_empty());
+        assert!(seen[1].iter().any(|h| h.starts_with("if-modified-since: ")));
+        assert!(seen[1].contains(&"if-none-match: \"v1\"".to_string()));
+    }
+
+    #[test]
+    fn test_revalidate_cache_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (url, _) = serve();
+        let dir = temp_dir.path().join("cache");
+        let fc = FileCache::in_dir(&dir.to_string_lossy(), Some(60));
+        for q in ["?a", "?b"] {
+            let hs = HttpSource {
+                url: format!("{url}{q}"),
+                ..Default::default()
+            };
+            let sf = SingleFileSource::Http(hs.clone(), fc.clone());
+            assert_eq!(sf.fetch().unwrap(), b"v1");
+            assert!(dir.join(hs.cache_key()).is_file());
+            assert!(dir.join(format!("{}.etag", hs.cache_key())).is_file());
+        }
+        // 缓存目录在第一次写入时创建
+        let files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name());
+        let files = files.filter(|n| !n.to_string_lossy().ends_with(".meta.json"));
+        assert_eq!(files.count(), 4);
+    }
+
+    #[test]
+    fn test_stale_if_error_5xx() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rules.dat", l.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let lines = BufReader::new(&s).lines().map(|l| l.unwrap());
+                lines.take_while(|l| !l.is_empty()).for_each(drop);
+                let r =
+                    "HTTP/1.1 502 Bad Gateway\r\nContent-Length: 3\r\nConnection: close\r\n\r\nbad";
+                s.write_all(r.as_bytes()).unwrap();
+            }
+        });
+        let hs = HttpSource {
+            url,
+            ..Default::default()
+        };
+        let dir = temp_dir.path().to_string_lossy().to_string();
+        let fc = FileCache::for_request(&dir, &hs, Some(60));
+        let cf = PathBuf::from(fc.cache_file_path.clone().unwrap());
+        std::fs::write(&cf, "old").unwrap();
+        expire(&cf);
+        let stale = fc.clone().with_stale_if_error(300);
+        assert_eq!(fetch_http_with_cache(&stale, &hs).unwrap(), b"old");
+        assert_eq!(std::fs::read(&cf).unwrap(), b"old");
+        // 没有设置时照旧返回响应内容
+        assert_eq!(fetch_http_with_cache(&fc, &hs).unwrap(), b"bad");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_revalidate_async() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (url, seen) = serve();
+        let hs = HttpSource {
+            url,
+            ..Default::default()
+        };
+        let dir = temp_dir.path().to_string_lossy().to_string();
+        let fc = FileCache::for_request(&dir, &hs, Some(60));
+        let cf = PathBuf::from(fc.cache_file_path.clone().unwrap());
+        assert_eq!(fetch_http_with_cache_async(&fc, &hs).await.unwrap(), b"v1");
+        std::fs::write(&cf, "old").unwrap();
+        expire(&cf);
+        // 304 时返回原缓存
+        assert_eq!(fetch_http_with_cache_async(&fc, &hs).await.unwrap(), b"old");
+        assert_eq!(seen.lock().unwrap().len(), 2);
+
+        std::fs::remove_file(fc.etag_path().unwrap()).unwrap();
+        expire(&cf);
+        assert_eq!(fetch_http_with_cache_async(&fc, &hs).await.unwrap(), b"v1");
+    }
+}
@@ -0,0 +1,293 @@
+//! SFTP 来源, 通过 ssh2 (libssh2) 实现. 只支持密钥认证 (私钥文件或 ssh-agent),
+//! 远程主机的公钥需要在 known_hosts 中
+
+use crate::*;
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Session, Sftp};
+use std::io::Read;
+use std::net::TcpStream;
+
+/// libssh2 中 SFTP 的状态码
+const FX_NO_SUCH_FILE: i32 = 2;
+const FX_PERMISSION_DENIED: i32 = 3;
+
+/// 远程主机及认证方式
+#[derive(Debug, Clone, Default)]
+pub struct SftpRemote {
+    pub host: String,
+    pub port: Option<u16>,
+    /// 为 None 时使用环境变量 `USER`
+    pub user: Option<String>,
+    /// 私钥文件, 为 None 时使用 ssh-agent
+    pub identity_file: Option<PathBuf>,
+    /// 为 None 时使用 `~/.ssh/known_hosts`. 主机公钥不在其中或不一致时拒绝连接
+    pub known_hosts_file: Option<PathBuf>,
+}
+
+impl SftpRemote {
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn with_identity_file(mut self, p: impl Into<PathBuf>) -> Self {
+        self.identity_file = Some(p.into());
+        self
+    }
+
+    pub fn with_known_hosts_file(mut self, p: impl Into<PathBuf>) -> Self {
+        self.known_hosts_file = Some(p.into());
+        self
+    }
+
+    fn known_hosts_path(&self) -> Result<PathBuf, FetchError> {
+        match &self.known_hosts_file {
+            Some(p) => Ok(p.clone()),
+            None => std::env::var_os("HOME")
+                .map(|h| Path::new(&h).join(".ssh/known_hosts"))
+                .ok_or_else(|| FetchError::Invalid("sftp: HOME is not set".to_string())),
+        }
+    }
+
+    /// 连接, 校验主机公钥并认证, 返回 SFTP 会话
+    fn connect(&self) -> Result<Sftp, FetchError> {
+        let e = |e: ssh2::Error| ssh_error(&self.host, e);
+        let port = self.port.unwrap_or(22);
+        let tcp = TcpStream::connect((self.host.as_str(), port))?;
+        let mut sess = Session::new().map_err(e)?;
+        sess.set_tcp_stream(tcp);
+        sess.handshake().map_err(e)?;
+
+        let mut kh = sess.known_hosts().map_err(e)?;
+        kh.read_file(&self.known_hosts_path()?, KnownHostFileKind::OpenSSH)
+            .map_err(e)?;
+        let (key, _) = sess
+            .host_key()
+            .ok_or_else(|| FetchError::Invalid("sftp: no host key".to_string()))?;
+        match kh.check_port(&self.host, port, key) {
+            CheckResult::Match => {}
+            r => {
+                return Err(FetchError::Invalid(format!(
+                    "sftp: host key of {} is not trusted ({r:?})",
+                    self.host
+                )))
+            }
+        }
+
+        let user = match &self.user {
+            Some(u) => u.clone(),
+            None => std::env::var("USER")
+                .map_err(|_| FetchError::Invalid("sftp: no user given".to_string()))?,
+        };
+        match &self.identity_file {
+            Some(i) => sess.userauth_pubkey_file(&user, None, i, None),
+            None => sess.userauth_agent(&user),
+        }
+        .map_err(e)?;
+        sess.sftp().map_err(e)
+    }
+
+    /// 读取远程文件
+    pub fn get(&self, path: &str) -> Result<Vec<u8>, FetchError> {
+        read_file(&self.connect()?, path)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn get_async(&self, path: &str) -> Result<Vec<u8>, FetchError> {
+        let (r, p) = (self.clone(), path.to_string());
+        tokio::task::spawn_blocking(move || r.get(&p))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+fn read_file(sftp: &Sftp, path: &str) -> Result<Vec<u8>, FetchError> {
+    let e = |e: ssh2::Error| sftp_error(path, e);
+    if sftp.stat(Path::new(path)).map_err(e)?.is_dir() {
+        return Err(FetchError::Lookup(LookupError::IsDirectory(
+            path.to_string(),
+        )));
+    }
+    let mut d = Vec::new();
+    sftp.open(Path::new(path)).map_err(e)?.read_to_end(&mut d)?;
+    Ok(d)
+}
+
+/// 列出远程目录, 返回 (名称, 是否为目录)
+fn list_dir(sftp: &Sftp, dir: &str) -> Result<Vec<(String, bool)>, FetchError> {
+    let entries = sftp
+        .readdir(Path::new(dir))
+        .map_err(|e| sftp_error(dir, e))?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(p, st)| {
+            let name = p.file_name()?.to_string_lossy().to_string();
+            (name != "." && name != "..").then_some((name, st.is_dir()))
+        })
+        .collect())
+}
+
+fn ssh_error(host: &str, e: ssh2::Error) -> FetchError {
+    FetchError::I(io::Error::other(format!("sftp {host}: {e}")))
+}
+
+/// 根据 SFTP 状态码确定原因
+fn sftp_error(path: &str, e: ssh2::Error) -> FetchError {
+    match e.code() {
+        ErrorCode::SFTP(FX_NO_SUCH_FILE) => FetchError::not_found(path),
+        ErrorCode::SFTP(FX_PERMISSION_DENIED) => {
+            FetchError::Lookup(LookupError::Denied(path.to_string()))
+        }
+        _ => FetchError::I(io::Error::other(format!("sftp {path}: {e}"))),
+    }
+}
+
+/// 远程主机上的单个文件
+#[derive(Debug, Clone)]
+pub struct SftpSource {
+    pub remote: SftpRemote,
+    pub path: String,
+}
+
+impl SftpSource {
+    pub fn new(remote: SftpRemote, path: &str) -> Self {
+        Self {
+            remote,
+            path: path.to_string(),
+        }
+    }
+}
+
+impl SyncSource for SftpSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.remote.get(&self.path)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for SftpSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        self.remote.get_async(&self.path).await
+    }
+}
+
+/// 远程主机上的一个目录, 文件名相对于 `root`
+#[derive(Debug, Clone)]
+pub struct SftpFolderSource {
+    pub remote: SftpRemote,
+    pub root: String,
+}
+
+impl SftpFolderSource {
+    pub fn new(remote: SftpRemote, root: &str) -> Self {
+        Self {
+            remote,
+            root: root.to_string(),
+        }
+    }
+
+    fn remote_path(&self, file_name: &Path) -> String {
+        let p = normalize_separators(file_name);
+        let p = p.to_string_lossy();
+        let p = p.trim_start_matches("./").trim_start_matches('/');
+        format!("{}/{p}", self.root.trim_end_matches('/'))
+    }
+}
+
+impl SyncFolderSource for SftpFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let p = self.remote_path(file_name);
+        Ok((self.remote.get(&p)?, Some(p)))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY.with_listable()
+    }
+
+    /// 在同一个连接中逐层列出 root 下的所有文件
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        let sftp = self.remote.connect()?;
+        let mut files = Vec::new();
+        let mut dirs = vec![String::new()];
+        while let Some(d) = dirs.pop() {
+            let full = if d.is_empty() {
+                self.root.clone()
+            } else {
+                format!("{}/{d}", self.root.trim_end_matches('/'))
+            };
+            for (name, is_dir) in list_dir(&sftp, &full)? {
+                let rel = if d.is_empty() {
+                    name
+                } else {
+                    format!("{d}/{name}")
+                };
+                if is_dir {
+                    dirs.push(rel);
+                } else {
+                    files.push(rel);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for SftpFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let p = self.remote_path(file_name);
+        Ok((self.remote.get_async(&p).await?, Some(p)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sftp_helpers() {
+        let fs = SftpFolderSource::new(SftpRemote::new("h").with_user("u"), "/srv/data/");
+        assert_eq!(fs.remote_path(Path::new("./rules/a")), "/srv/data/rules/a");
+        let r = SftpRemote::new("h").with_known_hosts_file("/etc/ssh/known");
+        assert_eq!(r.known_hosts_path().unwrap(), Path::new("/etc/ssh/known"));
+
+        let e = sftp_error(
+            "/a",
+            ssh2::Error::new(ErrorCode::SFTP(FX_NO_SUCH_FILE), "x"),
+        );
+        assert!(e.is_not_found());
+        let e = sftp_error(
+            "/a",
+            ssh2::Error::new(ErrorCode::SFTP(FX_PERMISSION_DENIED), "x"),
+        );
+        assert!(matches!(e, FetchError::Lookup(LookupError::Denied(_))));
+        let e = sftp_error("/a", ssh2::Error::new(ErrorCode::Session(-18), "x"));
+        assert!(matches!(e, FetchError::I(_)));
+    }
+
+    #[test]
+    fn test_sftp_connect_refused() {
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = l.local_addr().unwrap().port();
+        drop(l);
+        let r = SftpRemote::new("127.0.0.1").with_port(port).with_user("u");
+        assert!(SftpSource::new(r, "/a").fetch().is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use crate::*;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use std::sync::{Arc, Mutex};
+
+type SharedResult = Result<FileContent, Arc<FetchError>>;
+
+/// 合并请求的键: 路径, 以及带上下文读取时影响结果的部分 (不含 trace_id)
+type FlightKey = (
+    PathBuf,
+    Option<(Option<String>, Option<String>, Vec<String>)>,
+);
+
+/// 合并对同一路径的并发请求: 同一路径同时只会有一次真正的读取, 其它调用者共享结果.
+/// 带上下文的读取只与上下文相同的请求合并.
+///
+/// file server 刚部署新文件时可能同时收到大量相同请求, 可以这样包装:
+/// `DataSource::Async(Box::new(SingleFlight::new(data_source)))`
+pub struct SingleFlight<S> {
+    inner: Arc<S>,
+    in_flight: Mutex<HashMap<FlightKey, Shared<BoxFuture<'static, SharedResult>>>>,
+}
+
+impl<S> SingleFlight<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// 没有相同的请求在进行时用 `read` 读取, 否则等待并共享其结果
+    async fn flight<F>(&self, key: FlightKey, read: F) -> Result<FileContent, FetchError>
+    where
+        F: FnOnce(Arc<S>) -> BoxFuture<'static, Result<FileContent, FetchError>>,
+    {
+        let fut = {
+            let mut m = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            m.entry(key.clone())
+                .or_insert_with(|| {
+                    read(self.inner.clone())
+                        .map(|r| r.map_err(Arc::new))
+                        .boxed()
+                        .shared()
+                })
+                .clone()
+        };
+        let r = fut.clone().await;
+
+        // 最先拿到结果的调用者负责移除, 之后的请求会重新读取
+        {
+            let mut m = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            if m.get(&key).is_some_and(|f| f.ptr_eq(&fut)) {
+                m.remove(&key);
+            }
+        }
+        r.map_err(|e| Arc::try_unwrap(e).unwrap_or_else(FetchError::Shared))
+    }
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for SingleFlight<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SingleFlight")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl<S> AsyncFolderSource for SingleFlight<S>
+where
+    S: AsyncFolderSource + Send + Sync + 'static,
+{
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let c = self.get_file_content_typed_async(file_name).await?;
+        Ok((c.data, c.path))
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        let p = file_name.to_path_buf();
+        self.flight((p.clone(), None), move |inner| {
+            async move { inner.get_file_content_typed_async(&p).await }.boxed()
+        })
+        .await
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let p = file_name.to_path_buf();
+        let k = (
+            ctx.tenant.clone(),
+            ctx.locale.clone(),
+            ctx.languages.clone(),
+        );
+        let ctx = ctx.clone();
+        self.flight((p.clone(), Some(k)), move |inner| {
+            async move { inner.get_file_content_ctx_async(&p, &ctx).await }.boxed()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Debug, Default)]
+    struct Counting(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl AsyncFolderSource for Counting {
+        async fn get_file_content_async(
+            &self,
+            _file_name: &Path,
+        ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Ok((b"data".to_vec(), None))
+        }
+    }
+
+    /// 返回 content type, 带上下文时返回 locale
+    #[derive(Debug, Default)]
+    struct Typed(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl AsyncFolderSource for Typed {
+        async fn get_file_content_async(
+            &self,
+            file_name: &Path,
+        ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+            let c = self.get_file_content_typed_async(file_name).await?;
+            Ok((c.data, c.path))
+        }
+
+        async fn get_file_content_typed_async(
+            &self,
+            _file_name: &Path,
+        ) -> Result<FileContent, FetchError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Ok(FileContent {
+                data: b"data".to_vec(),
+                content_type: Some("text/plain".to_string()),
+                ..Default::default()
+            })
+        }
+
+        async fn get_file_content_ctx_async(
+            &self,
+            file_name: &Path,
+            ctx: &FetchContext,
+        ) -> Result<FileContent, FetchError> {
+            let mut c = self.get_file_content_typed_async(file_name).await?;
+            c.data = ctx.locale.clone().unwrap_or_default().into_bytes();
+            Ok(c)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_flight() {
+        let sf = Arc::new(SingleFlight::new(Counting::default()));
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let sf = sf.clone();
+                tokio::spawn(async move { sf.get_file_content_async(Path::new("a")).await })
+            })
+            .collect();
+        for t in tasks {
+            assert_eq!(t.await.unwrap().unwrap().0, b"data");
+        }
+        assert_eq!(sf.inner().0.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_typed() {
+        let sf = Arc::new(SingleFlight::new(Typed::default()));
+        let ctx = |l: &str| FetchContext {
+            locale: Some(l.to_string()),
+            trace_id: Some(l.to_string() + "-trace"),
+            ..Default::default()
+        };
+        let a = Path::new("a");
+        let (fr, de) = (ctx("fr"), ctx("de"));
+        let (t1, t2, fr, de) = tokio::join!(
+            sf.get_file_content_typed_async(a),
+            sf.get_file_content_typed_async(a),
+            sf.get_file_content_ctx_async(a, &fr),
+            sf.get_file_content_ctx_async(a, &de),
+        );
+        assert_eq!(t1.unwrap().content_type.as_deref(), Some("text/plain"));
+        assert_eq!(t2.unwrap().data, b"data");
+        let fr = fr.unwrap();
+        assert_eq!(
+            (fr.data, fr.content_type.as_deref()),
+            (b"fr".to_vec(), Some("text/plain"))
+        );
+        assert_eq!(de.unwrap().data, b"de");
+        // 不带上下文的两个请求合并, 不同上下文各读取一次
+        assert_eq!(sf.inner().0.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,215 @@
+//! 以 SQLite 数据库中的一张表作为目录, 一个 `.db` 文件即可代替大量小配置文件.
+//! 通过 rusqlite 访问, 不需要系统中安装 sqlite3
+
+use crate::*;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+/// 表结构为 `(name TEXT PRIMARY KEY, bytes BLOB, mtime INTEGER)`, 见 [`SqliteFolderSource::create_table`].
+/// `name` 即文件名, `mtime` 为 unix 秒. 每次读取时打开数据库, 不持有连接
+#[derive(Debug, Clone)]
+pub struct SqliteFolderSource {
+    pub db: PathBuf,
+    pub table: String,
+}
+
+/// 表名不能作为参数绑定, 按 SQL 标识符转义
+fn quote_ident(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn sqlite_error(e: rusqlite::Error) -> FetchError {
+    FetchError::I(io::Error::other(format!("sqlite: {e}")))
+}
+
+impl SqliteFolderSource {
+    pub fn new(db: impl Into<PathBuf>, table: &str) -> Self {
+        Self {
+            db: db.into(),
+            table: table.to_string(),
+        }
+    }
+
+    fn open(&self, readonly: bool) -> Result<Connection, FetchError> {
+        let flags = if readonly {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        };
+        Connection::open_with_flags(&self.db, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+            .map_err(sqlite_error)
+    }
+
+    fn read(&self, file_name: &Path) -> Result<Vec<u8>, FetchError> {
+        let name = normalize_separators(file_name);
+        let name = name.to_string_lossy();
+        self.open(true)?
+            .prepare_cached(&format!(
+                "SELECT bytes FROM {} WHERE name = ?1",
+                quote_ident(&self.table)
+            ))
+            .and_then(|mut st| st.query_row([&name], |r| r.get(0)).optional())
+            .map_err(sqlite_error)?
+            .ok_or_else(|| FetchError::not_found(name))
+    }
+
+    /// 表不存在时创建
+    pub fn create_table(&self) -> Result<(), FetchError> {
+        self.open(false)?
+            .execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (name TEXT PRIMARY KEY, bytes BLOB NOT NULL, mtime INTEGER)",
+                    quote_ident(&self.table)
+                ),
+                [],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    /// 写入或替换一个文件, `mtime` 为 None 时使用当前时间
+    pub fn put(&self, name: &str, data: &[u8], mtime: Option<i64>) -> Result<(), FetchError> {
+        self.open(false)?
+            .execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {} (name, bytes, mtime) VALUES (?1, ?2, coalesce(?3, strftime('%s', 'now')))",
+                    quote_ident(&self.table)
+                ),
+                rusqlite::params![name, data, mtime],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    /// 文件的修改时间 (unix 秒), 没有记录时为 None
+    pub fn mtime(&self, name: &str) -> Result<Option<i64>, FetchError> {
+        self.open(true)?
+            .query_row(
+                &format!(
+                    "SELECT mtime FROM {} WHERE name = ?1",
+                    quote_ident(&self.table)
+                ),
+                [name],
+                |r| r.get::<_, Option<i64>>(0),
+            )
+            .optional()
+            .map_err(sqlite_error)?
+            .ok_or_else(|| FetchError::not_found(name))
+    }
+}
+
+impl SyncFolderSource for SqliteFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let d = self.read(file_name)?;
+        Ok((d, Some(file_name.to_string_lossy().to_string())))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY
+            .with_listable()
+            .with_writable()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        let c = self.open(true)?;
+        let mut st = c
+            .prepare(&format!(
+                "SELECT name FROM {} ORDER BY name",
+                quote_ident(&self.table)
+            ))
+            .map_err(sqlite_error)?;
+        let names = st
+            .query_map([], |r| r.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(sqlite_error)?;
+        Ok(names)
+    }
+
+    fn list_entries(&self) -> Result<Vec<ListEntry>, FetchError> {
+        let c = self.open(true)?;
+        let mut st = c
+            .prepare(&format!(
+                "SELECT name, length(bytes), mtime FROM {} ORDER BY name",
+                quote_ident(&self.table)
+            ))
+            .map_err(sqlite_error)?;
+        let entries = st
+            .query_map([], |r| {
+                Ok(ListEntry {
+                    name: r.get(0)?,
+                    size: r.get(1)?,
+                    mtime: r
+                        .get::<_, Option<i64>>(2)?
+                        .and_then(|t| u64::try_from(t).ok()),
+                    etag: None,
+                })
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(sqlite_error)?;
+        Ok(entries)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for SqliteFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        let (s, p) = (self.clone(), file_name.to_path_buf());
+        tokio::task::spawn_blocking(move || s.get_file_content(&p))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sqlite_folder_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let s = SqliteFolderSource::new(temp_dir.path().join("conf.db"), "files");
+        s.create_table().unwrap();
+        s.put("rules/geoip.dat", b"\x00geo\nip", Some(1700000000))
+            .unwrap();
+        s.put("it's empty", b"", None).unwrap();
+
+        let r = s.get_file_content(Path::new("rules/geoip.dat")).unwrap();
+        assert_eq!(r.0, b"\x00geo\nip");
+        let r = s.get_file_content(Path::new("it's empty")).unwrap();
+        assert!(r.0.is_empty());
+        assert!(s
+            .get_file_content(Path::new("nope"))
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(
+            s.list_files().unwrap(),
+            vec!["it's empty", "rules/geoip.dat"]
+        );
+        assert_eq!(s.mtime("rules/geoip.dat").unwrap(), Some(1700000000));
+        assert!(s.mtime("it's empty").unwrap().is_some());
+        let e = &s.list_entries().unwrap()[1];
+        assert_eq!((e.size, e.mtime), (Some(7), Some(1700000000)));
+        assert!(!s.capabilities().watchable);
+
+        // 名称作为参数绑定, 不会被当作 SQL
+        let inject = "x' OR '1'='1";
+        assert!(s
+            .get_file_content(Path::new(inject))
+            .unwrap_err()
+            .is_not_found());
+        s.put(inject, b"i", None).unwrap();
+        assert_eq!(s.get_file_content(Path::new(inject)).unwrap().0, b"i");
+
+        // 数据库不存在时不会创建
+        let missing = SqliteFolderSource::new(temp_dir.path().join("nope.db"), "files");
+        assert!(missing.get_file_content(Path::new("a")).is_err());
+        assert!(!temp_dir.path().join("nope.db").exists());
+
+        let ds = DataSource::Sync(Box::new(s));
+        assert_eq!(ds.read_to_string("rules/geoip.dat").unwrap(), "\0geo\nip");
+    }
+}
@@ -0,0 +1,220 @@
+//! stale-while-revalidate: 缓存过期时立即返回旧缓存, 同时在后台刷新,
+//! 调用者不会因为缓慢的上游而阻塞. 没有缓存文件时仍然等待读取.
+//! 同一个缓存文件同时只有一个后台刷新
+
+use crate::*;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// 正在后台刷新的缓存文件
+static REFRESHING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+fn refreshing() -> std::sync::MutexGuard<'static, BTreeSet<String>> {
+    REFRESHING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 离开作用域时从 [`REFRESHING`] 中删除, 后台任务 panic 或被取消时也会删除
+struct RefreshGuard(String);
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        refreshing().remove(&self.0);
+    }
+}
+
+/// 在后台执行 `refresh`. `cf` 已有刷新在进行时什么也不做
+fn spawn_refresh<F>(cf: String, refresh: F)
+where
+    F: Future<Output = Result<Vec<u8>, FetchError>> + Send + 'static,
+{
+    if !refreshing().insert(cf.clone()) {
+        return;
+    }
+    let guard = RefreshGuard(cf);
+    tokio::spawn(async move {
+        if let Err(e) = refresh.await {
+            warn!("background refresh of {} failed: {e}", guard.0);
+        }
+        drop(guard);
+    });
+}
+
+/// 缓存已过期时读取旧缓存, 读取失败或缓存未过期时返回 None
+async fn read_stale(fc: &FileCache) -> Option<Vec<u8>> {
+    if !matches!(fc.is_cache_timeout(), Ok(Some(true))) {
+        return None;
+    }
+    let d = fc.read_cache_file_async().await.ok()?;
+    #[cfg(feature = "journal")]
+    journal::record_cache(fc, "stale");
+    Some(d)
+}
+
+/// 以 stale-while-revalidate 方式读取, 不需要设置 [`FileCache::stale_while_revalidate`].
+/// 缓存未过期或不存在时与 [`fetch_with_cache_async`] 相同
+pub async fn fetch_with_cache_swr_async(
+    fc: &FileCache,
+    s: Arc<dyn AsyncSource>,
+) -> Result<Vec<u8>, FetchError> {
+    let Some(d) = read_stale(fc).await else {
+        return fetch_with_cache_async(fc, &*s).await;
+    };
+    let bg = fc.clone();
+    spawn_refresh(fc.cache_file_path.clone().unwrap(), async move {
+        fetch_with_cache_async(&bg, &*s).await
+    });
+    Ok(d)
+}
+
+#[cfg(feature = "reqwest")]
+impl SingleFileSource {
+    /// 远程条目设置了 stale_while_revalidate 且缓存已过期时, 返回旧缓存并在后台刷新
+    pub(crate) async fn fetch_stale_async(&self) -> Option<Vec<u8>> {
+        let fc = match self {
+            SingleFileSource::Http(hs, fc) if hs.local_path().is_none() => fc,
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(_, fc) => fc,
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(_, fc) => fc,
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(_, fc) => fc,
+            _ => return None,
+        };
+        if !fc.stale_while_revalidate {
+            return None;
+        }
+        let fc = self.resolve_cache(fc).into_owned();
+        let d = read_stale(&fc).await?;
+        let cf = fc.cache_file_path.clone().unwrap();
+        match self {
+            SingleFileSource::Http(hs, _) => {
+                let hs = hs.clone();
+                spawn_refresh(cf, async move {
+                    revalidate::fetch_http_with_cache_async(&fc, &hs).await
+                })
+            }
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(s, _) => {
+                let s = s.clone();
+                spawn_refresh(cf, async move { fetch_with_cache_async(&fc, &s).await })
+            }
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(s, _) => {
+                let s = s.clone();
+                spawn_refresh(cf, async move { fetch_with_cache_async(&fc, &s).await })
+            }
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(s, _) => {
+                let s = s.clone();
+                spawn_refresh(cf, async move { fetch_with_cache_async(&fc, &s).await })
+            }
+            _ => unreachable!(),
+        }
+        Some(d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// 每次读取等待一段时间, 返回读取次数
+    #[derive(Default)]
+    struct Slow(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl AsyncSource for Slow {
+        async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(n.to_string().into_bytes())
+        }
+    }
+
+    fn expire(cf: &Path) {
+        std::fs::File::options()
+            .append(true)
+            .open(cf)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(120))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_swr() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .resolve("a")
+            .into_owned();
+        let cf = PathBuf::from(fc.cache_file_path.clone().unwrap());
+        let s = Arc::new(Slow::default());
+
+        // 没有缓存时等待读取
+        assert_eq!(
+            fetch_with_cache_swr_async(&fc, s.clone()).await.unwrap(),
+            b"1"
+        );
+        expire(&cf);
+        let t = std::time::Instant::now();
+        assert_eq!(
+            fetch_with_cache_swr_async(&fc, s.clone()).await.unwrap(),
+            b"1"
+        );
+        assert_eq!(
+            fetch_with_cache_swr_async(&fc, s.clone()).await.unwrap(),
+            b"1"
+        );
+        assert!(t.elapsed() < Duration::from_millis(50));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        // 两次过期读取只触发一次后台刷新
+        assert_eq!(s.0.load(Ordering::SeqCst), 2);
+        assert_eq!(std::fs::read(&cf).unwrap(), b"2");
+        assert_eq!(fc.is_cache_timeout().unwrap(), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_panic() {
+        let cf = "swr-test-panic".to_string();
+        spawn_refresh(cf.clone(), async { panic!("refresh") });
+        spawn_refresh(cf.clone(), std::future::pending());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // panic 后可以再次刷新
+        assert!(!refreshing().contains(&cf));
+
+        // 被取消的刷新也不会一直占用
+        let c = cf.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async { spawn_refresh(c.clone(), std::future::pending()) });
+            assert!(refreshing().contains(&c));
+        })
+        .join()
+        .unwrap();
+        assert!(!refreshing().contains(&cf));
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_swr_http() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let hs = HttpSource {
+            url: format!("http://{}/a", l.local_addr().unwrap()),
+            ..Default::default()
+        };
+        drop(l);
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .with_stale_while_revalidate();
+        let sf = SingleFileSource::Http(hs, fc);
+        let cf = PathBuf::from(sf.file_cache().unwrap().cache_file_path.clone().unwrap());
+        std::fs::write(&cf, "old").unwrap();
+        expire(&cf);
+        // 上游不可用时也立即返回旧缓存
+        assert_eq!(sf.fetch_async().await.unwrap(), b"old");
+        assert_eq!(sf.fetch_async().await.unwrap(), b"old");
+    }
+}
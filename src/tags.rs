@@ -0,0 +1,201 @@
+//! 来源和条目的标签 (如 `team=infra`, `tier=critical`), 读取时带到 [`FileContent::tags`]
+//! 和日志中, 并按标签统计读取次数, 便于一个进程中有多个数据根时按团队区分
+
+use crate::*;
+use std::sync::Mutex;
+
+/// 一组标签的读取统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagStats {
+    pub tags: Vec<(String, String)>,
+    pub reads: u64,
+    pub errors: u64,
+    pub bytes: u64,
+}
+
+static STATS: Mutex<Vec<TagStats>> = Mutex::new(Vec::new());
+
+/// 所有带标签的读取的统计, 每组不同的标签一项
+pub fn stats() -> Vec<TagStats> {
+    STATS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// 含有 `key=value` 标签的统计
+pub fn stats_with(key: &str, value: &str) -> Vec<TagStats> {
+    let mut v = stats();
+    v.retain(|s| s.tags.iter().any(|(k, v)| k == key && v == value));
+    v
+}
+
+pub fn reset_stats() {
+    STATS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+fn format_tags(tags: &[(String, String)]) -> String {
+    let v: Vec<String> = tags.iter().map(|(k, v)| format!("{k}={v}")).collect();
+    v.join(",")
+}
+
+/// 记录一次读取的结果, 没有标签时不记录
+pub(crate) fn observe(
+    tags: &[(String, String)],
+    file_name: &Path,
+    r: Result<FileContent, FetchError>,
+) -> Result<FileContent, FetchError> {
+    if tags.is_empty() {
+        return r;
+    }
+    match &r {
+        Ok(_) => debug!("read {} [{}]", file_name.display(), format_tags(tags)),
+        Err(e) => debug!(
+            "read {} failed [{}]: {e}",
+            file_name.display(),
+            format_tags(tags)
+        ),
+    }
+    let mut stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let i = match stats.iter().position(|s| s.tags == tags) {
+        Some(i) => i,
+        None => {
+            stats.push(TagStats {
+                tags: tags.to_vec(),
+                ..Default::default()
+            });
+            stats.len() - 1
+        }
+    };
+    let s = &mut stats[i];
+    s.reads += 1;
+    match &r {
+        Ok(fc) => s.bytes += fc.data.len() as u64,
+        Err(_) => s.errors += 1,
+    }
+    r
+}
+
+/// 为整个来源加上标签, 读到的 [`FileContent::tags`] 中先是条目自己的标签, 然后是来源的
+#[derive(Debug)]
+pub struct TaggedSource {
+    pub inner: DataSource,
+    pub tags: Vec<(String, String)>,
+}
+
+impl TaggedSource {
+    pub fn new(inner: DataSource) -> Self {
+        Self {
+            inner,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    fn tagged(
+        &self,
+        file_name: &Path,
+        r: Result<FileContent, FetchError>,
+    ) -> Result<FileContent, FetchError> {
+        observe(
+            &self.tags,
+            file_name,
+            r.map(|mut fc| {
+                fc.tags.extend(self.tags.iter().cloned());
+                fc
+            }),
+        )
+    }
+}
+
+impl SyncFolderSource for TaggedSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed(file_name)
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.tagged(file_name, self.inner.get_file_content_typed(file_name))
+    }
+
+    fn get_file_content_ctx(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        self.tagged(file_name, self.inner.get_file_content_ctx(file_name, ctx))
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        self.inner.capabilities().read_only()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        self.inner.list_files()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for TaggedSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.get_file_content_typed_async(file_name)
+            .await
+            .map(|fc| (fc.data, fc.path))
+    }
+
+    async fn get_file_content_typed_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<FileContent, FetchError> {
+        let r = self.inner.get_file_content_typed_async(file_name).await;
+        self.tagged(file_name, r)
+    }
+
+    async fn get_file_content_ctx_async(
+        &self,
+        file_name: &Path,
+        ctx: &FetchContext,
+    ) -> Result<FileContent, FetchError> {
+        let r = self.inner.get_file_content_ctx_async(file_name, ctx).await;
+        self.tagged(file_name, r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let ds = DataSource::FileMap(HashMap::from([
+            (
+                "a".to_string(),
+                SingleFileSource::Inline(b"ab".to_vec())
+                    .with_meta(FileMeta::default().with_tag("tier", "critical-test")),
+            ),
+            ("b".to_string(), SingleFileSource::Inline(b"b".to_vec())),
+        ]));
+        let ts = TaggedSource::new(ds).with_tag("team", "infra-test");
+        let fc = ts.get_file_content_typed(Path::new("a")).unwrap();
+        assert_eq!(
+            fc.tags,
+            vec![
+                ("tier".to_string(), "critical-test".to_string()),
+                ("team".to_string(), "infra-test".to_string())
+            ]
+        );
+        ts.get_file_content(Path::new("b")).unwrap();
+        ts.get_file_content(Path::new("c")).unwrap_err();
+
+        let s = stats_with("tier", "critical-test");
+        assert_eq!((s[0].reads, s[0].bytes), (1, 2));
+        let s = stats_with("team", "infra-test");
+        assert_eq!(s.len(), 1);
+        assert_eq!((s[0].reads, s[0].errors, s[0].bytes), (3, 1, 3));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::*;
+
+/// 从 DataSource 生成 tar 包, 生成的包可以直接用于 [`DataSource::TarInMemory`] 或
+/// [`DataSource::TarFile`], 条目名称与从原来的 DataSource 读取时使用的名称相同 (以 `/` 分隔).
+///
+/// 默认所有条目的修改时间为 0, 同样的内容总是得到同样的包
+#[derive(Debug, Clone, Default)]
+pub struct TarBuilder {
+    files: Vec<String>,
+    prefix: Option<String>,
+    strip_prefix: bool,
+    mtime: u64,
+    #[cfg(feature = "flate2")]
+    gzip: bool,
+}
+
+impl TarBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加指定的文件
+    pub fn files(mut self, files: &[&str]) -> Self {
+        self.files.extend(files.iter().map(|f| f.to_string()));
+        self
+    }
+
+    /// 添加 [`SyncFolderSource::list_files`] 列出的以 `prefix` 开头的所有文件.
+    /// `strip` 为 true 时条目名称去掉该前缀, 用于把一个子目录打包成独立的包
+    pub fn prefix(mut self, prefix: &str, strip: bool) -> Self {
+        self.prefix = Some(prefix.replace('\\', "/"));
+        self.strip_prefix = strip;
+        self
+    }
+
+    pub fn mtime(mut self, secs: u64) -> Self {
+        self.mtime = secs;
+        self
+    }
+
+    /// 用 gzip 压缩, 得到 `.tar.gz`
+    #[cfg(feature = "flate2")]
+    pub fn gzip(mut self, enable: bool) -> Self {
+        self.gzip = enable;
+        self
+    }
+
+    /// 要打包的 (读取时的名称, 条目名称), 按条目名称排序
+    fn entries(&self, ds: &DataSource) -> Result<Vec<(String, String)>, FetchError> {
+        let mut v: Vec<(String, String)> = self
+            .files
+            .iter()
+            .map(|f| (f.clone(), f.replace('\\', "/")))
+            .collect();
+        if let Some(p) = &self.prefix {
+            ds.capabilities().require_listable()?;
+            for f in ds.list_files()? {
+                if let Some(rest) = f.strip_prefix(p.as_str()) {
+                    let name = if self.strip_prefix {
+                        rest.trim_start_matches('/').to_string()
+                    } else {
+                        f.clone()
+                    };
+                    if !name.is_empty() {
+                        v.push((f, name));
+                    }
+                }
+            }
+        }
+        v.sort_by(|a, b| a.1.cmp(&b.1));
+        v.dedup_by(|a, b| a.1 == b.1);
+        Ok(v)
+    }
+
+    /// 把 tar 写入 `w`, 返回打包的文件数. 设置了 gzip 时写入压缩后的数据
+    pub fn write_to(&self, ds: &DataSource, w: &mut dyn io::Write) -> Result<usize, FetchError> {
+        let entries = self.entries(ds)?;
+        #[cfg(feature = "flate2")]
+        if self.gzip {
+            let mut t = Vec::new();
+            self.append_all(ds, &entries, &mut t)?;
+            w.write_all(&gzip::gzip(&t))?;
+            return Ok(entries.len());
+        }
+        self.append_all(ds, &entries, w)?;
+        Ok(entries.len())
+    }
+
+    pub fn build(&self, ds: &DataSource) -> Result<Vec<u8>, FetchError> {
+        let mut v = Vec::new();
+        self.write_to(ds, &mut v)?;
+        Ok(v)
+    }
+
+    fn append_all(
+        &self,
+        ds: &DataSource,
+        entries: &[(String, String)],
+        w: &mut dyn io::Write,
+    ) -> Result<(), FetchError> {
+        let mut b = tar::Builder::new(w);
+        for (f, name) in entries {
+            let (d, _) = ds.get_file_content(Path::new(f))?;
+            append_file(&mut b, name, &d, self.mtime)?;
+        }
+        b.finish()?;
+        Ok(())
+    }
+}
+
+/// 以 0644 权限添加一个普通文件
+pub(crate) fn append_file<W: io::Write>(
+    b: &mut tar::Builder<W>,
+    name: &str,
+    d: &[u8],
+    mtime: u64,
+) -> io::Result<()> {
+    let mut h = tar::Header::new_gnu();
+    h.set_size(d.len() as u64);
+    h.set_mode(0o644);
+    h.set_mtime(mtime);
+    b.append_data(&mut h, name, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_builder() {
+        let ds = DataSource::file_map(vec![
+            (
+                "top.txt".to_string(),
+                SingleFileSource::Inline(b"t".to_vec()),
+            ),
+            (
+                "rules/geoip.dat".to_string(),
+                SingleFileSource::Inline(b"g".to_vec()),
+            ),
+            (
+                "rules\\geosite.dat".to_string(),
+                SingleFileSource::Inline(b"s".to_vec()),
+            ),
+        ]);
+
+        let t = TarBuilder::new().prefix("rules/", true).build(&ds).unwrap();
+        assert_eq!(
+            t,
+            TarBuilder::new().prefix("rules/", true).build(&ds).unwrap()
+        );
+        let t = DataSource::TarInMemory(t);
+        assert_eq!(t.list_files().unwrap(), vec!["geoip.dat", "geosite.dat"]);
+        assert_eq!(t.read_to_string("geosite.dat").unwrap(), "s");
+
+        let b = TarBuilder::new().files(&["top.txt"]).prefix("rules", false);
+        #[cfg(feature = "flate2")]
+        let b = b.gzip(true);
+        let t = DataSource::TarInMemory(b.build(&ds).unwrap());
+        assert_eq!(
+            t.list_files().unwrap(),
+            vec!["rules/geoip.dat", "rules/geosite.dat", "top.txt"]
+        );
+        assert_eq!(t.read_to_string("top.txt").unwrap(), "t");
+    }
+}
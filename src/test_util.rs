@@ -0,0 +1,237 @@
+//! 随机生成各种形式的 DataSource, 供下游 crate 对自己的配置加载代码做基于属性的测试.
+//!
+//! ```ignore
+//! data_source::test_util::check_sources(64, |tree, ds| {
+//!     for (name, data) in &tree.files {
+//!         assert_eq!(&ds.get_file_content(Path::new(name)).unwrap().0, data);
+//!     }
+//! });
+//! ```
+
+use crate::*;
+use std::collections::BTreeMap;
+
+/// 确定性的伪随机数生成器 (xorshift64*), 同一种子总是得到同样的结果
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // 状态不能为 0
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// `[0, n)` 中的数, n 为 0 时返回 0
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, v: &'a [T]) -> &'a T {
+        &v[self.below(v.len())]
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+}
+
+const SEGMENTS: &[&str] = &[
+    "conf",
+    "rules",
+    "geo",
+    "a",
+    "b",
+    "data-1",
+    "x_y",
+    "with space",
+    "ünï",
+    "2024",
+];
+const EXTENSIONS: &[&str] = &[".json", ".dat", ".yaml", ".txt", ""];
+
+/// 一组文件, 键为以 `/` 分隔的相对路径
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTree {
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl FileTree {
+    /// 最多 `max_files` 个文件, 目录深度不超过 3. 不会出现某个文件名同时也是目录的情况
+    pub fn arbitrary(rng: &mut Rng, max_files: usize) -> Self {
+        let mut files = BTreeMap::new();
+        let n = rng.below(max_files) + 1;
+        for _ in 0..n * 2 {
+            if files.len() >= n {
+                break;
+            }
+            let depth = rng.below(3);
+            let mut name: Vec<&str> = (0..depth).map(|_| *rng.pick(SEGMENTS)).collect();
+            let file = format!("{}{}", rng.pick(SEGMENTS), rng.pick(EXTENSIONS));
+            name.push(&file);
+            let name = name.join("/");
+            let conflicts = files.keys().any(|k: &String| {
+                k == &name
+                    || name.starts_with(&format!("{k}/"))
+                    || k.starts_with(&format!("{name}/"))
+            });
+            if conflicts {
+                continue;
+            }
+            let len = *rng.pick(&[0, 1, 16, 300, 4096]);
+            files.insert(name, rng.bytes(len));
+        }
+        Self { files }
+    }
+
+    /// 写入 `dir`, 返回以它为唯一搜索路径的 [`DataSource::Folders`]
+    pub fn to_folders(&self, dir: &Path) -> io::Result<DataSource> {
+        for (name, data) in &self.files {
+            let p = dir.join(name);
+            if let Some(parent) = p.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(p, data)?;
+        }
+        Ok(DataSource::Folders(vec![dir.to_string_lossy().to_string()]))
+    }
+
+    pub fn to_file_map(&self) -> DataSource {
+        DataSource::FileMap(
+            self.files
+                .iter()
+                .map(|(k, v)| (k.clone(), SingleFileSource::Inline(v.clone())))
+                .collect(),
+        )
+    }
+
+    /// 打包成 tar, `gzip` 为 true 且开启了 gzip feature 时压缩
+    #[cfg(feature = "tar")]
+    pub fn to_tar(&self, gzip: bool) -> Result<Vec<u8>, FetchError> {
+        let b = tar_builder::TarBuilder::new().prefix("", false);
+        #[cfg(feature = "flate2")]
+        let b = b.gzip(gzip);
+        #[cfg(not(feature = "flate2"))]
+        let _ = gzip;
+        b.build(&self.to_file_map())
+    }
+}
+
+/// 生成的 DataSource 的形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Folders,
+    FileMap,
+    #[cfg(feature = "tar")]
+    TarInMemory,
+    #[cfg(feature = "tar")]
+    TarFile,
+}
+
+impl Shape {
+    pub const ALL: &'static [Shape] = &[
+        Shape::Folders,
+        Shape::FileMap,
+        #[cfg(feature = "tar")]
+        Shape::TarInMemory,
+        #[cfg(feature = "tar")]
+        Shape::TarFile,
+    ];
+
+    /// 以 `tree` 生成这种形式的 DataSource, 需要写文件时写入 `dir`
+    pub fn build(
+        self,
+        tree: &FileTree,
+        rng: &mut Rng,
+        dir: &Path,
+    ) -> Result<DataSource, FetchError> {
+        #[cfg(not(feature = "tar"))]
+        let _ = rng;
+        Ok(match self {
+            Shape::Folders => tree.to_folders(dir)?,
+            Shape::FileMap => tree.to_file_map(),
+            #[cfg(feature = "tar")]
+            Shape::TarInMemory => DataSource::TarInMemory(tree.to_tar(rng.below(2) == 0)?),
+            #[cfg(feature = "tar")]
+            Shape::TarFile => {
+                let p = dir.join("bundle.tar");
+                std::fs::write(&p, tree.to_tar(rng.below(2) == 0)?)?;
+                DataSource::TarFile(TarFile(p.to_string_lossy().to_string()))
+            }
+        })
+    }
+}
+
+/// 删除时清理的临时目录
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(seed: u64, shape: Shape) -> io::Result<Self> {
+        let p = std::env::temp_dir().join(format!(
+            "data-source-test-util-{}-{seed}-{shape:?}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&p)?;
+        Ok(Self(p))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 对 `cases` 个随机的 FileTree, 以每种 [`Shape`] 生成 DataSource 并调用 `f`.
+///
+/// `f` panic 时会先打印种子和形式; 设置环境变量 `DATA_SOURCE_SEED` 可以只重跑某个种子
+pub fn check_sources(cases: u64, mut f: impl FnMut(&FileTree, &DataSource)) {
+    let seeds: Vec<u64> = match std::env::var("DATA_SOURCE_SEED") {
+        Ok(s) => vec![s.parse().expect("DATA_SOURCE_SEED must be a number")],
+        Err(_) => (0..cases).collect(),
+    };
+    for seed in seeds {
+        for &shape in Shape::ALL {
+            let mut rng = Rng::new(seed);
+            let tree = FileTree::arbitrary(&mut rng, 12);
+            let dir = TempDir::new(seed, shape).expect("create temp dir");
+            let ds = shape
+                .build(&tree, &mut rng, &dir.0)
+                .expect("build data source");
+            let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(&tree, &ds)));
+            if let Err(e) = r {
+                eprintln!("check_sources failed: DATA_SOURCE_SEED={seed} shape={shape:?}");
+                std::panic::resume_unwind(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_sources() {
+        check_sources(16, |tree, ds| {
+            for (name, data) in &tree.files {
+                assert_eq!(&ds.get_file_content(Path::new(name)).unwrap().0, data);
+            }
+            let mut listed = ds.list_files().unwrap();
+            listed.sort();
+            assert_eq!(listed, tree.files.keys().cloned().collect::<Vec<_>>());
+        });
+
+        let a = FileTree::arbitrary(&mut Rng::new(7), 12);
+        assert_eq!(a, FileTree::arbitrary(&mut Rng::new(7), 12));
+    }
+}
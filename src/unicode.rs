@@ -0,0 +1,90 @@
+//! 文件名的 Unicode 规范化. macOS 上创建的文件名通常是 NFD, 而 Linux 客户端请求的名称
+//! 通常是 NFC, 两者字节不同, 默认找不到对应的文件.
+//!
+//! 用 [`set_unicode_normalization`] 设置后, Folders, StdReadFile, FileMap 和 tar 查找前把
+//! 请求的名称转为指定的形式; Folders 和 StdReadFile 中转换后的文件不存在时仍使用原名称,
+//! FileMap 和 tar 中的名称也按同样的形式比较
+
+use crate::*;
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
+use std::sync::atomic::AtomicU8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnicodeForm {
+    Nfc,
+    Nfd,
+}
+
+/// 0 表示不规范化
+static FORM: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_unicode_normalization(form: Option<UnicodeForm>) {
+    let v = match form {
+        None => 0,
+        Some(UnicodeForm::Nfc) => 1,
+        Some(UnicodeForm::Nfd) => 2,
+    };
+    FORM.store(v, Ordering::Relaxed);
+}
+
+pub fn unicode_normalization() -> Option<UnicodeForm> {
+    match FORM.load(Ordering::Relaxed) {
+        1 => Some(UnicodeForm::Nfc),
+        2 => Some(UnicodeForm::Nfd),
+        _ => None,
+    }
+}
+
+pub fn normalize_str(s: &str, form: UnicodeForm) -> Cow<'_, str> {
+    match form {
+        UnicodeForm::Nfc => ComposingNormalizerBorrowed::new_nfc().normalize(s),
+        UnicodeForm::Nfd => DecomposingNormalizerBorrowed::new_nfd().normalize(s),
+    }
+}
+
+/// 按 [`set_unicode_normalization`] 的设置转换, 未设置或不是 UTF-8 时原样返回
+pub fn normalize_path(p: &Path) -> Cow<'_, Path> {
+    let (Some(form), Some(s)) = (unicode_normalization(), p.to_str()) else {
+        return Cow::Borrowed(p);
+    };
+    match normalize_str(s, form) {
+        Cow::Borrowed(_) => Cow::Borrowed(p),
+        Cow::Owned(s) => Cow::Owned(PathBuf::from(s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const NFC: &str = "caf\u{e9}.json";
+    const NFD: &str = "cafe\u{301}.json";
+
+    #[test]
+    fn test_unicode_normalization() {
+        assert_eq!(normalize_str(NFD, UnicodeForm::Nfc), NFC);
+        assert_eq!(normalize_str(NFC, UnicodeForm::Nfd), NFD);
+
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join(NFD), "d").unwrap();
+        std::fs::write(temp_dir.path().join("plain.json"), "p").unwrap();
+        let ds = DataSource::Folders(vec![temp_dir.path().to_string_lossy().to_string()]);
+        let map = DataSource::FileMap(HashMap::from([(
+            NFD.to_string(),
+            SingleFileSource::Inline(b"m".to_vec()),
+        )]));
+        assert!(ds.get_file_content(Path::new(NFC)).is_err());
+
+        set_unicode_normalization(Some(UnicodeForm::Nfd));
+        let r = ds.get_file_content(Path::new(NFC));
+        let m = map.get_file_content(Path::new(NFC));
+        let std = DataSource::StdReadFile.get_file_content(&temp_dir.path().join(NFC));
+        let plain = ds.get_file_content(Path::new("plain.json"));
+        set_unicode_normalization(None);
+        assert_eq!(r.unwrap().0, b"d");
+        assert_eq!(m.unwrap().0, b"m");
+        assert_eq!(std.unwrap().0, b"d");
+        assert_eq!(plain.unwrap().0, b"p");
+    }
+}
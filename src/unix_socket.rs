@@ -0,0 +1,263 @@
+//! 通过 Unix 域套接字访问 HTTP 服务 (很多 sidecar 只在套接字上提供配置接口).
+//!
+//! [`HttpSource`] 的 url 写作 `unix:///var/run/app.sock:/v1/config`, 即套接字路径和请求路径
+//! 以 `:` 分隔 (与 nginx 的 `http://unix:/tmp/app.sock:/uri` 相同), 省略请求路径时为 `/`.
+//! 只发送 HTTP/1.1 GET, 不使用代理, 其它行为 (请求头, 大小限制, 不检查状态码) 与普通 url 相同
+
+use crate::*;
+
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// `unix://` url 对应的套接字路径和请求路径 (含查询串), 其它 url 返回 None
+pub fn parse_unix_url(url: &str) -> Option<(PathBuf, String)> {
+    if !url.get(..7)?.eq_ignore_ascii_case("unix://") {
+        return None;
+    }
+    let rest = &url[7..];
+    if !rest.starts_with('/') {
+        return None;
+    }
+    let (sock, path) = match rest.find(":/") {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, "/"),
+    };
+    Some((PathBuf::from(sock), path.to_string()))
+}
+
+fn request(hs: &HttpSource, path: &str) -> Vec<u8> {
+    let mut r = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n");
+    for (k, v) in hs.custom_request_headers.iter().flatten() {
+        r.push_str(&format!("{k}: {v}\r\n"));
+    }
+    r.push_str("\r\n");
+    r.into_bytes()
+}
+
+fn decode_chunked(mut d: &[u8]) -> Option<Vec<u8>> {
+    let mut r = Vec::new();
+    loop {
+        let i = d.windows(2).position(|w| w == b"\r\n")?;
+        let line = std::str::from_utf8(&d[..i]).ok()?;
+        let size = usize::from_str_radix(line.split(';').next()?.trim(), 16).ok()?;
+        d = &d[i + 2..];
+        if size == 0 {
+            return Some(r);
+        }
+        r.extend_from_slice(d.get(..size)?);
+        d = d.get(size + 2..)?;
+    }
+}
+
+/// 解析完整的响应 (服务端在发送后关闭连接), 返回内容, Content-Type 和状态码
+fn parse_response(hs: &HttpSource, d: &[u8]) -> Result<(Vec<u8>, Option<String>, u16), FetchError> {
+    let invalid = || FetchError::Invalid(format!("invalid http response from `{}`", hs.url));
+    let end = d
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(invalid)?;
+    let head = std::str::from_utf8(&d[..end]).map_err(|_| invalid())?;
+    let body = &d[end + 4..];
+    let mut lines = head.split("\r\n");
+    let status: u16 = lines
+        .next()
+        .and_then(|l| l.strip_prefix("HTTP/1."))
+        .and_then(|l| l.split(' ').nth(1))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid)?;
+    let (mut content_length, mut content_type, mut chunked) = (None, None, false);
+    for l in lines {
+        let Some((k, v)) = l.split_once(':') else {
+            continue;
+        };
+        let v = v.trim();
+        match k.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = Some(v.parse::<u64>().map_err(|_| invalid())?),
+            "content-type" => content_type = Some(v.to_string()),
+            "transfer-encoding" => chunked = v.eq_ignore_ascii_case("chunked"),
+            _ => {}
+        }
+    }
+    if let (Some(limit), Some(size)) = (hs.size_limit_bytes, content_length) {
+        if size as usize > limit {
+            return Err(HttpError::SizeLimit {
+                url: hs.url.clone(),
+                size,
+                limit,
+            }
+            .into());
+        }
+    }
+    let body = if chunked {
+        decode_chunked(body).ok_or_else(invalid)?
+    } else {
+        check_body_length(&hs.url, content_length, body.len(), None::<FetchError>)?;
+        let n = content_length.map_or(body.len(), |n| n as usize);
+        body[..n].to_vec()
+    };
+    Ok((body, content_type, status))
+}
+
+#[cfg(unix)]
+pub(crate) fn fetch(
+    hs: &HttpSource,
+    sock: &Path,
+    path: &str,
+) -> Result<(Vec<u8>, Option<String>), FetchError> {
+    use std::io::{Read, Write};
+    let mut s =
+        std::os::unix::net::UnixStream::connect(sock).map_err(|e| local_io_error(e, sock))?;
+    s.set_read_timeout(Some(TIMEOUT))?;
+    s.set_write_timeout(Some(TIMEOUT))?;
+    s.write_all(&request(hs, path))?;
+    let mut d = Vec::new();
+    s.read_to_end(&mut d)?;
+    parse_response(hs, &d).map(|(b, ct, _)| (b, ct))
+}
+
+#[cfg(all(unix, feature = "tokio"))]
+pub(crate) async fn fetch_async(
+    hs: &HttpSource,
+    sock: &Path,
+    path: &str,
+) -> Result<(Vec<u8>, Option<String>), FetchError> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let d = tokio::time::timeout(TIMEOUT, async {
+        let mut s = tokio::net::UnixStream::connect(sock)
+            .await
+            .map_err(|e| local_io_error(e, sock))?;
+        s.write_all(&request(hs, path)).await?;
+        let mut d = Vec::new();
+        s.read_to_end(&mut d).await?;
+        Ok::<_, FetchError>(d)
+    })
+    .await
+    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "unix socket request timed out"))??;
+    parse_response(hs, &d).map(|(b, ct, _)| (b, ct))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn fetch(
+    _hs: &HttpSource,
+    _sock: &Path,
+    _path: &str,
+) -> Result<(Vec<u8>, Option<String>), FetchError> {
+    Err(FetchError::Unsupported("unix socket"))
+}
+
+#[cfg(all(not(unix), feature = "tokio"))]
+pub(crate) async fn fetch_async(
+    hs: &HttpSource,
+    sock: &Path,
+    path: &str,
+) -> Result<(Vec<u8>, Option<String>), FetchError> {
+    fetch(hs, sock, path)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+    use tempfile::TempDir;
+
+    /// 返回请求行和 X-Token 请求头, 以 chunked 编码返回 `/chunked`
+    fn serve(sock: &Path) {
+        let l = UnixListener::bind(sock).unwrap();
+        std::thread::spawn(move || {
+            for s in l.incoming() {
+                let mut s = s.unwrap();
+                let mut lines = BufReader::new(&s).lines().map(|l| l.unwrap());
+                let req = lines.next().unwrap();
+                let token = lines
+                    .take_while(|l| !l.is_empty())
+                    .find_map(|l| l.strip_prefix("X-Token: ").map(|t| t.to_string()))
+                    .unwrap_or_default();
+                let body = format!("{req} {token}");
+                if req.starts_with("GET /chunked ") {
+                    write!(
+                        s,
+                        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n"
+                    )
+                    .unwrap();
+                } else {
+                    write!(
+                        s,
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_parse_unix_url() {
+        assert_eq!(
+            parse_unix_url("unix:///var/run/app.sock:/v1/a?x=1").unwrap(),
+            (PathBuf::from("/var/run/app.sock"), "/v1/a?x=1".to_string())
+        );
+        assert_eq!(
+            parse_unix_url("UNIX:///a.sock").unwrap(),
+            (PathBuf::from("/a.sock"), "/".to_string())
+        );
+        assert!(parse_unix_url("unix://a.sock").is_none());
+        assert!(parse_unix_url("http://a/b").is_none());
+    }
+
+    #[test]
+    fn test_unix_socket_fetch() {
+        let temp_dir = TempDir::new().unwrap();
+        let sock = temp_dir.path().join("app.sock");
+        serve(&sock);
+        let hs = HttpSource {
+            url: format!("unix://{}:/v1/config", sock.display()),
+            custom_request_headers: Some(vec![("X-Token".to_string(), "t".to_string())]),
+            ..Default::default()
+        };
+        let (d, ct) = hs.fetch_typed().unwrap();
+        assert_eq!(d, b"GET /v1/config HTTP/1.1 t");
+        assert_eq!(ct.as_deref(), Some("text/plain"));
+
+        let chunked = HttpSource {
+            url: format!("unix://{}:/chunked", sock.display()),
+            ..Default::default()
+        };
+        assert_eq!(chunked.fetch().unwrap(), b"abcde");
+
+        let limited = HttpSource {
+            size_limit_bytes: Some(4),
+            ..hs.clone()
+        };
+        assert!(limited.fetch().is_err());
+        let missing = HttpSource {
+            url: format!("unix://{}/none.sock:/", temp_dir.path().display()),
+            ..Default::default()
+        };
+        assert!(missing.fetch().is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_unix_socket_fetch_async() {
+        let temp_dir = TempDir::new().unwrap();
+        let sock = temp_dir.path().join("app.sock");
+        serve(&sock);
+        let hs = HttpSource {
+            url: format!("unix://{}:/a", sock.display()),
+            ..Default::default()
+        };
+        assert_eq!(hs.fetch_async().await.unwrap(), b"GET /a HTTP/1.1 ");
+    }
+
+    #[test]
+    fn test_truncated_response() {
+        let hs = HttpSource {
+            url: "unix:///a.sock".to_string(),
+            ..Default::default()
+        };
+        let e = parse_response(&hs, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nab").unwrap_err();
+        assert!(matches!(e, FetchError::Http(HttpError::Truncated { .. })));
+        assert!(parse_response(&hs, b"garbage").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! 用一个 URI 字符串描述任意来源, 见 [`SingleFileSource::from_uri`].
+//!
+//! 内置的 scheme:
+//! - `file:///path/a.json`: 本地文件
+//! - `http://...`, `https://...`: 不缓存的 [`HttpSource`]
+//! - `unix:///var/run/app.sock:/path`: 通过 Unix 域套接字请求的 [`HttpSource`], 见 `unix_socket`
+//! - `s3://bucket/key?region=us-east-1`: 需要 s3 feature, region 默认为 `us-east-1`
+//! - `tar:///path/a.tar#dir/a.json`: 本地 tar 包中的文件, 需要 tar feature
+//! - `env://NAME`: 读取时取环境变量 NAME 的值
+//! - `data:...`: 见 [`data_uri`]
+//!
+//! 其它 scheme 可以用 [`register_scheme`] 注册, 注册的 scheme 优先于内置的
+
+use crate::*;
+use std::sync::{Arc, RwLock};
+
+/// 由完整的 URI 构造来源
+pub type SchemeConstructor = dyn Fn(&str) -> Result<SingleFileSource, FetchError> + Send + Sync;
+
+static SCHEMES: RwLock<Vec<(String, Arc<SchemeConstructor>)>> = RwLock::new(Vec::new());
+
+/// 注册 (或替换) `scheme` 的构造函数, scheme 不区分大小写, 不含 `://`
+pub fn register_scheme(scheme: &str, f: Arc<SchemeConstructor>) {
+    let scheme = scheme.to_ascii_lowercase();
+    let mut schemes = SCHEMES.write().unwrap_or_else(|e| e.into_inner());
+    schemes.retain(|(s, _)| *s != scheme);
+    schemes.push((scheme, f));
+}
+
+/// 移除注册的构造函数, 返回是否存在
+pub fn unregister_scheme(scheme: &str) -> bool {
+    let mut schemes = SCHEMES.write().unwrap_or_else(|e| e.into_inner());
+    let n = schemes.len();
+    schemes.retain(|(s, _)| !s.eq_ignore_ascii_case(scheme));
+    schemes.len() != n
+}
+
+fn registered(scheme: &str) -> Option<Arc<SchemeConstructor>> {
+    SCHEMES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|(s, _)| s == scheme)
+        .map(|(_, f)| f.clone())
+}
+
+fn invalid(uri: &str, m: &str) -> FetchError {
+    FetchError::Invalid(format!("uri `{uri}`: {m}"))
+}
+
+impl SingleFileSource {
+    /// 按 scheme 构造来源, 未知的 scheme 返回 [`FetchError::Invalid`]
+    pub fn from_uri(uri: &str) -> Result<Self, FetchError> {
+        if data_uri::is_data_uri(uri) {
+            return Ok(Self::DataUri(uri.to_string()));
+        }
+        let (scheme, rest) = uri
+            .split_once("://")
+            .ok_or_else(|| invalid(uri, "missing scheme"))?;
+        let scheme = scheme.to_ascii_lowercase();
+        if let Some(f) = registered(&scheme) {
+            return f(uri);
+        }
+        match scheme.as_str() {
+            "file" => {
+                let hs = HttpSource {
+                    url: uri.to_string(),
+                    ..Default::default()
+                };
+                let p = hs
+                    .local_path()
+                    .ok_or_else(|| invalid(uri, "not a local file"))?;
+                Ok(Self::FilePath(p.to_string_lossy().to_string()))
+            }
+            "http" | "https" | "unix" => Ok(Self::Http(
+                HttpSource {
+                    url: uri.to_string(),
+                    ..Default::default()
+                },
+                FileCache::default(),
+            )),
+            "s3" => s3_from_uri(uri, rest),
+            "tar" => tar_from_uri(uri, rest),
+            "env" if rest.is_empty() => Err(invalid(uri, "missing variable name")),
+            "env" => Ok(Self::Env(rest.to_string())),
+            s => Err(invalid(uri, &format!("unknown scheme `{s}`"))),
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+fn s3_from_uri(uri: &str, rest: &str) -> Result<SingleFileSource, FetchError> {
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (bucket, key) = path
+        .split_once('/')
+        .filter(|(b, k)| !b.is_empty() && !k.is_empty())
+        .ok_or_else(|| invalid(uri, "expected s3://bucket/key"))?;
+    let region = query
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("region="))
+        .unwrap_or("us-east-1");
+    Ok(SingleFileSource::S3(
+        s3::S3Source::new(bucket, key, region),
+        FileCache::default(),
+    ))
+}
+
+#[cfg(not(feature = "s3"))]
+fn s3_from_uri(_uri: &str, _rest: &str) -> Result<SingleFileSource, FetchError> {
+    Err(FetchError::FeatureDisabled("s3"))
+}
+
+#[cfg(feature = "tar")]
+fn tar_from_uri(uri: &str, rest: &str) -> Result<SingleFileSource, FetchError> {
+    let (archive, inner) = rest
+        .split_once('#')
+        .filter(|(_, i)| !i.is_empty())
+        .ok_or_else(|| invalid(uri, "expected tar:///archive.tar#path"))?;
+    let archive = HttpSource {
+        url: format!("file://{archive}"),
+        ..Default::default()
+    }
+    .local_path()
+    .ok_or_else(|| invalid(uri, "not a local file"))?;
+    let inner = inner.to_string();
+    Ok(SingleFileSource::Generated(
+        generated::Generator::new(move || {
+            let d = read_local_file(&archive)?;
+            get_file_from_tar_in_memory(&inner, &d).map(|r| r.0)
+        }),
+        FileCache::default(),
+    ))
+}
+
+#[cfg(not(feature = "tar"))]
+fn tar_from_uri(_uri: &str, _rest: &str) -> Result<SingleFileSource, FetchError> {
+    Err(FetchError::FeatureDisabled("tar"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_from_uri() {
+        let temp_dir = TempDir::new().unwrap();
+        let f = temp_dir.path().join("a.txt");
+        std::fs::write(&f, "file").unwrap();
+        let sf = SingleFileSource::from_uri(&format!("file://{}", f.display())).unwrap();
+        assert_eq!(sf.fetch().unwrap(), b"file");
+
+        let sf = SingleFileSource::from_uri("https://example.com/a").unwrap();
+        assert_eq!(sf.get_path().unwrap(), "https://example.com/a");
+        let sf = SingleFileSource::from_uri("data:,hi").unwrap();
+        assert_eq!(sf.fetch().unwrap(), b"hi");
+
+        std::env::set_var("DATA_SOURCE_TEST_URI", "env");
+        let sf = SingleFileSource::from_uri("env://DATA_SOURCE_TEST_URI").unwrap();
+        assert_eq!(sf.fetch().unwrap(), b"env");
+        let sf = SingleFileSource::from_uri("env://DATA_SOURCE_TEST_URI_MISSING").unwrap();
+        assert!(sf.fetch().unwrap_err().is_not_found());
+
+        assert!(SingleFileSource::from_uri("nope://x").is_err());
+        assert!(SingleFileSource::from_uri("a.txt").is_err());
+        register_scheme(
+            "NOPE",
+            Arc::new(|u| Ok(SingleFileSource::Inline(u.as_bytes().to_vec()))),
+        );
+        let sf = SingleFileSource::from_uri("nope://x").unwrap();
+        assert_eq!(sf.fetch().unwrap(), b"nope://x");
+        assert!(unregister_scheme("nope"));
+        assert!(SingleFileSource::from_uri("nope://x").is_err());
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_from_tar_uri() {
+        let temp_dir = TempDir::new().unwrap();
+        let f = temp_dir.path().join("a.tar");
+        let mut b = tar::Builder::new(Vec::new());
+        let mut h = tar::Header::new_gnu();
+        h.set_size(2);
+        h.set_cksum();
+        b.append_data(&mut h, "dir/a.json", &b"{}"[..]).unwrap();
+        std::fs::write(&f, b.into_inner().unwrap()).unwrap();
+        let sf = SingleFileSource::from_uri(&format!("tar://{}#dir/a.json", f.display())).unwrap();
+        assert_eq!(sf.fetch().unwrap(), b"{}");
+        assert!(SingleFileSource::from_uri(&format!("tar://{}", f.display())).is_err());
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_from_s3_uri() {
+        let sf = SingleFileSource::from_uri("s3://cfg/a/b.json?region=eu-west-1").unwrap();
+        let SingleFileSource::S3(s, _) = sf else {
+            panic!()
+        };
+        assert_eq!((s.bucket.as_str(), s.key.as_str()), ("cfg", "a/b.json"));
+        assert_eq!(s.region, "eu-west-1");
+        assert!(SingleFileSource::from_uri("s3://cfg").is_err());
+    }
+}
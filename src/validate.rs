@@ -0,0 +1,295 @@
+use crate::*;
+
+/// [`DataSource::validate`] 发现的一个问题
+#[derive(Debug)]
+pub struct ValidationIssue {
+    /// 出问题的目录, tar 文件, FileMap 键等
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn push(&mut self, target: impl Into<String>, message: impl ToString) {
+        self.issues.push(ValidationIssue {
+            target: target.into(),
+            message: message.to_string(),
+        });
+    }
+}
+
+#[cfg(feature = "tar")]
+fn check_tar<R: std::io::Read>(r: R) -> Result<(), FetchError> {
+    let mut a = tar::Archive::new(r);
+    for e in a.entries()? {
+        e?;
+    }
+    Ok(())
+}
+
+/// 缓存文件所在的目录是否可写. 不会创建缓存文件本身, 以免被当作有效缓存
+fn check_cache_writable(cf: &str) -> io::Result<()> {
+    let p = Path::new(cf);
+    if p.exists() {
+        if std::fs::metadata(p)?.permissions().readonly() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "read only"));
+        }
+        return Ok(());
+    }
+    let dir = match p.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let probe = dir.join(format!(
+        ".{}.probe",
+        p.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(probe)
+}
+
+/// 目录模式下缓存目录会在写入时创建, 还不存在时不检查
+fn check_cache(report: &mut ValidationReport, key: &str, fc: &FileCache) {
+    let Some(cf) = &fc.cache_file_path else {
+        return;
+    };
+    #[cfg(not(feature = "cache_meta"))]
+    if fc.verify_checksum {
+        report.push(key, FetchError::FeatureDisabled("cache_meta"));
+    }
+    #[cfg(feature = "encrypted")]
+    if let Some(Err(e)) = fc
+        .encryption_key
+        .as_ref()
+        .map(|k| encrypted::check_key(&k.0))
+    {
+        report.push(key, e);
+    }
+    #[cfg(not(feature = "encrypted"))]
+    if fc.encryption_key.is_some() {
+        report.push(key, FetchError::FeatureDisabled("encrypted"));
+    }
+    #[cfg(not(feature = "zstd"))]
+    if fc.compress {
+        report.push(key, FetchError::FeatureDisabled("zstd"));
+    }
+    if fc
+        .cache_dir
+        .as_ref()
+        .is_some_and(|d| !Path::new(d).exists())
+    {
+        return;
+    }
+    if let Err(e) = check_cache_writable(cf) {
+        report.push(key, format!("cache path `{cf}` not writable: {e}"));
+    }
+}
+
+fn check_single_file(report: &mut ValidationReport, key: &str, sf: &SingleFileSource) {
+    match sf {
+        SingleFileSource::Http(hs, _) if hs.local_path().is_some() => {
+            if !hs.local_path().unwrap().is_file() {
+                report.push(key, format!("file `{}` not found", hs.url));
+            }
+        }
+        #[cfg(feature = "reqwest")]
+        SingleFileSource::Http(hs, fc) => {
+            if let Err(e) = reqwest::Url::parse(&hs.url) {
+                report.push(key, format!("invalid url `{}`: {e}", hs.url));
+            }
+            if let Some((sock, _)) = unix_socket::parse_unix_url(&hs.url) {
+                if !sock.exists() {
+                    report.push(key, format!("socket `{}` not found", sock.display()));
+                }
+            }
+            check_cache(report, key, &sf.resolve_cache(fc));
+        }
+        #[cfg(not(feature = "reqwest"))]
+        SingleFileSource::Http(..) => report.push(key, FetchError::FeatureDisabled("reqwest")),
+        SingleFileSource::FilePath(p) => {
+            if !Path::new(p).is_file() {
+                report.push(key, format!("file `{p}` not found"));
+            }
+        }
+        SingleFileSource::Inline(_) => {}
+        SingleFileSource::DataUri(u) => {
+            if let Err(e) = data_uri::parse(u) {
+                report.push(key, e);
+            }
+        }
+        SingleFileSource::InlineBase64(s) => {
+            if let Err(e) = decode_inline_base64(s) {
+                report.push(key, e);
+            }
+        }
+        SingleFileSource::Env(name) => {
+            if std::env::var_os(name).is_none() {
+                report.push(key, format!("environment variable `{name}` not set"));
+            }
+        }
+        #[cfg(feature = "keyring")]
+        SingleFileSource::Keyring(_) => {}
+        #[cfg(feature = "dns_txt")]
+        SingleFileSource::DnsTxt(_) => {}
+        #[cfg(feature = "sftp")]
+        SingleFileSource::Sftp(_) => {}
+        #[cfg(feature = "encrypted")]
+        SingleFileSource::Encrypted(e) => {
+            check_single_file(report, key, &e.inner);
+            check_single_file(report, key, &e.key);
+        }
+        SingleFileSource::Annotated(s, _) | SingleFileSource::Validated(s, _) => {
+            check_single_file(report, key, s)
+        }
+        SingleFileSource::Concat(c) => {
+            for p in &c.parts {
+                check_single_file(report, key, p);
+            }
+        }
+        #[cfg(feature = "json_merge")]
+        SingleFileSource::JsonMerge(m) => {
+            for p in &m.parts {
+                check_single_file(report, key, p);
+            }
+        }
+        #[cfg(feature = "extract")]
+        SingleFileSource::Extract(e) => check_single_file(report, key, &e.inner),
+        SingleFileSource::Generated(_, fc) => {
+            check_cache(report, key, &sf.resolve_cache(fc));
+        }
+        #[cfg(feature = "s3")]
+        SingleFileSource::S3(s, fc) => {
+            if let Err(e) = reqwest::Url::parse(&s.url()) {
+                report.push(key, format!("invalid s3 url `{}`: {e}", s.url()));
+            }
+            check_cache(report, key, &sf.resolve_cache(fc));
+        }
+        #[cfg(feature = "gcs")]
+        SingleFileSource::Gcs(s, fc) => {
+            if let Err(e) = reqwest::Url::parse(&s.url()) {
+                report.push(key, format!("invalid gcs url `{}`: {e}", s.url()));
+            }
+            check_cache(report, key, &sf.resolve_cache(fc));
+        }
+        #[cfg(feature = "azure")]
+        SingleFileSource::AzureBlob(s, fc) => {
+            if let Err(e) = reqwest::Url::parse(&s.url()) {
+                report.push(key, format!("invalid azure blob url `{}`: {e}", s.url()));
+            }
+            check_cache(report, key, &sf.resolve_cache(fc));
+        }
+    }
+}
+
+impl DataSource {
+    /// FileMap 中缓存已过期或还没有缓存文件的 http 条目
+    #[cfg(feature = "reqwest")]
+    pub fn stale_caches(&self) -> Vec<String> {
+        let DataSource::FileMap(map) = self else {
+            return Vec::new();
+        };
+        let mut v: Vec<String> = map
+            .iter()
+            .filter_map(|(k, sf)| {
+                let fc = sf.file_cache()?;
+                fc.cache_file_path.as_ref()?;
+                (!matches!(fc.is_cache_timeout(), Ok(Some(false)))).then(|| k.clone())
+            })
+            .collect();
+        v.sort();
+        v
+    }
+
+    /// 检查配置本身: 目录是否存在且可读, tar 是否能解析, FileMap 中的 url 是否合法,
+    /// 缓存路径是否可写. 不会读取 `critical` 以外的文件
+    fn validate_config(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        match self {
+            DataSource::Folders(dirs) => {
+                for d in dirs {
+                    if let Err(e) = std::fs::read_dir(d) {
+                        report.push(d.as_str(), local_io_error(e, Path::new(d)));
+                    }
+                }
+            }
+            #[cfg(feature = "tar")]
+            DataSource::TarInMemory(v) => {
+                if let Err(e) = decompress_tar(v).and_then(|t| check_tar(std::io::Cursor::new(t))) {
+                    report.push("<tar in memory>", e);
+                }
+            }
+            #[cfg(feature = "tar")]
+            DataSource::TarFile(tf) => {
+                let r = open_tar_file(&tf.0).and_then(check_tar);
+                if let Err(e) = r {
+                    report.push(tf.0.as_str(), e);
+                }
+            }
+            #[cfg(not(feature = "tar"))]
+            DataSource::TarInMemory(_) | DataSource::TarFile(_) => {
+                report.push("<tar>", FetchError::FeatureDisabled("tar"))
+            }
+            DataSource::FileMap(map) => {
+                for (k, sf) in map {
+                    check_single_file(&mut report, k, sf);
+                }
+            }
+            DataSource::StdReadFile | DataSource::Embedded(_) | DataSource::Sync(_) => {}
+            #[cfg(feature = "tokio")]
+            DataSource::Async(_) => {}
+        }
+        report
+    }
+
+    /// 启动时检查配置, 并试读 `critical` 中的文件, 以便配置错误在启动时就能发现
+    pub fn validate(&self, critical: &[&str]) -> ValidationReport {
+        let mut report = self.validate_config();
+        for f in critical {
+            if let Err(e) = SyncFolderSource::get_file_content(self, Path::new(f)) {
+                report.push(*f, e);
+            }
+        }
+        report
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn validate_async(&self, critical: &[&str]) -> ValidationReport {
+        let mut report = self.validate_config();
+        for f in critical {
+            if let Err(e) = self.get_file_content_async(Path::new(f)).await {
+                report.push(*f, e);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+        let dir = temp_dir.path().to_string_lossy().to_string();
+
+        let ds = DataSource::Folders(vec![dir.clone()]);
+        assert!(ds.validate(&["a.txt"]).is_ok());
+
+        let ds = DataSource::Folders(vec![dir, "/nonexistent/dir".to_string()]);
+        let r = ds.validate(&["a.txt", "b.txt"]);
+        assert_eq!(r.issues.len(), 2);
+        assert_eq!(r.issues[0].target, "/nonexistent/dir");
+        assert_eq!(r.issues[1].target, "b.txt");
+    }
+}
@@ -0,0 +1,331 @@
+use crate::*;
+use std::sync::{Arc, RwLock};
+
+type CheckFn = dyn Fn(&[u8]) -> Result<(), String> + Send + Sync;
+
+/// 检查读到的内容, 不通过时返回原因. 见 [`SingleFileSource::Validated`]
+#[derive(Clone)]
+pub struct Validator(pub Arc<CheckFn>);
+
+impl Validator {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<(), String> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub fn non_empty() -> Self {
+        Self::new(|d| {
+            if d.is_empty() {
+                Err("empty content".to_string())
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    pub fn utf8() -> Self {
+        Self::new(|d| {
+            std::str::from_utf8(d)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    pub fn check(&self, d: &[u8]) -> Result<(), FetchError> {
+        (self.0)(d).map_err(FetchError::Invalid)
+    }
+}
+
+impl std::fmt::Debug for Validator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Validator")
+    }
+}
+
+/// 内容验证失败的事件, 见 [`set_quarantine_observer`]
+#[derive(Debug, Clone)]
+pub struct QuarantineEvent {
+    /// 来源的 url 或路径
+    pub source: String,
+    pub reason: String,
+    pub size: usize,
+    /// 保存到隔离目录中的位置, 没有设置隔离目录或保存失败时为 None
+    pub quarantined_to: Option<PathBuf>,
+}
+
+type ObserverFn = dyn Fn(&QuarantineEvent) + Send + Sync;
+
+static QUARANTINE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+static QUARANTINE_OBSERVER: RwLock<Option<Arc<ObserverFn>>> = RwLock::new(None);
+
+/// 设置后, 验证失败的内容会保存到这个目录中, 同时写入一个 `.meta` 文件记录来源和原因,
+/// 方便排查上游到底返回了什么
+pub fn set_quarantine_dir(dir: Option<PathBuf>) {
+    *QUARANTINE_DIR.write().unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+/// 每次验证失败时调用
+pub fn set_quarantine_observer(f: Option<Arc<ObserverFn>>) {
+    *QUARANTINE_OBSERVER
+        .write()
+        .unwrap_or_else(|e| e.into_inner()) = f;
+}
+
+fn quarantine_file(dir: &Path, source: &str, reason: &str, d: &[u8]) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let t = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let name: String = source
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let p = dir.join(format!("{}-{name}", t.as_nanos()));
+    std::fs::write(&p, d)?;
+    let meta = format!(
+        "source: {source}\nreason: {reason}\ntime: {}\nsize: {}\n",
+        t.as_secs(),
+        d.len()
+    );
+    std::fs::write(p.with_extension("meta"), meta)?;
+    Ok(p)
+}
+
+/// 隔离验证失败的内容并通知观察者, 返回原来的错误
+fn reject(source: &str, d: &[u8], e: FetchError) -> FetchError {
+    let reason = match &e {
+        FetchError::Invalid(r) => r.clone(),
+        e => e.to_string(),
+    };
+    warn!("content from {source} rejected: {reason}");
+    let dir = QUARANTINE_DIR
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let quarantined_to = dir.and_then(|dir| match quarantine_file(&dir, source, &reason, d) {
+        Ok(p) => Some(p),
+        Err(err) => {
+            warn!("Failed to write quarantine file: {err}");
+            None
+        }
+    });
+    let observer = QUARANTINE_OBSERVER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    if let Some(f) = observer {
+        f(&QuarantineEvent {
+            source: source.to_string(),
+            reason,
+            size: d.len(),
+            quarantined_to,
+        });
+    }
+    e
+}
+
+/// 验证失败时使用 (可能已过期的) 缓存, 没有缓存时返回错误
+fn fallback_to_cache(fc: &FileCache, e: FetchError) -> Result<Vec<u8>, FetchError> {
+    if fc.is_cache_timeout()?.is_some() {
+        warn!("{e}, serving stale cache");
+        #[cfg(feature = "journal")]
+        crate::journal::record_cache(fc, "stale");
+        fc.read_cache_file()
+    } else {
+        Err(e)
+    }
+}
+
+/// 与 [`fetch_with_cache`] 相同, 但新内容只有通过 `v` 的检查才会写入缓存并返回,
+/// 否则隔离新内容 (见 [`set_quarantine_dir`]) 并继续使用旧的缓存.
+/// `source` 为隔离记录中的来源名称
+pub fn fetch_with_cache_validated(
+    fc: &FileCache,
+    s: &dyn SyncSource,
+    v: &Validator,
+    source: &str,
+) -> Result<Vec<u8>, FetchError> {
+    if fc.is_cache_timeout()?.is_some_and(|timeout| !timeout) {
+        match fc.read_cache_file() {
+            Err(e) if e.is_cache_corrupt() => {}
+            r => return r,
+        }
+    }
+    let d = match s.fetch() {
+        Ok(d) => d,
+        Err(e) => return fc.fallback_on_error(e),
+    };
+    if let Err(e) = v.check(&d) {
+        return fallback_to_cache(fc, reject(source, &d, e));
+    }
+    if fc.cache_file_path.is_some() {
+        fc.write_cache_file(&d);
+    }
+    Ok(d)
+}
+
+#[cfg(feature = "tokio")]
+pub async fn fetch_with_cache_validated_async(
+    fc: &FileCache,
+    s: &dyn AsyncSource,
+    v: &Validator,
+    source: &str,
+) -> Result<Vec<u8>, FetchError> {
+    if fc.is_cache_timeout()?.is_some_and(|timeout| !timeout) {
+        match fc.read_cache_file_async().await {
+            Err(e) if e.is_cache_corrupt() => {}
+            r => return r,
+        }
+    }
+    let d = match s.fetch_async().await {
+        Ok(d) => d,
+        Err(e) => return fc.fallback_on_error(e),
+    };
+    if let Err(e) = v.check(&d) {
+        return fallback_to_cache(fc, reject(source, &d, e));
+    }
+    if fc.cache_file_path.is_some() {
+        fc.write_cache_file_async(&d).await;
+    }
+    Ok(d)
+}
+
+impl SingleFileSource {
+    /// [`SingleFileSource::Validated`] 的读取: 有 FileCache 的来源在验证失败时使用旧缓存
+    pub(crate) fn fetch_validated(&self, v: &Validator) -> Result<Vec<u8>, FetchError> {
+        let name = self.get_path().unwrap_or_default();
+        match self {
+            #[cfg(feature = "reqwest")]
+            SingleFileSource::Http(hs, fc) if hs.local_path().is_none() => {
+                fetch_with_cache_validated(&self.resolve_cache(fc), hs, v, &name)
+            }
+            SingleFileSource::Generated(g, fc) => fetch_with_cache_validated(fc, g, v, &name),
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(s, fc) => {
+                fetch_with_cache_validated(&self.resolve_cache(fc), s, v, &name)
+            }
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(s, fc) => {
+                fetch_with_cache_validated(&self.resolve_cache(fc), s, v, &name)
+            }
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(s, fc) => {
+                fetch_with_cache_validated(&self.resolve_cache(fc), s, v, &name)
+            }
+            s => {
+                let d = s.fetch()?;
+                v.check(&d).map_err(|e| reject(&name, &d, e))?;
+                Ok(d)
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn fetch_validated_async(&self, v: &Validator) -> Result<Vec<u8>, FetchError> {
+        let name = self.get_path().unwrap_or_default();
+        match self {
+            #[cfg(feature = "reqwest")]
+            SingleFileSource::Http(hs, fc) if hs.local_path().is_none() => {
+                fetch_with_cache_validated_async(&self.resolve_cache(fc), hs, v, &name).await
+            }
+            SingleFileSource::Generated(g, fc) => {
+                fetch_with_cache_validated_async(fc, g, v, &name).await
+            }
+            #[cfg(feature = "s3")]
+            SingleFileSource::S3(s, fc) => {
+                fetch_with_cache_validated_async(&self.resolve_cache(fc), s, v, &name).await
+            }
+            #[cfg(feature = "azure")]
+            SingleFileSource::AzureBlob(s, fc) => {
+                fetch_with_cache_validated_async(&self.resolve_cache(fc), s, v, &name).await
+            }
+            #[cfg(feature = "gcs")]
+            SingleFileSource::Gcs(s, fc) => {
+                fetch_with_cache_validated_async(&self.resolve_cache(fc), s, v, &name).await
+            }
+            s => {
+                let d = s.fetch_async().await?;
+                v.check(&d).map_err(|e| reject(&name, &d, e))?;
+                Ok(d)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::Generator;
+    use std::sync::atomic::AtomicUsize;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validated_serves_stale_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let cf = temp_dir.path().join("c");
+        std::fs::write(&cf, "good").unwrap();
+
+        let n = Arc::new(AtomicUsize::new(0));
+        let c = n.clone();
+        let g = Generator::new(move || {
+            c.fetch_add(1, Ordering::SeqCst);
+            Ok(Vec::new())
+        });
+        let fc = FileCache {
+            update_interval_seconds: Some(0),
+            cache_file_path: Some(cf.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        let sf = SingleFileSource::Validated(
+            Box::new(SingleFileSource::Generated(g, fc)),
+            Validator::non_empty(),
+        );
+        assert_eq!(sf.fetch().unwrap(), b"good");
+        assert_eq!(n.load(Ordering::SeqCst), 1);
+        assert_eq!(std::fs::read(&cf).unwrap(), b"good");
+    }
+
+    #[test]
+    fn test_quarantine() {
+        let temp_dir = TempDir::new().unwrap();
+        let qd = temp_dir.path().join("q");
+        set_quarantine_dir(Some(qd.clone()));
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let ev = events.clone();
+        set_quarantine_observer(Some(Arc::new(move |e: &QuarantineEvent| {
+            ev.lock().unwrap().push(e.clone())
+        })));
+
+        let p = temp_dir.path().join("bad.txt");
+        std::fs::write(&p, [0xff, 0xfe]).unwrap();
+        let sf = SingleFileSource::Validated(
+            Box::new(SingleFileSource::FilePath(p.to_string_lossy().to_string())),
+            Validator::utf8(),
+        );
+        assert!(matches!(sf.fetch(), Err(FetchError::Invalid(_))));
+
+        set_quarantine_dir(None);
+        set_quarantine_observer(None);
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let q = events[0].quarantined_to.as_ref().unwrap();
+        assert_eq!(std::fs::read(q).unwrap(), [0xff, 0xfe]);
+        let meta = std::fs::read_to_string(q.with_extension("meta")).unwrap();
+        assert!(meta.contains("bad.txt"));
+
+        let sf = SingleFileSource::Validated(
+            Box::new(SingleFileSource::Inline(vec![0xff])),
+            Validator::utf8(),
+        );
+        assert!(matches!(sf.fetch(), Err(FetchError::Invalid(_))));
+    }
+}
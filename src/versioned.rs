@@ -0,0 +1,140 @@
+use crate::*;
+
+/// 按版本存放的目录: `root/<version>/...`, 以及记录当前版本的 `root/current` 文件.
+///
+/// 版本名按字典序排序, 通常使用定长的时间戳 (如 `20240101T000000`), 这样
+/// [`VersionedFolderSource::as_of`] 可以找到某个时间点的版本.
+/// 读取文件时使用 `current` 指向的版本, 切换版本只需调用 [`VersionedFolderSource::set_current`]
+#[derive(Clone, Debug)]
+pub struct VersionedFolderSource {
+    pub root: String,
+}
+
+const CURRENT: &str = "current";
+
+impl VersionedFolderSource {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: root.to_string(),
+        }
+    }
+
+    /// 所有版本, 从旧到新
+    pub fn versions(&self) -> Result<Vec<String>, FetchError> {
+        let root = Path::new(&self.root);
+        let mut v = Vec::new();
+        for e in std::fs::read_dir(root).map_err(|e| local_io_error(e, root))? {
+            let e = e?;
+            if e.file_type()?.is_dir() {
+                v.push(e.file_name().to_string_lossy().to_string());
+            }
+        }
+        v.sort();
+        Ok(v)
+    }
+
+    pub fn current_version(&self) -> Result<String, FetchError> {
+        let p = Path::new(&self.root).join(CURRENT);
+        if !p.exists() {
+            return Err(FetchError::not_found(p.to_string_lossy()));
+        }
+        let v = String::from_utf8_lossy(&read_local_file(&p)?)
+            .trim()
+            .to_string();
+        Ok(v)
+    }
+
+    /// 原子地切换当前版本: 先写临时文件再 rename
+    pub fn set_current(&self, version: &str) -> Result<(), FetchError> {
+        if !self.versions()?.iter().any(|v| v == version) {
+            return Err(FetchError::not_found(version));
+        }
+        let root = Path::new(&self.root);
+        let tmp = root.join(format!(".{CURRENT}.tmp"));
+        std::fs::write(&tmp, version).map_err(|e| local_io_error(e, &tmp))?;
+        std::fs::rename(&tmp, root.join(CURRENT)).map_err(|e| local_io_error(e, root))?;
+        Ok(())
+    }
+
+    /// 不晚于 `version` 的最新版本
+    pub fn as_of(&self, version: &str) -> Result<DataSource, FetchError> {
+        let v = self
+            .versions()?
+            .into_iter()
+            .rev()
+            .find(|v| v.as_str() <= version)
+            .ok_or_else(|| FetchError::not_found(version))?;
+        Ok(self.at(&v))
+    }
+
+    /// 指定版本的目录
+    pub fn at(&self, version: &str) -> DataSource {
+        DataSource::Folders(vec![Path::new(&self.root)
+            .join(version)
+            .to_string_lossy()
+            .to_string()])
+    }
+
+    pub fn current(&self) -> Result<DataSource, FetchError> {
+        Ok(self.at(&self.current_version()?))
+    }
+}
+
+impl SyncFolderSource for VersionedFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.current()?.get_file_content(file_name)
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY
+            .with_listable()
+            .with_watchable()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        self.current()?.list_files()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for VersionedFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.current()?.get_file_content_async(file_name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_versioned_folder() {
+        let temp_dir = TempDir::new().unwrap();
+        for v in ["001", "002"] {
+            let d = temp_dir.path().join(v);
+            std::fs::create_dir(&d).unwrap();
+            std::fs::write(d.join("a.txt"), v).unwrap();
+        }
+        let vs = VersionedFolderSource::new(&temp_dir.path().to_string_lossy());
+        assert_eq!(vs.versions().unwrap(), vec!["001", "002"]);
+        assert!(matches!(
+            vs.get_file_content(Path::new("a.txt")),
+            Err(FetchError::Lookup(LookupError::NotFound(_)))
+        ));
+
+        vs.set_current("002").unwrap();
+        assert_eq!(vs.get_file_content(Path::new("a.txt")).unwrap().0, b"002");
+        vs.set_current("001").unwrap();
+        assert_eq!(vs.get_file_content(Path::new("a.txt")).unwrap().0, b"001");
+        assert!(vs.set_current("003").is_err());
+
+        let old = vs.as_of("0015").unwrap();
+        assert_eq!(old.read_to_string("a.txt").unwrap(), "001");
+        assert!(vs.as_of("000").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! 启动时预热缓存: 同时读取所有来源并写入缓存, 报告每个来源的结果,
+//! 以便服务在必需的远程数据不可用时尽早失败
+
+use crate::*;
+
+/// 单个来源的预热结果
+#[derive(Debug)]
+pub enum WarmOutcome {
+    /// 缓存未过期, 没有读取来源
+    Fresh,
+    /// 从来源读取并写入了缓存
+    Fetched,
+    /// 来源读取失败, 按 `stale_if_error_seconds` 保留了过期的旧缓存
+    Stale,
+    Failed(FetchError),
+}
+
+#[derive(Debug, Default)]
+pub struct WarmReport {
+    /// 与传入的来源顺序相同
+    pub outcomes: Vec<WarmOutcome>,
+}
+
+impl WarmReport {
+    fn count(&self, f: impl Fn(&WarmOutcome) -> bool) -> usize {
+        self.outcomes.iter().filter(|o| f(o)).count()
+    }
+    pub fn fetched(&self) -> usize {
+        self.count(|o| matches!(o, WarmOutcome::Fetched))
+    }
+    pub fn stale(&self) -> usize {
+        self.count(|o| matches!(o, WarmOutcome::Stale))
+    }
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, WarmOutcome::Failed(_)))
+    }
+
+    /// 第一个失败的来源序号和错误
+    pub fn first_error(&self) -> Option<(usize, &FetchError)> {
+        self.outcomes.iter().enumerate().find_map(|(i, o)| match o {
+            WarmOutcome::Failed(e) => Some((i, e)),
+            _ => None,
+        })
+    }
+}
+
+/// 与 [`fetch_with_cache_async`] 相同, 但缓存写入失败时也算作失败
+async fn warm_one(fc: &FileCache, s: &dyn AsyncSource) -> WarmOutcome {
+    if fc.cache_file_path.is_none() {
+        return WarmOutcome::Failed(FetchError::Invalid(
+            "no cache file, resolve the FileCache first".to_string(),
+        ));
+    }
+    match fc.is_cache_timeout() {
+        Ok(Some(false)) => return WarmOutcome::Fresh,
+        Err(e) => return WarmOutcome::Failed(e),
+        _ => {}
+    }
+    let d = match s.fetch_async().await {
+        Ok(d) => d,
+        Err(e) => {
+            return match fc.fallback_on_error(e) {
+                Ok(_) => WarmOutcome::Stale,
+                Err(e) => WarmOutcome::Failed(e),
+            }
+        }
+    };
+    match fc
+        .try_write_cache_file_from_async(&d, &SourceInfo::default())
+        .await
+    {
+        Ok(()) => WarmOutcome::Fetched,
+        Err(e) => WarmOutcome::Failed(e),
+    }
+}
+
+/// 同时预热所有缓存, 未过期的缓存不会重新读取. 只有缓存文件已写入 (或按 `stale_if_error_seconds`
+/// 保留了旧缓存) 时才算成功, 目录模式的 FileCache 需先 [`FileCache::resolve`], 否则报告为失败
+pub async fn warm_cache(sources: &[(&FileCache, &dyn AsyncSource)]) -> WarmReport {
+    let outcomes = futures::future::join_all(sources.iter().map(|&(fc, s)| warm_one(fc, s))).await;
+    WarmReport { outcomes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_warm_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60));
+        let (a, b, c) = (dir.resolve("a"), dir.resolve("b"), dir.resolve("c"));
+        let c = c.into_owned().with_stale_if_error(3600);
+        assert!(b.write_cache_file(b"old b"));
+        assert!(c.write_cache_file(b"old c"));
+        let cf = c.cache_file_path.clone().unwrap();
+        std::fs::File::options()
+            .append(true)
+            .open(&cf)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(120))
+            .unwrap();
+
+        let ok = SingleFileSource::Inline(b"new".to_vec());
+        let missing = SingleFileSource::FilePath(
+            temp_dir
+                .path()
+                .join("missing")
+                .to_string_lossy()
+                .to_string(),
+        );
+        let report = warm_cache(&[
+            (&a, &ok),
+            (&b, &ok),
+            (&c, &missing),
+            (&dir.resolve("d"), &missing),
+        ])
+        .await;
+        assert!(matches!(
+            report.outcomes[..],
+            [
+                WarmOutcome::Fetched,
+                WarmOutcome::Fresh,
+                WarmOutcome::Stale,
+                WarmOutcome::Failed(_)
+            ]
+        ));
+        assert_eq!(
+            (report.fetched(), report.stale(), report.failed()),
+            (1, 1, 1)
+        );
+        assert!(report.first_error().unwrap().1.is_not_found());
+        assert_eq!(a.read_cache_file().unwrap(), b"new");
+        assert_eq!(b.read_cache_file().unwrap(), b"old b");
+
+        // 读取成功但没有写入缓存的不算预热成功
+        std::fs::write(temp_dir.path().join("file"), "").unwrap();
+        let unwritable = FileCache {
+            cache_file_path: Some(temp_dir.path().join("file/e").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let report = warm_cache(&[(&unwritable, &ok), (&dir, &ok)]).await;
+        assert!(matches!(
+            report.outcomes[..],
+            [
+                WarmOutcome::Failed(FetchError::Cache(CacheError::Io { .. })),
+                WarmOutcome::Failed(FetchError::Invalid(_))
+            ]
+        ));
+    }
+}
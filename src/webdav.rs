@@ -0,0 +1,332 @@
+use crate::*;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getcontentlength/><d:getetag/><d:getlastmodified/></d:prop></d:propfind>"#;
+
+/// PROPFIND 返回的一项
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DavEntry {
+    /// 解码后的 href, 如 `/remote.php/dav/files/u/rules/a.dat`, 目录以 `/` 结尾
+    pub href: String,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    pub etag: Option<String>,
+    /// getlastmodified, unix 时间戳 (秒)
+    pub mtime: Option<u64>,
+}
+
+/// `Authorization: Basic ...` 请求头, 用于 Nextcloud 的应用密码等
+pub fn basic_auth_header(user: &str, password: &str) -> (String, String) {
+    (
+        "Authorization".to_string(),
+        format!(
+            "Basic {}",
+            base64::encode(format!("{user}:{password}").as_bytes())
+        ),
+    )
+}
+
+fn percent_decode(s: &str) -> String {
+    String::from_utf8_lossy(&hex::percent_decode(s)).to_string()
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// 解析 PROPFIND 的 multistatus 响应. 只识别需要的几个元素, 忽略命名空间前缀
+pub fn parse_multistatus(xml: &str) -> Vec<DavEntry> {
+    let mut entries = Vec::new();
+    let mut cur: Option<DavEntry> = None;
+    let mut text_start = 0;
+    let mut rest = xml;
+    let mut offset = 0;
+    while let Some(lt) = rest.find('<') {
+        let Some(gt) = rest[lt..].find('>') else {
+            break;
+        };
+        let tag = &rest[lt + 1..lt + gt];
+        let text = &xml[text_start..offset + lt];
+        offset += lt + gt + 1;
+        rest = &xml[offset..];
+        text_start = offset;
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .trim_end_matches('/')
+            .split_whitespace()
+            .next()
+            .unwrap_or_default();
+        let local = name.rsplit(':').next().unwrap_or_default();
+        match (local, closing) {
+            ("response", false) => cur = Some(DavEntry::default()),
+            ("response", true) => entries.extend(cur.take()),
+            ("collection", false) => {
+                if let Some(e) = cur.as_mut() {
+                    e.is_dir = true;
+                }
+            }
+            (_, true) => {
+                let Some(e) = cur.as_mut() else {
+                    continue;
+                };
+                let t = xml_unescape(text.trim());
+                match local {
+                    "href" => e.href = percent_decode(&t),
+                    "getcontentlength" => e.size = t.parse().ok(),
+                    "getetag" => e.etag = Some(t),
+                    "getlastmodified" => e.mtime = parse_rfc1123_date(&t),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    // href 可能是完整的 url
+    for e in entries.iter_mut() {
+        if let Some(p) = e.href.find("://") {
+            let after = &e.href[p + 3..];
+            e.href = after
+                .find('/')
+                .map(|i| after[i..].to_string())
+                .unwrap_or_default();
+        }
+    }
+    entries
+}
+
+/// 发送 PROPFIND 请求, `depth` 为 "0" 或 "1"
+fn propfind(hs: &HttpSource, depth: &str) -> Result<Vec<DavEntry>, FetchError> {
+    let mut cb = hs.blocking_client_builder();
+    if hs.should_use_proxy {
+        cb = hs.set_proxy(cb)?;
+    }
+    let method = reqwest::Method::from_bytes(b"PROPFIND").expect("valid method");
+    let mut rb = cb
+        .build()?
+        .request(method, &hs.url)
+        .header("Depth", depth)
+        .header(reqwest::header::CONTENT_TYPE, "application/xml")
+        .body(PROPFIND_BODY);
+    for (k, v) in hs.custom_request_headers.iter().flatten() {
+        rb = rb.header(k, v);
+    }
+    let r = rb.send()?;
+    match r.status() {
+        reqwest::StatusCode::NOT_FOUND => Err(FetchError::not_found(&hs.url)),
+        s if s.is_success() => Ok(parse_multistatus(&r.text()?)),
+        s => Err(HttpError::Status {
+            url: hs.url.clone(),
+            status: s.as_u16(),
+        }
+        .into()),
+    }
+}
+
+/// 服务器上的单个文件. 读取使用普通的 GET, 需要缓存时可以把 `http` 放入
+/// [`SingleFileSource::Http`]
+#[derive(Debug, Clone, Default)]
+pub struct WebDavSource {
+    pub http: HttpSource,
+}
+
+impl WebDavSource {
+    pub fn new(url: &str) -> Self {
+        Self {
+            http: HttpSource {
+                url: url.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn with_basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.http
+            .custom_request_headers
+            .get_or_insert_with(Vec::new)
+            .push(basic_auth_header(user, password));
+        self
+    }
+
+    /// 通过 `Depth: 0` 的 PROPFIND 读取大小, etag 和 mtime, 不下载内容
+    pub fn stat(&self) -> Result<DavEntry, FetchError> {
+        propfind(&self.http, "0")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| FetchError::not_found(&self.http.url))
+    }
+}
+
+impl SyncSource for WebDavSource {
+    fn fetch(&self) -> Result<Vec<u8>, FetchError> {
+        self.http.fetch()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncSource for WebDavSource {
+    async fn fetch_async(&self) -> Result<Vec<u8>, FetchError> {
+        self.http.fetch_async().await
+    }
+}
+
+/// WebDAV 共享目录, 如 Nextcloud / ownCloud 的 `https://host/remote.php/dav/files/<user>/<dir>`.
+///
+/// 读取文件与 [`http_folder::HttpFolderSource`] 相同 (可以设置缓存和按前缀的请求头),
+/// `list_files` 通过逐层 `Depth: 1` 的 PROPFIND 列出所有文件
+#[derive(Debug, Clone, Default)]
+pub struct WebDavFolderSource {
+    pub http: http_folder::HttpFolderSource,
+}
+
+impl WebDavFolderSource {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: http_folder::HttpFolderSource::new(base_url),
+        }
+    }
+
+    pub fn with_basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.http
+            .base
+            .custom_request_headers
+            .get_or_insert_with(Vec::new)
+            .push(basic_auth_header(user, password));
+        self
+    }
+
+    pub fn with_cache_dir(mut self, dir: &str, update_interval_seconds: Option<u64>) -> Self {
+        self.http = self.http.with_cache_dir(dir, update_interval_seconds);
+        self
+    }
+
+    /// base url 的路径部分, 以 `/` 结尾, 用于把 href 转为相对路径
+    fn base_path(&self) -> Result<String, FetchError> {
+        let u = reqwest::Url::parse(&self.http.base.url)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(format!(
+            "{}/",
+            percent_decode(u.path()).trim_end_matches('/')
+        ))
+    }
+
+    /// 逐层 `Depth: 1` 的 PROPFIND, 返回所有文件的相对路径和对应的项, 按路径排序
+    fn walk(&self) -> Result<Vec<(String, DavEntry)>, FetchError> {
+        let base = self.base_path()?;
+        let mut files = Vec::new();
+        let mut dirs = vec![String::new()];
+        while let Some(d) = dirs.pop() {
+            let mut hs = self.http.base.clone();
+            hs.url = format!("{}/{d}", self.http.base.url.trim_end_matches('/'));
+            for e in propfind(&hs, "1")? {
+                let Some(rel) = e.href.strip_prefix(&base) else {
+                    continue;
+                };
+                let rel = rel.trim_end_matches('/').to_string();
+                if rel == d.trim_end_matches('/') {
+                    continue;
+                }
+                if e.is_dir {
+                    dirs.push(format!("{rel}/"));
+                } else {
+                    files.push((rel, e));
+                }
+            }
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(files)
+    }
+}
+
+impl SyncFolderSource for WebDavFolderSource {
+    fn get_file_content(&self, file_name: &Path) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.http.get_file_content(file_name)
+    }
+
+    fn get_file_content_typed(&self, file_name: &Path) -> Result<FileContent, FetchError> {
+        self.http.get_file_content_typed(file_name)
+    }
+
+    fn capabilities(&self) -> capabilities::Capabilities {
+        capabilities::Capabilities::READ_ONLY.with_listable()
+    }
+
+    fn list_files(&self) -> Result<Vec<String>, FetchError> {
+        Ok(self.walk()?.into_iter().map(|(n, _)| n).collect())
+    }
+
+    fn list_entries(&self) -> Result<Vec<ListEntry>, FetchError> {
+        Ok(self
+            .walk()?
+            .into_iter()
+            .map(|(name, e)| ListEntry {
+                name,
+                size: e.size,
+                mtime: e.mtime,
+                etag: e.etag,
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl AsyncFolderSource for WebDavFolderSource {
+    async fn get_file_content_async(
+        &self,
+        file_name: &Path,
+    ) -> Result<(Vec<u8>, Option<String>), FetchError> {
+        self.http.get_file_content_async(file_name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+ <d:response>
+  <d:href>/remote.php/dav/files/u/conf/</d:href>
+  <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+ </d:response>
+ <d:response>
+  <d:href>/remote.php/dav/files/u/conf/geo%20ip.dat</d:href>
+  <d:propstat><d:prop>
+   <d:resourcetype/>
+   <d:getcontentlength>5</d:getcontentlength>
+   <d:getetag>&quot;abc&quot;</d:getetag>
+   <d:getlastmodified>Sun, 06 Nov 1994 08:49:37 GMT</d:getlastmodified>
+  </d:prop></d:propstat>
+ </d:response>
+ <D:response xmlns:D="DAV:"><D:href>https://h/remote.php/dav/files/u/conf/a&amp;b</D:href></D:response>
+</d:multistatus>"#;
+        let e = parse_multistatus(xml);
+        assert_eq!(e.len(), 3);
+        assert!(e[0].is_dir);
+        assert_eq!(e[1].href, "/remote.php/dav/files/u/conf/geo ip.dat");
+        assert_eq!(e[1].size, Some(5));
+        assert_eq!(e[1].etag.as_deref(), Some("\"abc\""));
+        assert_eq!(e[1].mtime, Some(784111777));
+        assert!(!e[1].is_dir);
+        assert_eq!(e[2].href, "/remote.php/dav/files/u/conf/a&b");
+    }
+
+    #[test]
+    fn test_basic_auth() {
+        assert_eq!(basic_auth_header("u", "p").1, "Basic dTpw");
+        let w = WebDavFolderSource::new("https://h/remote.php/dav/files/u/conf/");
+        assert_eq!(w.base_path().unwrap(), "/remote.php/dav/files/u/conf/");
+    }
+}
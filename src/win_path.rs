@@ -0,0 +1,127 @@
+//! Windows 的长路径和 UNC 路径.
+//!
+//! 超过 `MAX_PATH` (260) 的路径需要写成 `\\?\C:\...` 或 `\\?\UNC\server\share\...`
+//! 才能打开, 而这种路径不接受 `/` 作为分隔符, 也不处理 `.` 和 `..`. Folders 和 StdReadFile
+//! 读取前用 [`join_local`] 和 [`local_path`] 转换路径, 其它平台上路径保持不变
+
+use crate::*;
+
+const MAX_PATH: usize = 260;
+
+/// 绝对路径对应的 `\\?\` 形式: 分隔符统一为 `\`, 去掉 `.`, 按字面处理 `..`.
+/// 已经是 `\\?\` 或 `\\.\` 形式的路径和相对路径返回 None
+pub fn extended_length_str(p: &str) -> Option<String> {
+    if p.starts_with(r"\\?\") || p.starts_with(r"\\.\") {
+        return None;
+    }
+    let is_sep = |c: char| c == '\\' || c == '/';
+    let (prefix, rest) = if let Some(unc) = p.strip_prefix(r"\\").or(p.strip_prefix("//")) {
+        let mut parts = unc.splitn(3, is_sep);
+        let (server, share) = (parts.next()?, parts.next()?);
+        if server.is_empty() || share.is_empty() {
+            return None;
+        }
+        (
+            format!(r"\\?\UNC\{server}\{share}"),
+            parts.next().unwrap_or_default(),
+        )
+    } else {
+        let b = p.as_bytes();
+        if b.len() < 3 || !b[0].is_ascii_alphabetic() || b[1] != b':' || !is_sep(b[2] as char) {
+            return None;
+        }
+        (format!(r"\\?\{}", &p[..2]), &p[3..])
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for c in rest.split(is_sep) {
+        match c {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            c => parts.push(c),
+        }
+    }
+    let mut r = prefix;
+    for c in parts {
+        r.push('\\');
+        r.push_str(c);
+    }
+    Some(r)
+}
+
+/// 在 Windows 上把过长的绝对路径转为 `\\?\` 形式, 其它情况原样返回
+pub fn local_path(p: &Path) -> Cow<'_, Path> {
+    if cfg!(windows) && p.as_os_str().len() >= MAX_PATH {
+        if let Some(s) = p.to_str().and_then(extended_length_str) {
+            return Cow::Owned(PathBuf::from(s));
+        }
+    }
+    Cow::Borrowed(p)
+}
+
+/// 把请求的文件名 (按 [`normalize_separators`] 处理 `\`) 逐段拼接到 `dir` 后,
+/// 这样 `dir` 是 `\\?\` 或 UNC 路径时也能得到正确的分隔符
+pub fn join_local(dir: &Path, file_name: &Path) -> PathBuf {
+    let mut p = PathBuf::from(dir);
+    for c in normalize_separators(file_name).components() {
+        match c {
+            std::path::Component::CurDir => {}
+            c => p.push(c),
+        }
+    }
+    local_path(&p).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_length_str() {
+        assert_eq!(
+            extended_length_str(r"C:\data/rules\.\a\..\b.json").unwrap(),
+            r"\\?\C:\data\rules\b.json"
+        );
+        assert_eq!(
+            extended_length_str(r"\\fs01\share\cfg/a.json").unwrap(),
+            r"\\?\UNC\fs01\share\cfg\a.json"
+        );
+        assert_eq!(
+            extended_length_str(r"\\fs01\share").unwrap(),
+            r"\\?\UNC\fs01\share"
+        );
+        assert!(extended_length_str(r"\\?\C:\a").is_none());
+        assert!(extended_length_str(r"\\.\pipe\a").is_none());
+        assert!(extended_length_str(r"a\b").is_none());
+        assert!(extended_length_str(r"\\fs01").is_none());
+        assert!(extended_length_str("/etc/a").is_none());
+    }
+
+    #[test]
+    fn test_join_local() {
+        let p = join_local(Path::new("/srv/cfg"), Path::new("./rules\\a.json"));
+        #[cfg(not(windows))]
+        assert_eq!(p, Path::new("/srv/cfg/rules/a.json"));
+        #[cfg(windows)]
+        assert_eq!(p, Path::new(r"/srv/cfg\rules\a.json"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_long_path() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("d".repeat(120)).join("e".repeat(120));
+        let long = local_path(&dir).into_owned();
+        std::fs::create_dir_all(&long).unwrap();
+        std::fs::write(long.join("a.json"), "{}").unwrap();
+
+        let ds = DataSource::Folders(vec![dir.to_string_lossy().to_string()]);
+        let (d, _) = ds.get_file_content(Path::new("a.json")).unwrap();
+        assert_eq!(d, b"{}");
+        let d = DataSource::StdReadFile
+            .get_file_content(&dir.join("a.json"))
+            .unwrap();
+        assert_eq!(d.0, b"{}");
+    }
+}
@@ -0,0 +1,131 @@
+//! `.tar.zst` 支持和缓存文件压缩, 使用 zstd crate
+
+use crate::*;
+
+pub fn is_zstd(d: &[u8]) -> bool {
+    d.starts_with(&[0x28, 0xb5, 0x2f, 0xfd])
+}
+
+fn compress(d: &[u8]) -> Result<Vec<u8>, FetchError> {
+    Ok(::zstd::stream::encode_all(d, 0)?)
+}
+
+/// 压缩要写入的缓存, 压缩后没有变小时原样返回
+pub(crate) fn pack_cache(d: &[u8]) -> Result<Cow<'_, [u8]>, FetchError> {
+    let c = compress(d)?;
+    if COMPRESSED_CACHE_MAGIC.len() + c.len() >= d.len() {
+        return Ok(Cow::Borrowed(d));
+    }
+    Ok(Cow::Owned([COMPRESSED_CACHE_MAGIC, &c].concat()))
+}
+
+/// 还原 [`pack_cache`] 的结果, 未压缩的缓存原样返回. 无法解压时返回 None
+pub(crate) fn unpack_cache(d: Vec<u8>) -> Result<Option<Vec<u8>>, FetchError> {
+    let Some(c) = d.strip_prefix(COMPRESSED_CACHE_MAGIC) else {
+        return Ok(Some(d));
+    };
+    match decompress(c) {
+        Ok(d) => Ok(Some(d)),
+        Err(FetchError::Archive(ArchiveError::Decompress { .. })) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 解压 zstd 数据, 解压后超过 [`MAX_DECOMPRESSED_SIZE`] 时返回 [`ArchiveError::TooLarge`]
+pub fn decompress(d: &[u8]) -> Result<Vec<u8>, FetchError> {
+    decompress_with_limit(d, MAX_DECOMPRESSED_SIZE)
+}
+
+pub fn decompress_with_limit(d: &[u8], limit: u64) -> Result<Vec<u8>, FetchError> {
+    let r =
+        ::zstd::stream::read::Decoder::with_buffer(d).map_err(|e| ArchiveError::Decompress {
+            format: "zstd",
+            reason: e.to_string(),
+        })?;
+    read_decompressed(r, "zstd", limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_zst() {
+        let mut b = tar::Builder::new(Vec::new());
+        tar_builder::append_file(&mut b, "geo/geoip.dat", b"geoip", 0).unwrap();
+        let t = b.into_inner().unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let zst = temp_dir.path().join("a.tar.zst");
+        std::fs::write(&zst, compress(&t).unwrap()).unwrap();
+
+        let d = std::fs::read(&zst).unwrap();
+        assert!(is_zstd(&d));
+        let ds = DataSource::TarInMemory(d);
+        assert_eq!(ds.read_to_string("geo/geoip.dat").unwrap(), "geoip");
+        let ds = DataSource::TarFile(TarFile(zst.to_string_lossy().to_string()));
+        assert_eq!(ds.list_files().unwrap(), vec!["geo/geoip.dat"]);
+
+        assert!(decompress(&[0x28, 0xb5, 0x2f, 0xfd, 0, 0]).is_err());
+
+        // 解压后的大小有上限
+        let bomb = compress(&vec![0; 1 << 20]).unwrap();
+        assert!(bomb.len() < 1 << 10);
+        assert_eq!(
+            decompress_with_limit(&bomb, 1 << 20).unwrap().len(),
+            1 << 20
+        );
+        let e = decompress_with_limit(&bomb, (1 << 20) - 1).unwrap_err();
+        assert_eq!(e.kind(), FetchErrorKind::TooLarge);
+    }
+
+    #[test]
+    fn test_compressed_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .with_compression()
+            .resolve("a")
+            .into_owned();
+        let cf = fc.cache_file_path.clone().unwrap();
+        let rules = "DOMAIN-SUFFIX,example.com,DIRECT\n".repeat(1000);
+        let s = SingleFileSource::Inline(rules.clone().into_bytes());
+        assert_eq!(fetch_with_cache(&fc, &s).unwrap(), rules.as_bytes());
+        let stored = std::fs::read(&cf).unwrap();
+        assert!(stored.starts_with(COMPRESSED_CACHE_MAGIC));
+        assert!(stored.len() < rules.len() / 5);
+        assert_eq!(fc.read_cache_file().unwrap(), rules.as_bytes());
+
+        // 关闭压缩后仍能读取旧缓存, 压不小的内容原样保存
+        let plain = FileCache {
+            compress: false,
+            ..fc.clone()
+        };
+        assert_eq!(plain.read_cache_file().unwrap(), rules.as_bytes());
+        assert!(fc.write_cache_file(b"ab"));
+        assert_eq!(std::fs::read(&cf).unwrap(), b"ab");
+
+        // 本身是 zstd 的来源数据不会被解压
+        let zst = compress(b"geoip").unwrap();
+        assert!(fc.write_cache_file(&zst));
+        assert_eq!(fc.read_cache_file().unwrap(), zst);
+
+        std::fs::write(&cf, [COMPRESSED_CACHE_MAGIC, b"junk"].concat()).unwrap();
+        assert!(fc.read_cache_file().unwrap_err().is_cache_corrupt());
+        assert!(!Path::new(&cf).exists());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_compressed_cache_async() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fc = FileCache::in_dir(&temp_dir.path().to_string_lossy(), Some(60))
+            .with_compression()
+            .resolve("a")
+            .into_owned();
+        let rules = "DOMAIN-SUFFIX,example.com,DIRECT\n".repeat(1000);
+        assert!(fc.write_cache_file_async(rules.as_bytes()).await);
+        let stored = std::fs::read(fc.cache_file_path.as_ref().unwrap()).unwrap();
+        assert!(stored.starts_with(COMPRESSED_CACHE_MAGIC));
+        assert_eq!(fc.read_cache_file_async().await.unwrap(), rules.as_bytes());
+    }
+}